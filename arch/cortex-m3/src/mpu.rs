@@ -514,6 +514,27 @@ impl kernel::mpu::MPU for MPU {
         Some(mpu::Region::new(start as *const u8, size))
     }
 
+    fn remove_memory_region(
+        &self,
+        region: mpu::Region,
+        config: &mut Self::MpuConfig,
+    ) -> Result<(), ()> {
+        let region_num = config
+            .regions
+            .iter()
+            .position(|r| r.location() == Some((region.start_address(), region.size())))
+            .ok_or(())?;
+
+        if region_num == APP_MEMORY_REGION_NUM {
+            return Err(());
+        }
+
+        config.regions[region_num] = CortexMRegion::empty(region_num);
+        config.is_dirty.set(true);
+
+        Ok(())
+    }
+
     fn allocate_app_memory_region(
         &self,
         unallocated_memory_start: *const u8,
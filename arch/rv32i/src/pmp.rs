@@ -362,6 +362,33 @@ impl kernel::mpu::MPU for PMP {
         Some(mpu::Region::new(start as *const u8, size))
     }
 
+    fn remove_memory_region(
+        &self,
+        region: mpu::Region,
+        config: &mut Self::MpuConfig,
+    ) -> Result<(), ()> {
+        let index = config
+            .regions
+            .iter()
+            .position(|r| {
+                r.map_or(false, |r| {
+                    r.location() == (region.start_address(), region.size())
+                })
+            })
+            .ok_or(())?;
+
+        if config.app_memory_region.contains(&index) {
+            return Err(());
+        }
+
+        config.regions[index] = None;
+        config.is_dirty.set(true);
+
+        config.sort_regions();
+
+        Ok(())
+    }
+
     fn allocate_app_memory_region(
         &self,
         unallocated_memory_start: *const u8,
@@ -7,6 +7,7 @@
 #![feature(const_fn, const_in_array_repeat_expressions)]
 
 use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
+use core::cell::Cell;
 use kernel::capabilities;
 use kernel::common::dynamic_deferred_call::{DynamicDeferredCall, DynamicDeferredCallClientState};
 use kernel::component::Component;
@@ -29,9 +30,9 @@ const NUM_PROCS: usize = 4;
 // How should the kernel respond when a process faults.
 const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
 
-// Actual memory for holding the active process structures.
-static mut PROCESSES: [Option<&'static dyn kernel::procs::ProcessType>; NUM_PROCS] =
-    [None, None, None, None];
+// The processes, read through the kernel by the panic handler.
+static mut PROCESSES: Option<&'static [Cell<Option<&'static dyn kernel::procs::ProcessType>>]> =
+    None;
 
 // Reference to the chip for panic dumps.
 static mut CHIP: Option<&'static arty_e21_chip::chip::ArtyExx> = None;
@@ -94,7 +95,13 @@ pub unsafe fn reset_handler() {
     let process_mgmt_cap = create_capability!(capabilities::ProcessManagementCapability);
     let main_loop_cap = create_capability!(capabilities::MainLoopCapability);
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    // Actual memory for holding the active process structures.
+    let processes = static_init!(
+        [Option<&'static dyn kernel::procs::ProcessType>; NUM_PROCS],
+        [None; NUM_PROCS]
+    );
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(processes));
+    PROCESSES = Some(board_kernel.process_slots(&process_mgmt_cap));
 
     let dynamic_deferred_call_clients =
         static_init!([DynamicDeferredCallClientState; 2], Default::default());
@@ -242,7 +249,6 @@ pub unsafe fn reset_handler() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         FAULT_RESPONSE,
         &process_mgmt_cap,
     )
@@ -75,6 +75,8 @@ impl<A: 'static + digest::Digest<'static, T>, T: 'static + digest::DigestType> C
     unsafe fn finalize(self, s: Self::StaticInput) -> Self::Output {
         let mux_hmac = static_init_half!(s, MuxHmac<'static, A, T>, MuxHmac::new(self.hmac));
 
+        digest::Digest::set_client(self.hmac, mux_hmac);
+
         mux_hmac
     }
 }
@@ -39,5 +39,4 @@ pub mod temperature;
 pub mod temperature_stm;
 pub mod test;
 pub mod touch;
-// pub mod vpm_no_cap;
-// pub mod vpm_cap;
+pub mod vpp_console;
@@ -5,12 +5,14 @@
 //! Usage
 //! -----
 //! ```rust
-//! let scheduler = components::cooperative::CooperativeComponent::new(&PROCESSES)
+//! let processes = board_kernel.process_slots(&process_management_capability);
+//! let scheduler = components::cooperative::CooperativeComponent::new(processes)
 //!     .finalize(components::coop_component_helper!(NUM_PROCS));
 //! ```
 
 // Author: Hudson Ayers <hayers@stanford.edu>
 
+use core::cell::Cell;
 use core::mem::MaybeUninit;
 use kernel::component::Component;
 use kernel::procs::ProcessType;
@@ -29,11 +31,11 @@ macro_rules! coop_component_helper {
 }
 
 pub struct CooperativeComponent {
-    processes: &'static [Option<&'static dyn ProcessType>],
+    processes: &'static [Cell<Option<&'static dyn ProcessType>>],
 }
 
 impl CooperativeComponent {
-    pub fn new(processes: &'static [Option<&'static dyn ProcessType>]) -> CooperativeComponent {
+    pub fn new(processes: &'static [Cell<Option<&'static dyn ProcessType>>]) -> CooperativeComponent {
         CooperativeComponent { processes }
     }
 }
@@ -1,14 +1,19 @@
 //! Component for a multi-level feedback queue scheduler.
 //!
-//! This provides one Component, MLFQComponent.
+//! This provides one Component, MLFQComponent. It registers the scheduler as
+//! a process observer of the kernel, so that a process loaded in a slot starts
+//! in the topmost queue.
 
 // Author: Hudson Ayers <hayers@stanford.edu>
 // Last modified: 03/31/2020
 
+use core::cell::Cell;
 use core::mem::MaybeUninit;
 
 use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
+use kernel::capabilities;
 use kernel::component::Component;
+use kernel::create_capability;
 use kernel::hil::time;
 use kernel::procs::ProcessType;
 use kernel::static_init_half;
@@ -29,16 +34,19 @@ macro_rules! mlfq_component_helper {
 }
 
 pub struct MLFQComponent<A: 'static + time::Alarm<'static>> {
+    board_kernel: &'static kernel::Kernel,
     alarm_mux: &'static MuxAlarm<'static, A>,
-    processes: &'static [Option<&'static dyn ProcessType>],
+    processes: &'static [Cell<Option<&'static dyn ProcessType>>],
 }
 
 impl<A: 'static + time::Alarm<'static>> MLFQComponent<A> {
     pub fn new(
+        board_kernel: &'static kernel::Kernel,
         alarm_mux: &'static MuxAlarm<'static, A>,
-        processes: &'static [Option<&'static dyn ProcessType>],
+        processes: &'static [Cell<Option<&'static dyn ProcessType>>],
     ) -> MLFQComponent<A> {
         MLFQComponent {
+            board_kernel,
             alarm_mux,
             processes,
         }
@@ -51,7 +59,7 @@ impl<A: 'static + time::Alarm<'static>> Component for MLFQComponent<A> {
         &'static mut MaybeUninit<MLFQSched<'static, VirtualMuxAlarm<'static, A>>>,
         &'static mut [MaybeUninit<MLFQProcessNode<'static>>],
     );
-    type Output = &'static mut MLFQSched<'static, VirtualMuxAlarm<'static, A>>;
+    type Output = &'static MLFQSched<'static, VirtualMuxAlarm<'static, A>>;
 
     unsafe fn finalize(self, static_buffer: Self::StaticInput) -> Self::Output {
         let (alarm_buf, sched_buf, proc_nodes) = static_buffer;
@@ -73,6 +81,9 @@ impl<A: 'static + time::Alarm<'static>> Component for MLFQComponent<A> {
             );
             scheduler.processes[0].push_head(init_node);
         }
+        let process_mgmt_cap = create_capability!(capabilities::ProcessManagementCapability);
+        self.board_kernel
+            .add_process_observer(scheduler, &process_mgmt_cap);
         scheduler
     }
 }
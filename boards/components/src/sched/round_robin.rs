@@ -5,13 +5,15 @@
 //! Usage
 //! -----
 //! ```rust
-//! let scheduler = components::round_robin::RoundRobinComponent::new(&PROCESSES)
+//! let processes = board_kernel.process_slots(&process_management_capability);
+//! let scheduler = components::round_robin::RoundRobinComponent::new(processes)
 //!     .finalize(components::rr_component_helper!(NUM_PROCS));
 //! ```
 
 // Author: Hudson Ayers <hayers@stanford.edu>
 // Last modified: 03/31/2020
 
+use core::cell::Cell;
 use core::mem::MaybeUninit;
 use kernel::component::Component;
 use kernel::procs::ProcessType;
@@ -31,11 +33,11 @@ macro_rules! rr_component_helper {
 }
 
 pub struct RoundRobinComponent {
-    processes: &'static [Option<&'static dyn ProcessType>],
+    processes: &'static [Cell<Option<&'static dyn ProcessType>>],
 }
 
 impl RoundRobinComponent {
-    pub fn new(processes: &'static [Option<&'static dyn ProcessType>]) -> RoundRobinComponent {
+    pub fn new(processes: &'static [Cell<Option<&'static dyn ProcessType>>]) -> RoundRobinComponent {
         RoundRobinComponent { processes }
     }
 }
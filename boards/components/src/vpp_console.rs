@@ -0,0 +1,63 @@
+//! Component for the VPP console, the management console of the VPP objects.
+//!
+//! Usage
+//! -----
+//! ```rust
+//! let vpp_console = VppConsoleComponent::new(vpp_kernel, uart_mux).finalize(());
+//! vpp_console.start();
+//! ```
+
+use capsules::virtual_uart::{MuxUart, UartDevice};
+use capsules::vpp::console;
+use capsules::vpp::vppkernel::VppKernel;
+use kernel::capabilities;
+use kernel::component::Component;
+use kernel::hil;
+use kernel::static_init;
+
+pub struct VppConsoleComponent {
+    vpp_kernel: &'static VppKernel,
+    uart_mux: &'static MuxUart<'static>,
+}
+
+impl VppConsoleComponent {
+    pub fn new(
+        vpp_kernel: &'static VppKernel,
+        uart_mux: &'static MuxUart,
+    ) -> VppConsoleComponent {
+        VppConsoleComponent {
+            vpp_kernel: vpp_kernel,
+            uart_mux: uart_mux,
+        }
+    }
+}
+
+pub struct Capability;
+unsafe impl capabilities::ProcessManagementCapability for Capability {}
+
+impl Component for VppConsoleComponent {
+    type StaticInput = ();
+    type Output = &'static console::VppConsole<'static, Capability>;
+
+    unsafe fn finalize(self, _s: Self::StaticInput) -> Self::Output {
+        // Create virtual device for console.
+        let console_uart = static_init!(UartDevice, UartDevice::new(self.uart_mux, true));
+        console_uart.setup();
+
+        let console = static_init!(
+            console::VppConsole<'static, Capability>,
+            console::VppConsole::new(
+                console_uart,
+                &mut console::WRITE_BUF,
+                &mut console::READ_BUF,
+                &mut console::COMMAND_BUF,
+                self.vpp_kernel,
+                Capability,
+            )
+        );
+        hil::uart::Transmit::set_transmit_client(console_uart, console);
+        hil::uart::Receive::set_receive_client(console_uart, console);
+
+        console
+    }
+}
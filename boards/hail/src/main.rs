@@ -13,6 +13,7 @@
 use capsules::virtual_alarm::VirtualMuxAlarm;
 use capsules::virtual_i2c::{I2CDevice, MuxI2C};
 use capsules::virtual_spi::VirtualSpiMasterDevice;
+use core::cell::Cell;
 use kernel::capabilities;
 use kernel::common::dynamic_deferred_call::{DynamicDeferredCall, DynamicDeferredCallClientState};
 use kernel::component::Component;
@@ -35,9 +36,9 @@ mod test_take_map_cell;
 // Number of concurrent processes this platform supports.
 const NUM_PROCS: usize = 20;
 
-// Actual memory for holding the active process structures.
-static mut PROCESSES: [Option<&'static dyn kernel::procs::ProcessType>; NUM_PROCS] =
-    [None; NUM_PROCS];
+// The processes, read through the kernel by the panic handler.
+static mut PROCESSES: Option<&'static [Cell<Option<&'static dyn kernel::procs::ProcessType>>]> =
+    None;
 static mut CHIP: Option<&'static sam4l::chip::Sam4l> = None;
 
 /// Dummy buffer that causes the linker to reserve enough space for the stack.
@@ -187,12 +188,18 @@ pub unsafe fn reset_handler() {
 
     set_pin_primary_functions();
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    // Actual memory for holding the active process structures.
+    let processes = static_init!(
+        [Option<&'static dyn kernel::procs::ProcessType>; NUM_PROCS],
+        [None; NUM_PROCS]
+    );
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(processes));
 
     // Create capabilities that the board needs to call certain protected kernel
     // functions.
     let process_management_capability =
         create_capability!(capabilities::ProcessManagementCapability);
+    PROCESSES = Some(board_kernel.process_slots(&process_management_capability));
     let main_loop_capability = create_capability!(capabilities::MainLoopCapability);
     let memory_allocation_capability = create_capability!(capabilities::MemoryAllocationCapability);
 
@@ -460,7 +467,6 @@ pub unsafe fn reset_handler() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         fault_response,
         &process_management_capability,
     )
@@ -469,7 +475,8 @@ pub unsafe fn reset_handler() {
         debug!("{:?}", err);
     });
 
-    let scheduler = components::sched::round_robin::RoundRobinComponent::new(&PROCESSES)
+    let processes = board_kernel.process_slots(&process_management_capability);
+    let scheduler = components::sched::round_robin::RoundRobinComponent::new(processes)
         .finalize(components::rr_component_helper!(NUM_PROCS));
     board_kernel.kernel_loop(
         &hail,
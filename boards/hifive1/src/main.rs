@@ -11,6 +11,7 @@
 #![feature(const_in_array_repeat_expressions)]
 
 use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
+use core::cell::Cell;
 use kernel::capabilities;
 use kernel::common::dynamic_deferred_call::{DynamicDeferredCall, DynamicDeferredCallClientState};
 use kernel::component::Component;
@@ -34,10 +35,9 @@ pub mod io;
 mod multi_alarm_test;
 
 pub const NUM_PROCS: usize = 4;
-// Actual memory for holding the active process structures. Need an empty list
-// at least.
-static mut PROCESSES: [Option<&'static dyn kernel::procs::ProcessType>; NUM_PROCS] =
-    [None;NUM_PROCS];
+// The processes, read through the kernel by the panic handler.
+static mut PROCESSES: Option<&'static [Cell<Option<&'static dyn kernel::procs::ProcessType>>]> =
+    None;
 /*
 static mut VPP_PROCESSES: [Option<VppProcess>;NUM_PROCS] = [None;NUM_PROCS];
 static mut MBOX_ARRAY: [Option<mbox>;MK_MAILBOX_LIMIT] = [None;MK_MAILBOX_LIMIT];
@@ -117,7 +117,13 @@ pub unsafe fn reset_handler() {
 
     let main_loop_cap = create_capability!(capabilities::MainLoopCapability);
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    // Actual memory for holding the active process structures.
+    let processes = static_init!(
+        [Option<&'static dyn kernel::procs::ProcessType>; NUM_PROCS],
+        [None; NUM_PROCS]
+    );
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(processes));
+    PROCESSES = Some(board_kernel.process_slots(&process_mgmt_cap));
 
     let dynamic_deferred_call_clients =
         static_init!([DynamicDeferredCallClientState; 2], Default::default());
@@ -218,10 +224,9 @@ pub unsafe fn reset_handler() {
     VppKernel::new(&VPP_PROCESSES,&MBOX_ARRAY,&IPC_ARRAY,board_kernel));
     let vpp_driver = static_init!(capsules::vpp::vppkernel::vpp_kernel_driver,
     capsules::vpp::vppkernel::vpp_kernel_driver::new(vpp_kernel));*/
-   /* let vpp_process_console = capsules::vpp::ProcessManagerConsoleCap
-    ::ProcessConsoleComponent::new(vpp_kernel,uart_mux)
-        .finalize(());
-    vpp_process_console.start();*/
+   /* let vpp_console =
+        components::vpp_console::VppConsoleComponent::new(vpp_kernel, uart_mux).finalize(());
+    vpp_console.start();*/
     // Need two debug!() calls to actually test with QEMU. QEMU seems to have
     // a much larger UART TX buffer (or it transmits faster).
     debug!("HiFive1 initialization complete.");
@@ -249,7 +254,6 @@ pub unsafe fn reset_handler() {
    /* capsules::vpp::process::load_vpp_processes(
         board_kernel,
         chip,
-        &mut PROCESSES,
         &mut VPP_PROCESSES,
         &mut MBOX_ARRAY,
         &mut IPC_ARRAY,
@@ -271,7 +275,6 @@ pub unsafe fn reset_handler() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         FAULT_RESPONSE,
         &process_mgmt_cap,
     )
@@ -280,7 +283,8 @@ pub unsafe fn reset_handler() {
         debug!("{:?}", err);
     });
 
-    let scheduler = components::sched::cooperative::CooperativeComponent::new(&PROCESSES)
+    let processes = board_kernel.process_slots(&process_mgmt_cap);
+    let scheduler = components::sched::cooperative::CooperativeComponent::new(processes)
         .finalize(components::coop_component_helper!(NUM_PROCS));
     board_kernel.kernel_loop(&hifive1, chip, None, scheduler, &main_loop_cap);
 }
@@ -18,6 +18,7 @@ use capsules::virtual_alarm::VirtualMuxAlarm;
 use capsules::virtual_i2c::MuxI2C;
 use capsules::virtual_spi::VirtualSpiMasterDevice;
 //use capsules::virtual_timer::MuxTimer;
+use core::cell::Cell;
 use kernel::capabilities;
 use kernel::common::dynamic_deferred_call::{DynamicDeferredCall, DynamicDeferredCallClientState};
 use kernel::component::Component;
@@ -92,8 +93,9 @@ const PAN_ID: u16 = 0xABCD;
 // how should the kernel respond when a process faults
 const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
 
-static mut PROCESSES: [Option<&'static dyn kernel::procs::ProcessType>; NUM_PROCS] =
-    [None; NUM_PROCS];
+// The processes, read through the kernel by the panic handler.
+static mut PROCESSES: Option<&'static [Cell<Option<&'static dyn kernel::procs::ProcessType>>]> =
+    None;
 static mut CHIP: Option<&'static sam4l::chip::Sam4l> = None;
 
 /// Dummy buffer that causes the linker to reserve enough space for the stack.
@@ -285,7 +287,13 @@ pub unsafe fn reset_handler() {
         trng: true,
     });
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    // Actual memory for holding the active process structures.
+    let processes = static_init!(
+        [Option<&'static dyn kernel::procs::ProcessType>; NUM_PROCS],
+        [None; NUM_PROCS]
+    );
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(processes));
+    PROCESSES = Some(board_kernel.process_slots(&process_mgmt_cap));
 
     let dynamic_deferred_call_clients =
         static_init!([DynamicDeferredCallClientState; 2], Default::default());
@@ -586,7 +594,6 @@ pub unsafe fn reset_handler() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         FAULT_RESPONSE,
         &process_mgmt_cap,
     )
@@ -595,7 +602,8 @@ pub unsafe fn reset_handler() {
         debug!("{:?}", err);
     });
 
-    let scheduler = components::sched::round_robin::RoundRobinComponent::new(&PROCESSES)
+    let processes = board_kernel.process_slots(&process_mgmt_cap);
+    let scheduler = components::sched::round_robin::RoundRobinComponent::new(processes)
         .finalize(components::rr_component_helper!(NUM_PROCS));
     board_kernel.kernel_loop(&imix, chip, Some(&imix.ipc), scheduler, &main_cap);
 }
@@ -9,6 +9,7 @@
 #![feature(const_in_array_repeat_expressions)]
 #![deny(missing_docs)]
 
+use core::cell::Cell;
 use kernel::capabilities;
 use kernel::common::dynamic_deferred_call::DynamicDeferredCall;
 use kernel::common::dynamic_deferred_call::DynamicDeferredCallClientState;
@@ -26,8 +27,9 @@ mod multi_alarm_test;
 const NUM_PROCS: usize = 4;
 
 /// Actual memory for holding the active process structures.
-static mut PROCESSES: [Option<&'static dyn kernel::procs::ProcessType>; NUM_PROCS] =
-    [None; NUM_PROCS];
+// The processes, read through the kernel by the panic handler.
+static mut PROCESSES: Option<&'static [Cell<Option<&'static dyn kernel::procs::ProcessType>>]> =
+    None;
 
 /// Static reference to chip for panic dumps.
 static mut CHIP: Option<&'static msp432::chip::Msp432> = None;
@@ -123,7 +125,12 @@ pub unsafe fn reset_handler() {
     msp432::gpio::PINS[msp432::gpio::PinNr::P01_2 as usize].enable_primary_function();
     msp432::gpio::PINS[msp432::gpio::PinNr::P01_3 as usize].enable_primary_function();
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    // Actual memory for holding the active process structures.
+    let processes = static_init!(
+        [Option<&'static dyn kernel::procs::ProcessType>; NUM_PROCS],
+        [None; NUM_PROCS]
+    );
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(processes));
     let chip = static_init!(msp432::chip::Msp432, msp432::chip::Msp432::new());
     CHIP = Some(chip);
 
@@ -168,6 +175,7 @@ pub unsafe fn reset_handler() {
     let main_loop_capability = create_capability!(capabilities::MainLoopCapability);
     let process_management_capability =
         create_capability!(capabilities::ProcessManagementCapability);
+    PROCESSES = Some(board_kernel.process_slots(&process_management_capability));
     let dynamic_deferred_call_clients =
         static_init!([DynamicDeferredCallClientState; 1], Default::default());
     let dynamic_deferred_caller = static_init!(
@@ -229,13 +237,13 @@ pub unsafe fn reset_handler() {
             &mut _sappmem as *mut u8,
             &_eappmem as *const u8 as usize - &_sappmem as *const u8 as usize,
         ),
-        &mut PROCESSES,
         FAULT_RESPONSE,
         &process_management_capability,
     )
     .unwrap();
 
-    let scheduler = components::sched::round_robin::RoundRobinComponent::new(&PROCESSES)
+    let processes = board_kernel.process_slots(&process_management_capability);
+    let scheduler = components::sched::round_robin::RoundRobinComponent::new(processes)
         .finalize(components::rr_component_helper!(NUM_PROCS));
 
     //Uncomment to run multi alarm test
@@ -1,4 +1,37 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
 fn main() {
     println!("cargo:rerun-if-changed=layout.ld");
     println!("cargo:rerun-if-changed=../kernel_layout.ld");
+    println!("cargo:rerun-if-env-changed=CREDENTIALS_KEY_FILE");
+
+    // The key of the HMAC-SHA256 credentials of the apps is provisioned at
+    // build time from a file of 32 bytes, it is not part of the sources.
+    let key = match env::var_os("CREDENTIALS_KEY_FILE") {
+        Some(path) => {
+            println!("cargo:rerun-if-changed={}", Path::new(&path).display());
+            let key = fs::read(&path)
+                .unwrap_or_else(|e| panic!("cannot read {}: {}", Path::new(&path).display(), e));
+            if key.len() != 32 {
+                panic!(
+                    "{} holds {} bytes, the credentials key is 32 bytes",
+                    Path::new(&path).display(),
+                    key.len()
+                );
+            }
+            format!("Some({:?})", key)
+        }
+        None => {
+            println!("cargo:warning=CREDENTIALS_KEY_FILE is not set, no app will be authenticated");
+            "None".to_string()
+        }
+    };
+    let out = Path::new(&env::var("OUT_DIR").unwrap()).join("credentials_key.rs");
+    fs::write(
+        out,
+        format!("const CREDENTIALS_KEY: Option<[u8; 32]> = {};\n", key),
+    )
+    .unwrap();
 }
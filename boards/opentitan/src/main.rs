@@ -8,6 +8,7 @@
 #![cfg_attr(not(doc), no_main)]
 #![feature(const_in_array_repeat_expressions)]
 
+use core::cell::Cell;
 use capsules::virtual_alarm::{MuxAlarm, VirtualMuxAlarm};
 use capsules::virtual_hmac::VirtualMuxHmac;
 use earlgrey::chip::EarlGreyDefaultPeripherals;
@@ -37,21 +38,38 @@ use capsules::vpp::vppkernel::NUM_PROCS;
 use capsules::vpp::mloi::{MK_MAILBOX_LIMIT, MK_IPC_LIMIT};
 use capsules::vpp::mailbox::mbox;
 use capsules::vpp::ipc::ipc;
+use capsules::vpp::library::{VppLibrary, VPP_LIB_SLOTS};
 
-// Actual memory for holding the active process structures. Need an empty list
-// at least.
-static mut PROCESSES: [Option<&'static dyn kernel::procs::ProcessType>; NUM_PROCS] =
-    [None;NUM_PROCS];
+// The processes, read through the kernel by the panic handler.
+static mut PROCESSES: Option<&'static [Cell<Option<&'static dyn kernel::procs::ProcessType>>]> =
+    None;
 static mut VPP_PROCESSES: [Option<VppProcess>;NUM_PROCS] = [None;NUM_PROCS];
 static mut MBOX_ARRAY: [Option<mbox>;MK_MAILBOX_LIMIT] = [None;MK_MAILBOX_LIMIT];
 static mut IPC_ARRAY : [Option<ipc>; MK_IPC_LIMIT] = [None;MK_IPC_LIMIT];
+static mut LIB_ARRAY: [Option<VppLibrary>; VPP_LIB_SLOTS] = [None; VPP_LIB_SLOTS];
+// Nonvolatile storage holding the Firmware registry of the MGT Service.
+kernel::storage_volume!(
+    VPP_FW_REGISTRY,
+    (capsules::vpp::mgt_service::MGT_REGISTRY_SIZE + 1023) / 1024
+);
+// Nonvolatile storage holding the Memory Partitions of the VPP Firmwares.
+kernel::storage_volume!(VPP_FW_PARTITIONS, 32);
+// Transactional NVM of the VPP Processes: two banks per Process, plus one page
+// for the alignment of the volume on flash pages.
+capsules::store_vpp_sec!(
+    VPP_NVM,
+    (2 * NUM_PROCS * capsules::vpp::nvm::NVM_BANK_PAGES + 1) * 1024
+);
+static mut NVM_PAGEBUFFER: lowrisc::flash_ctrl::LowRiscPage = lowrisc::flash_ctrl::LowRiscPage(
+    [0; 1024],
+);
+// Key of the HMAC-SHA256 credentials of the apps, read by build.rs from the
+// file named by `CREDENTIALS_KEY_FILE`. Without it no app runs.
+include!(concat!(env!("OUT_DIR"), "/credentials_key.rs"));
 static mut CHIP: Option<
     &'static earlgrey::chip::EarlGrey<VirtualMuxAlarm<'static, earlgrey::timer::RvTimer>>,
 > = None;
 
-// How should the kernel respond when a process faults.
-const FAULT_RESPONSE: kernel::procs::FaultResponse = kernel::procs::FaultResponse::Panic;
-
 /// Dummy buffer that causes the linker to reserve enough space for the stack.
 #[no_mangle]
 #[link_section = ".stack_buffer"]
@@ -80,9 +98,21 @@ struct OpenTitan {
     i2c_master: &'static capsules::i2c_master::I2CMasterDriver<lowrisc::i2c::I2c<'static>>,
     pm : &'static capsules::vpp::pmsyscall::ProcessManager,
     vpp_driver: &'static capsules::vpp::vppkernel::vpp_kernel_driver,
+    mgt_service: &'static capsules::vpp::mgt_service::mgt_process,
+    vpp_nvm: &'static capsules::vpp::nvm::VppNvm<
+        'static,
+        capsules::virtual_flash::FlashUser<'static, lowrisc::flash_ctrl::FlashCtrl<'static>>,
+    >,
+    vpp_timer: &'static capsules::vpp::timer::Timer<
+        'static,
+        VirtualMuxAlarm<'static, earlgrey::timer::RvTimer<'static>>,
+    >,
+    vre: &'static capsules::vpp::vre::VreBroker<
+        earlgrey::aes::Aes<'static>,
+        VirtualMuxHmac<'static, lowrisc::hmac::Hmac<'static>, [u8; 32]>,
+    >,
     nonvolatile_storage: &'static capsules::nonvolatile_storage_driver::NonvolatileStorage<'static>,
 
-    // vpmdriver: &'static capsules::vpp::ProcessManagerConsoleCap::VPMDriver,
     // testdriver: &'static capsules::vpp::SubTest::Test,
 }
 
@@ -94,9 +124,12 @@ impl Platform for OpenTitan {
     {
         match driver_num {
             capsules::vpp::vppkernel::DRIVER_NUM => f(Some(self.vpp_driver)),
+            capsules::vpp::mgt_service::DRIVER_NUM => f(Some(self.mgt_service)),
+            capsules::vpp::nvm::DRIVER_NUM => f(Some(self.vpp_nvm)),
+            capsules::vpp::timer::DRIVER_NUM => f(Some(self.vpp_timer)),
+            capsules::vpp::vre::DRIVER_NUM => f(Some(self.vre)),
             // 0x9000A => f(Some(self.testdriver)),
             0x90003 => f(Some(self.pm)),
-            // 0x90004 => f(Some(self.vpmdriver)),
             capsules::led::DRIVER_NUM => f(Some(self.led)),
             capsules::hmac::DRIVER_NUM => f(Some(self.hmac)),
             capsules::gpio::DRIVER_NUM => f(Some(self.gpio)),
@@ -132,11 +165,17 @@ pub unsafe fn reset_handler() {
     let grant_cap = create_capability!(capabilities::MemoryAllocationCapability);
     let main_loop_cap = create_capability!(capabilities::MainLoopCapability);
 
-    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(&PROCESSES));
+    // Actual memory for holding the active process structures.
+    let processes = static_init!(
+        [Option<&'static dyn kernel::procs::ProcessType>; NUM_PROCS],
+        [None; NUM_PROCS]
+    );
+    let board_kernel = static_init!(kernel::Kernel, kernel::Kernel::new(processes));
+    PROCESSES = Some(board_kernel.process_slots(&process_mgmt_cap));
 
 
     let dynamic_deferred_call_clients =
-        static_init!([DynamicDeferredCallClientState; 1], Default::default());
+        static_init!([DynamicDeferredCallClientState; 2], Default::default());
     let dynamic_deferred_caller = static_init!(
         DynamicDeferredCall,
         DynamicDeferredCall::new(dynamic_deferred_call_clients)
@@ -255,6 +294,44 @@ pub unsafe fn reset_handler() {
     // VppKernel::new(&VPP_PROCESSES,&MBOX_ARRAY,&IPC_ARRAY,board_kernel,virtual_alarm_user));
     let vpp_kernel = static_init!(VppKernel,
     VppKernel::new(&VPP_PROCESSES,&MBOX_ARRAY,&IPC_ARRAY,board_kernel));
+    // The VPP states follow the Tock processes.
+    board_kernel.add_process_observer(vpp_kernel, &process_mgmt_cap);
+
+    // Blocking _mk_Wait_Signal with timeout
+    let signal_wait_alarm = static_init!(
+        VirtualMuxAlarm<'static, earlgrey::timer::RvTimer>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let signal_wait = static_init!(
+        capsules::vpp::signal_wait::VppSignalWait<
+            'static,
+            VirtualMuxAlarm<'static, earlgrey::timer::RvTimer>,
+        >,
+        capsules::vpp::signal_wait::VppSignalWait::new(
+            vpp_kernel,
+            signal_wait_alarm,
+            board_kernel.create_grant(&memory_allocation_cap),
+        )
+    );
+    hil::time::Alarm::set_alarm_client(signal_wait_alarm, signal_wait);
+    vpp_kernel.set_signal_waiter(signal_wait);
+
+    // MLOI time service and VPP timers
+    let vpp_timer_alarm = static_init!(
+        VirtualMuxAlarm<'static, earlgrey::timer::RvTimer>,
+        VirtualMuxAlarm::new(mux_alarm)
+    );
+    let vpp_timer = static_init!(
+        capsules::vpp::timer::Timer<'static, VirtualMuxAlarm<'static, earlgrey::timer::RvTimer>>,
+        capsules::vpp::timer::Timer::new(
+            vpp_kernel,
+            vpp_timer_alarm,
+            board_kernel.create_grant(&memory_allocation_cap),
+        )
+    );
+    hil::time::Alarm::set_alarm_client(vpp_timer_alarm, vpp_timer);
+    vpp_kernel.set_clock(vpp_timer);
+    vpp_timer.start();
 
     let chip = static_init!(
         earlgrey::chip::EarlGrey<VirtualMuxAlarm<'static, earlgrey::timer::RvTimer>>,
@@ -290,6 +367,34 @@ pub unsafe fn reset_handler() {
         [u8; 32]
     ));
 
+    // VRE broker: AES and SHA-256 of the VPP Processes
+    let vre_hmac = static_init!(
+        VirtualMuxHmac<'static, lowrisc::hmac::Hmac<'static>, [u8; 32]>,
+        VirtualMuxHmac::new(mux_hmac)
+    );
+    let vre_buffer = static_init!(
+        [u8; capsules::vpp::vre::VRE_BUFFER_SIZE],
+        [0; capsules::vpp::vre::VRE_BUFFER_SIZE]
+    );
+    let vre_digest = static_init!([u8; 32], [0; 32]);
+    let vre = static_init!(
+        capsules::vpp::vre::VreBroker<
+            earlgrey::aes::Aes<'static>,
+            VirtualMuxHmac<'static, lowrisc::hmac::Hmac<'static>, [u8; 32]>,
+        >,
+        capsules::vpp::vre::VreBroker::new(
+            vpp_kernel,
+            Some(&earlgrey::aes::AES),
+            Some(vre_hmac),
+            None,
+            vre_buffer,
+            vre_digest,
+            board_kernel.create_grant(&memory_allocation_cap),
+        )
+    );
+    hil::symmetric_encryption::AES128::set_client(&earlgrey::aes::AES, vre);
+    hil::digest::Digest::set_client(vre_hmac, vre);
+
     let i2c_master = static_init!(
         capsules::i2c_master::I2CMasterDriver<lowrisc::i2c::I2c<'static>>,
         capsules::i2c_master::I2CMasterDriver::new(
@@ -317,23 +422,109 @@ pub unsafe fn reset_handler() {
         static _estorage: u8;
     }
 
-    // Flash
+    // Flash, shared between the nonvolatile storage and the VPP NVM
+    let mux_flash = static_init!(
+        capsules::virtual_flash::MuxFlash<'static, lowrisc::flash_ctrl::FlashCtrl<'static>>,
+        capsules::virtual_flash::MuxFlash::new(&peripherals.flash_ctrl)
+    );
+    hil::flash::HasClient::set_client(&peripherals.flash_ctrl, mux_flash);
+    let storage_flash = static_init!(
+        capsules::virtual_flash::FlashUser<'static, lowrisc::flash_ctrl::FlashCtrl<'static>>,
+        capsules::virtual_flash::FlashUser::new(mux_flash)
+    );
+    let nvm_flash = static_init!(
+        capsules::virtual_flash::FlashUser<'static, lowrisc::flash_ctrl::FlashCtrl<'static>>,
+        capsules::virtual_flash::FlashUser::new(mux_flash)
+    );
+
     let nonvolatile_storage = components::nonvolatile_storage::NonvolatileStorageComponent::new(
         board_kernel,
-        &peripherals.flash_ctrl,
+        storage_flash,
         0x20000000,                       // Start address for userspace accessible region
         0x8000,                           // Length of userspace accessible region
         &_sstorage as *const u8 as usize, // Start address of kernel region
         &_estorage as *const u8 as usize - &_sstorage as *const u8 as usize, // Length of kernel region
     )
         .finalize(components::nv_storage_component_helper!(
-        lowrisc::flash_ctrl::FlashCtrl
+        capsules::virtual_flash::FlashUser<'static, lowrisc::flash_ctrl::FlashCtrl<'static>>
     ));
 
-    let vpp_process_console = capsules::vpp::ProcessManagerConsoleCap
-    ::ProcessConsoleComponent::new(vpp_kernel,uart_mux)
-        .finalize(());
-    vpp_process_console.start();
+    // Transactional NVM of the VPP Processes (_mk_Commit / _mk_Rollback)
+    let vpp_nvm = static_init!(
+        capsules::vpp::nvm::VppNvm<
+            'static,
+            capsules::virtual_flash::FlashUser<'static, lowrisc::flash_ctrl::FlashCtrl<'static>>,
+        >,
+        capsules::vpp::nvm::VppNvm::new(
+            vpp_kernel,
+            &VPP_NVM,
+            nvm_flash,
+            &mut NVM_PAGEBUFFER,
+            board_kernel.create_grant(&memory_allocation_cap),
+        )
+    );
+    hil::flash::HasClient::set_client(nvm_flash, vpp_nvm);
+    vpp_kernel.set_nvm(vpp_nvm);
+
+    // Impersonation sessions of the MGT Process
+    let fw_mgt = static_init!(
+        capsules::vpp::vff::_mk_Fw_Mgt,
+        capsules::vpp::vff::_mk_Fw_Mgt::new(vpp_kernel)
+    );
+    // MGT Service: Firmware registry kept in the VPP_FW_REGISTRY volume
+    let mgt_service = static_init!(
+        capsules::vpp::mgt_service::mgt_process,
+        capsules::vpp::mgt_service::mgt_process::new(
+            nonvolatile_storage,
+            &VPP_FW_REGISTRY as *const [u8] as *const u8 as usize,
+            &VPP_FW_PARTITIONS as *const [u8] as *const u8 as usize,
+            VPP_FW_PARTITIONS.len() as u32,
+            vpp_kernel,
+            fw_mgt,
+            board_kernel.create_grant(&memory_allocation_cap),
+            &mut capsules::vpp::mgt_service::FW_RECORD_BUF,
+        )
+    );
+    hil::nonvolatile_storage::NonvolatileStorage::set_client(nonvolatile_storage, mgt_service);
+    mgt_service.load();
+
+    // The VPP console is not started: it would echo the binary COM frames received
+    // on the only UART. Boards with a second UART can give it to the console with
+    // `components::vpp_console::VppConsoleComponent`.
+
+    // COM Service: framed host link to the MAIN Process, shared with the console UART
+    let com_uart = static_init!(
+        capsules::virtual_uart::UartDevice<'static>,
+        capsules::virtual_uart::UartDevice::new(uart_mux, true)
+    );
+    com_uart.setup();
+    let com_rx_buffer = static_init!(
+        [u8; capsules::vpp::com_service::COM_BUFFER_SIZE],
+        [0; capsules::vpp::com_service::COM_BUFFER_SIZE]
+    );
+    let com_tx_buffer = static_init!(
+        [u8; capsules::vpp::com_service::COM_BUFFER_SIZE],
+        [0; capsules::vpp::com_service::COM_BUFFER_SIZE]
+    );
+    let com_service = static_init!(
+        capsules::vpp::com_service::ComService<'static, capsules::virtual_uart::UartDevice<'static>>,
+        capsules::vpp::com_service::ComService::new(
+            vpp_kernel,
+            com_uart,
+            com_rx_buffer,
+            com_tx_buffer,
+            dynamic_deferred_caller,
+        )
+    );
+    com_service.initialize_callback_handle(
+        dynamic_deferred_caller
+            .register(com_service)
+            .expect("no deferred call slot available for the COM service"),
+    );
+    hil::uart::Transmit::set_transmit_client(com_uart, com_service);
+    hil::uart::Receive::set_receive_client(com_uart, com_service);
+    vpp_kernel.set_com_bridge(com_service);
+    com_service.start();
     debug!("OpenTitan initialisation complete. Entering main loop");
     let opentitan = OpenTitan {
         gpio: gpio,
@@ -347,31 +538,81 @@ pub unsafe fn reset_handler() {
         i2c_master,
         pm,
         vpp_driver,
+        mgt_service,
+        vpp_nvm,
+        vpp_timer,
+        vre,
         nonvolatile_storage
     };
     // USB support is currently broken in the OpenTitan hardware
     // See https://github.com/lowRISC/opentitan/issues/2598 for more details
     // let usb = usb::UsbComponent::new(board_kernel).finalize(());
 
+    // Check the credentials of the apps before creating their processes.
+    extern "C" {
+        /// Beginning of the ROM region containing app images.
+        static _sapps: u8;
+        /// End of the ROM region containing app images.
+        static _eapps: u8;
+    }
+    let app_flash = core::slice::from_raw_parts(
+        &_sapps as *const u8,
+        &_eapps as *const u8 as usize - &_sapps as *const u8 as usize,
+    );
+    let credentials_hmac = static_init!(
+        VirtualMuxHmac<'static, lowrisc::hmac::Hmac<'static>, [u8; 32]>,
+        VirtualMuxHmac::new(mux_hmac)
+    );
+    let credentials_verdicts = static_init!(
+        [Cell<Option<capsules::app_credentials::Verdict>>; NUM_PROCS],
+        Default::default()
+    );
+    let credentials_buffer = static_init!([u8; 64], [0; 64]);
+    let credentials_digest = static_init!([u8; 32], [0; 32]);
+    let credentials_checker = static_init!(
+        capsules::app_credentials::HmacCredentialsChecker<
+            VirtualMuxHmac<'static, lowrisc::hmac::Hmac<'static>, [u8; 32]>,
+        >,
+        capsules::app_credentials::HmacCredentialsChecker::new(
+            credentials_hmac,
+            CREDENTIALS_KEY,
+            true,
+            credentials_verdicts,
+            credentials_buffer,
+            credentials_digest,
+        )
+    );
+    hil::digest::Digest::set_client(credentials_hmac, credentials_checker);
+    credentials_checker.start(app_flash);
+    while !credentials_checker.done() {
+        chip.service_pending_interrupts();
+    }
+    board_kernel.set_credentials_checker(credentials_checker, &process_mgmt_cap);
+
     capsules::vpp::process::load_vpp_processes(
         board_kernel,
         chip,
         // app_flash,
         // app_memory,
-        &mut PROCESSES,
         &mut VPP_PROCESSES,
         &mut MBOX_ARRAY,
         &mut IPC_ARRAY,
-        FAULT_RESPONSE,
+        &mut LIB_ARRAY,
+        // Faults are VPP Exceptions, the VPP kernel decides on the restart.
+        kernel::procs::FaultResponse::Restart(vpp_kernel),
         &process_mgmt_cap)
          .unwrap_or_else(|err| {
              debug!("Error loading processes!");
              debug!("{:?}", err);
          });
+    vpp_kernel.set_libraries(&LIB_ARRAY);
 
     //_vpp_kernel._mk_resume_process(0);
         //_vpp_kernel._mk_resume_process(1);
         //panic!("Panic");
-    let scheduler = components::sched::priority::PriorityComponent::new(board_kernel).finalize(());
+    let scheduler = static_init!(
+        capsules::vpp::scheduler::VppScheduler,
+        capsules::vpp::scheduler::VppScheduler::new(vpp_kernel)
+    );
     board_kernel.kernel_loop(&opentitan, chip, Some(&opentitan.ipc), scheduler, &main_loop_cap);
 }
@@ -0,0 +1,797 @@
+//! Checks the HMAC-SHA256 credentials of the apps before their processes are
+//! created.
+//!
+//! The checker walks the apps in flash once, before the processes are loaded.
+//! For every app with `HmacSha256` credentials, it hashes the part of the TBF
+//! the credentials cover with `hil::digest`, compares the hash with the one of
+//! the footer, then checks the HMAC-SHA256 of the hash with the key of the
+//! board. The verdicts are kept in a table provided by the board, in which the
+//! kernel looks them up through `AppCredentialsChecker` while loading the
+//! processes.
+//!
+//! Apps with credentials in another format are rejected. Apps without
+//! credentials run, unless the checker requires credentials. Without a key, no
+//! app is authenticated: the board keeps its key out of the sources and may be
+//! built without one.
+//!
+//! An app written to flash after boot is checked again with
+//! `recheck_credentials()`, through `ProcessLoader::verify()`. Its verdict
+//! replaces the previous one of the same address and is reported to the
+//! client of the checker. A `ProcessLoader` only loads an app with such a
+//! fresh verdict, and only once: the verdicts of the apps checked at boot or
+//! already loaded are stale, their image may have been rewritten since.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//!     let credentials_hmac = static_init!(
+//!         VirtualMuxHmac<'static, lowrisc::hmac::Hmac, [u8; 32]>,
+//!         VirtualMuxHmac::new(mux_hmac)
+//!     );
+//!     let credentials_checker = static_init!(
+//!         capsules::app_credentials::HmacCredentialsChecker<
+//!             VirtualMuxHmac<'static, lowrisc::hmac::Hmac, [u8; 32]>,
+//!         >,
+//!         capsules::app_credentials::HmacCredentialsChecker::new(
+//!             credentials_hmac,
+//!             CREDENTIALS_KEY,
+//!             true,
+//!             &mut CREDENTIALS_VERDICTS,
+//!             &mut CREDENTIALS_BUFFER,
+//!             &mut CREDENTIALS_DIGEST,
+//!         )
+//!     );
+//!     hil::digest::Digest::set_client(credentials_hmac, credentials_checker);
+//!     credentials_checker.start(app_flash);
+//!     while !credentials_checker.done() {
+//!         chip.service_pending_interrupts();
+//!     }
+//!     board_kernel.set_credentials_checker(credentials_checker, &process_mgmt_cap);
+//! ```
+
+use core::cell::Cell;
+use core::cmp;
+use core::convert::TryInto;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::leasable_buffer::LeasableBuffer;
+use kernel::hil::digest::{self, Digest, HMACSha256};
+use kernel::procs::{
+    AppCredentials, AppCredentialsChecker, AppCredentialsClient, CredentialsError,
+    CredentialsFormat, CREDENTIALS_HASH_LENGTH,
+};
+use kernel::tbfheader;
+use kernel::ReturnCode;
+
+/// The verdict of the checker on an app.
+#[derive(Clone, Copy)]
+pub struct Verdict {
+    /// Address of the TBF of the app.
+    app: usize,
+    result: Result<(), CredentialsError>,
+    /// Made by `recheck_credentials()` and not used to load the app yet.
+    fresh: bool,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    /// Hashing the part of the app covered by the credentials.
+    Hashing,
+    /// Computing the HMAC of the hash.
+    Authenticating,
+    Done,
+}
+
+pub struct HmacCredentialsChecker<H: 'static + Digest<'static, [u8; 32]> + HMACSha256> {
+    hmac: &'static H,
+    client: OptionalCell<&'static dyn AppCredentialsClient>,
+    key: Option<[u8; 32]>,
+    require_credentials: bool,
+    verdicts: &'static [Cell<Option<Verdict>>],
+    verdict_count: Cell<usize>,
+    /// The apps not checked yet.
+    remaining_flash: Cell<&'static [u8]>,
+    /// The app being checked.
+    app: Cell<&'static [u8]>,
+    credentials: OptionalCell<AppCredentials>,
+    /// How much of the covered part of the app was hashed.
+    hashed: Cell<usize>,
+    buffer: TakeCell<'static, [u8]>,
+    digest: TakeCell<'static, [u8; 32]>,
+    state: Cell<State>,
+    /// Whether the app being checked was written after boot.
+    rechecking: Cell<bool>,
+}
+
+impl<H: 'static + Digest<'static, [u8; 32]> + HMACSha256> HmacCredentialsChecker<H> {
+    /// `buffer` must hold at least `CREDENTIALS_HASH_LENGTH` bytes. Apps
+    /// without credentials are rejected if `require_credentials` is set, and
+    /// the apps beyond the length of `verdicts` are never allowed to run.
+    pub fn new(
+        hmac: &'static H,
+        key: Option<[u8; 32]>,
+        require_credentials: bool,
+        verdicts: &'static [Cell<Option<Verdict>>],
+        buffer: &'static mut [u8],
+        digest: &'static mut [u8; 32],
+    ) -> HmacCredentialsChecker<H> {
+        HmacCredentialsChecker {
+            hmac,
+            client: OptionalCell::empty(),
+            key,
+            require_credentials,
+            verdicts,
+            verdict_count: Cell::new(0),
+            remaining_flash: Cell::new(&[]),
+            app: Cell::new(&[]),
+            credentials: OptionalCell::empty(),
+            hashed: Cell::new(0),
+            buffer: TakeCell::new(buffer),
+            digest: TakeCell::new(digest),
+            state: Cell::new(State::Idle),
+            rechecking: Cell::new(false),
+        }
+    }
+
+    pub fn set_client(&self, client: &'static dyn AppCredentialsClient) {
+        self.client.set(client);
+    }
+
+    /// Checks the credentials of the apps in `app_flash`, which must be the
+    /// flash the processes are loaded from.
+    pub fn start(&self, app_flash: &'static [u8]) {
+        if self.state.get() != State::Idle {
+            return;
+        }
+        self.remaining_flash.set(app_flash);
+        self.next_app();
+    }
+
+    /// Whether all the apps were checked.
+    pub fn done(&self) -> bool {
+        self.state.get() == State::Done
+    }
+
+    /// The slot of the verdict on the app at `address`, or the first free
+    /// slot.
+    fn verdict_slot(&self, address: usize) -> Option<(usize, &Cell<Option<Verdict>>)> {
+        let count = self.verdict_count.get();
+        self.verdicts[..count]
+            .iter()
+            .position(|slot| slot.get().map_or(false, |verdict| verdict.app == address))
+            .or(if count < self.verdicts.len() {
+                Some(count)
+            } else {
+                None
+            })
+            .map(|index| (index, &self.verdicts[index]))
+    }
+
+    fn record(&self, result: Result<(), CredentialsError>) {
+        let address = self.app.get().as_ptr() as usize;
+        if let Some((index, slot)) = self.verdict_slot(address) {
+            slot.set(Some(Verdict {
+                app: address,
+                result,
+                fresh: self.rechecking.get(),
+            }));
+            self.verdict_count
+                .set(cmp::max(self.verdict_count.get(), index + 1));
+        }
+    }
+
+    /// Records the verdict on the current app and checks the next one, or
+    /// reports it if the app was written after boot.
+    fn finish_app(&self, result: Result<(), CredentialsError>) {
+        self.hmac.clear_data();
+        self.record(result);
+        self.credentials.clear();
+        if self.rechecking.get() {
+            self.rechecking.set(false);
+            self.state.set(State::Done);
+            let app = self.app.get();
+            self.client
+                .map(|client| client.credentials_checked(app, result));
+        } else {
+            self.next_app();
+        }
+    }
+
+    /// Walks the apps the same way the kernel loads them, until one has
+    /// `HmacSha256` credentials to check.
+    fn next_app(&self) {
+        loop {
+            let remaining_flash = self.remaining_flash.get();
+            let lengths = remaining_flash
+                .get(0..8)
+                .and_then(|header| header.try_into().ok())
+                .map(tbfheader::parse_tbf_header_lengths);
+            let (version, header_length, entry_length) = match lengths {
+                Some(Ok(lengths)) => lengths,
+                Some(Err(tbfheader::InitialTbfParseError::InvalidHeader(entry_length))) => {
+                    (0, 0, entry_length)
+                }
+                // The end of the apps.
+                _ => break,
+            };
+            let app = match remaining_flash.get(0..entry_length as usize) {
+                Some(app) if entry_length > 0 => app,
+                _ => break,
+            };
+            self.remaining_flash.set(&remaining_flash[app.len()..]);
+            self.app.set(app);
+
+            // The kernel does not create the apps it cannot parse, they need
+            // no verdict.
+            match tbfheader::parse_tbf_header_credentials(app, header_length as usize, version) {
+                Ok(Some(credentials)) => match credentials.format {
+                    CredentialsFormat::HmacSha256 => {
+                        self.credentials.set(credentials);
+                        self.hashed.set(0);
+                        self.state.set(State::Hashing);
+                        if let Err(error) = self.hash_next() {
+                            self.hmac.clear_data();
+                            self.record(Err(error));
+                            continue;
+                        }
+                        return;
+                    }
+                    format => self.record(Err(CredentialsError::UnsupportedFormat(format))),
+                },
+                Ok(None) | Err(_) => {}
+            }
+        }
+        self.state.set(State::Done);
+    }
+
+    /// Adds the next chunk of the covered part of the app to the hash.
+    fn hash_next(&self) -> Result<(), CredentialsError> {
+        let covered = self
+            .credentials
+            .map_or(&[][..], |credentials| credentials.covered);
+        let hashed = self.hashed.get();
+        let buffer = self.buffer.take().ok_or(CredentialsError::Unchecked)?;
+        let length = cmp::min(buffer.len(), covered.len() - hashed);
+        buffer[..length].copy_from_slice(&covered[hashed..hashed + length]);
+        self.hashed.set(hashed + length);
+        self.add_data(buffer, length)
+    }
+
+    fn add_data(&self, buffer: &'static mut [u8], length: usize) -> Result<(), CredentialsError> {
+        let mut data = LeasableBuffer::new(buffer);
+        data.slice(0..length);
+        self.hmac.add_data(data).map(|_| ()).map_err(|(_, buffer)| {
+            self.buffer.replace(buffer);
+            CredentialsError::Unchecked
+        })
+    }
+
+    /// Starts the HMAC of the hash of the app.
+    fn authenticate(&self, hash: &[u8]) -> Result<(), CredentialsError> {
+        let key = self.key.as_ref().ok_or(CredentialsError::Unchecked)?;
+        self.hmac.clear_data();
+        self.hmac
+            .set_mode_hmacsha256(key)
+            .or(Err(CredentialsError::Unchecked))?;
+        self.state.set(State::Authenticating);
+        let buffer = self.buffer.take().ok_or(CredentialsError::Unchecked)?;
+        buffer[..CREDENTIALS_HASH_LENGTH].copy_from_slice(hash);
+        self.add_data(buffer, CREDENTIALS_HASH_LENGTH)
+    }
+}
+
+/// Compares `a` and `b` in a time independent of their content.
+fn equal(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (x, y)| diff | (x ^ y)) == 0
+}
+
+impl<H: 'static + Digest<'static, [u8; 32]> + HMACSha256> digest::Client<'static, [u8; 32]>
+    for HmacCredentialsChecker<H>
+{
+    fn add_data_done(&'static self, result: Result<(), ReturnCode>, data: &'static mut [u8]) {
+        self.buffer.replace(data);
+        if result.is_err() {
+            self.finish_app(Err(CredentialsError::Unchecked));
+            return;
+        }
+        let covered_length = self
+            .credentials
+            .map_or(0, |credentials| credentials.covered.len());
+        let result = if self.state.get() == State::Hashing && self.hashed.get() < covered_length {
+            self.hash_next()
+        } else {
+            match self.digest.take() {
+                Some(digest) => self.hmac.run(digest).map_err(|(_, digest)| {
+                    self.digest.replace(digest);
+                    CredentialsError::Unchecked
+                }),
+                None => Err(CredentialsError::Unchecked),
+            }
+        };
+        if let Err(error) = result {
+            self.finish_app(Err(error));
+        }
+    }
+
+    fn hash_done(&'static self, result: Result<(), ReturnCode>, digest: &'static mut [u8; 32]) {
+        let credentials = self.credentials.map(|credentials| *credentials);
+        let verdict = match credentials {
+            _ if result.is_err() => Err(CredentialsError::Unchecked),
+            None => Err(CredentialsError::Unchecked),
+            Some(credentials) => match self.state.get() {
+                State::Hashing if !equal(&digest[..], credentials.hash) => {
+                    Err(CredentialsError::HashMismatch)
+                }
+                State::Hashing => {
+                    let result = self.authenticate(&digest[..]);
+                    self.digest.replace(digest);
+                    if let Err(error) = result {
+                        self.finish_app(Err(error));
+                    }
+                    return;
+                }
+                _ if !equal(&digest[..], credentials.tag) => Err(CredentialsError::InvalidTag),
+                _ => Ok(()),
+            },
+        };
+        self.digest.replace(digest);
+        self.finish_app(verdict);
+    }
+}
+
+impl<H: 'static + Digest<'static, [u8; 32]> + HMACSha256> AppCredentialsChecker
+    for HmacCredentialsChecker<H>
+{
+    fn check_credentials(
+        &self,
+        app: &'static [u8],
+        credentials: Option<AppCredentials>,
+    ) -> Result<(), CredentialsError> {
+        if credentials.is_none() {
+            return if self.require_credentials {
+                Err(CredentialsError::Missing)
+            } else {
+                Ok(())
+            };
+        }
+        let address = app.as_ptr() as usize;
+        self.verdicts
+            .iter()
+            .filter_map(|slot| slot.get())
+            .find(|verdict| verdict.app == address)
+            .map_or(Err(CredentialsError::Unchecked), |verdict| verdict.result)
+    }
+
+    /// Only apps with `HmacSha256` credentials need to be checked again, the
+    /// others are decided on by `check_loaded_credentials()` alone: `EINVAL`
+    /// is returned for them.
+    fn recheck_credentials(&self, app: &'static [u8]) -> ReturnCode {
+        if self.state.get() != State::Done {
+            return ReturnCode::EBUSY;
+        }
+        let lengths = app
+            .get(0..8)
+            .and_then(|header| header.try_into().ok())
+            .map(tbfheader::parse_tbf_header_lengths);
+        let (version, header_length, entry_length) = match lengths {
+            Some(Ok(lengths)) => lengths,
+            _ => return ReturnCode::EINVAL,
+        };
+        let app = match app.get(0..entry_length as usize) {
+            Some(app) => app,
+            None => return ReturnCode::EINVAL,
+        };
+        let credentials =
+            match tbfheader::parse_tbf_header_credentials(app, header_length as usize, version) {
+                Ok(Some(credentials)) if credentials.format == CredentialsFormat::HmacSha256 => {
+                    credentials
+                }
+                _ => return ReturnCode::EINVAL,
+            };
+        if self.verdict_slot(app.as_ptr() as usize).is_none() {
+            return ReturnCode::ENOMEM;
+        }
+
+        self.app.set(app);
+        self.credentials.set(credentials);
+        self.hashed.set(0);
+        self.rechecking.set(true);
+        self.state.set(State::Hashing);
+        if self.hash_next().is_err() {
+            self.hmac.clear_data();
+            self.credentials.clear();
+            self.rechecking.set(false);
+            self.state.set(State::Done);
+            return ReturnCode::FAIL;
+        }
+        ReturnCode::SUCCESS
+    }
+
+    fn check_loaded_credentials(
+        &self,
+        app: &'static [u8],
+        credentials: Option<AppCredentials>,
+    ) -> Result<(), CredentialsError> {
+        match credentials.map(|credentials| credentials.format) {
+            Some(CredentialsFormat::HmacSha256) => {}
+            Some(format) => return Err(CredentialsError::UnsupportedFormat(format)),
+            None => return self.check_credentials(app, credentials),
+        }
+        let address = app.as_ptr() as usize;
+        let fresh = self.verdicts.iter().find_map(|slot| {
+            slot.get()
+                .filter(|verdict| verdict.app == address && verdict.fresh)
+                .map(|verdict| (slot, verdict))
+        });
+        match fresh {
+            Some((slot, verdict)) => {
+                // A verdict is used by one load only.
+                slot.set(Some(Verdict {
+                    fresh: false,
+                    ..verdict
+                }));
+                verdict.result
+            }
+            None => Err(CredentialsError::Unchecked),
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use self::std::boxed::Box;
+    use self::std::cell::RefCell;
+    use self::std::collections::VecDeque;
+    use self::std::vec::Vec;
+    use super::*;
+
+    const KEY: [u8; 32] = [0x5A; 32];
+    const OTHER_KEY: [u8; 32] = [0xA5; 32];
+    /// Length of the app binary, which spans several chunks of the buffer of
+    /// the checker.
+    const BINARY_LENGTH: usize = 100;
+
+    /// Stand-in for SHA-256 and HMAC-SHA256: a deterministic mix of the data,
+    /// seeded with the key in HMAC mode.
+    fn digest_of(key: Option<&[u8; 32]>, data: &[u8]) -> [u8; 32] {
+        let mut digest = key.map_or([0; 32], |key| *key);
+        for (i, byte) in data.iter().enumerate() {
+            let slot = &mut digest[i % 32];
+            *slot = slot.wrapping_mul(31).wrapping_add(*byte) ^ (i as u8);
+        }
+        digest
+    }
+
+    enum Pending {
+        AddData(&'static mut [u8]),
+        Hash(&'static mut [u8; 32]),
+    }
+
+    /// A digest engine completing its operations when `service()` is called,
+    /// like an interrupt driven one.
+    struct MockHmac {
+        client: OptionalCell<&'static dyn digest::Client<'static, [u8; 32]>>,
+        key: Cell<Option<[u8; 32]>>,
+        data: RefCell<Vec<u8>>,
+        pending: RefCell<VecDeque<Pending>>,
+    }
+
+    impl MockHmac {
+        fn new() -> MockHmac {
+            MockHmac {
+                client: OptionalCell::empty(),
+                key: Cell::new(None),
+                data: RefCell::new(Vec::new()),
+                pending: RefCell::new(VecDeque::new()),
+            }
+        }
+
+        /// Completes the pending operations until there are none left.
+        fn service(&self) {
+            loop {
+                let pending = self.pending.borrow_mut().pop_front();
+                match pending {
+                    Some(Pending::AddData(data)) => self
+                        .client
+                        .map(move |client| client.add_data_done(Ok(()), data)),
+                    Some(Pending::Hash(digest)) => self
+                        .client
+                        .map(move |client| client.hash_done(Ok(()), digest)),
+                    None => break,
+                };
+            }
+        }
+    }
+
+    impl Digest<'static, [u8; 32]> for MockHmac {
+        fn set_client(&'static self, client: &'static dyn digest::Client<'static, [u8; 32]>) {
+            self.client.set(client);
+        }
+
+        fn add_data(
+            &self,
+            data: LeasableBuffer<'static, u8>,
+        ) -> Result<usize, (ReturnCode, &'static mut [u8])> {
+            let length = data.len();
+            self.data.borrow_mut().extend((0..length).map(|i| data[i]));
+            self.pending
+                .borrow_mut()
+                .push_back(Pending::AddData(data.take()));
+            Ok(length)
+        }
+
+        fn run(
+            &'static self,
+            digest: &'static mut [u8; 32],
+        ) -> Result<(), (ReturnCode, &'static mut [u8; 32])> {
+            let key = self.key.get();
+            *digest = digest_of(key.as_ref(), &self.data.borrow());
+            self.pending.borrow_mut().push_back(Pending::Hash(digest));
+            Ok(())
+        }
+
+        fn clear_data(&self) {
+            self.key.set(None);
+            self.data.borrow_mut().clear();
+        }
+    }
+
+    impl HMACSha256 for MockHmac {
+        fn set_mode_hmacsha256(&self, key: &[u8; 32]) -> Result<(), ReturnCode> {
+            self.key.set(Some(*key));
+            Ok(())
+        }
+    }
+
+    /// The TBF of an app with a `Credentials` TLV of `format`, or without
+    /// credentials, and an empty footer.
+    fn tbf(format: Option<u32>) -> Vec<u8> {
+        let header_size = if format.is_some() { 24 } else { 16 };
+        let footer_length =
+            format.map_or(0, |format| CredentialsFormat::from(format).footer_length());
+        let total_size = header_size + BINARY_LENGTH + footer_length;
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&2u16.to_le_bytes());
+        header.extend_from_slice(&(header_size as u16).to_le_bytes());
+        header.extend_from_slice(&(total_size as u32).to_le_bytes());
+        // Enabled.
+        header.extend_from_slice(&1u32.to_le_bytes());
+        header.extend_from_slice(&0u32.to_le_bytes());
+        if let Some(format) = format {
+            header.extend_from_slice(&130u16.to_le_bytes());
+            header.extend_from_slice(&4u16.to_le_bytes());
+            header.extend_from_slice(&format.to_le_bytes());
+        }
+        let checksum = header
+            .chunks_exact(4)
+            .enumerate()
+            .filter(|&(i, _)| i != 3)
+            .fold(0, |checksum, (_, word)| {
+                checksum ^ u32::from_le_bytes(word.try_into().unwrap())
+            });
+        header[12..16].copy_from_slice(&checksum.to_le_bytes());
+
+        let mut app = header;
+        app.extend((0..BINARY_LENGTH).map(|i| i as u8));
+        app.resize(total_size, 0);
+        app
+    }
+
+    /// The TBF of an app with `HmacSha256` credentials made with `key`.
+    fn signed_tbf(key: &[u8; 32]) -> Vec<u8> {
+        let mut app = tbf(Some(1));
+        let covered_length = app.len() - 64;
+        let hash = digest_of(None, &app[..covered_length]);
+        let tag = digest_of(Some(key), &hash);
+        app[covered_length..covered_length + 32].copy_from_slice(&hash);
+        app[covered_length + 32..].copy_from_slice(&tag);
+        app
+    }
+
+    type Checker = HmacCredentialsChecker<MockHmac>;
+
+    /// Runs a checker on a flash holding `apps`. Returns the checker, its
+    /// digest engine and the TBF of each app in the flash.
+    fn start(
+        key: Option<[u8; 32]>,
+        require_credentials: bool,
+        apps: &[Vec<u8>],
+    ) -> (&'static Checker, &'static MockHmac, Vec<&'static [u8]>) {
+        let mut flash = Vec::new();
+        for app in apps {
+            flash.extend_from_slice(app);
+        }
+        // The end of the apps.
+        flash.extend_from_slice(&[0; 8]);
+        let flash: &'static [u8] = Box::leak(flash.into_boxed_slice());
+
+        let hmac: &'static MockHmac = Box::leak(Box::new(MockHmac::new()));
+        let verdicts: Vec<Cell<Option<Verdict>>> = (0..4).map(|_| Cell::new(None)).collect();
+        let checker: &'static Checker = Box::leak(Box::new(HmacCredentialsChecker::new(
+            hmac,
+            key,
+            require_credentials,
+            Box::leak(verdicts.into_boxed_slice()),
+            Box::leak(Box::new([0; 32])),
+            Box::leak(Box::new([0; 32])),
+        )));
+        hmac.set_client(checker);
+
+        checker.start(flash);
+        hmac.service();
+        assert!(checker.done());
+
+        let mut offset = 0;
+        let tbfs = apps
+            .iter()
+            .map(|app| {
+                offset += app.len();
+                &flash[offset - app.len()..offset]
+            })
+            .collect();
+        (checker, hmac, tbfs)
+    }
+
+    fn credentials(tbf: &'static [u8]) -> Option<AppCredentials> {
+        let header_length = u16::from_le_bytes([tbf[2], tbf[3]]) as usize;
+        tbfheader::parse_tbf_header_credentials(tbf, header_length, 2).unwrap()
+    }
+
+    /// Runs a checker on a flash holding `apps`, then asks it about each app
+    /// as the kernel does when loading the processes.
+    fn check(
+        key: Option<[u8; 32]>,
+        require_credentials: bool,
+        apps: &[Vec<u8>],
+    ) -> Vec<Result<(), CredentialsError>> {
+        let (checker, _, tbfs) = start(key, require_credentials, apps);
+        tbfs.iter()
+            .map(|&tbf| checker.check_credentials(tbf, credentials(tbf)))
+            .collect()
+    }
+
+    struct MockClient {
+        checked: Cell<Option<Result<(), CredentialsError>>>,
+    }
+
+    impl AppCredentialsClient for MockClient {
+        fn credentials_checked(&self, _app: &'static [u8], result: Result<(), CredentialsError>) {
+            self.checked.set(Some(result));
+        }
+    }
+
+    /// Checks again `tbf` as if it was written after boot. Returns the
+    /// verdict told to the client.
+    fn recheck(
+        checker: &'static Checker,
+        hmac: &MockHmac,
+        tbf: &'static [u8],
+    ) -> Option<Result<(), CredentialsError>> {
+        let client: &'static MockClient = Box::leak(Box::new(MockClient {
+            checked: Cell::new(None),
+        }));
+        checker.set_client(client);
+        assert_eq!(checker.recheck_credentials(tbf), ReturnCode::SUCCESS);
+        hmac.service();
+        client.checked.get()
+    }
+
+    #[test]
+    fn accepts_valid_credentials() {
+        let apps = [signed_tbf(&KEY), signed_tbf(&KEY)];
+        assert_eq!(check(Some(KEY), true, &apps), [Ok(()), Ok(())]);
+    }
+
+    #[test]
+    fn rejects_hash_mismatch() {
+        let mut app = signed_tbf(&KEY);
+        app[30] ^= 1;
+        let apps = [app, signed_tbf(&KEY)];
+        assert_eq!(
+            check(Some(KEY), true, &apps),
+            [Err(CredentialsError::HashMismatch), Ok(())]
+        );
+    }
+
+    #[test]
+    fn rejects_bad_tag() {
+        let apps = [signed_tbf(&OTHER_KEY), signed_tbf(&KEY)];
+        assert_eq!(
+            check(Some(KEY), true, &apps),
+            [Err(CredentialsError::InvalidTag), Ok(())]
+        );
+    }
+
+    #[test]
+    fn missing_credentials_rejected_only_when_required() {
+        let apps = [tbf(None), signed_tbf(&KEY)];
+        assert_eq!(
+            check(Some(KEY), true, &apps),
+            [Err(CredentialsError::Missing), Ok(())]
+        );
+        assert_eq!(check(Some(KEY), false, &apps), [Ok(()), Ok(())]);
+    }
+
+    #[test]
+    fn rejects_unsupported_format() {
+        let apps = [tbf(Some(2)), signed_tbf(&KEY)];
+        assert_eq!(
+            check(Some(KEY), true, &apps),
+            [
+                Err(CredentialsError::UnsupportedFormat(
+                    CredentialsFormat::EcdsaNistP256
+                )),
+                Ok(())
+            ]
+        );
+    }
+
+    #[test]
+    fn authenticates_nothing_without_a_key() {
+        let apps = [signed_tbf(&KEY)];
+        assert_eq!(check(None, true, &apps), [Err(CredentialsError::Unchecked)]);
+    }
+
+    #[test]
+    fn loaded_apps_need_a_fresh_verdict() {
+        let (checker, hmac, tbfs) = start(Some(KEY), true, &[signed_tbf(&KEY)]);
+        let tbf = tbfs[0];
+        assert_eq!(checker.check_credentials(tbf, credentials(tbf)), Ok(()));
+        // The verdict made at boot is stale for an app loaded afterwards.
+        assert_eq!(
+            checker.check_loaded_credentials(tbf, credentials(tbf)),
+            Err(CredentialsError::Unchecked)
+        );
+
+        assert_eq!(recheck(checker, hmac, tbf), Some(Ok(())));
+        assert_eq!(
+            checker.check_loaded_credentials(tbf, credentials(tbf)),
+            Ok(())
+        );
+        // A verdict is used by one load only.
+        assert_eq!(
+            checker.check_loaded_credentials(tbf, credentials(tbf)),
+            Err(CredentialsError::Unchecked)
+        );
+    }
+
+    #[test]
+    fn recheck_reports_rejected_apps() {
+        let mut tampered = signed_tbf(&KEY);
+        tampered[30] ^= 1;
+        let (checker, hmac, tbfs) = start(
+            Some(KEY),
+            true,
+            &[
+                signed_tbf(&KEY),
+                tampered,
+                signed_tbf(&OTHER_KEY),
+                tbf(None),
+            ],
+        );
+
+        assert_eq!(recheck(checker, hmac, tbfs[0]), Some(Ok(())));
+        assert_eq!(
+            recheck(checker, hmac, tbfs[1]),
+            Some(Err(CredentialsError::HashMismatch))
+        );
+        assert_eq!(
+            checker.check_loaded_credentials(tbfs[1], credentials(tbfs[1])),
+            Err(CredentialsError::HashMismatch)
+        );
+        assert_eq!(
+            recheck(checker, hmac, tbfs[2]),
+            Some(Err(CredentialsError::InvalidTag))
+        );
+        // Apps without credentials need no hashing.
+        assert_eq!(checker.recheck_credentials(tbfs[3]), ReturnCode::EINVAL);
+        assert_eq!(
+            checker.check_loaded_credentials(tbfs[3], None),
+            Err(CredentialsError::Missing)
+        );
+    }
+}
@@ -72,6 +72,10 @@ pub enum NUM {
     Screen                = 0x90001,
     Touch                 = 0x90002,
     VppDriver             = 0x90100,
+    VppMgtDriver          = 0x90101,
+    VppNvmDriver          = 0x90102,
+    VppTimerDriver        = 0x90103,
+    VppVreDriver          = 0x90104,
    
 }
 }
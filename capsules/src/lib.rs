@@ -14,6 +14,7 @@ pub mod ambient_light;
 pub mod analog_comparator;
 pub mod analog_sensor;
 pub mod apds9960;
+pub mod app_credentials;
 pub mod app_flash_driver;
 pub mod ble_advertising_driver;
 pub mod button;
@@ -64,6 +65,7 @@ pub mod si7021;
 pub mod spi_controller;
 pub mod spi_peripheral;
 pub mod st7735;
+pub mod syscall_trace;
 pub mod temperature;
 pub mod temperature_stm;
 pub mod touch;
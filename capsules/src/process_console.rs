@@ -28,6 +28,11 @@
 //! - `State`: The state the process is in.
 //! - `Grants`: The number of grants that have been initialized for the process
 //!   out of the total number of grants defined by the kernel.
+//! - `CPU`: How long the process has executed, in milliseconds. Only the time
+//!   the process runs with a timeslice is measured: it is `-` for a process
+//!   which never ran with one, as with a cooperative scheduler.
+//! - `Stack`: The deepest the stack of the process went, in bytes.
+//! - `Grant Mem`: How many bytes of grant memory the process holds.
 //!
 //! Setup
 //! -----
@@ -90,9 +95,9 @@
 //! Initialization complete. Entering main loop
 //! Hello World!
 //! list
-//! PID    Name    Quanta  Syscalls  Dropped Callbacks  Restarts    State  Grants
-//! 00     blink        0       113                  0         0  Yielded    1/12
-//! 01     c_hello      0         8                  0         0  Yielded    3/12
+//! PID    Name    Quanta  Syscalls  Dropped Callbacks  Restarts    State  Grants    CPU  Stack  Grant Mem
+//! 00     blink        0       113                  0         0  Yielded    1/12     12    344         84
+//! 01     c_hello      0         8                  0         0  Yielded    3/12      1    216        172
 //! ```
 //!
 //! To get a general view of the system, use the status command:
@@ -102,8 +107,11 @@
 //! Total processes: 2
 //! Active processes: 2
 //! Timeslice expirations: 0
+//! Process CPU time: 13 ms
 //! ```
 //!
+//! The CPU time is left out when no process ran with a timeslice.
+//!
 //! and you can control processes with the `start` and `stop` commands:
 //!
 //! ```text
@@ -114,6 +122,7 @@
 #![allow(unused_imports)]
 use core::cell::Cell;
 use core::cmp;
+use core::fmt;
 use core::str;
 use kernel::capabilities::ProcessManagementCapability;
 use kernel::common::cells::TakeCell;
@@ -135,6 +144,19 @@ pub static mut READ_BUF: [u8; 4] = [0; 4];
 // characters, limiting arguments to 25 bytes or so seems fine for now.
 pub static mut COMMAND_BUF: [u8; 32] = [0; 32];
 
+/// Formats a CPU time in microseconds as milliseconds, `-` if it was not
+/// measured.
+struct CpuTime(Option<u64>);
+
+impl fmt::Display for CpuTime {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(us) => fmt::Display::fmt(&(us / 1000), f),
+            None => f.pad("-"),
+        }
+    }
+}
+
 pub struct ProcessConsole<'a, C: ProcessManagementCapability> {
     uart: &'a dyn uart::UartData<'a>,
     tx_in_progress: Cell<bool>,
@@ -275,7 +297,7 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                             });
                         }
                         else if clean_str.starts_with("list") {
-                            debug!(" PID    Name                Quanta  Syscalls  Dropped Callbacks  Restarts    State  Grants");
+                            debug!(" PID    Name                Quanta  Syscalls  Dropped Callbacks  Restarts    State  Grants    CPU  Stack  Grant Mem");
                             self.kernel
                                 .process_each_capability(&self.capability, |proc| {
                                     let info: KernelInfo = KernelInfo::new(self.kernel);
@@ -285,7 +307,7 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                                     let (grants_used, grants_total) = info.number_app_grant_uses(appid, &self.capability);
 
                                     debug!(
-                                        "  {:?}\t{:<20}{:6}{:10}{:19}{:10}  {:?}{:5}/{}",
+                                        "  {:?}\t{:<20}{:6}{:10}{:19}{:10}  {:?}{:5}/{}{:>7}{:7}{:11}",
                                         appid,
                                         pname,
                                         proc.debug_timeslice_expiration_count(),
@@ -294,7 +316,10 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                                         proc.get_restart_count(),
                                         proc.get_state(),
                                         grants_used,
-                                        grants_total
+                                        grants_total,
+                                        CpuTime(info.app_cpu_time_us(appid, &self.capability)),
+                                        info.app_max_stack_depth(appid, &self.capability),
+                                        info.app_grant_memory_size(appid, &self.capability)
                                     );
                                 });
                         }
@@ -312,6 +337,9 @@ impl<'a, C: ProcessManagementCapability> ProcessConsole<'a, C> {
                                 "Timeslice expirations: {}",
                                 info.timeslice_expirations(&self.capability)
                             );
+                            if let Some(us) = info.cpu_time_us(&self.capability) {
+                                debug!("Process CPU time: {} ms", us / 1000);
+                            }
                         }
                         else {
                             debug!("Valid commands are: help status list stop start fault");
@@ -0,0 +1,143 @@
+//! Sends the syscall trace of the kernel to the host over a UART.
+//!
+//! The kernel records the system calls of the processes in a
+//! `kernel::syscall_trace::SyscallTraceBuffer` when
+//! `kernel::config::CONFIG.trace_syscalls` is set. `SyscallTraceUart` drains
+//! that buffer as binary records over any `hil::uart::Transmit`, a UART of its
+//! own or a Segger RTT channel, and `tools/syscall_trace_decode` turns them
+//! back into a readable trace. The records must not share the UART of the
+//! console, their bytes would be mixed with the text.
+//!
+//! The stream starts with a `Start` record giving the frequency of the
+//! timestamps. A `Lost` record tells how many records were dropped while the
+//! buffer was full.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//! static mut SYSCALL_TRACE: [SyscallTraceRecord; 64] = [SyscallTraceRecord::EMPTY; 64];
+//! static mut SYSCALL_TRACE_BUFFER: [u8; 512] = [0; 512];
+//!
+//!     let trace_buffer = static_init!(
+//!         SyscallTraceBuffer<'static, VirtualMuxAlarm<'static, Alarm>>,
+//!         SyscallTraceBuffer::new(trace_alarm, &mut SYSCALL_TRACE)
+//!     );
+//!     let trace_uart = static_init!(
+//!         capsules::syscall_trace::SyscallTraceUart<
+//!             'static,
+//!             VirtualMuxAlarm<'static, Alarm>,
+//!             SeggerRtt<'static, VirtualMuxAlarm<'static, Alarm>>,
+//!         >,
+//!         capsules::syscall_trace::SyscallTraceUart::new(
+//!             trace_buffer,
+//!             rtt,
+//!             &mut SYSCALL_TRACE_BUFFER,
+//!         )
+//!     );
+//!     trace_buffer.set_client(trace_uart);
+//!     hil::uart::Transmit::set_transmit_client(rtt, trace_uart);
+//!     board_kernel.set_syscall_trace(trace_buffer, &process_mgmt_cap);
+//!     trace_uart.start();
+//! ```
+
+use core::cell::Cell;
+use core::convert::TryInto;
+use kernel::common::cells::TakeCell;
+use kernel::hil::time::Time;
+use kernel::hil::uart;
+use kernel::syscall_trace::{
+    SyscallTraceBuffer, SyscallTraceClient, SyscallTraceRecord, RECORD_LENGTH,
+};
+use kernel::ReturnCode;
+
+pub struct SyscallTraceUart<'a, T: Time, U: uart::Transmit<'a>> {
+    trace: &'a SyscallTraceBuffer<'a, T>,
+    uart: &'a U,
+    /// Holds the records being sent, it must fit at least one record.
+    tx_buffer: TakeCell<'static, [u8]>,
+    started: Cell<bool>,
+    /// Whether the `Start` record still has to be sent.
+    send_start: Cell<bool>,
+}
+
+impl<'a, T: Time, U: uart::Transmit<'a>> SyscallTraceUart<'a, T, U> {
+    pub fn new(
+        trace: &'a SyscallTraceBuffer<'a, T>,
+        uart: &'a U,
+        tx_buffer: &'static mut [u8],
+    ) -> SyscallTraceUart<'a, T, U> {
+        SyscallTraceUart {
+            trace,
+            uart,
+            tx_buffer: TakeCell::new(tx_buffer),
+            started: Cell::new(false),
+            send_start: Cell::new(true),
+        }
+    }
+
+    /// Starts sending the records, beginning with the `Start` record. The
+    /// records are kept in the buffer until then.
+    pub fn start(&self) {
+        self.started.set(true);
+        self.send();
+    }
+
+    /// Sends as many records as fit in the transmit buffer, if it is not
+    /// being sent already. Called for every new record, which also retries
+    /// after the UART refused a batch.
+    fn send(&self) {
+        if !self.started.get() {
+            return;
+        }
+        if let Some(buffer) = self.tx_buffer.take() {
+            let capacity = buffer.len() / RECORD_LENGTH;
+            let mut count = 0;
+            while count < capacity {
+                let record = if self.send_start.get() {
+                    self.send_start.set(false);
+                    SyscallTraceRecord::start(self.trace.frequency())
+                } else {
+                    match self.trace.take_lost() {
+                        0 => match self.trace.dequeue() {
+                            Some(record) => record,
+                            None => break,
+                        },
+                        lost => SyscallTraceRecord::lost(lost),
+                    }
+                };
+                let offset = count * RECORD_LENGTH;
+                if let Ok(slot) = (&mut buffer[offset..offset + RECORD_LENGTH]).try_into() {
+                    record.encode(slot);
+                }
+                count += 1;
+            }
+
+            if count == 0 {
+                self.tx_buffer.replace(buffer);
+                return;
+            }
+            let (result, buffer) = self.uart.transmit_buffer(buffer, count * RECORD_LENGTH);
+            if result != ReturnCode::SUCCESS {
+                // The records of this batch are dropped, the next record sends
+                // the ones left in the buffer.
+                if let Some(buffer) = buffer {
+                    self.tx_buffer.replace(buffer);
+                }
+            }
+        }
+    }
+}
+
+impl<'a, T: Time, U: uart::Transmit<'a>> SyscallTraceClient for SyscallTraceUart<'a, T, U> {
+    fn records_available(&self) {
+        self.send();
+    }
+}
+
+impl<'a, T: Time, U: uart::Transmit<'a>> uart::TransmitClient for SyscallTraceUart<'a, T, U> {
+    fn transmitted_buffer(&self, tx_buffer: &'static mut [u8], _tx_len: usize, _rval: ReturnCode) {
+        self.tx_buffer.replace(tx_buffer);
+        self.send();
+    }
+}
@@ -5,7 +5,7 @@ use core::cell::Cell;
 use core::marker::PhantomData;
 use kernel::common::cells::OptionalCell;
 use kernel::common::leasable_buffer::LeasableBuffer;
-use kernel::common::{ListLink, ListNode};
+use kernel::common::{List, ListLink, ListNode};
 use kernel::hil::digest;
 use kernel::hil::digest::DigestType;
 use kernel::ReturnCode;
@@ -45,7 +45,8 @@ impl<'a, A: digest::Digest<'a, T>, T: DigestType> digest::Digest<'a, T>
     /// Set the client instance which will receive `add_data_done()` and
     /// `hash_done()` callbacks
     fn set_client(&'a self, client: &'a dyn digest::Client<'a, T>) {
-        self.mux.hmac.set_client(client);
+        self.mux.users.push_head(self);
+        self.client.set(client);
     }
 
     /// Add data to the hmac IP.
@@ -126,6 +127,7 @@ impl<'a, A: digest::Digest<'a, T> + digest::HMACSha256, T: DigestType> digest::H
 
 pub struct MuxHmac<'a, A: digest::Digest<'a, T>, T: DigestType> {
     hmac: &'a A,
+    users: List<'a, VirtualMuxHmac<'a, A, T>>,
     running: Cell<bool>,
     running_id: Cell<u32>,
     next_id: Cell<u32>,
@@ -136,6 +138,7 @@ impl<'a, A: digest::Digest<'a, T>, T: DigestType> MuxHmac<'a, A, T> {
     pub const fn new(hmac: &'a A) -> MuxHmac<'a, A, T> {
         MuxHmac {
             hmac,
+            users: List::new(),
             running: Cell::new(false),
             running_id: Cell::new(0),
             next_id: Cell::new(0),
@@ -143,3 +146,23 @@ impl<'a, A: digest::Digest<'a, T>, T: DigestType> MuxHmac<'a, A, T> {
         }
     }
 }
+
+/// The HMAC hardware reports to the mux, which forwards the callbacks to the
+/// virtual device running the current operation.
+impl<'a, A: digest::Digest<'a, T>, T: DigestType> digest::Client<'a, T> for MuxHmac<'a, A, T> {
+    fn add_data_done(&'a self, result: Result<(), ReturnCode>, data: &'static mut [u8]) {
+        let id = self.running_id.get();
+        self.users
+            .iter()
+            .find(|user| user.id == id)
+            .map(move |user| user.add_data_done(result, data));
+    }
+
+    fn hash_done(&'a self, result: Result<(), ReturnCode>, digest: &'static mut T) {
+        let id = self.running_id.get();
+        self.users
+            .iter()
+            .find(|user| user.id == id)
+            .map(move |user| user.hash_done(result, digest));
+    }
+}
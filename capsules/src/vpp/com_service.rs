@@ -0,0 +1,516 @@
+//! COM service of the VPP: bridges a host link to the MAIN Process.
+//!
+//! The host exchanges frames with the VPP over a `hil::uart` transport, a UART or
+//! the USB CDC ACM of `capsules::usb::cdc`:
+//!
+//! ```text
+//! 0      1      2               4                 4+n           6+n
+//! +------+------+---------------+-----------------+-------------+
+//! | 0xA5 | type | length n (LE) | payload         | CRC-16 (LE) |
+//! +------+------+---------------+-----------------+-------------+
+//! ```
+//!
+//! The CRC is the CRC-16/CCITT-FALSE of the type, the length and the payload. The
+//! host sends `FrameType::Command` frames, the VPP answers with `FrameType::Response`
+//! frames, or with a `FrameType::Error` frame whose payload is a `ComError`.
+//!
+//! The payload of a command is written, prefixed by its length (2 bytes, LE), into
+//! the COM to MAIN IPC and `MK_SIGNAL_COM_REQUEST` is sent to the COM to MAIN Mailbox.
+//! The COM Process only allows the memory of that IPC, of which it is the writer.
+//! The MAIN Process answers the same way: it writes the response, prefixed by its
+//! length, into the MAIN to COM IPC and sends `MK_SIGNAL_COM_RESPONSE` to the MAIN to
+//! COM Mailbox. A command is refused with `ComError::Busy` while the MAIN Process
+//! holds the COM to MAIN IPC.
+//!
+//! If the transport refuses to start a reception, the COM service tries again from
+//! a deferred call, so the link keeps listening.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//!     let com_uart = static_init!(UartDevice, UartDevice::new(uart_mux, true));
+//!     com_uart.setup();
+//!     let com_service = static_init!(
+//!         capsules::vpp::com_service::ComService<'static, UartDevice<'static>>,
+//!         capsules::vpp::com_service::ComService::new(
+//!             vpp_kernel,
+//!             com_uart,
+//!             &mut COM_RX_BUFFER,
+//!             &mut COM_TX_BUFFER,
+//!             dynamic_deferred_caller,
+//!         )
+//!     );
+//!     com_service.initialize_callback_handle(
+//!         dynamic_deferred_caller
+//!             .register(com_service)
+//!             .expect("no deferred call slot available for the COM service"),
+//!     );
+//!     hil::uart::Transmit::set_transmit_client(com_uart, com_service);
+//!     hil::uart::Receive::set_receive_client(com_uart, com_service);
+//!     vpp_kernel.set_com_bridge(com_service);
+//!     com_service.start();
+//! ```
+
+use crate::vpp::mloi::{
+    MK_BITMAP_t, MK_MAILBOX_ID_u, MK_IPC_COM_MAIN_ID, MK_IPC_MAIN_COM_ID, MK_MAILBOX_COM_MAIN_ID,
+    MK_MAILBOX_MAIN_COM_ID,
+};
+use crate::vpp::vppkernel::VppKernel;
+use core::cell::Cell;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::dynamic_deferred_call::{
+    DeferredCallHandle, DynamicDeferredCall, DynamicDeferredCallClient,
+};
+use kernel::hil::uart;
+use kernel::ReturnCode;
+
+/// Signal sent to the COM to MAIN Mailbox when a command is in the COM to MAIN IPC
+/// (`MK_SIGNAL_DOMAIN_BASE_1`).
+pub const MK_SIGNAL_COM_REQUEST: MK_BITMAP_t = 0x0000_0010;
+/// Signal sent by the MAIN Process to the MAIN to COM Mailbox when a response is in
+/// the MAIN to COM IPC (`MK_SIGNAL_DOMAIN_BASE_2`).
+pub const MK_SIGNAL_COM_RESPONSE: MK_BITMAP_t = 0x0000_0020;
+
+/// Longest payload of a frame.
+pub const COM_MAX_PAYLOAD: usize = 256;
+const COM_SYNC: u8 = 0xA5;
+const COM_HEADER_SIZE: usize = 4;
+const COM_CRC_SIZE: usize = 2;
+/// Size of the receive and transmit buffers of the COM service.
+pub const COM_BUFFER_SIZE: usize = COM_HEADER_SIZE + COM_MAX_PAYLOAD + COM_CRC_SIZE;
+/// Size of the length prefixing a message in an IPC.
+const IPC_LENGTH_SIZE: usize = 2;
+
+/// Type of a frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameType {
+    Command = 1,
+    Response = 2,
+    Error = 3,
+}
+
+/// Payload of a `FrameType::Error` frame.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ComError {
+    /// The CRC of the command is wrong.
+    Crc = 1,
+    /// The command or the response does not fit in a frame or in the IPC.
+    TooLong = 2,
+    /// The MAIN Process still holds the previous command.
+    Busy = 3,
+    /// The IPC or the Process at the other side is missing.
+    Unreachable = 4,
+    /// The host sent a frame that is not a command.
+    Type = 5,
+}
+
+/// Notification of the Signals sent to the Mailboxes, as used by the COM service.
+pub trait ComBridge {
+    /// `signals` were sent to the Mailbox `mailbox`.
+    fn signal(&self, mailbox: MK_MAILBOX_ID_u, signals: MK_BITMAP_t);
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum RxState {
+    /// Waiting for the synchronization byte.
+    Sync,
+    /// Receiving the type and the length.
+    Header,
+    /// Receiving the payload and the CRC.
+    Body { tipe: u8, length: usize },
+}
+
+impl RxState {
+    /// How many bytes to receive in this state.
+    fn length(self) -> usize {
+        match self {
+            RxState::Sync => 1,
+            RxState::Header => COM_HEADER_SIZE - 1,
+            RxState::Body { length, .. } => length + COM_CRC_SIZE,
+        }
+    }
+}
+
+pub struct ComService<'a, U: uart::UartData<'a>> {
+    vpp_kernel: &'static VppKernel,
+    uart: &'a U,
+    rx_buffer: TakeCell<'static, [u8]>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    rx_state: Cell<RxState>,
+    /// A response waits for the end of the current transmission.
+    response_pending: Cell<bool>,
+    /// An error frame waits for the end of the current transmission.
+    error_pending: Cell<Option<ComError>>,
+    deferred_caller: &'a DynamicDeferredCall,
+    /// Handle of the deferred call retrying a reception the transport refused.
+    handle: OptionalCell<DeferredCallHandle>,
+}
+
+impl<'a, U: uart::UartData<'a>> ComService<'a, U> {
+    pub fn new(
+        vpp_kernel: &'static VppKernel,
+        uart: &'a U,
+        rx_buffer: &'static mut [u8; COM_BUFFER_SIZE],
+        tx_buffer: &'static mut [u8; COM_BUFFER_SIZE],
+        deferred_caller: &'a DynamicDeferredCall,
+    ) -> ComService<'a, U> {
+        ComService {
+            vpp_kernel: vpp_kernel,
+            uart: uart,
+            rx_buffer: TakeCell::new(rx_buffer),
+            tx_buffer: TakeCell::new(tx_buffer),
+            rx_state: Cell::new(RxState::Sync),
+            response_pending: Cell::new(false),
+            error_pending: Cell::new(None),
+            deferred_caller: deferred_caller,
+            handle: OptionalCell::empty(),
+        }
+    }
+
+    pub fn initialize_callback_handle(&self, handle: DeferredCallHandle) {
+        self.handle.replace(handle);
+    }
+
+    /// Starts receiving frames. Must be called once at boot.
+    pub fn start(&self) {
+        self.receive(RxState::Sync);
+    }
+
+    /// Receives the bytes of `state`. If the transport refuses, the reception is
+    /// retried from a deferred call.
+    fn receive(&self, state: RxState) {
+        self.rx_state.set(state);
+        self.rx_buffer.take().map(|buffer| {
+            let (result, buffer) = self.uart.receive_buffer(buffer, state.length());
+            buffer.map(|buffer| self.rx_buffer.replace(buffer));
+            if result != ReturnCode::SUCCESS {
+                self.handle.map(|handle| self.deferred_caller.set(*handle));
+            }
+        });
+    }
+
+    /// Writes the payload of a command into the COM to MAIN IPC and signals the MAIN
+    /// Process.
+    fn deliver(&self, payload: &[u8]) -> Result<(), ComError> {
+        let ipc = self
+            .vpp_kernel
+            ._mk_Get_IPC_Handle(MK_IPC_COM_MAIN_ID)
+            .and_then(|handle| self.vpp_kernel.resolve_ipc(handle).ok())
+            .ok_or(ComError::Unreachable)?;
+        if ipc.get_holder().is_some() {
+            return Err(ComError::Busy);
+        }
+        let writer = self
+            .vpp_kernel
+            .process_appid(ipc.get_writer_proc_i())
+            .ok_or(ComError::Unreachable)?;
+        let capacity = ipc.get_ipc_len() as usize;
+        ipc.data
+            .enter(writer, |data, _| match data.shared_memory.as_mut() {
+                Some(memory) => {
+                    let length = IPC_LENGTH_SIZE + payload.len();
+                    if length > capacity || length > memory.len() {
+                        return Err(ComError::TooLong);
+                    }
+                    let memory = memory.as_mut();
+                    memory[..IPC_LENGTH_SIZE]
+                        .copy_from_slice(&(payload.len() as u16).to_le_bytes());
+                    memory[IPC_LENGTH_SIZE..length].copy_from_slice(payload);
+                    Ok(())
+                }
+                None => Err(ComError::Unreachable),
+            })
+            .unwrap_or(Err(ComError::Unreachable))?;
+        self.vpp_kernel
+            ._mk_Get_Mailbox_Handle(MK_MAILBOX_COM_MAIN_ID)
+            .map(|handle| self.vpp_kernel._mk_Send_Signal(handle, MK_SIGNAL_COM_REQUEST))
+            .ok_or(ComError::Unreachable)?;
+        Ok(())
+    }
+
+    /// Frames the response found in the MAIN to COM IPC into `buffer`, returns the
+    /// length of the frame.
+    fn frame_response(&self, buffer: &mut [u8]) -> Result<usize, ComError> {
+        let ipc = self
+            .vpp_kernel
+            ._mk_Get_IPC_Handle(MK_IPC_MAIN_COM_ID)
+            .and_then(|handle| self.vpp_kernel.resolve_ipc(handle).ok())
+            .ok_or(ComError::Unreachable)?;
+        let writer = self
+            .vpp_kernel
+            .process_appid(ipc.get_writer_proc_i())
+            .ok_or(ComError::Unreachable)?;
+        ipc.data
+            .enter(writer, |data, _| match data.shared_memory.as_ref() {
+                Some(memory) => {
+                    let memory = memory.as_ref();
+                    if memory.len() < IPC_LENGTH_SIZE {
+                        return Err(ComError::Unreachable);
+                    }
+                    let length = u16::from_le_bytes([memory[0], memory[1]]) as usize;
+                    let payload = memory
+                        .get(IPC_LENGTH_SIZE..IPC_LENGTH_SIZE + length)
+                        .filter(|payload| payload.len() <= COM_MAX_PAYLOAD)
+                        .ok_or(ComError::TooLong)?;
+                    Ok(encode_frame(buffer, FrameType::Response, payload))
+                }
+                None => Err(ComError::Unreachable),
+            })
+            .unwrap_or(Err(ComError::Unreachable))
+    }
+
+    /// Sends the pending error frame or response, if the transmitter is idle.
+    fn transmit_pending(&self) {
+        let buffer = match self.tx_buffer.take() {
+            Some(buffer) => buffer,
+            None => return,
+        };
+        let length = if let Some(error) = self.error_pending.take() {
+            encode_frame(buffer, FrameType::Error, &[error as u8])
+        } else if self.response_pending.replace(false) {
+            match self.frame_response(buffer) {
+                Ok(length) => length,
+                Err(error) => encode_frame(buffer, FrameType::Error, &[error as u8]),
+            }
+        } else {
+            self.tx_buffer.replace(buffer);
+            return;
+        };
+        let (_, buffer) = self.uart.transmit_buffer(buffer, length);
+        buffer.map(|buffer| self.tx_buffer.replace(buffer));
+    }
+
+    fn report(&self, error: ComError) {
+        self.error_pending.set(Some(error));
+        self.transmit_pending();
+    }
+}
+
+impl<'a, U: uart::UartData<'a>> ComBridge for ComService<'a, U> {
+    fn signal(&self, mailbox: MK_MAILBOX_ID_u, signals: MK_BITMAP_t) {
+        if mailbox == MK_MAILBOX_MAIN_COM_ID && signals & MK_SIGNAL_COM_RESPONSE != 0 {
+            self.response_pending.set(true);
+            self.transmit_pending();
+        }
+    }
+}
+
+impl<'a, U: uart::UartData<'a>> uart::TransmitClient for ComService<'a, U> {
+    fn transmitted_buffer(&self, tx_buffer: &'static mut [u8], _tx_len: usize, _rval: ReturnCode) {
+        self.tx_buffer.replace(tx_buffer);
+        self.transmit_pending();
+    }
+}
+
+impl<'a, U: uart::UartData<'a>> uart::ReceiveClient for ComService<'a, U> {
+    fn received_buffer(
+        &self,
+        rx_buffer: &'static mut [u8],
+        rx_len: usize,
+        rval: ReturnCode,
+        _error: uart::Error,
+    ) {
+        if rval != ReturnCode::SUCCESS {
+            self.rx_buffer.replace(rx_buffer);
+            self.receive(RxState::Sync);
+            return;
+        }
+        let next = match self.rx_state.get() {
+            RxState::Sync if rx_buffer[0] == COM_SYNC => RxState::Header,
+            RxState::Sync => RxState::Sync,
+            RxState::Header => {
+                let tipe = rx_buffer[0];
+                let length = u16::from_le_bytes([rx_buffer[1], rx_buffer[2]]) as usize;
+                if length > COM_MAX_PAYLOAD {
+                    self.report(ComError::TooLong);
+                    RxState::Sync
+                } else {
+                    RxState::Body { tipe, length }
+                }
+            }
+            RxState::Body { tipe, length } => {
+                let header = [tipe, length as u8, (length >> 8) as u8];
+                let crc = crc16(crc16(0xFFFF, &header), &rx_buffer[..length]);
+                let result = if rx_len != length + COM_CRC_SIZE
+                    || u16::from_le_bytes([rx_buffer[length], rx_buffer[length + 1]]) != crc
+                {
+                    Err(ComError::Crc)
+                } else if tipe != FrameType::Command as u8 {
+                    Err(ComError::Type)
+                } else {
+                    self.deliver(&rx_buffer[..length])
+                };
+                if let Err(error) = result {
+                    self.report(error);
+                }
+                RxState::Sync
+            }
+        };
+        self.rx_buffer.replace(rx_buffer);
+        self.receive(next);
+    }
+}
+
+impl<'a, U: uart::UartData<'a>> DynamicDeferredCallClient for ComService<'a, U> {
+    fn call(&self, _handle: DeferredCallHandle) {
+        self.receive(self.rx_state.get());
+    }
+}
+
+/// Encodes a frame of type `tipe` carrying `payload` into `buffer`, returns its length.
+fn encode_frame(buffer: &mut [u8], tipe: FrameType, payload: &[u8]) -> usize {
+    let length = payload.len();
+    buffer[0] = COM_SYNC;
+    buffer[1] = tipe as u8;
+    buffer[2..COM_HEADER_SIZE].copy_from_slice(&(length as u16).to_le_bytes());
+    buffer[COM_HEADER_SIZE..COM_HEADER_SIZE + length].copy_from_slice(payload);
+    let crc = crc16(0xFFFF, &buffer[1..COM_HEADER_SIZE + length]);
+    buffer[COM_HEADER_SIZE + length..COM_HEADER_SIZE + length + COM_CRC_SIZE]
+        .copy_from_slice(&crc.to_le_bytes());
+    COM_HEADER_SIZE + length + COM_CRC_SIZE
+}
+
+/// CRC-16/CCITT-FALSE of `data`, continuing from `crc` (0xFFFF to start).
+fn crc16(mut crc: u16, data: &[u8]) -> u16 {
+    for &byte in data {
+        crc ^= (byte as u16) << 8;
+        for _ in 0..8 {
+            crc = if crc & 0x8000 != 0 {
+                (crc << 1) ^ 0x1021
+            } else {
+                crc << 1
+            };
+        }
+    }
+    crc
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use crate::vpp::sim::SimBuilder;
+    use kernel::common::dynamic_deferred_call::DynamicDeferredCallClientState;
+    use std::boxed::Box;
+
+    /// Transport refusing the first `refusals` receptions, which keeps the buffer
+    /// of the reception in progress.
+    struct SimUart {
+        refusals: Cell<usize>,
+        rx_buffer: TakeCell<'static, [u8]>,
+    }
+
+    impl<'a> uart::Transmit<'a> for SimUart {
+        fn set_transmit_client(&self, _client: &'a dyn uart::TransmitClient) {}
+
+        fn transmit_buffer(
+            &self,
+            tx_buffer: &'static mut [u8],
+            _tx_len: usize,
+        ) -> (ReturnCode, Option<&'static mut [u8]>) {
+            (ReturnCode::FAIL, Some(tx_buffer))
+        }
+
+        fn transmit_word(&self, _word: u32) -> ReturnCode {
+            ReturnCode::FAIL
+        }
+
+        fn transmit_abort(&self) -> ReturnCode {
+            ReturnCode::SUCCESS
+        }
+    }
+
+    impl<'a> uart::Receive<'a> for SimUart {
+        fn set_receive_client(&self, _client: &'a dyn uart::ReceiveClient) {}
+
+        fn receive_buffer(
+            &self,
+            rx_buffer: &'static mut [u8],
+            _rx_len: usize,
+        ) -> (ReturnCode, Option<&'static mut [u8]>) {
+            if self.refusals.get() > 0 {
+                self.refusals.set(self.refusals.get() - 1);
+                return (ReturnCode::EBUSY, Some(rx_buffer));
+            }
+            self.rx_buffer.replace(rx_buffer);
+            (ReturnCode::SUCCESS, None)
+        }
+
+        fn receive_word(&self) -> ReturnCode {
+            ReturnCode::FAIL
+        }
+
+        fn receive_abort(&self) -> ReturnCode {
+            ReturnCode::SUCCESS
+        }
+    }
+
+    impl<'a> uart::UartData<'a> for SimUart {}
+
+    #[test]
+    fn a_refused_reception_is_retried() {
+        let sim = SimBuilder::new().build();
+        let uart: &'static SimUart = Box::leak(Box::new(SimUart {
+            refusals: Cell::new(1),
+            rx_buffer: TakeCell::empty(),
+        }));
+        let clients: &'static [DynamicDeferredCallClientState] =
+            Box::leak(Box::new([DynamicDeferredCallClientState::default()]));
+        let deferred_caller: &'static DynamicDeferredCall =
+            Box::leak(Box::new(DynamicDeferredCall::new(clients)));
+        let com_service: &'static ComService<SimUart> = Box::leak(Box::new(ComService::new(
+            sim.vpp_kernel,
+            uart,
+            Box::leak(Box::new([0; COM_BUFFER_SIZE])),
+            Box::leak(Box::new([0; COM_BUFFER_SIZE])),
+            deferred_caller,
+        )));
+        let handle = deferred_caller.register(com_service).unwrap();
+        com_service.initialize_callback_handle(handle);
+
+        com_service.start();
+        assert!(uart.rx_buffer.is_none());
+        assert!(deferred_caller.has_pending());
+
+        com_service.call(handle);
+        assert!(uart.rx_buffer.is_some());
+    }
+
+    #[test]
+    fn crc16_is_the_ccitt_false_crc() {
+        assert_eq!(crc16(0xFFFF, b""), 0xFFFF);
+        // Check value of CRC-16/CCITT-FALSE.
+        assert_eq!(crc16(0xFFFF, b"123456789"), 0x29B1);
+        // The receiver computes it over the header, then over the payload.
+        assert_eq!(crc16(crc16(0xFFFF, b"1234"), b"56789"), 0x29B1);
+    }
+
+    #[test]
+    fn a_frame_is_the_header_the_payload_and_the_crc() {
+        let mut buffer = [0; COM_BUFFER_SIZE];
+        let length = encode_frame(&mut buffer, FrameType::Response, &[1, 2, 3]);
+        assert_eq!(length, 9);
+        assert_eq!(buffer[..length], [0xA5, 2, 3, 0, 1, 2, 3, 0xB3, 0x0A]);
+
+        let length = encode_frame(&mut buffer, FrameType::Error, &[ComError::Busy as u8]);
+        assert_eq!(buffer[..length], [0xA5, 3, 1, 0, 3, 0x4F, 0x18]);
+    }
+
+    #[test]
+    fn the_longest_payload_fills_the_buffer() {
+        let mut buffer = [0; COM_BUFFER_SIZE];
+        let payload = [0x5A; COM_MAX_PAYLOAD];
+        let length = encode_frame(&mut buffer, FrameType::Command, &payload);
+        assert_eq!(length, COM_BUFFER_SIZE);
+        assert_eq!(buffer[2..4], [0x00, 0x01]);
+        assert_eq!(buffer[4..length - 2], payload[..]);
+        let crc = crc16(0xFFFF, &buffer[1..length - 2]);
+        assert_eq!(buffer[length - 2..], crc.to_le_bytes());
+
+        let length = encode_frame(&mut buffer, FrameType::Command, &[]);
+        assert_eq!(length, COM_HEADER_SIZE + COM_CRC_SIZE);
+        assert_eq!(buffer[..length], [0xA5, 1, 0, 0, 0xAC, 0xFB]);
+    }
+}
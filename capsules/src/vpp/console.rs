@@ -0,0 +1,416 @@
+//! Management console of the VPP.
+//!
+//! The console inspects and controls the VPP objects over a UART: the Processes,
+//! the Mailboxes and the IPCs. The console is privileged: its control commands call
+//! the MLOI functions of the `VppKernel` directly, without the caller and parent
+//! checks `vpp_kernel_driver` makes for the Processes (`check_process` and
+//! `check_mailbox`), so it can suspend, signal or reprioritize any Process. Boards
+//! must only expose it to a trusted operator.
+//!
+//! Setup
+//! -----
+//!
+//! The board creates the console with the `vpp_console` component:
+//!
+//! ```rust
+//! let vpp_console = components::vpp_console::VppConsoleComponent::new(vpp_kernel, uart_mux)
+//!     .finalize(());
+//! vpp_console.start();
+//! ```
+//!
+//! Commands
+//! --------
+//!
+//! Identifiers are decimal or hexadecimal with a `0x` prefix.
+//!
+//! - `help`: list the commands.
+//! - `status`: show the number of loaded and active processes.
+//! - `list`: list the Processes with their state, priority and last error.
+//! - `mailboxes`: list the Mailboxes with their pending Signals.
+//! - `ipcs`: list the IPCs with their writer, reader and length.
+//! - `suspend <process id>` and `resume <process id>`: suspend or resume a Process.
+//! - `signal <mailbox id> <bitmap>`: send Signals to a Mailbox.
+//! - `priority <process id> <0x0|0x4|0x8>`: set the priority of a Process.
+//! - `header <process id>`: dump the Firmware header carried by a Process.
+
+use crate::vpp::mloi::{
+    MK_BITMAP_t, MK_ERROR_e, MK_MAILBOX_ID_u, MK_PROCESS_PRIORITY_e, MK_Process_ID_u,
+};
+use crate::vpp::process::firmware_header;
+use crate::vpp::vff::parse_vpp_header;
+use crate::vpp::vppkernel::VppKernel;
+use core::cell::Cell;
+use core::cmp;
+use core::convert::TryFrom;
+use core::str;
+use kernel::capabilities::ProcessManagementCapability;
+use kernel::common::cells::TakeCell;
+use kernel::debug;
+use kernel::hil::uart;
+use kernel::introspection::KernelInfo;
+use kernel::ReturnCode;
+
+// Since writes are character echoes, we do not need more than 4 bytes:
+// the longest write is 3 bytes for a backspace (backspace, space, backspace).
+pub static mut WRITE_BUF: [u8; 4] = [0; 4];
+// Since reads are byte-by-byte, to properly echo what's typed,
+// we can use a very small read buffer.
+pub static mut READ_BUF: [u8; 4] = [0; 4];
+// The longest command, `signal 0x4010 0xFFFFFFFF`, takes 24 bytes.
+pub static mut COMMAND_BUF: [u8; 48] = [0; 48];
+
+const COMMANDS: &str =
+    "help status list mailboxes ipcs suspend resume signal priority header";
+
+pub struct VppConsole<'a, C: ProcessManagementCapability> {
+    uart: &'a dyn uart::UartData<'a>,
+    tx_in_progress: Cell<bool>,
+    tx_buffer: TakeCell<'static, [u8]>,
+    rx_buffer: TakeCell<'static, [u8]>,
+    command_buffer: TakeCell<'static, [u8]>,
+    command_index: Cell<usize>,
+    /// Flag to mark that the console is active and has called receive from the
+    /// underlying UART.
+    running: Cell<bool>,
+    /// Internal flag that the console should parse the command it just received
+    /// after finishing echoing the last newline character.
+    execute: Cell<bool>,
+    vpp_kernel: &'static VppKernel,
+    capability: C,
+}
+
+/// Parses a decimal number, or a hexadecimal one with a `0x` prefix.
+fn parse_number(text: &str) -> Option<u32> {
+    if text.starts_with("0x") || text.starts_with("0X") {
+        u32::from_str_radix(&text[2..], 16).ok()
+    } else {
+        text.parse().ok()
+    }
+}
+
+impl<'a, C: ProcessManagementCapability> VppConsole<'a, C> {
+    pub fn new(
+        uart: &'a dyn uart::UartData<'a>,
+        tx_buffer: &'static mut [u8],
+        rx_buffer: &'static mut [u8],
+        cmd_buffer: &'static mut [u8],
+        vpp_kernel: &'static VppKernel,
+        capability: C,
+    ) -> VppConsole<'a, C> {
+        VppConsole {
+            uart: uart,
+            tx_in_progress: Cell::new(false),
+            tx_buffer: TakeCell::new(tx_buffer),
+            rx_buffer: TakeCell::new(rx_buffer),
+            command_buffer: TakeCell::new(cmd_buffer),
+            command_index: Cell::new(0),
+            running: Cell::new(false),
+            execute: Cell::new(false),
+            vpp_kernel: vpp_kernel,
+            capability: capability,
+        }
+    }
+
+    pub fn start(&self) -> ReturnCode {
+        if !self.running.get() {
+            self.rx_buffer.take().map(|buffer| {
+                self.uart.receive_buffer(buffer, 1);
+                self.running.set(true);
+                debug!("Starting VPP console");
+            });
+        }
+        ReturnCode::SUCCESS
+    }
+
+    // Process the command in the command buffer and clear the buffer.
+    fn read_command(&self) {
+        self.command_buffer.map(|command| {
+            let terminator = command.iter().position(|&byte| byte == 0).unwrap_or(0);
+            if terminator > 0 {
+                match str::from_utf8(&command[0..terminator]) {
+                    Ok(line) => self.execute_command(line),
+                    Err(_) => debug!("Invalid command: {:?}", &command[0..terminator]),
+                }
+            }
+        });
+        self.command_buffer.map(|command| {
+            command[0] = 0;
+        });
+        self.command_index.set(0);
+    }
+
+    fn execute_command(&self, line: &str) {
+        let mut words = line.split_whitespace();
+        let command = match words.next() {
+            Some(command) => command,
+            None => return,
+        };
+        let first = words.next().and_then(parse_number);
+        let second = words.next().and_then(parse_number);
+        match (command, first, second) {
+            ("help", _, _) => {
+                debug!("Welcome to the VPP console.");
+                debug!("Valid commands are: {}", COMMANDS);
+            }
+            ("status", _, _) => self.status(),
+            ("list", _, _) => self.list_processes(),
+            ("mailboxes", _, _) => self.list_mailboxes(),
+            ("ipcs", _, _) => self.list_ipcs(),
+            ("suspend", Some(id), _) => self.suspend(id as MK_Process_ID_u),
+            ("resume", Some(id), _) => self.resume(id as MK_Process_ID_u),
+            ("signal", Some(id), Some(signals)) => self.signal(id as MK_MAILBOX_ID_u, signals),
+            ("priority", Some(id), Some(priority)) => {
+                self.priority(id as MK_Process_ID_u, priority as u16)
+            }
+            ("header", Some(id), _) => self.dump_header(id as MK_Process_ID_u),
+            ("suspend", _, _) | ("resume", _, _) | ("header", _, _) => {
+                debug!("Usage: {} <process id>", command)
+            }
+            ("signal", _, _) => debug!("Usage: signal <mailbox id> <bitmap>"),
+            ("priority", _, _) => debug!("Usage: priority <process id> <0x0|0x4|0x8>"),
+            _ => debug!("Valid commands are: {}", COMMANDS),
+        }
+    }
+
+    fn status(&self) {
+        let info = KernelInfo::new(self.vpp_kernel.kernel);
+        debug!(
+            "Total processes: {}",
+            info.number_loaded_processes(&self.capability)
+        );
+        debug!(
+            "Active processes: {}",
+            info.number_active_processes(&self.capability)
+        );
+        debug!(
+            "Timeslice expirations: {}",
+            info.timeslice_expirations(&self.capability)
+        );
+    }
+
+    fn list_processes(&self) {
+        debug!(" Index  ID      Name                VPP State     Tock State      Priority  Error");
+        for (index, process) in self.vpp_kernel.vpp_processes.iter().enumerate() {
+            if let Some(process) = process {
+                let (name, state) = match process.tockprocess.get() {
+                    Some(tockprocess) => (tockprocess.get_process_name(), Some(tockprocess.get_state())),
+                    None => ("-", None),
+                };
+                debug!(
+                    " {:<6} {:#06x}  {:<20}{:<14?}{:<16?}{:<10?}{:?}",
+                    index,
+                    process.get_vpp_id(),
+                    name,
+                    process.get_vpp_state(),
+                    state,
+                    process.get_vpp_priority(),
+                    process.get_last_generated_error(),
+                );
+            }
+        }
+    }
+
+    fn list_mailboxes(&self) {
+        debug!(" ID      Owner  Sender  Signals     Activated");
+        for mailbox in self.vpp_kernel.mailboxes.iter().flatten() {
+            debug!(
+                " {:#06x}  {:<6} {:<7} {:#010x}  {}",
+                mailbox.get_mb_id(),
+                mailbox.get_owner_proc_i(),
+                mailbox.get_sender_proc_i(),
+                mailbox.get_sig(),
+                mailbox.is_activated(),
+            );
+        }
+    }
+
+    fn list_ipcs(&self) {
+        debug!(" ID      Writer  Reader  Length  Accessed");
+        for ipc in self.vpp_kernel.ipcs.iter().flatten() {
+            debug!(
+                " {:#06x}  {:<7} {:<7} {:<7} {}",
+                ipc.get_ipc_id(),
+                ipc.get_writer_proc_i(),
+                ipc.get_reader_proc_i(),
+                ipc.get_ipc_len(),
+                ipc.get_holder().is_some(),
+            );
+        }
+    }
+
+    fn suspend(&self, id: MK_Process_ID_u) {
+        match self.vpp_kernel._mk_Get_Process_Handle(id) {
+            Some(handle) => debug!("{:?}", self.vpp_kernel._mk_suspend_process(handle)),
+            None => debug!("{:?}", MK_ERROR_e::MK_ERROR_UNKNOWN_ID),
+        }
+    }
+
+    fn resume(&self, id: MK_Process_ID_u) {
+        match self.vpp_kernel._mk_Get_Process_Handle(id) {
+            Some(handle) => debug!("{:?}", self.vpp_kernel._mk_resume_process(handle)),
+            None => debug!("{:?}", MK_ERROR_e::MK_ERROR_UNKNOWN_ID),
+        }
+    }
+
+    fn signal(&self, id: MK_MAILBOX_ID_u, signals: MK_BITMAP_t) {
+        match self.vpp_kernel._mk_Get_Mailbox_Handle(id) {
+            Some(handle) => debug!("{:?}", self.vpp_kernel._mk_Send_Signal(handle, signals)),
+            None => debug!("{:?}", MK_ERROR_e::MK_ERROR_UNKNOWN_ID),
+        }
+    }
+
+    fn priority(&self, id: MK_Process_ID_u, priority: u16) {
+        let priority = match MK_PROCESS_PRIORITY_e::try_from(priority) {
+            Ok(priority) => priority,
+            Err(_) => {
+                debug!("{:?}", MK_ERROR_e::MK_ERROR_UNKNOWN_PRIORITY);
+                return;
+            }
+        };
+        match self.vpp_kernel._mk_Get_Process_Handle(id) {
+            Some(handle) => debug!(
+                "{:?}",
+                self.vpp_kernel._mk_set_process_priority(handle, priority)
+            ),
+            None => debug!("{:?}", MK_ERROR_e::MK_ERROR_UNKNOWN_ID),
+        }
+    }
+
+    fn dump_header(&self, id: MK_Process_ID_u) {
+        let raw = self
+            .vpp_kernel
+            .vpp_processes
+            .iter()
+            .flatten()
+            .find(|process| process.get_vpp_id() == id)
+            .and_then(|process| process.tockprocess.get())
+            .and_then(firmware_header);
+        let header = match raw.map(parse_vpp_header) {
+            Some(Ok(header)) => header,
+            Some(Err(error)) => {
+                debug!("Invalid Firmware header: {:?}", error);
+                return;
+            }
+            None => {
+                debug!("No Firmware header for Process {:#06x}", id);
+                return;
+            }
+        };
+        let fw = header.fw_descriptor;
+        debug!("Firmware       {:#034x}", fw.m_xID);
+        debug!("Family         {:#034x}", fw.m_xFamilyID);
+        debug!(
+            "Header length  {}  Format {:#06x}  Version {:#06x}",
+            fw.m_uHeaderLength, fw.m_VersionFormat, fw.m_VersionFirmware
+        );
+        debug!(
+            "Type           {:?}  Scheduling {}",
+            fw.m_eFirmare_Software_Type,
+            u8::from(fw.m_eSchedluingType)
+        );
+        for process in header.process_descriptors.iter() {
+            debug!(
+                "Process {:#06x}  parent {}  kernel mailbox {:#06x}  VRE {:#010x}  code {}  constants {}  data {}  nvm {}  stack {}",
+                process.m_xID,
+                process.m_uParent_Process,
+                process.m_xKernel_Mailbox,
+                process.m_eVRE,
+                process.m_uLength_Process_CODE,
+                process.m_uLength_Process_CONSTANTS,
+                process.m_uLength_Process_DATA,
+                process.m_uLength_Process_NVM,
+                process.m_uSizeStack,
+            );
+        }
+        for mailbox in header.mailbox_descriptors.iter() {
+            debug!(
+                "Mailbox {:#06x}  owner {}  sender {}",
+                mailbox.m_xID, mailbox.m_uIX_Owner, mailbox.m_uIX_Sender
+            );
+        }
+        for ipc in header.ipc_descriptors.iter() {
+            debug!(
+                "IPC     {:#06x}  writer {}  reader {}  length {}",
+                ipc.m_xID, ipc.m_uIX_Writer, ipc.m_uIX_Reader, ipc.m_uLength_IPC
+            );
+        }
+        for library in header.lib_descriptors.iter() {
+            debug!(
+                "LIB     {:#06x}  version {:#06x}  processes {:#010x}",
+                library.m_xID, library.m_uVersion, library.m_bProcesses
+            );
+        }
+        if let Some(llos) = header.llos_descriptor {
+            debug!(
+                "LLOS    entry {:#010x}  code {}",
+                llos.m_pvLLOSCode, llos.m_uLength_LLOS_CODE
+            );
+        }
+    }
+
+    fn write_byte(&self, byte: u8) -> ReturnCode {
+        self.write_bytes(&[byte])
+    }
+
+    fn write_bytes(&self, bytes: &[u8]) -> ReturnCode {
+        if self.tx_in_progress.get() {
+            ReturnCode::EBUSY
+        } else {
+            self.tx_in_progress.set(true);
+            self.tx_buffer.take().map(|buffer| {
+                let len = cmp::min(bytes.len(), buffer.len());
+                // Copy elements of `bytes` into `buffer`
+                (&mut buffer[..len]).copy_from_slice(&bytes[..len]);
+                self.uart.transmit_buffer(buffer, len);
+            });
+            ReturnCode::SUCCESS
+        }
+    }
+}
+
+impl<'a, C: ProcessManagementCapability> uart::TransmitClient for VppConsole<'a, C> {
+    fn transmitted_buffer(&self, buffer: &'static mut [u8], _tx_len: usize, _rcode: ReturnCode) {
+        self.tx_buffer.replace(buffer);
+        self.tx_in_progress.set(false);
+
+        // Check if we just received and echoed a newline character, and
+        // therefore need to process the received message.
+        if self.execute.get() {
+            self.execute.set(false);
+            self.read_command();
+        }
+    }
+}
+
+impl<'a, C: ProcessManagementCapability> uart::ReceiveClient for VppConsole<'a, C> {
+    fn received_buffer(
+        &self,
+        read_buf: &'static mut [u8],
+        rx_len: usize,
+        _rcode: ReturnCode,
+        error: uart::Error,
+    ) {
+        if error == uart::Error::None && rx_len == 1 {
+            self.command_buffer.map(|command| {
+                let index = self.command_index.get();
+                if read_buf[0] == b'\n' || read_buf[0] == b'\r' {
+                    self.execute.set(true);
+                    self.write_bytes(&[b'\r', b'\n']);
+                } else if read_buf[0] == b'\x08' && index > 0 {
+                    // Backspace, echo and remove last byte
+                    // Note echo is '\b \b' to erase
+                    self.write_bytes(&[b'\x08', b' ', b'\x08']);
+                    command[index - 1] = 0;
+                    self.command_index.set(index - 1);
+                } else if index < (command.len() - 1) && read_buf[0] < 128 {
+                    // Echo the byte and store it
+                    self.write_byte(read_buf[0]);
+                    command[index] = read_buf[0];
+                    self.command_index.set(index + 1);
+                    command[index + 1] = 0;
+                }
+            });
+        }
+        self.uart.receive_buffer(read_buf, 1);
+    }
+}
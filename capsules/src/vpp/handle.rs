@@ -0,0 +1,70 @@
+//! Handles of the VPP kernel objects.
+//!
+//! A `MK_HANDLE_t` is opaque to the Processes. It packs the type of the object, the
+//! generation of the object and the index of its slot in the table of the VPP kernel
+//! (`vpp_processes`, `mailboxes` or `ipcs`):
+//!
+//! ```text
+//!  31          24 23                          8 7            0
+//! +--------------+------------------------------+--------------+
+//! |     type     |          generation          |     slot     |
+//! +--------------+------------------------------+--------------+
+//! ```
+//!
+//! The generation of an object is incremented when the object dies or when the
+//! Process owning it restarts, so the handles obtained before are stale. A handle
+//! never has a type of 0, so 0 is never a valid handle.
+
+use crate::vpp::mloi::MK_ERROR_e::*;
+use crate::vpp::mloi::{MK_ERROR_e, MK_HANDLE_t};
+
+/// Type of the object referenced by a handle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum HandleType {
+    Process = 1,
+    Mailbox = 2,
+    Ipc = 3,
+}
+
+/// Fields of a handle.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Handle {
+    pub tipe: HandleType,
+    pub generation: u16,
+    pub slot: usize,
+}
+
+impl Handle {
+    pub fn new(tipe: HandleType, generation: u16, slot: usize) -> Handle {
+        Handle {
+            tipe: tipe,
+            generation: generation,
+            slot: slot,
+        }
+    }
+
+    /// Decodes `handle`, expected to reference an object of type `tipe`.
+    ///
+    /// Returns `MK_ERROR_UNKNOWN_HANDLE` if `handle` is not a handle, and
+    /// `MK_ERROR_ACCESS_DENIED` if it references an object of another type.
+    pub fn decode(handle: MK_HANDLE_t, tipe: HandleType) -> Result<Handle, MK_ERROR_e> {
+        let found = match handle >> 24 {
+            1 => HandleType::Process,
+            2 => HandleType::Mailbox,
+            3 => HandleType::Ipc,
+            _ => return Err(MK_ERROR_UNKNOWN_HANDLE),
+        };
+        if found != tipe {
+            return Err(MK_ERROR_ACCESS_DENIED);
+        }
+        Ok(Handle::new(found, (handle >> 8) as u16, (handle & 0xFF) as usize))
+    }
+}
+
+impl From<Handle> for MK_HANDLE_t {
+    fn from(handle: Handle) -> MK_HANDLE_t {
+        (handle.tipe as MK_HANDLE_t) << 24
+            | (handle.generation as MK_HANDLE_t) << 8
+            | (handle.slot as MK_HANDLE_t & 0xFF)
+    }
+}
@@ -6,8 +6,14 @@ use crate::vpp::mloi::*;
 use core::cell::Cell;
 use kernel::{AppSlice, Shared, Grant, Kernel, AppId, debug, Callback};
 use kernel::capabilities::MemoryAllocationCapability;
+use kernel::common::cells::OptionalCell;
+use kernel::mpu;
 use core::borrow::{BorrowMut, Borrow};
 
+/// Signal sent by a Process releasing an IPC to the Mailbox it shares with the other
+/// side of the IPC (`MK_SIGNAL_DOMAIN_BASE_0`).
+pub const MK_SIGNAL_IPC_RELEASED: MK_BITMAP_t = 0x0000_0008;
+
 #[derive(Default)]
 pub struct ipcData {
     /// App slice that this application has shared with other applications.
@@ -20,6 +26,12 @@ pub struct ipc {
     writer_process_index: Cell<MK_Index_t> ,
     reader_process_index: Cell<MK_Index_t>,
     pub(crate) data: Grant<ipcData>,
+    /// Side of the IPC currently accessing the shared memory.
+    holder: OptionalCell<AppId>,
+    /// MPU region exposing the shared memory to the reader while it holds the IPC.
+    reader_region: Cell<Option<mpu::Region>>,
+    /// Generation of the handles of the IPC.
+    generation: Cell<u16>,
 }
 
 impl  ipc{
@@ -35,6 +47,9 @@ impl  ipc{
             writer_process_index: Cell::new(writer_proc),
             reader_process_index: Cell::new(reader_proc),
             data: kernel.create_grant(capability),
+            holder: OptionalCell::empty(),
+            reader_region: Cell::new(None),
+            generation: Cell::new(0),
         }
     }
     pub fn create_mgt_main_ipc(kernel: &'static Kernel,
@@ -45,6 +60,9 @@ impl  ipc{
             writer_process_index: Cell::new(0),
             reader_process_index: Cell::new(1),
             data: kernel.create_grant(capability),
+            holder: OptionalCell::empty(),
+            reader_region: Cell::new(None),
+            generation: Cell::new(0),
         }
     }
     pub fn create_com_main_ipc(kernel: &'static Kernel,
@@ -55,6 +73,9 @@ impl  ipc{
             writer_process_index: Cell::new(1),
             reader_process_index: Cell::new(2),
             data: kernel.create_grant(capability),
+            holder: OptionalCell::empty(),
+            reader_region: Cell::new(None),
+            generation: Cell::new(0),
         }
     }
     pub fn create_main_mgt_ipc(kernel: &'static Kernel,
@@ -65,6 +86,9 @@ impl  ipc{
             writer_process_index: Cell::new(1),
             reader_process_index: Cell::new(0),
             data: kernel.create_grant(capability),
+            holder: OptionalCell::empty(),
+            reader_region: Cell::new(None),
+            generation: Cell::new(0),
         }
     }
     pub fn create_main_com_ipc(kernel: &'static Kernel,
@@ -75,6 +99,9 @@ impl  ipc{
             writer_process_index: Cell::new(2),
             reader_process_index: Cell::new(1),
             data: kernel.create_grant(capability),
+            holder: OptionalCell::empty(),
+            reader_region: Cell::new(None),
+            generation: Cell::new(0),
         }
     }
     pub fn get_ipc_id (&self) -> MK_IPC_ID_u {
@@ -91,6 +118,30 @@ impl  ipc{
         self.reader_process_index.get()
     }
 
+    pub(crate) fn get_generation(&self) -> u16 {
+        self.generation.get()
+    }
+    /// Invalidates the handles of the IPC obtained so far.
+    pub(crate) fn invalidate_handles(&self) {
+        self.generation.set(self.generation.get().wrapping_add(1));
+    }
+
+    /// Side of the IPC accessing the shared memory, if it is still alive.
+    pub(crate) fn get_holder(&self) -> Option<AppId> {
+        self.holder.map(|holder| *holder).filter(|holder| holder.index().is_some())
+    }
+
+    pub(crate) fn set_holder(&self, holder: AppId, reader_region: Option<mpu::Region>) {
+        self.holder.set(holder);
+        self.reader_region.set(reader_region);
+    }
+
+    /// Ends the access to the shared memory, returns the MPU region of the reader to revoke.
+    pub(crate) fn clear_holder(&self) -> Option<mpu::Region> {
+        self.holder.clear();
+        self.reader_region.take()
+    }
+
 
     pub fn expose_slice_to_app(&self, reader_id: AppId, writer_id: AppId) -> bool{
         debug!("Exposer ID {:?},Caller ID {:?}",writer_id.id(),reader_id.id());
@@ -0,0 +1,109 @@
+//! Shared libraries of the VPP Firmwares.
+//!
+//! A shared library is a TBF object in the app flash whose TBF header carries a
+//! `TbfHeaderVppLibrary` TLV. It is not a Process: its binary is a read-execute code
+//! region mapped, execute-only where the MPU allows it, into every Process importing
+//! it, so several Firmwares reuse one copy of a crypto or protocol library.
+//!
+//! The RISC-V PMP gives a true execute-only mapping. The ARMv7-M MPU has no
+//! execute-only access for unprivileged code (its `ExecuteOnly` regions are
+//! privileged only), so on Arm the library is mapped read-execute and the Processes
+//! importing it can read its binary.
+//!
+//! The TLV holds the LIB header:
+//!
+//! ```text
+//! 0        2          4          6          8
+//! +--------+----------+----------+----------+
+//! | m_xID  | version  | entries  | reserved |
+//! +--------+----------+----------+----------+
+//! ```
+//!
+//! The version holds the major version in its high byte and the minor version in its
+//! low byte. The binary starts with the entry table: `entries` slots of 4 bytes, each
+//! one a jump to an entry point of the library. The Process calls the entry `n` at
+//! `entry_table + 4 * n`; the table being code, it stays usable with an execute-only
+//! mapping. A Firmware imports a library through its LIB Descriptors, the version it
+//! requires must have the major version of the library and a minor version not above
+//! the one of the library.
+
+use crate::vpp::mloi::MK_LIB_ID_u;
+use crate::vpp::vff::MGT_ERROR_e;
+use kernel::mpu;
+use kernel::procs::ProcessType;
+
+/// Number of shared libraries in the app flash.
+pub const VPP_LIB_SLOTS: usize = 4;
+/// Size in bytes of the LIB header.
+pub const LIB_HEADER_SIZE: usize = 8;
+/// Size in bytes of a slot of the entry table.
+pub const LIB_ENTRY_SIZE: usize = 4;
+/// Permissions of a library mapped into a Process: read-execute on the ARMv7-M MPU,
+/// which has no unprivileged execute-only access.
+#[cfg(target_arch = "arm")]
+const CODE_PERMISSIONS: mpu::Permissions = mpu::Permissions::ReadExecuteOnly;
+/// Permissions of a library mapped into a Process.
+#[cfg(not(target_arch = "arm"))]
+const CODE_PERMISSIONS: mpu::Permissions = mpu::Permissions::ExecuteOnly;
+
+#[derive(Clone, Copy)]
+pub struct VppLibrary {
+    id: MK_LIB_ID_u,
+    version: u16,
+    entries: u16,
+    /// Binary of the library, starting with the entry table.
+    code: &'static [u8],
+}
+
+impl VppLibrary {
+    /// Builds a library from its LIB `header` and its binary `code`.
+    pub fn parse(header: &[u8], code: &'static [u8]) -> Result<VppLibrary, MGT_ERROR_e> {
+        if header.len() < LIB_HEADER_SIZE {
+            return Err(MGT_ERROR_e::MGT_ERROR_ILLEGAL_PARAMETER);
+        }
+        let library = VppLibrary {
+            id: u16::from_le_bytes([header[0], header[1]]),
+            version: u16::from_le_bytes([header[2], header[3]]),
+            entries: u16::from_le_bytes([header[4], header[5]]),
+            code: code,
+        };
+        if library.entries == 0
+            || library.entries as usize * LIB_ENTRY_SIZE > code.len()
+        {
+            return Err(MGT_ERROR_e::MGT_ERROR_ILLEGAL_PARAMETER);
+        }
+        Ok(library)
+    }
+
+    pub fn get_id(&self) -> MK_LIB_ID_u {
+        self.id
+    }
+
+    pub fn get_version(&self) -> u16 {
+        self.version
+    }
+
+    pub fn get_entries(&self) -> u16 {
+        self.entries
+    }
+
+    /// Address of the entry table.
+    pub fn entry_table(&self) -> usize {
+        self.code.as_ptr() as usize
+    }
+
+    /// Returns `true` if the library satisfies a LIB Descriptor requiring `version`.
+    pub fn is_compatible(&self, version: u16) -> bool {
+        self.version >> 8 == version >> 8 && self.version & 0xFF >= version & 0xFF
+    }
+
+    /// Maps the library into `process` with `CODE_PERMISSIONS`.
+    pub fn map_into(&self, process: &dyn ProcessType) -> Option<mpu::Region> {
+        process.add_mpu_region(
+            self.code.as_ptr(),
+            self.code.len(),
+            self.code.len(),
+            CODE_PERMISSIONS,
+        )
+    }
+}
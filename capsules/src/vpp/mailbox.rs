@@ -24,8 +24,12 @@ pub struct mbox {
     owner_process_index: Cell<MK_Index_t>,
     sender_process_index: Cell<MK_Index_t>,
     signals: Cell<MK_BITMAP_t>,
+    /// Signals were received since the last `_mk_Get_Mailbox_ID_Activated` returned
+    /// the Mailbox or since they were read.
+    activated: Cell<bool>,
     pub(crate) data: Grant<MbData>,
-
+    /// Generation of the handles of the Mailbox.
+    generation: Cell<u16>,
 }
 
 impl  mbox {
@@ -40,7 +44,9 @@ impl  mbox {
             owner_process_index: Cell::new(owner_pi),
             sender_process_index: Cell::new(sender_pi),
             signals: Cell::new(0),
+            activated: Cell::new(false),
             data: kernel.create_grant(capability),
+            generation: Cell::new(0),
 
         }
     }
@@ -53,7 +59,9 @@ impl  mbox {
             owner_process_index: Cell::new(2),
             sender_process_index: Cell::new(1),
             signals: Cell::new(0),
+            activated: Cell::new(false),
             data: kernel.create_grant(capability),
+            generation: Cell::new(0),
         }
     }
     pub fn create_main_mgt_mb( kernel: &'static Kernel,
@@ -64,7 +72,9 @@ impl  mbox {
             owner_process_index: Cell::new(1),
             sender_process_index: Cell::new(0),
             signals: Cell::new(0),
+            activated: Cell::new(false),
             data: kernel.create_grant(capability),
+            generation: Cell::new(0),
         }
     }
     pub  fn create_mgt_mb( kernel: &'static Kernel,
@@ -75,7 +85,9 @@ impl  mbox {
             owner_process_index: Cell::new(0),
             sender_process_index: Cell::new(1),
             signals: Cell::new(0),
+            activated: Cell::new(false),
             data: kernel.create_grant(capability),
+            generation: Cell::new(0),
         }
     }
     pub fn create_main_com_mb( kernel: &'static Kernel,
@@ -86,12 +98,22 @@ impl  mbox {
             owner_process_index: Cell::new(1),
             sender_process_index: Cell::new(2),
             signals: Cell::new(0),
+            activated: Cell::new(false),
             data: kernel.create_grant(capability),
+            generation: Cell::new(0),
         }
     }
 
     pub (crate) fn get_mb_id(&self) -> MK_MAILBOX_ID_u {self.mailbox_id.get()}
 
+    pub(crate) fn get_generation(&self) -> u16 {
+        self.generation.get()
+    }
+    /// Invalidates the handles of the Mailbox obtained so far.
+    pub(crate) fn invalidate_handles(&self) {
+        self.generation.set(self.generation.get().wrapping_add(1));
+    }
+
     pub(crate) fn get_owner_proc_i (&self) -> MK_Index_t {
         self.owner_process_index.get()
     }
@@ -102,6 +124,7 @@ impl  mbox {
         let old_sigs_bitmap = self.signals.get();
 
         if new_sigs_bitmap != 0 {
+            self.activated.set(true);
             self.fired();
         }
         new_sigs_bitmap |= old_sigs_bitmap ;
@@ -116,6 +139,14 @@ impl  mbox {
     }
     pub(crate) fn clear_sig(&self) {
         self.signals.set(0);
+        self.activated.set(false);
+    }
+
+    pub(crate) fn is_activated(&self) -> bool {
+        self.activated.get()
+    }
+    pub(crate) fn clear_activated(&self) {
+        self.activated.set(false);
     }
 
     pub(crate) fn fired(&self){
@@ -1,16 +1,83 @@
+//! MGT Service of the VPP: Firmware lifecycle management.
+//!
+//! The MGT Process owns a registry of Firmware headers kept in nonvolatile
+//! storage. Every record occupies one fixed-size slot:
+//!
+//! ```text
+//! 0        4       5       6             8                 12                 16
+//! +--------+-------+-------+-------------+-----------------+------------------+-----------
+//! | magic  | state | flags | header len  | partition size  | partition offset | header ...
+//! +--------+-------+-------+-------------+-----------------+------------------+-----------
+//! ```
+//!
+//! The registry index is rebuilt from the slots at boot (`mgt_process::load`), so a
+//! stored, allocated or enabled Firmware survives a power cycle. Allocating a
+//! Firmware reserves its Memory Partition in the partition region of the storage.
+//!
+//! While an impersonation session is open (see `vff::_mk_Fw_Mgt`), the MGT Process
+//! can read and write the part of the Memory Partition owned by the impersonated
+//! Process, Library or LLOS.
+//!
+//! Userspace interface (only the MGT Process may use it):
+//!
+//! - allow 0: Firmware header buffer (Store / Retrieve), data buffer (Read / Write).
+//! - allow 1: 16 bytes Firmware UUID, big endian.
+//! - subscribe 0: completion callback `(command, ResponseCode, value)`.
+//! - command 1: Store the header of `data` bytes.
+//! - command 2: Retrieve the header, `value` is its length.
+//! - command 3: Allocate the Memory Partition of the Firmware.
+//! - command 4: Delete the Firmware.
+//! - command 5: Enable the Firmware.
+//! - command 6: Disable the Firmware.
+//! - command 7: Is Firmware Enabled, `value` is 1 if enabled.
+//! - command 8 / 9: Open (Process index in `data`) / Close Process Impersonation.
+//! - command 10 / 11: Open (Library index in `data`) / Close Library Impersonation.
+//! - command 12 / 13: Open / Close LLOS Impersonation.
+//! - command 20: Read `data2` bytes at offset `data` of the impersonated memory.
+//! - command 21: Write `data2` bytes at offset `data` of the impersonated memory.
 #![allow(non_camel_case_types)]
 #![allow(non_snake_case)]
 #![allow(dead_code)]
 
-use crate::vpp::mloi::*;
-// use crate::vpp::vff;
-use crate::vpp::vff::{FW, FwHeader, parse_vpp_header, FwState, FwBody};
-use crate::vpp::mgt_service::ServiceCommands::MGT_Store_Firmware_Header;
-use crate::vpp::mgt_service::ResponseCode::MGT_ERROR_NONE;
+use crate::driver;
+use crate::vpp::mloi::{MK_ERROR_e, MK_Index_t, UUID_t};
+use crate::vpp::vff::{
+    parse_vpp_header, FwState, ImpersonationSession, ImpersonationTarget, MGT_ERROR_e,
+    _mk_Fw_Mgt, FW_MAX_HEADER_LENGTH,
+};
+use crate::vpp::vppkernel::VppKernel;
 use core::cell::Cell;
+use core::cmp;
+use core::convert::TryInto;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::debug;
+use kernel::hil;
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
 
+pub const DRIVER_NUM: usize = driver::NUM::VppMgtDriver as usize;
 
-#[derive(Clone, Copy,PartialEq)]
+/// Number of Firmware records the registry can hold.
+pub const MGT_FIRMWARE_SLOTS: usize = 4;
+/// Size of the record header preceding the Firmware header in a slot.
+pub const FW_RECORD_HEADER_SIZE: usize = 16;
+/// Size of one slot in nonvolatile storage.
+pub const FW_RECORD_SIZE: usize = (FW_RECORD_HEADER_SIZE + FW_MAX_HEADER_LENGTH + 3) & !3;
+/// Nonvolatile storage needed by the registry.
+pub const MGT_REGISTRY_SIZE: usize = MGT_FIRMWARE_SLOTS * FW_RECORD_SIZE;
+
+/// Driver command reading the impersonated memory.
+pub const MGT_READ_IMPERSONATED: usize = 20;
+/// Driver command writing the impersonated memory.
+pub const MGT_WRITE_IMPERSONATED: usize = 21;
+
+const FW_RECORD_MAGIC: u32 = 0x5646_4652; // "VFFR"
+const FW_RECORD_ALLOCATED: u8 = 0x01;
+
+/// Buffer used by the registry to move records from and to nonvolatile storage.
+pub static mut FW_RECORD_BUF: [u8; FW_RECORD_SIZE] = [0; FW_RECORD_SIZE];
+
+
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ServiceCommands{
     MGT_Store_Firmware_Header,
     MGT_Retrieve_Firmware_Header,
@@ -45,7 +112,7 @@ impl From<ServiceCommands> for u8 {
         }
     }
 }
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ResponseCode {
     MGT_ERROR_NONE,
     MGT_ERROR_ILLEGAL_PARAMETER,
@@ -53,6 +120,17 @@ pub enum ResponseCode {
     MGT_ERROR_UNKNOWN_UUID,
     MGT_ERROR_COMMAND_NOK,
 }
+impl From<MGT_ERROR_e> for ResponseCode {
+    fn from(original: MGT_ERROR_e) -> ResponseCode {
+        match original {
+            MGT_ERROR_e::MGT_ERROR_NONE => ResponseCode::MGT_ERROR_NONE,
+            MGT_ERROR_e::MGT_ERROR_ILLEGAL_PARAMETER => ResponseCode::MGT_ERROR_ILLEGAL_PARAMETER,
+            MGT_ERROR_e::MGT_ERROR_INTERNAL => ResponseCode::MGT_ERROR_INTERNAL,
+            MGT_ERROR_e::MGT_ERROR_UNKNOWN_UUID => ResponseCode::MGT_ERROR_UNKNOWN_UUID,
+            MGT_ERROR_e::MGT_ERROR_COMMAND_NOK => ResponseCode::MGT_ERROR_COMMAND_NOK,
+        }
+    }
+}
 impl From<ResponseCode> for u8 {
     fn from (original: ResponseCode) -> u8 {
         match original {
@@ -65,94 +143,970 @@ impl From<ResponseCode> for u8 {
     }
 }
 
+impl ServiceCommands {
+    /// Maps a driver command number (ServiceCommands code + 1) to the MGT command.
+    fn from_command_num(command_num: usize) -> Option<ServiceCommands> {
+        match command_num {
+            1 => Some(ServiceCommands::MGT_Store_Firmware_Header),
+            2 => Some(ServiceCommands::MGT_Retrieve_Firmware_Header),
+            3 => Some(ServiceCommands::MGT_Allocate_Firmware),
+            4 => Some(ServiceCommands::MGT_Delete_Firmware),
+            5 => Some(ServiceCommands::MGT_Enable_Firmware),
+            6 => Some(ServiceCommands::MGT_Disable_Firmware),
+            7 => Some(ServiceCommands::MGT_Is_Firmware_Enabled),
+            8 => Some(ServiceCommands::MGT_Open_Process_Impersonation),
+            9 => Some(ServiceCommands::MGT_Close_Process_Impersonation),
+            10 => Some(ServiceCommands::MGT_Open_Library_Impersonation),
+            11 => Some(ServiceCommands::MGT_Close_Library_Impersonation),
+            12 => Some(ServiceCommands::MGT_Open_LLOS_Impersonation),
+            13 => Some(ServiceCommands::MGT_Close_LLOS_Impersonation),
+            _ => None,
+        }
+    }
+
+    fn command_num(self) -> usize {
+        u8::from(self) as usize + 1
+    }
+}
+
+/// RAM copy of the record header of a used slot.
+#[derive(Clone, Copy)]
+struct FwRecord {
+    uuid: UUID_t,
+    state: FwState,
+    allocated: bool,
+    header_length: u16,
+    partition_size: u32,
+    partition_offset: u32,
+}
+
+impl FwRecord {
+    /// Decodes a record from the beginning of a slot, `None` if the slot is free or
+    /// `raw` is too short to hold the record.
+    fn decode(raw: &[u8]) -> Option<FwRecord> {
+        let magic = u32::from_le_bytes(raw.get(0..4)?.try_into().ok()?);
+        if magic != FW_RECORD_MAGIC {
+            return None;
+        }
+        let header_length = u16::from_le_bytes(raw.get(6..8)?.try_into().ok()?);
+        if header_length as usize > FW_MAX_HEADER_LENGTH {
+            return None;
+        }
+        let header =
+            raw.get(FW_RECORD_HEADER_SIZE..FW_RECORD_HEADER_SIZE + header_length as usize)?;
+        let uuid = parse_vpp_header(header).ok()?.fw_descriptor.m_xID;
+        Some(FwRecord {
+            uuid: uuid,
+            state: FwState::from(raw[4]),
+            allocated: raw[5] & FW_RECORD_ALLOCATED != 0,
+            header_length: header_length,
+            partition_size: u32::from_le_bytes(raw[8..12].try_into().ok()?),
+            partition_offset: u32::from_le_bytes(raw[12..16].try_into().ok()?),
+        })
+    }
+
+    fn encode(&self, raw: &mut [u8]) {
+        raw[0..4].copy_from_slice(&FW_RECORD_MAGIC.to_le_bytes());
+        raw[4] = self.state.into();
+        raw[5] = if self.allocated { FW_RECORD_ALLOCATED } else { 0 };
+        raw[6..8].copy_from_slice(&self.header_length.to_le_bytes());
+        raw[8..12].copy_from_slice(&self.partition_size.to_le_bytes());
+        raw[12..16].copy_from_slice(&self.partition_offset.to_le_bytes());
+    }
+
+    fn partition_end(&self) -> u32 {
+        self.partition_offset.saturating_add(self.partition_size)
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum RegistryState {
+    /// Rebuilding the RAM index, reading the given slot.
+    Loading(usize),
+    Idle,
+    /// A storage operation of the command is in progress on the given slot.
+    Busy(ServiceCommands, usize),
+    /// Reading the header of the given slot to open an impersonation session
+    /// for the given MGT Process.
+    Opening(ServiceCommands, ImpersonationTarget, usize, AppId),
+    /// Reading (command 20) or writing (command 21) the impersonated memory.
+    Transfer(usize),
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    header: Option<AppSlice<Shared, u8>>,
+    uuid: Option<AppSlice<Shared, u8>>,
+}
+
+/// Firmware registry of the MGT Process.
 pub struct mgt_process {
-    firmware: FW
+    storage: &'static dyn hil::nonvolatile_storage::NonvolatileStorage<'static>,
+    /// Address of the first slot in `storage`.
+    storage_start: usize,
+    /// Address in `storage` of the region holding the Memory Partitions.
+    partition_start: usize,
+    /// Length of the partition region.
+    partition_budget: u32,
+    vpp_kernel: &'static VppKernel,
+    fw_mgt: &'static _mk_Fw_Mgt,
+    apps: Grant<App>,
+    buffer: TakeCell<'static, [u8]>,
+    records: [Cell<Option<FwRecord>>; MGT_FIRMWARE_SLOTS],
+    state: Cell<RegistryState>,
+    requester: OptionalCell<AppId>,
 }
+
 impl mgt_process {
-    pub fn new(firmware: FW) -> mgt_process {
-        mgt_process{
-         firmware: firmware,
+    pub fn new(
+        storage: &'static dyn hil::nonvolatile_storage::NonvolatileStorage<'static>,
+        storage_start: usize,
+        partition_start: usize,
+        partition_budget: u32,
+        vpp_kernel: &'static VppKernel,
+        fw_mgt: &'static _mk_Fw_Mgt,
+        grant: Grant<App>,
+        buffer: &'static mut [u8],
+    ) -> mgt_process {
+        mgt_process {
+            storage: storage,
+            storage_start: storage_start,
+            partition_start: partition_start,
+            partition_budget: partition_budget,
+            vpp_kernel: vpp_kernel,
+            fw_mgt: fw_mgt,
+            apps: grant,
+            buffer: TakeCell::new(buffer),
+            records: Default::default(),
+            state: Cell::new(RegistryState::Loading(0)),
+            requester: OptionalCell::empty(),
         }
     }
-    // Firmware Header Management
-    pub fn MGT_Store_Firmware_Header(
-        firmware_header_data: &'static [u8],
-        command: ServiceCommands )
-        -> ResponseCode {
-        if command == MGT_Store_Firmware_Header {
-            //prase the firmware
-            // add Error handling for parse_vpp_header
-            let fw_header = parse_vpp_header(firmware_header_data);
-            // compare the firmware_header to the Primary Platform capabilities
-            // ?
-            // if Firmware_header not supported => illegal_parameter ?
-            // Extract the UUID from firmware_header
-            let _firmware_uuid = fw_header.fw_descriptor.m_xID ;
-            // Retrieve the firmware_header from the MGT Process NVM, based on its provided fw
-            // ?
-            // if Firmware_header cannot be found => add a new fw_hd, store the provided fw_id
-            // asthe key to this record. State is disabled
-            // missing ???
-            let _Firmware  = FW {
-                FwHeader : fw_header,
-                FwBody : FwBody::default(),
-                state: Cell::new(FwState::Disabled),
-            };
 
-        }
-    MGT_ERROR_NONE
+    /// Rebuilds the registry index from nonvolatile storage. Must be called once
+    /// at boot, after the registry has been set as the storage client.
+    pub fn load(&self) -> ReturnCode {
+        self.load_slot(0)
+    }
+
+    // Firmware Header Management
+    /// Stores a Firmware header in the registry. A new Firmware is recorded in the
+    /// Disabled state; a Disabled Firmware with the same UUID is replaced, an
+    /// Enabled one cannot be.
+    pub fn MGT_Store_Firmware_Header(&self, firmware_header_data: &[u8]) -> ResponseCode {
+        // parse the firmware header and evaluate the consistency rules
+        let fw_header = match parse_vpp_header(firmware_header_data) {
+            Ok(header) => header,
+            Err(error) => return error.into(),
+        };
+        // Only the header is stored, not the data following it.
+        let header_length = fw_header.fw_descriptor.m_uHeaderLength as usize;
+        let firmware_header = match firmware_header_data.get(..header_length) {
+            Some(header) if header_length <= FW_MAX_HEADER_LENGTH => header,
+            _ => return ResponseCode::MGT_ERROR_ILLEGAL_PARAMETER,
+        };
+        let firmware_uuid = fw_header.fw_descriptor.m_xID;
+        let slot = match self.find_slot(firmware_uuid) {
+            Some(slot) => {
+                if self.record(slot).state == FwState::Enabled || self.is_impersonated(firmware_uuid) {
+                    return ResponseCode::MGT_ERROR_COMMAND_NOK;
+                }
+                slot
+            }
+            None => match self.records.iter().position(|record| record.get().is_none()) {
+                Some(slot) => slot,
+                None => return ResponseCode::MGT_ERROR_INTERNAL,
+            },
+        };
+        let record = FwRecord {
+            uuid: firmware_uuid,
+            state: FwState::Disabled,
+            allocated: false,
+            header_length: header_length as u16,
+            partition_size: fw_header.memory_partition_size(),
+            partition_offset: 0,
+        };
+        self.write_record(
+            ServiceCommands::MGT_Store_Firmware_Header,
+            slot,
+            record,
+            Some(firmware_header),
+        )
     }
 
-    pub fn MGT_Retrieve_Firmware_Header(
-        _firmware_identifier: UUID_t,
-        _command: ServiceCommands)
-    -> (ResponseCode,FwHeader) {
-        unimplemented!();
+    /// Reads a Firmware header back from the registry. The header is copied to
+    /// the buffer of the requesting Process once read.
+    pub fn MGT_Retrieve_Firmware_Header(&self, firmware_identifier: UUID_t) -> ResponseCode {
+        let slot = match self.find_slot(firmware_identifier) {
+            Some(slot) => slot,
+            None => return ResponseCode::MGT_ERROR_UNKNOWN_UUID,
+        };
+        self.read_header(
+            RegistryState::Busy(ServiceCommands::MGT_Retrieve_Firmware_Header, slot),
+            slot,
+        )
     }
+
     // Firmware State Management
-    pub fn MGT_Enable_Firmware(
-        _firmware_identifier: UUID_t,
-        _command: ServiceCommands)
-        -> ResponseCode{
-        unimplemented!();
-    }
-    pub fn MGT_Disable_Firmware(
-        _firmware_identifier: UUID_t,
-        _command: ServiceCommands)
-        -> ResponseCode {
-        unimplemented!();
+    /// Enables an allocated Firmware.
+    pub fn MGT_Enable_Firmware(&self, firmware_identifier: UUID_t) -> ResponseCode {
+        let slot = match self.find_slot(firmware_identifier) {
+            Some(slot) => slot,
+            None => return ResponseCode::MGT_ERROR_UNKNOWN_UUID,
+        };
+        let mut record = self.record(slot);
+        if !record.allocated {
+            return ResponseCode::MGT_ERROR_COMMAND_NOK;
+        }
+        record.state = FwState::Enabled;
+        self.write_record(ServiceCommands::MGT_Enable_Firmware, slot, record, None)
+    }
+
+    pub fn MGT_Disable_Firmware(&self, firmware_identifier: UUID_t) -> ResponseCode {
+        let slot = match self.find_slot(firmware_identifier) {
+            Some(slot) => slot,
+            None => return ResponseCode::MGT_ERROR_UNKNOWN_UUID,
+        };
+        let mut record = self.record(slot);
+        record.state = FwState::Disabled;
+        self.write_record(ServiceCommands::MGT_Disable_Firmware, slot, record, None)
     }
+
+    /// Returns the state of a Firmware. This command does not touch the storage.
     pub fn MGT_Is_Firmware_Enabled(
-        _firmware_identifier: UUID_t,
-        _command: ServiceCommands)
-        -> ResponseCode {
-        unimplemented!();
+        &self,
+        firmware_identifier: UUID_t,
+    ) -> Result<FwState, ResponseCode> {
+        self.find_slot(firmware_identifier)
+            .map(|slot| self.record(slot).state)
+            .ok_or(ResponseCode::MGT_ERROR_UNKNOWN_UUID)
     }
-    pub fn MGT_Delete_Firmware(
-        _firmware_identifier: UUID_t,
-        _command: ServiceCommands)
-        -> ResponseCode {
-        unimplemented!();
+
+    /// Deletes a Disabled Firmware and releases its Memory Partition. A Firmware
+    /// under impersonation cannot be deleted.
+    pub fn MGT_Delete_Firmware(&self, firmware_identifier: UUID_t) -> ResponseCode {
+        let slot = match self.find_slot(firmware_identifier) {
+            Some(slot) => slot,
+            None => return ResponseCode::MGT_ERROR_UNKNOWN_UUID,
+        };
+        if self.record(slot).state == FwState::Enabled || self.is_impersonated(firmware_identifier) {
+            return ResponseCode::MGT_ERROR_COMMAND_NOK;
+        }
+        if self.state.get() != RegistryState::Idle {
+            return ResponseCode::MGT_ERROR_INTERNAL;
+        }
+        let buffer = match self.buffer.take() {
+            Some(buffer) => buffer,
+            None => return ResponseCode::MGT_ERROR_INTERNAL,
+        };
+        // Clearing the magic frees the slot.
+        for byte in buffer[..FW_RECORD_HEADER_SIZE].iter_mut() {
+            *byte = 0;
+        }
+        self.start_write(
+            RegistryState::Busy(ServiceCommands::MGT_Delete_Firmware, slot),
+            self.slot_address(slot),
+            buffer,
+            FW_RECORD_HEADER_SIZE,
+        )
     }
+
     // Firmware Impersonation Management
+    /// Opens an impersonation session on the Process at `index` of an allocated
+    /// Firmware.
     pub fn MGT_Open_Process_Impersonation(
-        _firmware_identifier: UUID_t,
-        _command: ServiceCommands,
-        _index: MK_Index_t)
-        -> ResponseCode {
-        unimplemented!();
+        &self,
+        owner: AppId,
+        firmware_identifier: UUID_t,
+        index: MK_Index_t,
+    ) -> ResponseCode {
+        self.open_impersonation(
+            ServiceCommands::MGT_Open_Process_Impersonation,
+            owner,
+            firmware_identifier,
+            ImpersonationTarget::Process(index),
+        )
+    }
+    pub fn MGT_Close_Process_Impersonation(&self, owner: AppId) -> ResponseCode {
+        self.close_impersonation(owner, |target| match target {
+            ImpersonationTarget::Process(_) => true,
+            _ => false,
+        })
+    }
+    /// Opens an impersonation session on the Library at `index` of an allocated
+    /// Firmware.
+    pub fn MGT_Open_Library_Impersonation(
+        &self,
+        owner: AppId,
+        firmware_identifier: UUID_t,
+        index: MK_Index_t,
+    ) -> ResponseCode {
+        self.open_impersonation(
+            ServiceCommands::MGT_Open_Library_Impersonation,
+            owner,
+            firmware_identifier,
+            ImpersonationTarget::Library(index),
+        )
+    }
+    pub fn MGT_Close_Library_Impersonation(&self, owner: AppId) -> ResponseCode {
+        self.close_impersonation(owner, |target| match target {
+            ImpersonationTarget::Library(_) => true,
+            _ => false,
+        })
+    }
+    /// Opens an impersonation session on the LLOS of an allocated LLOS Firmware.
+    pub fn MGT_Open_LLOS_Impersonation(
+        &self,
+        owner: AppId,
+        firmware_identifier: UUID_t,
+    ) -> ResponseCode {
+        self.open_impersonation(
+            ServiceCommands::MGT_Open_LLOS_Impersonation,
+            owner,
+            firmware_identifier,
+            ImpersonationTarget::Llos,
+        )
+    }
+    pub fn MGT_Close_LLOS_Impersonation(&self, owner: AppId) -> ResponseCode {
+        self.close_impersonation(owner, |target| target == ImpersonationTarget::Llos)
+    }
+
+    /// Reserves the Memory Partition of a Firmware at the first offset of the
+    /// partition region where it fits.
+    pub fn MGT_Allocate_Firmware(&self, firmware_identifier: UUID_t) -> ResponseCode {
+        let slot = match self.find_slot(firmware_identifier) {
+            Some(slot) => slot,
+            None => return ResponseCode::MGT_ERROR_UNKNOWN_UUID,
+        };
+        let mut record = self.record(slot);
+        if record.allocated {
+            return ResponseCode::MGT_ERROR_COMMAND_NOK;
+        }
+        let allocated = || {
+            self.records
+                .iter()
+                .filter_map(|record| record.get())
+                .filter(|record| record.allocated)
+        };
+        // A partition can only start at the beginning of the region or right after
+        // another partition.
+        let offset = core::iter::once(0)
+            .chain(allocated().map(|other| other.partition_end()))
+            .filter(|&offset| {
+                let end = offset.saturating_add(record.partition_size);
+                end <= self.partition_budget
+                    && allocated()
+                        .all(|other| end <= other.partition_offset || offset >= other.partition_end())
+            })
+            .min();
+        record.partition_offset = match offset {
+            Some(offset) => offset,
+            None => return ResponseCode::MGT_ERROR_INTERNAL,
+        };
+        record.allocated = true;
+        self.write_record(ServiceCommands::MGT_Allocate_Firmware, slot, record, None)
     }
-    pub fn MGT_Close_Process_Impersonation(
-        _command: ServiceCommands)
-    -> ResponseCode {
-        unimplemented!();
+
+    /// Reads `length` bytes at `offset` of the memory impersonated by `owner`.
+    /// The data is copied to the buffer of the requesting Process once read.
+    pub fn read_impersonated(&self, owner: AppId, offset: usize, length: usize) -> ResponseCode {
+        let address = match self.impersonated_address(owner, offset, length) {
+            Ok(address) => address,
+            Err(response) => return response,
+        };
+        if self.state.get() != RegistryState::Idle {
+            return ResponseCode::MGT_ERROR_INTERNAL;
+        }
+        self.buffer.take().map_or(ResponseCode::MGT_ERROR_INTERNAL, |buffer| {
+            self.state.set(RegistryState::Transfer(MGT_READ_IMPERSONATED));
+            match self.storage.read(buffer, address, length) {
+                ReturnCode::SUCCESS => ResponseCode::MGT_ERROR_NONE,
+                _ => {
+                    self.state.set(RegistryState::Idle);
+                    ResponseCode::MGT_ERROR_INTERNAL
+                }
+            }
+        })
+    }
+
+    /// Writes `data` at `offset` of the memory impersonated by `owner`.
+    pub fn write_impersonated(&self, owner: AppId, offset: usize, data: &[u8]) -> ResponseCode {
+        let address = match self.impersonated_address(owner, offset, data.len()) {
+            Ok(address) => address,
+            Err(response) => return response,
+        };
+        if self.state.get() != RegistryState::Idle {
+            return ResponseCode::MGT_ERROR_INTERNAL;
+        }
+        let buffer = match self.buffer.take() {
+            Some(buffer) => buffer,
+            None => return ResponseCode::MGT_ERROR_INTERNAL,
+        };
+        buffer[..data.len()].copy_from_slice(data);
+        self.start_write(
+            RegistryState::Transfer(MGT_WRITE_IMPERSONATED),
+            address,
+            buffer,
+            data.len(),
+        )
     }
-    pub fn MGT_Allocate_Firmware(
-        _firmware_identifier: UUID_t,
-        _command: ServiceCommands)
-        -> ResponseCode {
-        unimplemented!();
+
+    fn open_impersonation(
+        &self,
+        command: ServiceCommands,
+        owner: AppId,
+        firmware_identifier: UUID_t,
+        target: ImpersonationTarget,
+    ) -> ResponseCode {
+        let slot = match self.find_slot(firmware_identifier) {
+            Some(slot) => slot,
+            None => return ResponseCode::MGT_ERROR_UNKNOWN_UUID,
+        };
+        if !self.record(slot).allocated || self.fw_mgt._mk_Get_Impersonation().is_some() {
+            return ResponseCode::MGT_ERROR_COMMAND_NOK;
+        }
+        // The window of the target is only known from the Firmware header.
+        self.read_header(RegistryState::Opening(command, target, slot, owner), slot)
+    }
+
+    fn close_impersonation<F>(&self, owner: AppId, kind: F) -> ResponseCode
+    where
+        F: Fn(ImpersonationTarget) -> bool,
+    {
+        match self.fw_mgt.impersonation_of(owner) {
+            Some(session) if kind(session.target) => {
+                self.fw_mgt._mk_Close_Impersonation(owner);
+                ResponseCode::MGT_ERROR_NONE
+            }
+            _ => ResponseCode::MGT_ERROR_COMMAND_NOK,
+        }
     }
 
+    /// Opens the session once the Firmware header of `slot` is in `header`.
+    fn finish_open(
+        &self,
+        owner: AppId,
+        target: ImpersonationTarget,
+        slot: usize,
+        header: &[u8],
+    ) -> ResponseCode {
+        let window = parse_vpp_header(header)
+            .ok()
+            .and_then(|header| header.impersonation_window(target));
+        let (offset, length) = match window {
+            Some(window) => window,
+            None => return ResponseCode::MGT_ERROR_ILLEGAL_PARAMETER,
+        };
+        let session = ImpersonationSession {
+            owner: owner,
+            firmware: self.record(slot).uuid,
+            target: target,
+            offset: offset,
+            length: length,
+        };
+        match self.fw_mgt._mk_Open_Impersonation(session) {
+            MK_ERROR_e::MK_ERROR_NONE => ResponseCode::MGT_ERROR_NONE,
+            _ => ResponseCode::MGT_ERROR_COMMAND_NOK,
+        }
+    }
+
+    /// Storage address of `length` bytes at `offset` of the memory impersonated
+    /// by `owner`.
+    fn impersonated_address(
+        &self,
+        owner: AppId,
+        offset: usize,
+        length: usize,
+    ) -> Result<usize, ResponseCode> {
+        let session = self
+            .fw_mgt
+            .impersonation_of(owner)
+            .ok_or(ResponseCode::MGT_ERROR_COMMAND_NOK)?;
+        let record = self
+            .find_slot(session.firmware)
+            .map(|slot| self.record(slot))
+            .ok_or(ResponseCode::MGT_ERROR_UNKNOWN_UUID)?;
+        let buffer_length = self.buffer.map_or(0, |buffer| buffer.len());
+        match offset.checked_add(length) {
+            Some(end) if end <= session.length && length <= buffer_length => Ok(self
+                .partition_start
+                + record.partition_offset as usize
+                + session.offset
+                + offset),
+            _ => Err(ResponseCode::MGT_ERROR_ILLEGAL_PARAMETER),
+        }
+    }
+
+    fn is_impersonated(&self, uuid: UUID_t) -> bool {
+        self.fw_mgt
+            ._mk_Get_Impersonation()
+            .map_or(false, |session| session.firmware == uuid)
+    }
+
+    fn record(&self, slot: usize) -> FwRecord {
+        self.records[slot].get().unwrap()
+    }
 
-}
\ No newline at end of file
+    fn find_slot(&self, uuid: UUID_t) -> Option<usize> {
+        self.records
+            .iter()
+            .position(|record| record.get().map_or(false, |record| record.uuid == uuid))
+    }
+
+    fn slot_address(&self, slot: usize) -> usize {
+        self.storage_start + slot * FW_RECORD_SIZE
+    }
+
+    fn read_slot(&self, slot: usize, length: usize) -> ReturnCode {
+        self.buffer.take().map_or(ReturnCode::ERESERVE, |buffer| {
+            self.storage.read(buffer, self.slot_address(slot), length)
+        })
+    }
+
+    /// Reads the record of `slot` while rebuilding the index. If the read
+    /// cannot start, loading stops with the slots read so far: the storage
+    /// keeps the buffer, so the commands needing the storage fail afterwards.
+    fn load_slot(&self, slot: usize) -> ReturnCode {
+        self.state.set(RegistryState::Loading(slot));
+        let result = self.read_slot(slot, FW_RECORD_SIZE);
+        if result != ReturnCode::SUCCESS {
+            self.state.set(RegistryState::Idle);
+            debug!("MGT: cannot read the registry slot {}: {:?}", slot, result);
+        }
+        result
+    }
+
+    /// Reads the record of `slot` up to the end of its Firmware header.
+    fn read_header(&self, state: RegistryState, slot: usize) -> ResponseCode {
+        if self.state.get() != RegistryState::Idle {
+            return ResponseCode::MGT_ERROR_INTERNAL;
+        }
+        self.state.set(state);
+        let length = FW_RECORD_HEADER_SIZE + self.record(slot).header_length as usize;
+        match self.read_slot(slot, length) {
+            ReturnCode::SUCCESS => ResponseCode::MGT_ERROR_NONE,
+            _ => {
+                self.state.set(RegistryState::Idle);
+                ResponseCode::MGT_ERROR_INTERNAL
+            }
+        }
+    }
+
+    /// Writes the record header of `slot`, followed by the Firmware header if one
+    /// is given. The RAM index is updated once the write is done.
+    fn write_record(
+        &self,
+        command: ServiceCommands,
+        slot: usize,
+        record: FwRecord,
+        header: Option<&[u8]>,
+    ) -> ResponseCode {
+        if self.state.get() != RegistryState::Idle {
+            return ResponseCode::MGT_ERROR_INTERNAL;
+        }
+        let buffer = match self.buffer.take() {
+            Some(buffer) => buffer,
+            None => return ResponseCode::MGT_ERROR_INTERNAL,
+        };
+        record.encode(&mut buffer[..FW_RECORD_HEADER_SIZE]);
+        let mut length = FW_RECORD_HEADER_SIZE;
+        if let Some(header) = header {
+            match buffer.get_mut(FW_RECORD_HEADER_SIZE..FW_RECORD_HEADER_SIZE + header.len()) {
+                Some(stored_header) => stored_header.copy_from_slice(header),
+                None => {
+                    self.buffer.replace(buffer);
+                    return ResponseCode::MGT_ERROR_INTERNAL;
+                }
+            }
+            length += header.len();
+        }
+        self.start_write(
+            RegistryState::Busy(command, slot),
+            self.slot_address(slot),
+            buffer,
+            length,
+        )
+    }
+
+    fn start_write(
+        &self,
+        state: RegistryState,
+        address: usize,
+        buffer: &'static mut [u8],
+        length: usize,
+    ) -> ResponseCode {
+        self.state.set(state);
+        match self.storage.write(buffer, address, length) {
+            ReturnCode::SUCCESS => ResponseCode::MGT_ERROR_NONE,
+            _ => {
+                self.state.set(RegistryState::Idle);
+                ResponseCode::MGT_ERROR_INTERNAL
+            }
+        }
+    }
+
+    /// Copies `data` to the buffer shared by the requesting Process through
+    /// allow 0. Returns `false` if it does not fit.
+    fn copy_to_requester(&self, data: &[u8]) -> bool {
+        let mut copied = 0;
+        self.requester.map(|appid| {
+            let _ = self.apps.enter(*appid, |app, _| {
+                app.header.as_mut().map(|dest| {
+                    copied = cmp::min(dest.len(), data.len());
+                    dest.as_mut()[..copied].copy_from_slice(&data[..copied]);
+                });
+            });
+        });
+        copied == data.len()
+    }
+
+    /// Delivers the result of a command to the Process that requested it.
+    fn respond(&self, command_num: usize, response: ResponseCode, value: usize) {
+        self.requester.take().map(|appid| {
+            let _ = self.apps.enter(appid, |app, _| {
+                app.callback.map(|mut callback| {
+                    callback.schedule(command_num, u8::from(response) as usize, value)
+                });
+            });
+        });
+    }
+
+    /// Reads the Firmware UUID shared by the Process through allow 1.
+    fn app_uuid(app: &App) -> Option<UUID_t> {
+        app.uuid.as_ref().and_then(|uuid| {
+            uuid.as_ref()
+                .get(0..16)
+                .and_then(|bytes| bytes.try_into().ok())
+                .map(u128::from_be_bytes)
+        })
+    }
+
+    /// Runs a Firmware command for `appid`. Returns the response and, for commands
+    /// which do not reach the storage, the value of the completion callback.
+    fn dispatch(
+        &self,
+        appid: AppId,
+        app: &App,
+        command: ServiceCommands,
+        data: usize,
+    ) -> (ResponseCode, Option<usize>) {
+        if command == ServiceCommands::MGT_Store_Firmware_Header {
+            return match app.header.as_ref() {
+                Some(header) if data <= header.len() => {
+                    (self.MGT_Store_Firmware_Header(&header.as_ref()[..data]), None)
+                }
+                _ => (ResponseCode::MGT_ERROR_ILLEGAL_PARAMETER, Some(0)),
+            };
+        }
+        match command {
+            ServiceCommands::MGT_Close_Process_Impersonation => {
+                return (self.MGT_Close_Process_Impersonation(appid), Some(0))
+            }
+            ServiceCommands::MGT_Close_Library_Impersonation => {
+                return (self.MGT_Close_Library_Impersonation(appid), Some(0))
+            }
+            ServiceCommands::MGT_Close_LLOS_Impersonation => {
+                return (self.MGT_Close_LLOS_Impersonation(appid), Some(0))
+            }
+            _ => {}
+        }
+        let uuid = match Self::app_uuid(app) {
+            Some(uuid) => uuid,
+            None => return (ResponseCode::MGT_ERROR_ILLEGAL_PARAMETER, Some(0)),
+        };
+        match command {
+            ServiceCommands::MGT_Retrieve_Firmware_Header => {
+                (self.MGT_Retrieve_Firmware_Header(uuid), None)
+            }
+            ServiceCommands::MGT_Allocate_Firmware => (self.MGT_Allocate_Firmware(uuid), None),
+            ServiceCommands::MGT_Delete_Firmware => (self.MGT_Delete_Firmware(uuid), None),
+            ServiceCommands::MGT_Enable_Firmware => (self.MGT_Enable_Firmware(uuid), None),
+            ServiceCommands::MGT_Disable_Firmware => (self.MGT_Disable_Firmware(uuid), None),
+            ServiceCommands::MGT_Open_Process_Impersonation => (
+                self.MGT_Open_Process_Impersonation(appid, uuid, data as MK_Index_t),
+                None,
+            ),
+            ServiceCommands::MGT_Open_Library_Impersonation => (
+                self.MGT_Open_Library_Impersonation(appid, uuid, data as MK_Index_t),
+                None,
+            ),
+            ServiceCommands::MGT_Open_LLOS_Impersonation => {
+                (self.MGT_Open_LLOS_Impersonation(appid, uuid), None)
+            }
+            _ => match self.MGT_Is_Firmware_Enabled(uuid) {
+                Ok(state) => (ResponseCode::MGT_ERROR_NONE, Some(u8::from(state) as usize)),
+                Err(response) => (response, Some(0)),
+            },
+        }
+    }
+}
+
+impl hil::nonvolatile_storage::NonvolatileStorageClient<'static> for mgt_process {
+    fn read_done(&self, buffer: &'static mut [u8], length: usize) {
+        match self.state.get() {
+            RegistryState::Loading(slot) => {
+                self.records[slot].set(buffer.get(..length).and_then(FwRecord::decode));
+                self.buffer.replace(buffer);
+                if slot + 1 < MGT_FIRMWARE_SLOTS {
+                    self.load_slot(slot + 1);
+                } else {
+                    self.state.set(RegistryState::Idle);
+                }
+            }
+            RegistryState::Busy(command, _) => {
+                self.state.set(RegistryState::Idle);
+                // A read shorter than the record header is a storage failure.
+                let (response, header_length) = match buffer.get(FW_RECORD_HEADER_SIZE..length) {
+                    Some(header) if self.copy_to_requester(header) => {
+                        (ResponseCode::MGT_ERROR_NONE, header.len())
+                    }
+                    Some(header) => (ResponseCode::MGT_ERROR_ILLEGAL_PARAMETER, header.len()),
+                    None => (ResponseCode::MGT_ERROR_INTERNAL, 0),
+                };
+                self.buffer.replace(buffer);
+                self.respond(command.command_num(), response, header_length);
+            }
+            RegistryState::Opening(command, target, slot, owner) => {
+                self.state.set(RegistryState::Idle);
+                let response = match buffer.get(FW_RECORD_HEADER_SIZE..length) {
+                    Some(header) => self.finish_open(owner, target, slot, header),
+                    None => ResponseCode::MGT_ERROR_INTERNAL,
+                };
+                self.buffer.replace(buffer);
+                self.respond(command.command_num(), response, 0);
+            }
+            RegistryState::Transfer(command_num) => {
+                self.state.set(RegistryState::Idle);
+                let response = match buffer.get(..length) {
+                    Some(data) if self.copy_to_requester(data) => ResponseCode::MGT_ERROR_NONE,
+                    Some(_) => ResponseCode::MGT_ERROR_ILLEGAL_PARAMETER,
+                    None => ResponseCode::MGT_ERROR_INTERNAL,
+                };
+                self.buffer.replace(buffer);
+                self.respond(command_num, response, length);
+            }
+            RegistryState::Idle => {
+                self.buffer.replace(buffer);
+            }
+        }
+    }
+
+    fn write_done(&self, buffer: &'static mut [u8], length: usize) {
+        match self.state.get() {
+            RegistryState::Busy(command, slot) => {
+                self.state.set(RegistryState::Idle);
+                let record = match command {
+                    ServiceCommands::MGT_Delete_Firmware => None,
+                    ServiceCommands::MGT_Store_Firmware_Header => FwRecord::decode(&buffer[..]),
+                    // Only the record header was written, the UUID is unchanged.
+                    _ => self.records[slot].get().map(|mut record| {
+                        record.state = FwState::from(buffer[4]);
+                        record.allocated = buffer[5] & FW_RECORD_ALLOCATED != 0;
+                        record.partition_offset =
+                            u32::from_le_bytes([buffer[12], buffer[13], buffer[14], buffer[15]]);
+                        record
+                    }),
+                };
+                self.records[slot].set(record);
+                self.buffer.replace(buffer);
+                self.respond(command.command_num(), ResponseCode::MGT_ERROR_NONE, 0);
+            }
+            RegistryState::Transfer(command_num) => {
+                self.state.set(RegistryState::Idle);
+                self.buffer.replace(buffer);
+                self.respond(command_num, ResponseCode::MGT_ERROR_NONE, length);
+            }
+            _ => {
+                self.buffer.replace(buffer);
+            }
+        }
+    }
+}
+
+impl Driver for mgt_process {
+    fn command(&self, command_num: usize, data: usize, data2: usize, appid: AppId) -> ReturnCode {
+        if command_num == 0 {
+            return ReturnCode::SUCCESS;
+        }
+        let command = ServiceCommands::from_command_num(command_num);
+        if command.is_none()
+            && command_num != MGT_READ_IMPERSONATED
+            && command_num != MGT_WRITE_IMPERSONATED
+        {
+            return ReturnCode::ENOSUPPORT;
+        }
+        // The MGT Service is reserved to the MGT Process.
+        if !self.vpp_kernel.is_mgt_process(appid) {
+            return ReturnCode::ERESERVE;
+        }
+        if self.state.get() != RegistryState::Idle || self.requester.is_some() {
+            return ReturnCode::EBUSY;
+        }
+        self.requester.set(appid);
+        let (response, value) = self
+            .apps
+            .enter(appid, |app, _| match command {
+                Some(command) => self.dispatch(appid, app, command, data),
+                None if command_num == MGT_READ_IMPERSONATED => {
+                    (self.read_impersonated(appid, data, data2), None)
+                }
+                None => match app.header.as_ref() {
+                    Some(source) if data2 <= source.len() => (
+                        self.write_impersonated(appid, data, &source.as_ref()[..data2]),
+                        None,
+                    ),
+                    _ => (ResponseCode::MGT_ERROR_ILLEGAL_PARAMETER, Some(0)),
+                },
+            })
+            .unwrap_or((ResponseCode::MGT_ERROR_INTERNAL, Some(0)));
+        // A command that did not reach the storage completes right away.
+        match value {
+            Some(value) => self.respond(command_num, response, value),
+            None if response != ResponseCode::MGT_ERROR_NONE => {
+                self.respond(command_num, response, 0)
+            }
+            None => {}
+        }
+        ReturnCode::SUCCESS
+    }
+
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 | 1 => self
+                .apps
+                .enter(appid, |app, _| {
+                    if allow_num == 0 {
+                        app.header = slice;
+                    } else {
+                        app.uuid = slice;
+                    }
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use crate::vpp::mloi::MK_PROCESS_MGT_VPP_ID;
+    use crate::vpp::sim::SimBuilder;
+    use kernel::hil::nonvolatile_storage::{NonvolatileStorage, NonvolatileStorageClient};
+    use std::boxed::Box;
+
+    /// Storage of erased records, which refuses to start a read once `reads`
+    /// reads were made. A read completes when the test calls `step`.
+    struct SimStorage {
+        reads: Cell<usize>,
+        pending: TakeCell<'static, [u8]>,
+        length: Cell<usize>,
+    }
+
+    impl SimStorage {
+        fn step(&self, client: &dyn NonvolatileStorageClient<'static>) {
+            let buffer = self.pending.take().unwrap();
+            for byte in buffer.iter_mut() {
+                *byte = 0xFF;
+            }
+            client.read_done(buffer, self.length.get());
+        }
+    }
+
+    impl NonvolatileStorage<'static> for SimStorage {
+        fn set_client(&self, _client: &'static dyn NonvolatileStorageClient<'static>) {}
+
+        fn read(&self, buffer: &'static mut [u8], _address: usize, length: usize) -> ReturnCode {
+            if self.reads.get() == 0 {
+                return ReturnCode::FAIL;
+            }
+            self.reads.set(self.reads.get() - 1);
+            self.pending.replace(buffer);
+            self.length.set(length);
+            ReturnCode::SUCCESS
+        }
+
+        fn write(&self, _buffer: &'static mut [u8], _address: usize, _length: usize) -> ReturnCode {
+            ReturnCode::FAIL
+        }
+    }
+
+    #[test]
+    fn a_truncated_record_is_a_free_slot() {
+        let mut raw = [0; FW_RECORD_HEADER_SIZE + 8];
+        FwRecord {
+            uuid: 0,
+            state: FwState::from(0),
+            allocated: false,
+            header_length: 8,
+            partition_size: 0,
+            partition_offset: 0,
+        }
+        .encode(&mut raw);
+
+        assert!(FwRecord::decode(&raw[..2]).is_none());
+        assert!(FwRecord::decode(&raw[..6]).is_none());
+        assert!(FwRecord::decode(&raw[..FW_RECORD_HEADER_SIZE]).is_none());
+        assert!(FwRecord::decode(&raw[..FW_RECORD_HEADER_SIZE + 4]).is_none());
+    }
+
+    #[test]
+    fn a_failed_read_ends_the_loading() {
+        let sim = SimBuilder::new()
+            .process(MK_PROCESS_MGT_VPP_ID, 0, 0)
+            .build();
+        let storage: &'static SimStorage = Box::leak(Box::new(SimStorage {
+            reads: Cell::new(1),
+            pending: TakeCell::empty(),
+            length: Cell::new(0),
+        }));
+        let registry: &'static mgt_process = Box::leak(Box::new(mgt_process::new(
+            storage,
+            0,
+            MGT_REGISTRY_SIZE,
+            0,
+            sim.vpp_kernel,
+            Box::leak(Box::new(_mk_Fw_Mgt::new(sim.vpp_kernel))),
+            sim.create_grant(),
+            Box::leak(Box::new([0; FW_RECORD_SIZE])),
+        )));
+
+        assert_eq!(registry.load(), ReturnCode::SUCCESS);
+        assert!(registry.state.get() == RegistryState::Loading(0));
+        storage.step(registry);
+        assert!(registry.state.get() == RegistryState::Idle);
+
+        let command = ServiceCommands::MGT_Retrieve_Firmware_Header.command_num();
+        assert_eq!(
+            registry.command(command, 0, 0, sim.appid(0)),
+            ReturnCode::SUCCESS
+        );
+    }
+}
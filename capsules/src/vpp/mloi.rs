@@ -12,12 +12,14 @@
 //use alloc::boxed::Box;
 //the 8-bit, 16-bit, 32-bit and 64-bit unsigned types are already defined as
 // u8, u16, u32, u64 respectively. No need to re-define them.
+use core::convert::TryFrom;
 use core::fmt;
 /// XLEN is the size memory address. It is platform dependent value and for 32-bit platforms
 /// equals 4 bytes.
 pub (crate) type XLEN = u32;
 
 /// Firmware/Software Types (Table 7-6)
+#[derive(Clone, Copy, PartialEq)]
 pub  enum VPP_FRW_TYPE_e {
     /// The firmware of a VPP Application
     FIRMWARE_SOFTWARE_TYPE_APP,
@@ -38,6 +40,18 @@ impl From<VPP_FRW_TYPE_e> for u8 {
         }
     }
 }
+impl TryFrom<u8> for VPP_FRW_TYPE_e {
+    type Error = ();
+    fn try_from(original: u8) -> Result<VPP_FRW_TYPE_e, ()> {
+        match original {
+            0x01 => Ok(VPP_FRW_TYPE_e::FIRMWARE_SOFTWARE_TYPE_APP),
+            0x02 => Ok(VPP_FRW_TYPE_e::FIRMWARE_SOFTWARE_TYPE_VPP),
+            0x04 => Ok(VPP_FRW_TYPE_e::FIRMWARE_SOFTWARE_TYPE_SYSAPP),
+            0x08 => Ok(VPP_FRW_TYPE_e::FIRMWARE_SOFTWARE_TYPE_LLOS),
+            _ => Err(()),
+        }
+    }
+}
 
 impl fmt::Debug for VPP_FRW_TYPE_e {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
@@ -92,16 +106,10 @@ impl From<MK_EXECUTION_DOMAIN_TYPE_e> for u16 {
 
 /// Composite Identifier of an IPC (Table 7-2)
 pub type MK_IPC_ID_u = u16;
-pub fn convert_ipcID_to_handle(_eIPCID: MK_IPC_ID_u) -> MK_HANDLE_t {_eIPCID as u32}
-pub fn convert_handle_to_ipcID(handle: MK_HANDLE_t) -> MK_IPC_ID_u {handle as u16}
 /// Composite Identifier of a Mailbox (Table 7-2)
 pub type MK_MAILBOX_ID_u = u16 ;
-pub fn convert_mbid_to_handle(_eMailboxID: MK_MAILBOX_ID_u) -> MK_HANDLE_t{_eMailboxID as u32}
-pub fn convert_handle_to_mbid(handle: MK_HANDLE_t) -> MK_MAILBOX_ID_u{handle as u16}
 /// Composite Identifier of a Process (Table 7-2)
 pub type MK_Process_ID_u = u16;
-pub fn convert_to_handle(id: MK_Process_ID_u) -> MK_HANDLE_t{id as u32}
-pub fn convert_to_id(handle: MK_HANDLE_t) -> MK_Process_ID_u{handle as u16}
 
 
 #[derive(Copy, Clone, Eq, PartialEq)]
@@ -126,6 +134,17 @@ impl From<MK_PROCESS_PRIORITY_e> for u16 {
         }
     }
 }
+impl TryFrom<u16> for MK_PROCESS_PRIORITY_e {
+    type Error = ();
+    fn try_from(original: u16) -> Result<MK_PROCESS_PRIORITY_e, ()> {
+        match original {
+            0x0000 => Ok(MK_PROCESS_PRIORITY_e::MK_PROCESS_PRIORITY_LOW),
+            0x0004 => Ok(MK_PROCESS_PRIORITY_e::MK_PROCESS_PRIORITY_NORMAL),
+            0x0008 => Ok(MK_PROCESS_PRIORITY_e::MK_PROCESS_PRIORITY_HIGH),
+            _ => Err(()),
+        }
+    }
+}
 impl fmt::Debug for MK_PROCESS_PRIORITY_e {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
@@ -152,6 +171,7 @@ pub type MK_LIB_ID_u = u16 ;
 pub type MK_LIB_ID_e = u16 ;
 
 /// VRE Identifiers (Table 7-5)
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum MK_VRE_e{
     /// Access to the interfaces of the AES Function
     MK_VRE_AES,
@@ -184,6 +204,22 @@ impl From<MK_VRE_e> for u32 {
         }
     }
 }
+impl TryFrom<u32> for MK_VRE_e {
+    type Error = ();
+    fn try_from(original: u32) -> Result<MK_VRE_e, ()> {
+        match original {
+            0x01 => Ok(MK_VRE_e::MK_VRE_AES),
+            0x04 => Ok(MK_VRE_e::MK_VRE_ECC),
+            0x08 => Ok(MK_VRE_e::MK_VRE_RSA),
+            0x10 => Ok(MK_VRE_e::MK_VRE_ROT),
+            0x20 => Ok(MK_VRE_e::MK_VRE_HASH),
+            0x40 => Ok(MK_VRE_e::MK_VRE_RNG),
+            0x80 => Ok(MK_VRE_e::MK_VRE_RAF),
+            0x100 => Ok(MK_VRE_e::MK_VRE_DOMAIN_BASE),
+            _ => Err(()),
+        }
+    }
+}
 
 /// Memory Address to a Process entry point
 // should be changed depending on pointers handling in rust (as_ptr)
@@ -202,7 +238,7 @@ pub  type UUID_t = u128;
 pub type v32_u = u32 ;
 
 /// Errors (Table 7-12)
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum MK_ERROR_e {
     /// No error
     MK_ERROR_NONE,
@@ -228,6 +264,8 @@ pub enum MK_ERROR_e {
     MK_ERROR_GET_ERROR_HANDLE_NOT_A_PROCESS,
     /// Reserved for VPP imlementation-specific
     MK_ERROR_VENDOR_BASE,
+    /// The wait for a Signal timed out (implementation-specific)
+    MK_ERROR_TIME_OUT,
     /// Maximal error value
     MK_ERROR_MAX,
 }
@@ -246,6 +284,7 @@ impl From<MK_ERROR_e> for usize {
             MK_ERROR_e::MK_ERROR_HANDLE_NOT_ACCESSED =>9,
             MK_ERROR_e::MK_ERROR_GET_ERROR_HANDLE_NOT_A_PROCESS => 10,
             MK_ERROR_e::MK_ERROR_VENDOR_BASE => 32,
+            MK_ERROR_e::MK_ERROR_TIME_OUT => 33,
             MK_ERROR_e::MK_ERROR_MAX => 255
         }
     }
@@ -289,6 +328,9 @@ impl fmt::Debug for MK_ERROR_e {
             MK_ERROR_e::MK_ERROR_VENDOR_BASE=> {
                 write!(f, "Reserved for VPP implementation specific\n")
             }
+            MK_ERROR_e::MK_ERROR_TIME_OUT => {
+                write!(f, "Time out\n")
+            }
             MK_ERROR_e::MK_ERROR_MAX => {
                 write!(f, "Maximal Error Value\n")
             }
@@ -415,6 +457,12 @@ pub (crate) type  MK_HANDLE_t = u32;
 /// Time unsigned 64-bit integer
 pub (crate) type  MK_TIME_t = u64;
 
+/// Timeout value of _mk_Wait_Signal waiting without a time limit
+pub const MK_TIME_INFINITE: u32 = 0xFFFF_FFFF;
+
+/// Mailbox handle of _mk_Wait_Signal waiting on any Mailbox of the caller Process
+pub const MK_HANDLE_ANY_MAILBOX: MK_HANDLE_t = 0;
+
 #[derive(Copy, Clone, Eq, PartialEq)]
 /// VPP States
 pub enum VppState {
@@ -464,6 +512,7 @@ impl fmt::Debug for VppState {
 }
 
 // Scheduling Types (Table 7-7)
+#[derive(Clone, Copy, PartialEq)]
 pub enum VPP_SCHEDULING_TYPE_e {
     /// Collaborative Scheduling
     MK_SCHEDULING_TYPE_COLLABORATIVE,
@@ -478,6 +527,16 @@ impl From<VPP_SCHEDULING_TYPE_e> for u8 {
         }
     }
 }
+impl TryFrom<u8> for VPP_SCHEDULING_TYPE_e {
+    type Error = ();
+    fn try_from(original: u8) -> Result<VPP_SCHEDULING_TYPE_e, ()> {
+        match original {
+            0x01 => Ok(VPP_SCHEDULING_TYPE_e::MK_SCHEDULING_TYPE_COLLABORATIVE),
+            0x02 => Ok(VPP_SCHEDULING_TYPE_e::MK_SCHEDULING_TYPE_PREEMPTIVE),
+            _ => Err(()),
+        }
+    }
+}
 
 
 //Constants and Limits for Any Primary Platfrom (Table 7-9)
@@ -520,7 +579,7 @@ pub(crate) const MK_LIB_MAX_ID : u16 = 0x3FFF ;
 
 /// Minimal enumerated Mailbox identifiers within the
 /// scope of an Execution Domain
-const MK_MAILBOX_DOMAIN_BASE_ID : u8 = 100 ;
+pub (crate) const MK_MAILBOX_DOMAIN_BASE_ID : u8 = 100 ;
 
 ///Maximal number of Mailbox descriptors per Firmware
 /// excluding the kernel Mailbox
@@ -2,15 +2,22 @@ pub mod mloi;
 pub mod process;
 //pub mod ProcessManager;
 pub mod pmsyscall;
-// with capabilities
-pub mod ProcessManagerConsoleCap;
-// without capabilities
-// pub mod ProcessManagerConsole;
 pub mod sub_test;
 pub mod mgt_service;
 pub mod vff;
 pub mod mailbox;
 pub mod com_service;
+pub mod console;
 pub mod vppkernel;
 pub mod ipc;
-//pub mod timer;
+pub mod library;
+pub mod handle;
+pub mod nvm;
+pub mod signal_wait;
+pub mod scheduler;
+pub mod timer;
+pub mod vre;
+#[cfg(test)]
+mod sim;
+#[cfg(test)]
+mod tests;
@@ -0,0 +1,983 @@
+//! Transactional NVM of the VPP Processes.
+//!
+//! Every VPP Process owns an NVM area in the `.vpp_app` section made of two banks of
+//! `NVM_BANK_PAGES` flash pages. The area is the one of the Process in the
+//! `VppKernel`, not of the slot of its app: an app loaded in the slot of an unloaded
+//! Process does not see its data, and apps which are not VPP Processes have no NVM. One bank holds the committed content, the other one
+//! (the shadow bank) receives the writes of the current transaction. Each bank
+//! starts with a header:
+//!
+//! ```text
+//! 0        4          8        12         16
+//! +--------+----------+--------+----------+-----------
+//! | magic  | sequence | CRC-32 | reserved | data ...
+//! +--------+----------+--------+----------+-----------
+//! ```
+//!
+//! Every page of the shadow bank is erased before it is programmed.
+//! `_mk_Commit` copies the pages left untouched by the transaction to the shadow
+//! bank, then writes the first page of the shadow bank last, with a sequence number
+//! one above the committed bank and the CRC of its data. That single page write is
+//! the commit point: at boot the valid bank with the highest sequence number wins,
+//! so a commit interrupted by a power loss leaves the previous content in place.
+//!
+//! Uncommitted writes only ever reach the shadow bank, so they are discarded by
+//! `_mk_Rollback`, by a reboot, and by a fault of the Process: a restarted Process
+//! gets a new `AppId`, which drops the transaction of the previous instance.
+//!
+//! A shadow page only holds writes of the transaction once it is programmed. A write
+//! or a commit failing after it started programming the flash rolls the transaction
+//! back, as the page being programmed may be erased or half written.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//!     store_vpp_sec!(VPP_NVM, 17 * 1024);
+//!     let nvm = static_init!(
+//!         capsules::vpp::nvm::VppNvm<'static, FlashUser<'static, FlashCtrl>>,
+//!         capsules::vpp::nvm::VppNvm::new(
+//!             vpp_kernel,
+//!             &VPP_NVM,
+//!             nvm_flash,
+//!             &mut NVM_PAGEBUFFER,
+//!             board_kernel.create_grant(&memory_allocation_cap),
+//!         )
+//!     );
+//!     hil::flash::HasClient::set_client(nvm_flash, nvm);
+//!     vpp_kernel.set_nvm(nvm);
+//! ```
+//!
+//! Userspace interface:
+//!
+//! - allow 0: data buffer.
+//! - subscribe 0: completion callback `(command, ReturnCode, length)`.
+//! - command 1: size of the NVM area.
+//! - command 2: read `data2` bytes at offset `data` into the buffer.
+//! - command 3: write `data2` bytes of the buffer at offset `data`.
+//! - command 4: commit the transaction.
+//! - command 5: rollback the transaction.
+
+use crate::driver;
+use crate::vpp::vppkernel::{VppKernel, NUM_PROCS};
+use core::cell::Cell;
+use core::cmp;
+use core::convert::TryInto;
+use kernel::common::cells::TakeCell;
+use kernel::hil::flash::{self, Flash};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+pub const DRIVER_NUM: usize = driver::NUM::VppNvmDriver as usize;
+
+/// Flash pages of one bank.
+pub const NVM_BANK_PAGES: usize = 2;
+const BANK_HEADER_SIZE: usize = 16;
+const BANK_MAGIC: u32 = 0x4E56_4D42; // "NVMB"
+
+/// Transactions of the VPP Process NVM, as used by `_mk_Commit` and `_mk_Rollback`.
+pub trait NvmTransaction {
+    /// Makes the writes of the transaction of `appid` persistent. The completion
+    /// is signaled through the NVM driver callback.
+    fn commit(&self, appid: AppId) -> ReturnCode;
+    /// Discards the writes of the transaction of `appid`.
+    fn rollback(&self, appid: AppId) -> ReturnCode;
+}
+
+#[derive(Clone, Copy)]
+struct Transaction {
+    /// Bank holding the committed content, `None` while the NVM is blank.
+    active: Option<usize>,
+    sequence: u32,
+    /// Pages of the shadow bank holding uncommitted writes.
+    dirty: u32,
+    /// Process instance the uncommitted writes belong to.
+    owner: Option<AppId>,
+}
+
+impl Default for Transaction {
+    fn default() -> Self {
+        Transaction {
+            active: None,
+            sequence: 0,
+            dirty: 0,
+            owner: None,
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum State {
+    Idle,
+    /// Writing `length` bytes at `offset` of the NVM of `process`, `done` so far
+    /// once the given page of the shadow bank is programmed.
+    Write {
+        process: usize,
+        offset: usize,
+        length: usize,
+        done: usize,
+        page: usize,
+    },
+    /// Writing the given page of the shadow bank of `process`.
+    Commit { process: usize, page: usize },
+}
+
+#[derive(Default)]
+pub struct App {
+    callback: Option<Callback>,
+    buffer: Option<AppSlice<Shared, u8>>,
+}
+
+pub struct VppNvm<'a, F: Flash + 'static> {
+    vpp_kernel: &'static VppKernel,
+    /// Storage volume in the `.vpp_app` section.
+    volume: &'static [u8],
+    driver: &'a F,
+    pagebuffer: TakeCell<'static, F::Page>,
+    page_size: usize,
+    /// First page of the volume aligned on a flash page.
+    first_page: usize,
+    transactions: [Cell<Transaction>; NUM_PROCS],
+    state: Cell<State>,
+    /// Page being erased, programmed with the page buffer once erased.
+    erasing: Cell<Option<usize>>,
+    apps: Grant<App>,
+}
+
+impl<'a, F: Flash + 'static> VppNvm<'a, F> {
+    pub fn new(
+        vpp_kernel: &'static VppKernel,
+        volume: &'static [u8],
+        driver: &'a F,
+        pagebuffer: &'static mut F::Page,
+        grant: Grant<App>,
+    ) -> VppNvm<'a, F> {
+        let page_size = pagebuffer.as_mut().len();
+        let first_page = (volume.as_ptr() as usize + page_size - 1) / page_size;
+        let nvm = VppNvm {
+            vpp_kernel,
+            volume,
+            driver,
+            pagebuffer: TakeCell::new(pagebuffer),
+            page_size,
+            first_page,
+            transactions: Default::default(),
+            state: Cell::new(State::Idle),
+            erasing: Cell::new(None),
+            apps: grant,
+        };
+        nvm.reconstruct();
+        nvm
+    }
+
+    /// Size in bytes of the NVM area of a Process.
+    pub fn capacity(&self) -> usize {
+        NVM_BANK_PAGES * self.page_size - BANK_HEADER_SIZE
+    }
+
+    /// Finds the committed bank of every Process.
+    fn reconstruct(&self) {
+        for (process, transaction) in self.transactions.iter().enumerate() {
+            let mut committed = Transaction::default();
+            for bank in 0..2 {
+                if let Some(sequence) = self.valid_sequence(process, bank) {
+                    if committed.active.is_none() || sequence > committed.sequence {
+                        committed.active = Some(bank);
+                        committed.sequence = sequence;
+                    }
+                }
+            }
+            transaction.set(committed);
+        }
+    }
+
+    /// Sequence number of a bank, `None` if its header or CRC is invalid.
+    fn valid_sequence(&self, process: usize, bank: usize) -> Option<u32> {
+        let raw = self.bank(process, bank)?;
+        let word = |i: usize| u32::from_le_bytes(raw[i..i + 4].try_into().unwrap());
+        if word(0) != BANK_MAGIC || word(8) != crc32(&raw[BANK_HEADER_SIZE..]) {
+            return None;
+        }
+        Some(word(4))
+    }
+
+    /// Memory mapped content of a bank.
+    fn bank(&self, process: usize, bank: usize) -> Option<&'static [u8]> {
+        let start = self.page_number(process, bank, 0) * self.page_size
+            - self.volume.as_ptr() as usize;
+        self.volume.get(start..start + NVM_BANK_PAGES * self.page_size)
+    }
+
+    fn page_number(&self, process: usize, bank: usize, page: usize) -> usize {
+        self.first_page + (process * 2 + bank) * NVM_BANK_PAGES + page
+    }
+
+    /// Transaction of the Process `appid`, `None` if `appid` is not a VPP Process.
+    /// The uncommitted writes of a previous instance of that Process are dropped.
+    fn transaction(&self, appid: AppId) -> Option<(usize, Transaction)> {
+        let process = self.vpp_kernel.process_index(appid)? as usize;
+        self.bank(process, 1)?;
+        let mut transaction = self.transactions[process].get();
+        if transaction.owner != Some(appid) {
+            transaction.dirty = 0;
+            transaction.owner = Some(appid);
+            self.transactions[process].set(transaction);
+        }
+        Some((process, transaction))
+    }
+
+    fn shadow(transaction: &Transaction) -> usize {
+        transaction.active.map_or(0, |bank| 1 - bank)
+    }
+
+    /// Current content of a page as seen by the transaction: the shadow page if
+    /// it was written, else the committed page. `None` while the NVM is blank.
+    fn page(&self, process: usize, transaction: &Transaction, page: usize) -> Option<&'static [u8]> {
+        let bank = if transaction.dirty & (1 << page) != 0 {
+            Some(Self::shadow(transaction))
+        } else {
+            transaction.active
+        };
+        bank.and_then(|bank| self.bank(process, bank))
+            .map(|raw| &raw[page * self.page_size..(page + 1) * self.page_size])
+    }
+
+    fn load_page(&self, process: usize, transaction: &Transaction, page: usize, dest: &mut [u8]) {
+        match self.page(process, transaction, page) {
+            Some(raw) => dest.copy_from_slice(raw),
+            None => {
+                for byte in dest.iter_mut() {
+                    *byte = 0;
+                }
+            }
+        }
+    }
+
+    /// Reads `dest.len()` bytes at `offset` of the NVM, including the uncommitted
+    /// writes of the transaction.
+    pub fn read(&self, appid: AppId, offset: usize, dest: &mut [u8]) -> ReturnCode {
+        let (process, transaction) = match self.transaction(appid) {
+            Some(transaction) => transaction,
+            None => return ReturnCode::EINVAL,
+        };
+        if offset.checked_add(dest.len()).map_or(true, |end| end > self.capacity()) {
+            return ReturnCode::ESIZE;
+        }
+        let mut position = BANK_HEADER_SIZE + offset;
+        let mut done = 0;
+        while done < dest.len() {
+            let start = position % self.page_size;
+            let chunk = cmp::min(dest.len() - done, self.page_size - start);
+            match self.page(process, &transaction, position / self.page_size) {
+                Some(raw) => dest[done..done + chunk].copy_from_slice(&raw[start..start + chunk]),
+                None => {
+                    for byte in dest[done..done + chunk].iter_mut() {
+                        *byte = 0;
+                    }
+                }
+            }
+            done += chunk;
+            position += chunk;
+        }
+        ReturnCode::SUCCESS
+    }
+
+    /// Writes `length` bytes of the buffer of `appid` at `offset` of its NVM, as
+    /// part of its transaction.
+    fn write(&self, appid: AppId, offset: usize, length: usize) -> ReturnCode {
+        let (process, _) = match self.transaction(appid) {
+            Some(transaction) => transaction,
+            None => return ReturnCode::EINVAL,
+        };
+        if offset.checked_add(length).map_or(true, |end| end > self.capacity()) {
+            return ReturnCode::ESIZE;
+        }
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        self.state.set(State::Write {
+            process,
+            offset,
+            length,
+            done: 0,
+            page: 0,
+        });
+        let ret = self.write_next_page(appid);
+        if ret != ReturnCode::SUCCESS {
+            self.state.set(State::Idle);
+        }
+        ret
+    }
+
+    /// Writes the next page of the ongoing write to the shadow bank.
+    fn write_next_page(&self, appid: AppId) -> ReturnCode {
+        let (process, offset, length, done) = match self.state.get() {
+            State::Write {
+                process,
+                offset,
+                length,
+                done,
+                ..
+            } => (process, offset, length, done),
+            _ => return ReturnCode::FAIL,
+        };
+        let pagebuffer = match self.pagebuffer.take() {
+            Some(pagebuffer) => pagebuffer,
+            None => return ReturnCode::EBUSY,
+        };
+        let transaction = self.transactions[process].get();
+        let position = BANK_HEADER_SIZE + offset + done;
+        let page = position / self.page_size;
+        let start = position % self.page_size;
+        let chunk = cmp::min(length - done, self.page_size - start);
+        self.load_page(process, &transaction, page, pagebuffer.as_mut());
+        let copied = self
+            .apps
+            .enter(appid, |app, _| {
+                app.buffer.as_ref().map_or(false, |buffer| {
+                    match buffer.as_ref().get(done..done + chunk) {
+                        Some(source) => {
+                            pagebuffer.as_mut()[start..start + chunk].copy_from_slice(source);
+                            true
+                        }
+                        None => false,
+                    }
+                })
+            })
+            .unwrap_or(false);
+        if !copied {
+            self.pagebuffer.replace(pagebuffer);
+            return ReturnCode::ESIZE;
+        }
+        if page == 0 {
+            // The shadow bank stays invalid until it is committed.
+            pagebuffer.as_mut()[0..4].copy_from_slice(&[0; 4]);
+        }
+        self.state.set(State::Write {
+            process,
+            offset,
+            length,
+            done: done + chunk,
+            page,
+        });
+        let shadow = Self::shadow(&transaction);
+        self.program_page(self.page_number(process, shadow, page), pagebuffer)
+    }
+
+    /// Writes the given page of the shadow bank during a commit. The untouched
+    /// pages are copied from the committed bank; the first page, holding the bank
+    /// header, is written last.
+    fn commit_page(&self, process: usize, page: usize) -> ReturnCode {
+        let pagebuffer = match self.pagebuffer.take() {
+            Some(pagebuffer) => pagebuffer,
+            None => return ReturnCode::EBUSY,
+        };
+        let transaction = self.transactions[process].get();
+        let shadow = Self::shadow(&transaction);
+        self.load_page(process, &transaction, page, pagebuffer.as_mut());
+        if page == 0 {
+            let mut crc = crc32_update(0xFFFF_FFFF, &pagebuffer.as_mut()[BANK_HEADER_SIZE..]);
+            if let Some(raw) = self.bank(process, shadow) {
+                crc = crc32_update(crc, &raw[self.page_size..]);
+            }
+            let sequence = transaction.sequence.wrapping_add(1);
+            let header = pagebuffer.as_mut();
+            header[0..4].copy_from_slice(&BANK_MAGIC.to_le_bytes());
+            header[4..8].copy_from_slice(&sequence.to_le_bytes());
+            header[8..12].copy_from_slice(&(!crc).to_le_bytes());
+            header[12..16].copy_from_slice(&[0; 4]);
+        }
+        self.state.set(State::Commit { process, page });
+        let ret = self.program_page(self.page_number(process, shadow, page), pagebuffer);
+        if ret != ReturnCode::SUCCESS {
+            self.state.set(State::Idle);
+        }
+        ret
+    }
+
+    /// Erases the flash page `page_number`, then writes `pagebuffer` to it. The
+    /// write completes in `write_complete`.
+    fn program_page(&self, page_number: usize, pagebuffer: &'static mut F::Page) -> ReturnCode {
+        self.pagebuffer.replace(pagebuffer);
+        self.erasing.set(Some(page_number));
+        let ret = self.driver.erase_page(page_number);
+        if ret != ReturnCode::SUCCESS {
+            self.erasing.set(None);
+        }
+        ret
+    }
+
+    /// Ends the ongoing write or commit with the error `ret`. The page being
+    /// programmed may have been erased, losing writes of the transaction: the
+    /// transaction is rolled back.
+    fn abort(&self, ret: ReturnCode) {
+        let (process, command) = match self.state.get() {
+            State::Write { process, .. } => (process, 3),
+            State::Commit { process, .. } => (process, 4),
+            State::Idle => return,
+        };
+        let mut transaction = self.transactions[process].get();
+        transaction.dirty = 0;
+        self.transactions[process].set(transaction);
+        self.state.set(State::Idle);
+        self.callback(process, command, ret, 0);
+    }
+
+    fn callback(&self, process: usize, command: usize, ret: ReturnCode, length: usize) {
+        if let Some(appid) = self.transactions[process].get().owner {
+            let _ = self.apps.enter(appid, |app, _| {
+                app.callback.map(|mut callback| {
+                    callback.schedule(command, usize::from(ret), length)
+                });
+            });
+        }
+    }
+}
+
+impl<F: Flash + 'static> NvmTransaction for VppNvm<'_, F> {
+    fn commit(&self, appid: AppId) -> ReturnCode {
+        let (process, transaction) = match self.transaction(appid) {
+            Some(transaction) => transaction,
+            None => return ReturnCode::EINVAL,
+        };
+        if self.state.get() != State::Idle {
+            return ReturnCode::EBUSY;
+        }
+        if transaction.dirty == 0 {
+            self.callback(process, 4, ReturnCode::SUCCESS, 0);
+            return ReturnCode::SUCCESS;
+        }
+        // Page 0 is the commit point and goes last.
+        let first = if NVM_BANK_PAGES > 1 { 1 } else { 0 };
+        self.commit_page(process, first)
+    }
+
+    fn rollback(&self, appid: AppId) -> ReturnCode {
+        let (process, mut transaction) = match self.transaction(appid) {
+            Some(transaction) => transaction,
+            None => return ReturnCode::EINVAL,
+        };
+        if let State::Write { process: writer, .. } | State::Commit { process: writer, .. } =
+            self.state.get()
+        {
+            if writer == process {
+                return ReturnCode::EBUSY;
+            }
+        }
+        transaction.dirty = 0;
+        self.transactions[process].set(transaction);
+        ReturnCode::SUCCESS
+    }
+}
+
+impl<F: Flash + 'static> flash::Client<F> for VppNvm<'_, F> {
+    fn read_complete(&self, _read_buffer: &'static mut F::Page, _error: flash::Error) {
+        // Reads are made directly from the storage volume, not through the flash
+        // interface, a read completing is not ours.
+    }
+
+    fn write_complete(&self, pagebuffer: &'static mut F::Page, error: flash::Error) {
+        self.pagebuffer.replace(pagebuffer);
+        match self.state.get() {
+            State::Write {
+                process,
+                length,
+                done,
+                page,
+                ..
+            } => {
+                if error != flash::Error::CommandComplete {
+                    self.abort(ReturnCode::FAIL);
+                    return;
+                }
+                // The shadow page now holds the writes of the transaction.
+                let mut transaction = self.transactions[process].get();
+                transaction.dirty |= 1 << page;
+                self.transactions[process].set(transaction);
+                let ret = if done < length {
+                    match transaction.owner {
+                        Some(appid) => self.write_next_page(appid),
+                        None => ReturnCode::FAIL,
+                    }
+                } else {
+                    self.state.set(State::Idle);
+                    self.callback(process, 3, ReturnCode::SUCCESS, length);
+                    return;
+                };
+                if ret != ReturnCode::SUCCESS {
+                    self.abort(ret);
+                }
+            }
+            State::Commit { process, page } => {
+                if error != flash::Error::CommandComplete {
+                    self.abort(ReturnCode::FAIL);
+                    return;
+                }
+                if page != 0 {
+                    let next = if page + 1 < NVM_BANK_PAGES { page + 1 } else { 0 };
+                    let ret = self.commit_page(process, next);
+                    if ret != ReturnCode::SUCCESS {
+                        self.callback(process, 4, ret, 0);
+                    }
+                    return;
+                }
+                // The header is written: the shadow bank is now the committed one.
+                let mut transaction = self.transactions[process].get();
+                transaction.active = Some(Self::shadow(&transaction));
+                transaction.sequence = transaction.sequence.wrapping_add(1);
+                transaction.dirty = 0;
+                self.transactions[process].set(transaction);
+                self.state.set(State::Idle);
+                self.callback(process, 4, ReturnCode::SUCCESS, 0);
+            }
+            State::Idle => {}
+        }
+    }
+
+    fn erase_complete(&self, error: flash::Error) {
+        let page_number = match self.erasing.take() {
+            Some(page_number) => page_number,
+            None => return,
+        };
+        if error != flash::Error::CommandComplete {
+            self.abort(ReturnCode::FAIL);
+            return;
+        }
+        let ret = match self.pagebuffer.take() {
+            Some(pagebuffer) => match self.driver.write_page(page_number, pagebuffer) {
+                Ok(()) => return,
+                Err((ret, pagebuffer)) => {
+                    self.pagebuffer.replace(pagebuffer);
+                    ret
+                }
+            },
+            None => ReturnCode::FAIL,
+        };
+        self.abort(ret);
+    }
+}
+
+impl<F: Flash + 'static> Driver for VppNvm<'_, F> {
+    fn command(&self, command_num: usize, data: usize, data2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => ReturnCode::SuccessWithValue {
+                value: self.capacity(),
+            },
+            2 => self
+                .apps
+                .enter(appid, |app, _| match app.buffer.as_mut() {
+                    Some(buffer) if data2 <= buffer.len() => {
+                        self.read(appid, data, &mut buffer.as_mut()[..data2])
+                    }
+                    _ => ReturnCode::ESIZE,
+                })
+                .unwrap_or_else(|err| err.into()),
+            3 => self.write(appid, data, data2),
+            4 => self.commit(appid),
+            5 => self.rollback(appid),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        match allow_num {
+            0 => self
+                .apps
+                .enter(appid, |app, _| {
+                    app.buffer = slice;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
+
+fn crc32_update(mut crc: u32, data: &[u8]) -> u32 {
+    for &byte in data {
+        crc ^= byte as u32;
+        for _ in 0..8 {
+            let mask = (crc & 1).wrapping_neg();
+            crc = (crc >> 1) ^ (0xEDB8_8320 & mask);
+        }
+    }
+    crc
+}
+
+/// CRC-32 (IEEE 802.3) of `data`.
+fn crc32(data: &[u8]) -> u32 {
+    !crc32_update(0xFFFF_FFFF, data)
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use crate::vpp::sim::{Sim, SimBuilder};
+    use kernel::procs::{ProcessEvent, ProcessObserver};
+    use kernel::common::cells::OptionalCell;
+    use std::boxed::Box;
+    use std::vec;
+
+    const PAGE_SIZE: usize = 64;
+
+    struct SimPage([u8; PAGE_SIZE]);
+
+    impl Default for SimPage {
+        fn default() -> SimPage {
+            SimPage([0; PAGE_SIZE])
+        }
+    }
+
+    impl AsMut<[u8]> for SimPage {
+        fn as_mut(&mut self) -> &mut [u8] {
+            &mut self.0
+        }
+    }
+
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    enum Operation {
+        Erase(usize),
+        Write(usize),
+    }
+
+    /// NOR flash mapped at the page numbers it is given: erasing sets the bits
+    /// of a page, programming can only clear them. Operations complete when the
+    /// test calls `step` or `run`.
+    struct SimFlash {
+        pending: Cell<Option<Operation>>,
+        buffer: TakeCell<'static, SimPage>,
+        client: OptionalCell<&'static dyn flash::Client<SimFlash>>,
+    }
+
+    impl SimFlash {
+        fn page(page_number: usize) -> &'static mut [u8] {
+            unsafe {
+                core::slice::from_raw_parts_mut((page_number * PAGE_SIZE) as *mut u8, PAGE_SIZE)
+            }
+        }
+
+        /// Completes the pending operation.
+        fn step(&self) -> Option<Operation> {
+            let operation = self.pending.take()?;
+            match operation {
+                Operation::Erase(page_number) => {
+                    for byte in Self::page(page_number).iter_mut() {
+                        *byte = 0xFF;
+                    }
+                    self.client
+                        .map(|client| client.erase_complete(flash::Error::CommandComplete));
+                }
+                Operation::Write(page_number) => {
+                    let buffer = self.buffer.take().unwrap();
+                    for (byte, &value) in Self::page(page_number).iter_mut().zip(buffer.0.iter()) {
+                        *byte &= value;
+                    }
+                    self.client.map(move |client| {
+                        client.write_complete(buffer, flash::Error::CommandComplete)
+                    });
+                }
+            }
+            Some(operation)
+        }
+
+        fn run(&self) {
+            while self.step().is_some() {}
+        }
+
+        /// Fails the pending operation, leaving its page erased.
+        fn fail(&self) -> Option<Operation> {
+            let operation = self.pending.take()?;
+            let page_number = match operation {
+                Operation::Erase(page_number) | Operation::Write(page_number) => page_number,
+            };
+            for byte in Self::page(page_number).iter_mut() {
+                *byte = 0xFF;
+            }
+            match operation {
+                Operation::Erase(_) => self
+                    .client
+                    .map(|client| client.erase_complete(flash::Error::FlashError)),
+                Operation::Write(_) => {
+                    let buffer = self.buffer.take().unwrap();
+                    self.client
+                        .map(move |client| client.write_complete(buffer, flash::Error::FlashError))
+                }
+            };
+            Some(operation)
+        }
+    }
+
+    impl Flash for SimFlash {
+        type Page = SimPage;
+
+        fn read_page(
+            &self,
+            _page_number: usize,
+            buf: &'static mut SimPage,
+        ) -> Result<(), (ReturnCode, &'static mut SimPage)> {
+            Err((ReturnCode::ENOSUPPORT, buf))
+        }
+
+        fn write_page(
+            &self,
+            page_number: usize,
+            buf: &'static mut SimPage,
+        ) -> Result<(), (ReturnCode, &'static mut SimPage)> {
+            if self.pending.get().is_some() {
+                return Err((ReturnCode::EBUSY, buf));
+            }
+            self.buffer.replace(buf);
+            self.pending.set(Some(Operation::Write(page_number)));
+            Ok(())
+        }
+
+        fn erase_page(&self, page_number: usize) -> ReturnCode {
+            if self.pending.get().is_some() {
+                return ReturnCode::EBUSY;
+            }
+            self.pending.set(Some(Operation::Erase(page_number)));
+            ReturnCode::SUCCESS
+        }
+    }
+
+    type Nvm = VppNvm<'static, SimFlash>;
+
+    /// A blank NVM volume holding the NVM areas of every Process.
+    fn volume() -> &'static [u8] {
+        let length = (NUM_PROCS * 2 * NVM_BANK_PAGES + 1) * PAGE_SIZE;
+        Box::leak(vec![0xFFu8; length].into_boxed_slice())
+    }
+
+    fn flash() -> &'static SimFlash {
+        Box::leak(Box::new(SimFlash {
+            pending: Cell::new(None),
+            buffer: TakeCell::empty(),
+            client: OptionalCell::empty(),
+        }))
+    }
+
+    /// The NVM over `volume`, as found at boot.
+    fn boot(sim: &Sim, volume: &'static [u8], flash: &'static SimFlash) -> &'static Nvm {
+        let nvm: &'static Nvm = Box::leak(Box::new(VppNvm::new(
+            sim.vpp_kernel,
+            volume,
+            flash,
+            Box::leak(Box::new(SimPage::default())),
+            sim.create_grant(),
+        )));
+        flash.client.set(nvm);
+        nvm
+    }
+
+    fn sim() -> Sim {
+        SimBuilder::new().process(0x4000, 0, 0x4010).build()
+    }
+
+    /// Writes `data` at `offset` of the NVM of the Process 0.
+    fn write(sim: &Sim, nvm: &Nvm, flash: &SimFlash, offset: usize, data: &[u8]) {
+        let buffer = Box::leak(data.to_vec().into_boxed_slice());
+        let slice = sim.app_slice(0, buffer);
+        assert_eq!(nvm.allow(sim.appid(0), 0, Some(slice)), ReturnCode::SUCCESS);
+        assert_eq!(
+            nvm.write(sim.appid(0), offset, data.len()),
+            ReturnCode::SUCCESS
+        );
+        flash.run();
+    }
+
+    fn commit(sim: &Sim, nvm: &Nvm, flash: &SimFlash) {
+        assert_eq!(nvm.commit(sim.appid(0)), ReturnCode::SUCCESS);
+        flash.run();
+    }
+
+    fn read(sim: &Sim, nvm: &Nvm, offset: usize, length: usize) -> std::vec::Vec<u8> {
+        let mut data = vec![0; length];
+        assert_eq!(
+            nvm.read(sim.appid(0), offset, &mut data),
+            ReturnCode::SUCCESS
+        );
+        data
+    }
+
+    /// Reads the committed content of the NVM of the Process 0 after a reboot.
+    fn committed(
+        sim: &Sim,
+        volume: &'static [u8],
+        offset: usize,
+        length: usize,
+    ) -> std::vec::Vec<u8> {
+        read(sim, boot(sim, volume, flash()), offset, length)
+    }
+
+    #[test]
+    fn writes_are_seen_at_once_and_kept_once_committed() {
+        let sim = sim();
+        let (volume, flash) = (volume(), flash());
+        let nvm = boot(&sim, volume, flash);
+        assert_eq!(
+            nvm.capacity(),
+            NVM_BANK_PAGES * PAGE_SIZE - BANK_HEADER_SIZE
+        );
+        assert_eq!(read(&sim, nvm, 0, 4), [0; 4]);
+
+        // Spans both pages of the bank.
+        let data: std::vec::Vec<u8> = (0..80).collect();
+        write(&sim, nvm, flash, 10, &data);
+        assert_eq!(read(&sim, nvm, 10, 80), data);
+        assert_eq!(committed(&sim, volume, 10, 80), [0; 80].to_vec());
+
+        commit(&sim, nvm, flash);
+        assert_eq!(committed(&sim, volume, 10, 80), data);
+    }
+
+    #[test]
+    fn every_commit_erases_the_shadow_bank() {
+        let sim = sim();
+        let (volume, flash) = (volume(), flash());
+        let nvm = boot(&sim, volume, flash);
+
+        // The third commit reuses the bank of the first one.
+        for value in 1..4u8 {
+            write(&sim, nvm, flash, 0, &[value; 8]);
+            assert_eq!(nvm.commit(sim.appid(0)), ReturnCode::SUCCESS);
+            let mut operations = std::vec::Vec::new();
+            while let Some(operation) = flash.step() {
+                operations.push(operation);
+            }
+            assert!(matches!(
+                operations.as_slice(),
+                [Operation::Erase(a), Operation::Write(b), Operation::Erase(c), Operation::Write(d)]
+                    if a == b && c == d && a != c
+            ));
+            assert_eq!(committed(&sim, volume, 0, 8), [value; 8]);
+        }
+    }
+
+    #[test]
+    fn rollback_discards_the_uncommitted_writes() {
+        let sim = sim();
+        let (volume, flash) = (volume(), flash());
+        let nvm = boot(&sim, volume, flash);
+        write(&sim, nvm, flash, 0, b"committed");
+        commit(&sim, nvm, flash);
+
+        write(&sim, nvm, flash, 0, b"discarded");
+        assert_eq!(read(&sim, nvm, 0, 9), b"discarded");
+        assert_eq!(nvm.rollback(sim.appid(0)), ReturnCode::SUCCESS);
+        assert_eq!(read(&sim, nvm, 0, 9), b"committed");
+
+        // Nothing is left to commit.
+        commit(&sim, nvm, flash);
+        assert_eq!(committed(&sim, volume, 0, 9), b"committed");
+    }
+
+    #[test]
+    fn an_interrupted_commit_keeps_the_committed_content() {
+        let sim = sim();
+        let (volume, flash) = (volume(), flash());
+        let nvm = boot(&sim, volume, flash);
+        write(&sim, nvm, flash, 0, b"old");
+        commit(&sim, nvm, flash);
+
+        write(&sim, nvm, flash, 0, b"new");
+        assert_eq!(nvm.commit(sim.appid(0)), ReturnCode::SUCCESS);
+        // Power is lost before the header page of the shadow bank is written.
+        flash.step();
+        flash.step();
+        flash.step();
+        assert_eq!(committed(&sim, volume, 0, 3), b"old");
+    }
+
+    #[test]
+    fn a_failed_write_rolls_the_transaction_back() {
+        let sim = sim();
+        let (volume, flash) = (volume(), flash());
+        let nvm = boot(&sim, volume, flash);
+        write(&sim, nvm, flash, 0, b"committed");
+        commit(&sim, nvm, flash);
+        write(&sim, nvm, flash, 0, b"first");
+
+        // The shadow page is erased, then its write fails.
+        let slice = sim.app_slice(0, Box::leak(b"second".to_vec().into_boxed_slice()));
+        nvm.allow(sim.appid(0), 0, Some(slice));
+        assert_eq!(nvm.write(sim.appid(0), 0, 6), ReturnCode::SUCCESS);
+        assert!(matches!(flash.step(), Some(Operation::Erase(_))));
+        assert!(matches!(flash.fail(), Some(Operation::Write(_))));
+        assert_eq!(read(&sim, nvm, 0, 9), b"committed");
+
+        // The erased page does not reach the committed bank.
+        commit(&sim, nvm, flash);
+        assert_eq!(committed(&sim, volume, 0, 9), b"committed");
+
+        // A failed erase of a page never written does not expose it either.
+        let slice = sim.app_slice(0, Box::leak(b"third".to_vec().into_boxed_slice()));
+        nvm.allow(sim.appid(0), 0, Some(slice));
+        assert_eq!(nvm.write(sim.appid(0), 60, 5), ReturnCode::SUCCESS);
+        assert!(matches!(flash.fail(), Some(Operation::Erase(_))));
+        assert_eq!(read(&sim, nvm, 0, 9), b"committed");
+        assert_eq!(read(&sim, nvm, 60, 5), [0; 5]);
+        commit(&sim, nvm, flash);
+        assert_eq!(committed(&sim, volume, 0, 9), b"committed");
+    }
+
+    #[test]
+    fn writes_are_bounded_and_exclusive() {
+        let sim = sim();
+        let (volume, flash) = (volume(), flash());
+        let nvm = boot(&sim, volume, flash);
+        let capacity = nvm.capacity();
+        let slice = sim.app_slice(0, Box::leak(vec![0; 2 * capacity].into_boxed_slice()));
+        nvm.allow(sim.appid(0), 0, Some(slice));
+
+        assert_eq!(nvm.write(sim.appid(0), 1, capacity), ReturnCode::ESIZE);
+        assert_eq!(nvm.write(sim.appid(0), usize::MAX, 2), ReturnCode::ESIZE);
+        assert_eq!(nvm.write(sim.appid(0), 0, capacity), ReturnCode::SUCCESS);
+        assert_eq!(nvm.write(sim.appid(0), 0, 1), ReturnCode::EBUSY);
+        assert_eq!(nvm.commit(sim.appid(0)), ReturnCode::EBUSY);
+        assert_eq!(nvm.rollback(sim.appid(0)), ReturnCode::EBUSY);
+        flash.run();
+        assert_eq!(nvm.rollback(sim.appid(0)), ReturnCode::SUCCESS);
+    }
+
+    #[test]
+    fn an_app_in_the_slot_of_an_unloaded_process_has_no_nvm() {
+        let sim = sim();
+        let (volume, flash) = (volume(), flash());
+        let nvm = boot(&sim, volume, flash);
+        write(&sim, nvm, flash, 0, b"committed");
+        commit(&sim, nvm, flash);
+        assert_eq!(committed(&sim, volume, 0, 9), b"committed");
+
+        sim.vpp_kernel.process_event(sim.appid(0), ProcessEvent::Unloaded);
+        let app = sim.next_appid(0);
+        let mut data = [0; 9];
+        assert_eq!(nvm.read(app, 0, &mut data), ReturnCode::EINVAL);
+        assert_eq!(nvm.write(app, 0, 9), ReturnCode::EINVAL);
+        assert_eq!(nvm.commit(app), ReturnCode::EINVAL);
+    }
+}
@@ -4,7 +4,7 @@
 #![allow(non_snake_case)]
 use crate::vpp::mloi::*;
 use core::cell::Cell;
-use kernel::procs::{State, ProcessType, Process, FaultResponse, FunctionCall, FunctionCallSource, ProcessLoadError};
+use kernel::procs::{State, ProcessType, Process, FaultResponse, FunctionCall, FunctionCallSource, ProcessLoadError, check_process_credentials};
 use crate::vpp::mloi::VppState::*;
 use crate::vpp::mloi::MK_Process_ID_u;
 use crate::vpp::mailbox::mbox;
@@ -15,29 +15,131 @@ use core::convert::TryInto;
 use kernel::tbfheader;
 use crate::vpp;
 use kernel::{debug, static_init,create_capability};
-use crate::vpp::vppkernel::NUM_PROCS;
+use crate::vpp::vppkernel::{VppKernel, NUM_PROCS};
+use crate::vpp::vff::{self, DescriptorTable, FwHeader, LibDescriptor, MGT_ERROR_e, ProcessDescriptors};
+use crate::vpp::library::VppLibrary;
 use kernel::mpu::MPU;
 
+/// Number of restarts allowed to the Main Process of an APP Firmware after a fault.
+pub const MAIN_RESTART_THRESHOLD: usize = 3;
+
+/// What the kernel does with a VPP Process after it faulted.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum VppRestartPolicy {
+    /// The Process is always restarted.
+    Always,
+    /// The Process is restarted until it has been restarted more than this many times.
+    Threshold(usize),
+    /// The Process is left dead, its parent Process is notified.
+    Never,
+}
+
+impl VppRestartPolicy {
+    /// Restart policy of the Process `id` at `index` in the group of Processes, as
+    /// described by its Process Descriptor:
+    ///
+    /// * Processes of the VPP Execution Domain are system services, always restarted.
+    /// * The Main Process of an APP Firmware (its own parent) is restarted up to
+    ///   `MAIN_RESTART_THRESHOLD` times.
+    /// * Any other Process is not restarted, its parent decides what to do.
+    pub fn for_process(id: MK_Process_ID_u, index: MK_Index_t, parent: MK_Index_t) -> VppRestartPolicy {
+        if id >> 14 == 2 {
+            VppRestartPolicy::Always
+        } else if parent == index {
+            VppRestartPolicy::Threshold(MAIN_RESTART_THRESHOLD)
+        } else {
+            VppRestartPolicy::Never
+        }
+    }
+}
+
+/// Error loading the VPP Processes.
+#[derive(Debug)]
+pub enum VppLoadError {
+    /// A Tock Process could not be loaded.
+    Process(ProcessLoadError),
+    /// The VPP Firmware header of an app is invalid.
+    Firmware(MGT_ERROR_e),
+    /// A Firmware has more Processes than there are Process slots left after its app.
+    TooManyProcesses,
+    /// The Firmwares have more Mailboxes than there are Mailbox slots.
+    TooManyMailboxes,
+    /// The Firmwares have more IPCs than there are IPC slots.
+    TooManyIpcs,
+    /// Two Firmwares claim the same Process slot.
+    FirmwareOverlap,
+    /// Two Mailboxes or two IPCs have the same identifier.
+    DuplicateId,
+    /// The LIB header of a shared library is invalid.
+    Library(MGT_ERROR_e),
+    /// There are more shared libraries than there are library slots.
+    TooManyLibraries,
+    /// A Firmware imports a library absent from the flash, or of another version.
+    UnknownLibrary,
+    /// A library cannot be mapped into a Process importing it.
+    LibraryMapping,
+}
+
+impl From<ProcessLoadError> for VppLoadError {
+    fn from(error: ProcessLoadError) -> VppLoadError {
+        VppLoadError::Process(error)
+    }
+}
+
 #[derive(Clone)]
 pub struct VppProcess {
-    pub(crate) tockprocess: Option<&'static dyn ProcessType>,
+    /// Tock process running the Process, `None` once it is unloaded.
+    pub(crate) tockprocess: Cell<Option<&'static dyn ProcessType>>,
     pub(crate) vppstate: Cell<VppState>,
     pub(crate) vpppriority: Cell<MK_PROCESS_PRIORITY_e>,
     pub(crate) vppid: Cell<MK_Process_ID_u>,
     pub(crate) error: Cell<MK_ERROR_e>,
+    /// Index of the parent Process in the group of Processes (itself for a root Process).
+    pub(crate) parent: Cell<MK_Index_t>,
+    /// Mailbox receiving the Signals of the kernel.
+    pub(crate) kernel_mailbox: Cell<MK_MAILBOX_ID_u>,
+    /// Pending Exceptions, one bit per `MK_EXCEPTION_e` rank.
+    pub(crate) exceptions: Cell<MK_BITMAP_t>,
+    pub(crate) restart_policy: Cell<VppRestartPolicy>,
+    /// Generation of the handles of the Process.
+    pub(crate) generation: Cell<u16>,
+    /// VREs the Process may attach to, ORing of `MK_VRE_e` (`m_eVRE` of its descriptor).
+    pub(crate) vre_rights: Cell<MK_BITMAP_t>,
+    /// Shared libraries mapped into the Process, one bit per library slot.
+    pub(crate) libraries: Cell<MK_BITMAP_t>,
+    /// Slot of the Mailbox last returned by `_mk_Get_Mailbox_ID_Activated`, where its
+    /// round robin resumes.
+    pub(crate) mailbox_cursor: Cell<usize>,
+    /// Scheduling type of the Firmware of the Process.
+    pub(crate) scheduling: Cell<VPP_SCHEDULING_TYPE_e>,
 }
 /// This is a replication of `load_processes` function by tock with the addition of VPP
 /// Process specification. It returns an array `procs` of VPP Processes.
+///
+/// An app whose TBF header carries a VPP Firmware header (VFF) is the first Process of
+/// that Firmware, its other Processes are the apps following it in flash. The VPP
+/// Processes, Mailboxes and IPCs of the Firmware are built from its descriptors, and
+/// carry its scheduling type. When no app carries a VFF header, the MGT, COM and MAIN
+/// Processes (the first three apps) and their Mailboxes and IPCs fall back to their
+/// fixed definitions. Otherwise an app which no Firmware describes is not a Process.
+///
+/// An app carrying a VPP shared library header is a library, stored in `libs`, not a
+/// Process. Once the Processes are created, the libraries imported by a Firmware are
+/// mapped into the Processes listed by its LIB Descriptors.
+///
+/// An app rejected by the credentials checker of the kernel leaves its Process slot
+/// empty. The other apps are loaded, then the first rejection is returned as
+/// `ProcessLoadError::CredentialsRejected`.
 pub unsafe fn  load_vpp_processes<C: Chip>(
     kernel: &'static Kernel,
     chip: &'static C,
-    tock_procs: &'static mut [Option<&'static dyn ProcessType>],
     vpp_procs : &'static mut [Option<VppProcess>],
     mbs: &'static mut [Option<mbox>],
     ipcs: &'static mut [Option<ipc>],
+    libs: &'static mut [Option<VppLibrary>],
     fault_response: FaultResponse,
-    _capability: &dyn ProcessManagementCapability)
-    -> Result<(), ProcessLoadError> {
+    capability: &dyn ProcessManagementCapability)
+    -> Result<(), VppLoadError> {
     // I am importing these symbols to calculate SRAM and FLASH Addresses from the main function
     /// These symbols are defined in the linker script.
     /// These variables refer to the start and length of SRAM and FLASH Addresses.
@@ -75,28 +177,70 @@ pub unsafe fn  load_vpp_processes<C: Chip>(
         );
     }
     // Before instantiating any Process, mailboxes and IPCs need to be instantiated. IPCs
-    // use grants which can no longer be used after calling the create() function. Declaring
-    // them out of the loop is a better option.
-    //There are 4 Mailboxes.
+    // use grants which can no longer be used after calling the create() function. They
+    // are created from the VFF headers before any Process.
     let memory_allocation_cap = create_capability!(MemoryAllocationCapability);
-
-    let mgt_main_mb = mbox::create_mgt_mb(kernel,&memory_allocation_cap);
-    let com_main_mb = mbox::create_com_mb(kernel,&memory_allocation_cap);
-    let main_mgt_mb = mbox::create_main_mgt_mb(kernel,&memory_allocation_cap);
-    let main_com_mb = mbox::create_main_com_mb(kernel,&memory_allocation_cap);
-    mbs[0] = Some(mgt_main_mb);
-    mbs[1] = Some(com_main_mb);
-    mbs[2] = Some(main_mgt_mb);
-    mbs[3] = Some(main_com_mb);
-    // There are 4 ipc structs
-    let main_com_ipc = ipc::create_main_com_ipc(kernel,&memory_allocation_cap);
-    let com_main_ipc = ipc::create_com_main_ipc(kernel,&memory_allocation_cap);
-    let main_mgt_ipc = ipc::create_main_mgt_ipc(kernel,&memory_allocation_cap);
-    let mgt_main_ipc = ipc::create_mgt_main_ipc(kernel,&memory_allocation_cap) ;
-    ipcs[0] = Some(main_com_ipc);
-    ipcs[1] = Some(com_main_ipc);
-    ipcs[2] = Some(main_mgt_ipc);
-    ipcs[3] = Some(mgt_main_ipc);
+    let mut mb_count = 0;
+    let mut ipc_count = 0;
+    let mut declared = false;
+    let mut lib_count = 0;
+    // LIB Descriptors of the Firmware whose first Process is at each index.
+    let mut imports: [Option<DescriptorTable<'static, LibDescriptor>>; NUM_PROCS] = [None; NUM_PROCS];
+    let mut firmware_flash = app_flash;
+    let mut index = 0;
+    loop {
+        let (version, header, rest) = match next_app(firmware_flash)? {
+            Some(app) => app,
+            None => break,
+        };
+        let entry = &firmware_flash[..firmware_flash.len() - rest.len()];
+        firmware_flash = rest;
+        if !header.is_empty() {
+            let lib_header = tbfheader::parse_tbf_header_vpp_library(header, version)
+                .map_err(ProcessLoadError::from)?;
+            if let Some(lib_header) = lib_header {
+                // A library does not take a Process slot.
+                let library = VppLibrary::parse(lib_header, &entry[header.len()..])
+                    .map_err(VppLoadError::Library)?;
+                *libs.get_mut(lib_count).ok_or(VppLoadError::TooManyLibraries)? = Some(library);
+                lib_count += 1;
+                continue;
+            }
+            let vff_header = tbfheader::parse_tbf_header_vpp_firmware(header, version)
+                .map_err(ProcessLoadError::from)?;
+            if let Some(vff_header) = vff_header {
+                let firmware = vff::parse_vpp_header(vff_header).map_err(VppLoadError::Firmware)?;
+                declare_firmware(
+                    &firmware,
+                    index,
+                    vpp_procs,
+                    mbs,
+                    &mut mb_count,
+                    ipcs,
+                    &mut ipc_count,
+                    kernel,
+                    &memory_allocation_cap,
+                )?;
+                declared = true;
+                if firmware.lib_descriptors.len() > 0 {
+                    imports[index] = Some(firmware.lib_descriptors);
+                }
+            }
+        }
+        index += 1;
+    }
+    // Without any Firmware, the MGT, COM and MAIN Processes use the 4 Mailboxes and the
+    // 4 IPCs of their fixed definitions.
+    if !declared {
+        mbs[0] = Some(mbox::create_mgt_mb(kernel, &memory_allocation_cap));
+        mbs[1] = Some(mbox::create_com_mb(kernel, &memory_allocation_cap));
+        mbs[2] = Some(mbox::create_main_mgt_mb(kernel, &memory_allocation_cap));
+        mbs[3] = Some(mbox::create_main_com_mb(kernel, &memory_allocation_cap));
+        ipcs[0] = Some(ipc::create_main_com_ipc(kernel, &memory_allocation_cap));
+        ipcs[1] = Some(ipc::create_com_main_ipc(kernel, &memory_allocation_cap));
+        ipcs[2] = Some(ipc::create_main_mgt_ipc(kernel, &memory_allocation_cap));
+        ipcs[3] = Some(ipc::create_mgt_main_ipc(kernel, &memory_allocation_cap));
+    }
     /* |15 14 13 12 11 10 9 8 7 6 5 4 3 2 1 0|
   VPP : 1  0   ->>>>>>>Enumerated ID<<<<<<<<-
    For example MGT ID
@@ -133,8 +277,12 @@ IPC MAIN_MGT_ID is     0x4001
 
     let mut remaining_flash = app_flash;
     let mut remaining_memory = app_memory;
+    let mut rejected = None;
+    // The Tock Processes, also installed in the processes array of the kernel.
+    let mut tock_procs: [Option<&'static dyn ProcessType>; NUM_PROCS] = [None; NUM_PROCS];
 
-    for i in 0..vpp_procs.len(){
+    let mut i = 0;
+    while i < vpp_procs.len() {
         // Get the first eight bytes of flash to check if there is another
         // app.
         let test_header_slice = match remaining_flash.get(0..8) {
@@ -143,7 +291,7 @@ IPC MAIN_MGT_ID is     0x4001
                 // Not enough flash to test for another app. This just means
                 // we are at the end of flash, and there are no more apps to
                 // load.
-                return Ok(());
+                break;
             }
         };
         // Pass the first eight bytes to tbfheader to parse out the length of
@@ -165,7 +313,7 @@ IPC MAIN_MGT_ID is     0x4001
                 // header we started to parse is intentionally invalid to signal
                 // the end of apps. This is ok and just means we have finished
                 // loading apps.
-                return Ok(());
+                break;
             }
         };
 
@@ -185,12 +333,34 @@ IPC MAIN_MGT_ID is     0x4001
 
         // Need to reassign remaining_memory in every iteration so the compiler
         // knows it will not be re-borrowed.
+        // Libraries were found by the first pass, they are not Processes.
+        if header_length > 0
+            && tbfheader::parse_tbf_header_vpp_library(
+                &entry_flash[..header_length as usize],
+                version,
+            )
+            .map_err(ProcessLoadError::from)?
+            .is_some()
+        {
+            continue;
+        }
+
         remaining_memory = if header_length > 0 {
             // If we found an actual app header, try to create a `Process`
             // object. We also need to shrink the amount of remaining memory
             // based on whatever is assigned to the new process if one is
             // created.
 
+            // Skip an app rejected by the credentials checker, keeping the
+            // memory for the next apps.
+            if let Err(error) =
+                check_process_credentials(kernel, entry_flash, header_length as usize, version)
+            {
+                rejected = rejected.or(Some(error));
+                i += 1;
+                continue;
+            }
+
             // Try to create a process object from that app slice. If we don't
             // get a process and we didn't get a loading error (aka we got to
             // this point), then the app is a disabled process or just padding.
@@ -206,7 +376,7 @@ IPC MAIN_MGT_ID is     0x4001
                     i,
                 )?;
 
-            process_option.map(|process| {
+            if let Some(process) = process_option {
                 if config::CONFIG.debug_load_processes {
                     debug!(
                         "Loaded process[{}] from flash={:#010X}-{:#010X} into sram={:#010X}-{:#010X} = {:?}",
@@ -219,6 +389,7 @@ IPC MAIN_MGT_ID is     0x4001
                     );
                 }
                 tock_procs[i] = Some(process);
+                kernel.set_process(i, Some(process), capability)?;
 
 
                 // let mailbox= static_init!(vpp::mailbox::mbox,
@@ -250,104 +421,335 @@ IPC MAIN_MGT_ID is     0x4001
 
                 // Save the reference to this process in the processes array.
                 //vpp_procs[i] = Some(vpp_process);*/
-            });
+            }
             unused_memory
         } else {
             // We are just skipping over this region of flash, so we have the
             // same amount of process memory to allocate from.
             remaining_memory
         };
-        // Before instantiating the Vpp Kernel with vpp_processes, ipc structs and mailboxes
-        // ,let us make sure that the first Process is the MGT Process, the second Process is
-        // the COM Process and the 3rd Process is the MAIN Process (which is the
-        // actual Userspace App). Also the ipc and mailbox structs of the MGT/COM/MAIN Processes
-        // will be instantiated.
-        let mgt_process = VppProcess::create_mgt_process(tock_procs[0]);
-        let com_process = VppProcess::create_com_process(tock_procs[1]);
-        let main_process = VppProcess::create_main_process(tock_procs[2]);
-        main_process.tockprocess.map(|proc| {
-            let ccb = FunctionCall {
-                source: FunctionCallSource::Kernel,
-                pc: proc.flash_non_protected_start() as usize,
-                argument0: proc.flash_start() as usize,
-                argument1: proc.mem_start() as usize,
-                argument2: proc.mem_end() as usize - proc.mem_start() as usize,
-                argument3: proc.kernel_memory_break() as usize,
-            };
-            proc.set_process_function(ccb);
-            proc.set_yielded_state();
-            proc.stop();});
-        vpp_procs[0] = Some(mgt_process);
-        vpp_procs[1] = Some(com_process);
-        vpp_procs[2] = Some(main_process);
+        i += 1;
+    }
+
+    for i in 0..vpp_procs.len() {
+        match vpp_procs[i].as_mut() {
+            // Described by a Firmware.
+            Some(process) => process.tockprocess.set(tock_procs[i]),
+            // Without any Firmware, the first Process is the MGT Process, the second
+            // Process is the COM Process and the 3rd Process is the MAIN Process (which
+            // is the actual Userspace App).
+            None if !declared => {
+                vpp_procs[i] = match i {
+                    0 => Some(VppProcess::create_mgt_process(tock_procs[0])),
+                    1 => Some(VppProcess::create_com_process(tock_procs[1])),
+                    2 => Some(VppProcess::create_main_process(tock_procs[2])),
+                    _ => None,
+                }
+            }
+            // Not described by any Firmware: a plain Tock process.
+            None => {}
+        }
+    }
+    for (base, lib_descriptors) in imports.iter().enumerate() {
+        for descriptor in lib_descriptors.iter().flat_map(|table| table.iter()) {
+            import_library(&descriptor, base, vpp_procs, libs)?;
+        }
+    }
+    // The MAIN Process starts stopped, in the yielded state.
+    let main_process = vpp_procs
+        .iter()
+        .flatten()
+        .find(|process| process.get_vpp_id() == MK_PROCESS_MAIN_APP_ID);
+    if let Some(proc) = main_process.and_then(|process| process.tockprocess.get()) {
+        let ccb = FunctionCall {
+            source: FunctionCallSource::Kernel,
+            pc: proc.flash_non_protected_start() as usize,
+            argument0: proc.flash_start() as usize,
+            argument1: proc.mem_start() as usize,
+            argument2: proc.mem_end() as usize - proc.mem_start() as usize,
+            argument3: proc.kernel_memory_break() as usize,
+        };
+        proc.set_process_function(ccb);
+        proc.set_yielded_state();
+        proc.stop();
+    }
+    kernel.set_unused_app_memory(remaining_memory, capability);
+    rejected.map_or(Ok(()), |error| {
+        Err(ProcessLoadError::CredentialsRejected(error).into())
+    })
+}
+
+/// Returns the TBF version and header of the first app in `flash`, the header being
+/// empty if it is invalid, and the flash following that app. `None` at the end of the
+/// apps.
+fn next_app(
+    flash: &'static [u8],
+) -> Result<Option<(u16, &'static [u8], &'static [u8])>, ProcessLoadError> {
+    let test_header_slice = match flash.get(0..8) {
+        Some(s) => s,
+        None => return Ok(None),
+    };
+    let (version, header_length, entry_length) = match tbfheader::parse_tbf_header_lengths(
+        test_header_slice
+            .try_into()
+            .or(Err(ProcessLoadError::InternalError))?,
+    ) {
+        Ok((v, hl, el)) => (v, hl, el),
+        Err(tbfheader::InitialTbfParseError::InvalidHeader(entry_length)) => (0, 0, entry_length),
+        Err(tbfheader::InitialTbfParseError::UnableToParse) => return Ok(None),
+    };
+    let header = flash
+        .get(0..header_length as usize)
+        .ok_or(ProcessLoadError::NotEnoughFlash)?;
+    let rest = flash
+        .get(entry_length as usize..)
+        .ok_or(ProcessLoadError::NotEnoughFlash)?;
+    Ok(Some((version, header, rest)))
+}
+
+/// VFF header carried by the TBF header of `process`, if it is the first Process of a
+/// Firmware.
+pub(crate) fn firmware_header(process: &dyn ProcessType) -> Option<&'static [u8]> {
+    let flash = unsafe {
+        core::slice::from_raw_parts(
+            process.flash_start(),
+            process.flash_end() as usize - process.flash_start() as usize,
+        )
+    };
+    let (version, header, _) = next_app(flash).ok()??;
+    tbfheader::parse_tbf_header_vpp_firmware(header, version).ok()?
+}
+
+/// Maps the library required by `descriptor` into the Processes importing it, the
+/// Processes of a Firmware whose first Process is at index `base`.
+fn import_library(
+    descriptor: &LibDescriptor,
+    base: usize,
+    vpp_procs: &[Option<VppProcess>],
+    libs: &[Option<VppLibrary>],
+) -> Result<(), VppLoadError> {
+    let (slot, library) = libs
+        .iter()
+        .enumerate()
+        .filter_map(|(slot, library)| library.as_ref().map(|library| (slot, library)))
+        .find(|(_, library)| {
+            library.get_id() == descriptor.m_xID && library.is_compatible(descriptor.m_uVersion)
+        })
+        .ok_or(VppLoadError::UnknownLibrary)?;
+    for index in 0..MK_BITMAP_t::max_value().count_ones() as usize {
+        if descriptor.m_bProcesses & 1 << index == 0 {
+            continue;
+        }
+        let process = vpp_procs
+            .get(base + index)
+            .and_then(|process| process.as_ref())
+            .ok_or(VppLoadError::LibraryMapping)?;
+        let tockprocess = process.tockprocess.get().ok_or(VppLoadError::LibraryMapping)?;
+        library
+            .map_into(tockprocess)
+            .ok_or(VppLoadError::LibraryMapping)?;
+        process.libraries.set(process.libraries.get() | 1 << slot);
+    }
+    Ok(())
+}
+
+/// Creates the VPP Processes, Mailboxes and IPCs of `firmware`, whose first Process is
+/// the app at index `base`. The indices in the descriptors are relative to the
+/// Firmware, they are offset by `base` in the group of Processes. Each Process keeps
+/// the scheduling type of its Firmware.
+unsafe fn declare_firmware(
+    firmware: &FwHeader,
+    base: usize,
+    vpp_procs: &mut [Option<VppProcess>],
+    mbs: &mut [Option<mbox>],
+    mb_count: &mut usize,
+    ipcs: &mut [Option<ipc>],
+    ipc_count: &mut usize,
+    kernel: &'static Kernel,
+    capability: &dyn MemoryAllocationCapability,
+) -> Result<(), VppLoadError> {
+    let slots = vpp_procs
+        .get_mut(base..base + firmware.process_descriptors.len())
+        .ok_or(VppLoadError::TooManyProcesses)?;
+    if slots.iter().any(|slot| slot.is_some()) {
+        return Err(VppLoadError::FirmwareOverlap);
+    }
+    for (index, descriptor) in firmware.process_descriptors.iter().enumerate() {
+        let process = VppProcess::create_from_descriptor(
+            None,
+            &descriptor,
+            (base + index) as MK_Index_t,
+            base as MK_Index_t,
+        );
+        process.set_scheduling_type(firmware.fw_descriptor.m_eSchedluingType);
+        slots[index] = Some(process);
+    }
+    let base = base as MK_Index_t;
+    for descriptor in firmware.mailbox_descriptors.iter() {
+        if mbs[..*mb_count]
+            .iter()
+            .flatten()
+            .any(|mb| mb.get_mb_id() == descriptor.m_xID)
+        {
+            return Err(VppLoadError::DuplicateId);
+        }
+        let slot = mbs.get_mut(*mb_count).ok_or(VppLoadError::TooManyMailboxes)?;
+        *slot = Some(mbox::new(
+            descriptor.m_xID,
+            base + descriptor.m_uIX_Owner,
+            base + descriptor.m_uIX_Sender,
+            kernel,
+            capability,
+        ));
+        *mb_count += 1;
+    }
+    for descriptor in firmware.ipc_descriptors.iter() {
+        if ipcs[..*ipc_count]
+            .iter()
+            .flatten()
+            .any(|ipc| ipc.get_ipc_id() == descriptor.m_xID)
+        {
+            return Err(VppLoadError::DuplicateId);
+        }
+        let slot = ipcs.get_mut(*ipc_count).ok_or(VppLoadError::TooManyIpcs)?;
+        *slot = Some(ipc::new(
+            descriptor.m_xID,
+            descriptor.m_uLength_IPC,
+            base + descriptor.m_uIX_Writer,
+            base + descriptor.m_uIX_Reader,
+            kernel,
+            capability,
+        ));
+        *ipc_count += 1;
     }
     Ok(())
 }
 
 impl  VppProcess{
+    // Callers pass the index of the Process in the group as `pid`: the Process is a root
+    // whose kernel Mailbox has the same identifier.
     pub fn create_vpp_process(
         tockprocess: Option<&'static dyn ProcessType>,
         pid : MK_Process_ID_u
         )-> VppProcess{
         VppProcess {
-            tockprocess: tockprocess,
+            tockprocess: Cell::new(tockprocess),
             vppstate: Cell::new(VppState::SUSPENDED_R),
             vpppriority: Cell::new(MK_PROCESS_PRIORITY_e::MK_PROCESS_PRIORITY_NORMAL),
             vppid: Cell::new(pid),
-            error: Cell::new(MK_ERROR_e::MK_ERROR_NONE)
+            error: Cell::new(MK_ERROR_e::MK_ERROR_NONE),
+            parent: Cell::new(pid),
+            kernel_mailbox: Cell::new(pid),
+            exceptions: Cell::new(0),
+            restart_policy: Cell::new(VppRestartPolicy::for_process(pid, pid, pid)),
+            generation: Cell::new(0),
+            vre_rights: Cell::new(0),
+            libraries: Cell::new(0),
+            mailbox_cursor: Cell::new(MK_MAILBOX_LIMIT - 1),
+            scheduling: Cell::new(VPP_SCHEDULING_TYPE_e::MK_SCHEDULING_TYPE_PREEMPTIVE),
+        }
+    }
+    /// Creates the Process at `index` in the group of Processes from its Process
+    /// Descriptor, in a Firmware whose first Process is at index `base`.
+    pub fn create_from_descriptor(
+        tockprocess: Option<&'static dyn ProcessType>,
+        descriptor: &ProcessDescriptors,
+        index: MK_Index_t,
+        base: MK_Index_t,
+    ) -> VppProcess {
+        let parent = base + descriptor.m_uParent_Process;
+        VppProcess {
+            tockprocess: Cell::new(tockprocess),
+            vppstate: Cell::new(VppState::READY),
+            vpppriority: Cell::new(MK_PROCESS_PRIORITY_e::MK_PROCESS_PRIORITY_NORMAL),
+            vppid: Cell::new(descriptor.m_xID),
+            error: Cell::new(MK_ERROR_e::MK_ERROR_NONE),
+            parent: Cell::new(parent),
+            kernel_mailbox: Cell::new(descriptor.m_xKernel_Mailbox),
+            exceptions: Cell::new(0),
+            restart_policy: Cell::new(VppRestartPolicy::for_process(descriptor.m_xID, index, parent)),
+            generation: Cell::new(0),
+            vre_rights: Cell::new(descriptor.m_eVRE),
+            libraries: Cell::new(0),
+            mailbox_cursor: Cell::new(MK_MAILBOX_LIMIT - 1),
+            scheduling: Cell::new(VPP_SCHEDULING_TYPE_e::MK_SCHEDULING_TYPE_PREEMPTIVE),
         }
     }
+    // The MGT, COM and MAIN Processes are the roots of the group of Processes, at index
+    // 0, 1 and 2. Their kernel Mailbox is the Mailbox each of them owns.
     pub fn create_mgt_process(tockprocess: Option<&'static dyn ProcessType> ) -> VppProcess{
         VppProcess{
-            tockprocess: tockprocess,
+            tockprocess: Cell::new(tockprocess),
             vppstate: Cell::new(VppState::READY),
             vpppriority: Cell::new(MK_PROCESS_PRIORITY_e::MK_PROCESS_PRIORITY_NORMAL),
             vppid: Cell::new(MK_PROCESS_MGT_VPP_ID),
-            error: Cell::new(MK_ERROR_e::MK_ERROR_NONE)
+            error: Cell::new(MK_ERROR_e::MK_ERROR_NONE),
+            parent: Cell::new(0),
+            kernel_mailbox: Cell::new(MK_MAILBOX_MGT_MAIN_ID),
+            exceptions: Cell::new(0),
+            restart_policy: Cell::new(VppRestartPolicy::for_process(MK_PROCESS_MGT_VPP_ID, 0, 0)),
+            generation: Cell::new(0),
+            vre_rights: Cell::new(0),
+            libraries: Cell::new(0),
+            mailbox_cursor: Cell::new(MK_MAILBOX_LIMIT - 1),
+            scheduling: Cell::new(VPP_SCHEDULING_TYPE_e::MK_SCHEDULING_TYPE_PREEMPTIVE),
         }
     }
     pub fn create_com_process(tockprocess: Option<&'static dyn ProcessType>) -> VppProcess{
         VppProcess{
-            tockprocess,
+            tockprocess: Cell::new(tockprocess),
             vppstate: Cell::new(VppState::READY),
             vpppriority: Cell::new(MK_PROCESS_PRIORITY_e::MK_PROCESS_PRIORITY_NORMAL),
             vppid: Cell::new(MK_PROCESS_COM_VPP_ID),
-            error: Cell::new(MK_ERROR_e::MK_ERROR_NONE)
+            error: Cell::new(MK_ERROR_e::MK_ERROR_NONE),
+            parent: Cell::new(1),
+            kernel_mailbox: Cell::new(MK_MAILBOX_MAIN_COM_ID),
+            exceptions: Cell::new(0),
+            restart_policy: Cell::new(VppRestartPolicy::for_process(MK_PROCESS_COM_VPP_ID, 1, 1)),
+            generation: Cell::new(0),
+            vre_rights: Cell::new(0),
+            libraries: Cell::new(0),
+            mailbox_cursor: Cell::new(MK_MAILBOX_LIMIT - 1),
+            scheduling: Cell::new(VPP_SCHEDULING_TYPE_e::MK_SCHEDULING_TYPE_PREEMPTIVE),
         }
     }
     pub fn create_main_process(tockprocess: Option<&'static dyn ProcessType>) -> VppProcess{
         VppProcess{
-            tockprocess,
+            tockprocess: Cell::new(tockprocess),
             vppstate: Cell::new(VppState::READY),
             vpppriority: Cell::new(MK_PROCESS_PRIORITY_e::MK_PROCESS_PRIORITY_NORMAL),
             vppid: Cell::new(MK_PROCESS_MAIN_APP_ID),
-            error: Cell::new(MK_ERROR_e::MK_ERROR_NONE)
+            error: Cell::new(MK_ERROR_e::MK_ERROR_NONE),
+            parent: Cell::new(2),
+            kernel_mailbox: Cell::new(MK_MAILBOX_COM_MAIN_ID),
+            exceptions: Cell::new(0),
+            restart_policy: Cell::new(VppRestartPolicy::for_process(MK_PROCESS_MAIN_APP_ID, 2, 2)),
+            generation: Cell::new(0),
+            vre_rights: Cell::new(0),
+            libraries: Cell::new(0),
+            mailbox_cursor: Cell::new(MK_MAILBOX_LIMIT - 1),
+            scheduling: Cell::new(VPP_SCHEDULING_TYPE_e::MK_SCHEDULING_TYPE_PREEMPTIVE),
         }
     }
 
-    /*pub(crate) fn snyc_tock_vpp_states(&self){
-        let tock_state = self.tockprocess.unwrap().get_state();
-        match tock_state {
-            State::Unstarted => self.vppstate.set(VppState::READY),
-            State::Yielded => self.vppstate.set(VppState::READY),
-            State::Running => self.vppstate.set(VppState::RUNNING),
-            State::StoppedYielded => self.vppstate.set(VppState::SUSPENDED_R),
-            State::StoppedRunning => self.vppstate.set(VppState::SUSPENDED_R),
-            State::StoppedFaulted => self.vppstate.set(VppState::DEAD),
-            State::Fault => self.vppstate.set(VppState::DEAD),
+    /// Follows the Tock process of this Process from the state `from` to `to`. A Process
+    /// waiting for a Signal stays `WAITING` while Tock runs its other callbacks.
+    pub(crate) fn follow_tock_state(&self, from: State, to: State) {
+        if from == State::StoppedRunning || from == State::StoppedYielded {
+            self.resume_vpp_process();
         }
-    }
-     pub(crate) fn sync_vpp_tock_states(&self) {
-        let vpp_state = self.vppstate.get();
-        let tock_process = self.tockprocess.unwrap()  ;
-        match vpp_state {
-            VppState::READY =>  tock_process.set_state(State::Yielded)  ,
-            VppState::RUNNING => tock_process.set_state(State::Running),
-            VppState::SUSPENDED_R => tock_process.set_state(State::StoppedYielded),
-            VppState::DEAD => tock_process.set_state(State::StoppedFaulted),
-            _ => {},
+        match to {
+            State::Unstarted => self.vppstate.set(READY),
+            State::Running => {
+                if self.vppstate.get() == READY {
+                    self.vppstate.set(RUNNING);
+                }
+            }
+            State::Yielded => self.yield_vpp_process(),
+            State::StoppedRunning | State::StoppedYielded => self.suspend_vpp_process(),
+            State::StoppedFaulted | State::Fault => self.vppstate.set(DEAD),
         }
-    }  */
+    }
     pub(crate) fn get_last_generated_error(&self) -> MK_ERROR_e {
         self.error.get()
     }
@@ -364,6 +766,15 @@ impl  VppProcess{
         self.vppstate.get()
     }
 
+    /// Scheduling type of the Firmware of the Process.
+    pub(crate) fn get_scheduling_type(&self) -> VPP_SCHEDULING_TYPE_e {
+        self.scheduling.get()
+    }
+
+    pub(crate) fn set_scheduling_type(&self, scheduling: VPP_SCHEDULING_TYPE_e) {
+        self.scheduling.set(scheduling)
+    }
+
     pub(crate) fn get_vpp_priority (&self) -> MK_PROCESS_PRIORITY_e {
         self.vpppriority.get()
     }
@@ -408,12 +819,82 @@ impl  VppProcess{
         }
     }
 
+    /// Records an Exception, returns the pending Exceptions.
+    pub(crate) fn raise_exception(&self, exception: MK_EXCEPTION_e) -> MK_BITMAP_t {
+        self.exceptions.set(self.exceptions.get() | 1 << u16::from(exception));
+        self.exceptions.get()
+    }
+
+    /// Returns and clears the pending Exceptions.
+    pub(crate) fn take_exceptions(&self) -> MK_BITMAP_t {
+        self.exceptions.replace(0)
+    }
+
+    pub(crate) fn get_vre_rights(&self) -> MK_BITMAP_t {
+        self.vre_rights.get()
+    }
+    pub(crate) fn get_generation(&self) -> u16 {
+        self.generation.get()
+    }
+    /// Invalidates the handles of the Process obtained so far.
+    pub(crate) fn invalidate_handles(&self) {
+        self.generation.set(self.generation.get().wrapping_add(1));
+    }
     pub(crate) fn set_vpp_id(&self, id :MK_Process_ID_u ) {
         self.vppid.set(id);
     }
 
     pub(crate) fn get_process_name(&self)-> &'static str{
-        self.tockprocess.unwrap().get_process_name()
+        self.tockprocess.get().map_or("-", |tockprocess| tockprocess.get_process_name())
     }
 }
 
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use crate::vpp::sim::{SimBuilder, VffBuilder};
+    use std::vec::Vec;
+
+    struct Cap;
+    unsafe impl MemoryAllocationCapability for Cap {}
+
+    #[test]
+    fn each_firmware_keeps_its_scheduling_type() {
+        let sim = SimBuilder::new().build();
+        let types = [
+            VPP_SCHEDULING_TYPE_e::MK_SCHEDULING_TYPE_COLLABORATIVE,
+            VPP_SCHEDULING_TYPE_e::MK_SCHEDULING_TYPE_PREEMPTIVE,
+        ];
+        let mut vpp_procs: [Option<VppProcess>; NUM_PROCS] = Default::default();
+        let mut mbs: Vec<Option<mbox>> = (0..MK_MAILBOX_LIMIT).map(|_| None).collect();
+        let mut ipcs: Vec<Option<ipc>> = (0..MK_IPC_LIMIT).map(|_| None).collect();
+        let (mut mb_count, mut ipc_count) = (0, 0);
+        for (base, &scheduling_type) in types.iter().enumerate() {
+            let header = VffBuilder::new()
+                .id(VffBuilder::ID + base as UUID_t)
+                .scheduling_type(scheduling_type.into())
+                .build();
+            let firmware = vff::parse_vpp_header(&header).unwrap();
+            unsafe {
+                declare_firmware(
+                    &firmware,
+                    base,
+                    &mut vpp_procs,
+                    &mut mbs,
+                    &mut mb_count,
+                    &mut ipcs,
+                    &mut ipc_count,
+                    sim.kernel,
+                    &Cap,
+                )
+            }
+            .unwrap();
+        }
+        for (base, &scheduling_type) in types.iter().enumerate() {
+            let process = vpp_procs[base].as_ref().unwrap();
+            assert!(process.get_scheduling_type() == scheduling_type);
+        }
+    }
+}
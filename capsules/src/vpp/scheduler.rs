@@ -0,0 +1,139 @@
+//! Scheduler of the VPP Processes.
+//!
+//! The scheduler runs the ready Process with the highest `MK_PROCESS_PRIORITY_e`, in
+//! round robin among the Processes of the same priority. The priorities are read from
+//! the VPP Processes at each scheduling decision, so `_mk_set_process_priority` takes
+//! effect immediately.
+//!
+//! The scheduling type of the Firmware of a Process (`VPP_SCHEDULING_TYPE_e`) selects
+//! how long the Process runs:
+//!
+//! * `MK_SCHEDULING_TYPE_COLLABORATIVE`: the Process runs until it yields, and is not
+//!   preempted by Processes of higher priority.
+//! * `MK_SCHEDULING_TYPE_PREEMPTIVE`: the Process runs for a timeslice, and is
+//!   preempted as soon as a Process of higher priority becomes ready.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//!     let scheduler = static_init!(
+//!         capsules::vpp::scheduler::VppScheduler,
+//!         capsules::vpp::scheduler::VppScheduler::new(vpp_kernel)
+//!     );
+//! ```
+
+use crate::vpp::mloi::{MK_Index_t, MK_PROCESS_PRIORITY_e, VPP_SCHEDULING_TYPE_e};
+use crate::vpp::vppkernel::{VppKernel, NUM_PROCS};
+use core::cell::Cell;
+use kernel::common::cells::OptionalCell;
+use kernel::common::dynamic_deferred_call::DynamicDeferredCall;
+use kernel::{AppId, Chip, Kernel, Scheduler, SchedulingDecision, StoppedExecutingReason};
+
+/// Timeslice of a Process in preemptive scheduling, in microseconds.
+pub const VPP_TIMESLICE_US: u32 = 10000;
+
+pub struct VppScheduler {
+    vpp_kernel: &'static VppKernel,
+    /// Index of the last Process scheduled, where the round robin resumes.
+    last: Cell<usize>,
+    running: OptionalCell<AppId>,
+}
+
+impl VppScheduler {
+    pub fn new(vpp_kernel: &'static VppKernel) -> VppScheduler {
+        VppScheduler {
+            vpp_kernel: vpp_kernel,
+            last: Cell::new(NUM_PROCS - 1),
+            running: OptionalCell::empty(),
+        }
+    }
+
+    /// Whether the Process of `appid` runs for a timeslice. An app which is not a VPP
+    /// Process always does.
+    fn preemptive(&self, appid: AppId) -> bool {
+        self.vpp_kernel.get_process_ref_appid(appid).map_or(true, |process| {
+            process.get_scheduling_type() == VPP_SCHEDULING_TYPE_e::MK_SCHEDULING_TYPE_PREEMPTIVE
+        })
+    }
+
+    /// Priority of the Process at `index` if it is ready to run.
+    fn ready_priority(&self, index: usize) -> Option<u16> {
+        let process = self.vpp_kernel.get_process_ref_index(index as MK_Index_t)?;
+        let tockprocess = process.tockprocess.get()?;
+        match process.get_vpp_priority() {
+            MK_PROCESS_PRIORITY_e::MK_PROCESS_PRIORITY_ERROR => None,
+            priority if tockprocess.ready() => Some(u16::from(priority)),
+            _ => None,
+        }
+    }
+
+    /// Ready Process with the highest priority, the first one after the last Process
+    /// scheduled among those of that priority.
+    fn next_ready(&self) -> Option<(usize, u16)> {
+        let start = self.last.get() + 1;
+        (start..start + NUM_PROCS)
+            .map(|i| i % NUM_PROCS)
+            .filter_map(|index| self.ready_priority(index).map(|priority| (index, priority)))
+            .fold(None, |best: Option<(usize, u16)>, (index, priority)| match best {
+                Some((_, best_priority)) if best_priority >= priority => best,
+                _ => Some((index, priority)),
+            })
+    }
+}
+
+impl<C: Chip> Scheduler<C> for VppScheduler {
+    fn next(&self, kernel: &Kernel) -> SchedulingDecision {
+        if kernel.processes_blocked() {
+            return SchedulingDecision::TrySleep;
+        }
+        let next = match self.next_ready() {
+            Some((index, _)) => {
+                self.last.set(index);
+                self.vpp_kernel.process_appid(index as MK_Index_t)
+            }
+            // The ready Process is not a VPP Process.
+            None => kernel
+                .get_process_iter()
+                .find(|process| process.ready())
+                .map(|process| process.appid()),
+        };
+        match next {
+            Some(appid) => {
+                self.running.set(appid);
+                let timeslice = if self.preemptive(appid) {
+                    Some(VPP_TIMESLICE_US)
+                } else {
+                    None
+                };
+                SchedulingDecision::RunProcess((appid, timeslice))
+            }
+            None => SchedulingDecision::TrySleep,
+        }
+    }
+
+    unsafe fn continue_process(&self, appid: AppId, chip: &C) -> bool {
+        if chip.has_pending_interrupts()
+            || DynamicDeferredCall::global_instance_calls_pending().unwrap_or(false)
+        {
+            return false;
+        }
+        if !self.preemptive(appid) {
+            return true;
+        }
+        // A system call of the running Process may have made a Process of higher
+        // priority ready, for instance by sending it a Signal.
+        let running = self
+            .running
+            .map_or(None, |running| self.vpp_kernel.process_index(*running))
+            .and_then(|index| self.ready_priority(index as usize));
+        match (running, self.next_ready()) {
+            (Some(running), Some((_, ready))) => ready <= running,
+            _ => true,
+        }
+    }
+
+    fn result(&self, _: StoppedExecutingReason, _: Option<u32>) {
+        self.running.clear()
+    }
+}
@@ -0,0 +1,225 @@
+//! Blocking wait for a Signal on a VPP Mailbox (`_mk_Wait_Signal`).
+//!
+//! A Process waits on one of its Mailboxes: it is put in the `WAITING` state until a
+//! Signal is sent to that Mailbox or until its timeout, measured with a virtual
+//! alarm, expires. It then resumes through the callback registered with subscribe 0
+//! of the VPP kernel driver, which receives `(MK_ERROR_e, Signals, Mailbox handle)`.
+//! The Signals are consumed from the Mailbox, as `_mk_Get_Signal` would do. On a
+//! timeout the error is `MK_ERROR_TIME_OUT` and the Signals are `MK_SIGNAL_TIME_OUT`.
+//!
+//! A Process can also wait on all its Mailboxes at once with `MK_HANDLE_ANY_MAILBOX`.
+//! It is resumed when one of them is activated, with no Signals and the handle
+//! `MK_HANDLE_ANY_MAILBOX`: the Signals stay in the Mailboxes, which the Process finds
+//! with `_mk_Get_Mailbox_ID_Activated`. On a timeout, `MK_SIGNAL_TIME_OUT` is also
+//! sent to its kernel Mailbox.
+//!
+//! From userspace the wait is a command followed by a `yield`, which blocks the
+//! Process until the callback is scheduled.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//!     let signal_wait_alarm = static_init!(
+//!         VirtualMuxAlarm<'static, earlgrey::timer::RvTimer>,
+//!         VirtualMuxAlarm::new(mux_alarm)
+//!     );
+//!     let signal_wait = static_init!(
+//!         capsules::vpp::signal_wait::VppSignalWait<'static, VirtualMuxAlarm<'static, RvTimer>>,
+//!         capsules::vpp::signal_wait::VppSignalWait::new(
+//!             vpp_kernel,
+//!             signal_wait_alarm,
+//!             board_kernel.create_grant(&memory_allocation_cap),
+//!         )
+//!     );
+//!     hil::time::Alarm::set_alarm_client(signal_wait_alarm, signal_wait);
+//!     vpp_kernel.set_signal_waiter(signal_wait);
+//! ```
+
+use crate::vpp::mloi::MK_ERROR_e::*;
+use crate::vpp::mloi::{
+    MK_BITMAP_t, MK_ERROR_e, MK_HANDLE_t, MK_Index_t, MK_SIGNAL_e, VppState, MK_HANDLE_ANY_MAILBOX,
+    MK_TIME_INFINITE,
+};
+use crate::vpp::vppkernel::{VppKernel, NUM_PROCS};
+use core::cell::Cell;
+use kernel::hil::time::{self, Alarm, Ticks};
+use kernel::{AppId, Callback, Grant, ReturnCode};
+
+/// Waits of the VPP Processes, as used by `_mk_Wait_Signal`.
+pub trait SignalWaiter {
+    /// Blocks `appid` until a Signal reaches `mailbox`, or any of its Mailboxes with
+    /// `MK_HANDLE_ANY_MAILBOX`, or `time` milliseconds elapsed. The Mailbox and its
+    /// ownership are checked by the caller.
+    fn wait(&self, appid: AppId, mailbox: MK_HANDLE_t, time: u32) -> MK_ERROR_e;
+    /// A Signal was sent to `mailbox`: resumes the Process waiting on it.
+    fn signal(&self, mailbox: MK_HANDLE_t);
+    /// Registers the callback resuming `appid`.
+    fn subscribe(&self, callback: Option<Callback>, appid: AppId) -> ReturnCode;
+}
+
+#[derive(Default)]
+pub struct WaitData {
+    callback: Option<Callback>,
+}
+
+#[derive(Clone, Copy)]
+struct Wait<T: Ticks> {
+    appid: AppId,
+    mailbox: MK_HANDLE_t,
+    /// `(reference, dt)` of the timeout, `None` without time limit.
+    timeout: Option<(T, T)>,
+}
+
+pub struct VppSignalWait<'a, A: Alarm<'a>> {
+    vpp_kernel: &'static VppKernel,
+    alarm: &'a A,
+    waits: [Cell<Option<Wait<A::Ticks>>>; NUM_PROCS],
+    apps: Grant<WaitData>,
+}
+
+impl<'a, A: Alarm<'a>> VppSignalWait<'a, A> {
+    pub fn new(
+        vpp_kernel: &'static VppKernel,
+        alarm: &'a A,
+        grant: Grant<WaitData>,
+    ) -> VppSignalWait<'a, A> {
+        VppSignalWait {
+            vpp_kernel: vpp_kernel,
+            alarm: alarm,
+            waits: Default::default(),
+            apps: grant,
+        }
+    }
+
+    /// Ends the wait of the Process at `index` and schedules its callback.
+    fn resume(&self, index: usize, error: MK_ERROR_e, signals: MK_BITMAP_t) {
+        if let Some(wait) = self.waits[index].take() {
+            if let Some(process) = self.vpp_kernel.get_process_ref_appid(wait.appid) {
+                if process.get_vpp_state() == VppState::WAITING {
+                    process.vppstate.set(VppState::READY);
+                }
+            }
+            let _ = self.apps.enter(wait.appid, |app, _| {
+                app.callback.map(|mut callback| {
+                    callback.schedule(usize::from(error), signals as usize, wait.mailbox as usize)
+                });
+            });
+            self.rearm();
+        }
+    }
+
+    /// Arms the alarm for the closest timeout, if any.
+    fn rearm(&self) {
+        let now = self.alarm.now();
+        let next = self
+            .waits
+            .iter()
+            .filter_map(|wait| wait.get().and_then(|wait| wait.timeout))
+            .min_by_key(|&(reference, dt)| reference.wrapping_add(dt).wrapping_sub(now));
+        match next {
+            Some((reference, dt)) => self.alarm.set_alarm(reference, dt),
+            None => {
+                self.alarm.disarm();
+            }
+        }
+    }
+}
+
+impl<'a, A: Alarm<'a>> SignalWaiter for VppSignalWait<'a, A> {
+    fn wait(&self, appid: AppId, mailbox: MK_HANDLE_t, time: u32) -> MK_ERROR_e {
+        let index = match self.vpp_kernel.process_index(appid) {
+            Some(index) => index as usize,
+            None => return MK_ERROR_ACCESS_DENIED,
+        };
+        let (activated, pending) = if mailbox == MK_HANDLE_ANY_MAILBOX {
+            (self.vpp_kernel.has_activated_mailbox(index as MK_Index_t), 0)
+        } else {
+            let pending = self
+                .vpp_kernel
+                .Get_Mailbox_ref_internal(mailbox)
+                .map_or(0, |mbox| mbox.get_sig());
+            (pending != 0, pending)
+        };
+        let timeout = if time == MK_TIME_INFINITE {
+            None
+        } else {
+            Some((self.alarm.now(), A::ticks_from_ms(time)))
+        };
+        self.waits[index].set(Some(Wait {
+            appid: appid,
+            mailbox: mailbox,
+            timeout: timeout,
+        }));
+        // A Signal already pending, or a timeout of 0, ends the wait right away.
+        if activated || time == 0 {
+            self.resume(index, MK_ERROR_NONE, pending);
+            return MK_ERROR_NONE;
+        }
+        if let Some(process) = self.vpp_kernel.get_process_ref_appid(appid) {
+            process.vppstate.set(VppState::WAITING);
+        }
+        self.rearm();
+        MK_ERROR_NONE
+    }
+
+    fn signal(&self, mailbox: MK_HANDLE_t) {
+        let mbox = match self.vpp_kernel.Get_Mailbox_ref_internal(mailbox) {
+            Some(mbox) => mbox,
+            None => return,
+        };
+        let owner = mbox.get_owner_proc_i() as usize;
+        let waiting = self.waits.iter().enumerate().find_map(|(index, wait)| {
+            wait.get()
+                .filter(|wait| {
+                    wait.mailbox == mailbox
+                        || (wait.mailbox == MK_HANDLE_ANY_MAILBOX && index == owner)
+                })
+                .map(|wait| (index, wait.mailbox))
+        });
+        match waiting {
+            Some((index, MK_HANDLE_ANY_MAILBOX)) => self.resume(index, MK_ERROR_NONE, 0),
+            Some((index, _)) => self.resume(index, MK_ERROR_NONE, mbox.get_sig()),
+            None => {}
+        }
+    }
+
+    fn subscribe(&self, callback: Option<Callback>, appid: AppId) -> ReturnCode {
+        self.apps
+            .enter(appid, |app, _| {
+                app.callback = callback;
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+}
+
+impl<'a, A: Alarm<'a>> time::AlarmClient for VppSignalWait<'a, A> {
+    fn alarm(&self) {
+        let now = self.alarm.now();
+        for index in 0..NUM_PROCS {
+            let expired = self.waits[index].get().filter(|wait| {
+                wait.timeout.map_or(false, |(reference, dt)| {
+                    !now.within_range(reference, reference.wrapping_add(dt))
+                })
+            });
+            if let Some(wait) = expired {
+                if wait.mailbox == MK_HANDLE_ANY_MAILBOX {
+                    self.vpp_kernel
+                        .get_process_ref_appid(wait.appid)
+                        .and_then(|process| {
+                            self.vpp_kernel._mk_Get_Mailbox_Handle(process.kernel_mailbox.get())
+                        })
+                        .and_then(|handle| self.vpp_kernel.Get_Mailbox_ref_internal(handle))
+                        .map(|mbox| mbox.add_sig(MK_SIGNAL_e::MK_SIGNAL_TIME_OUT.into()));
+                }
+                self.resume(
+                    index,
+                    MK_ERROR_TIME_OUT,
+                    MK_SIGNAL_e::MK_SIGNAL_TIME_OUT.into(),
+                );
+            }
+        }
+        self.rearm();
+    }
+}
@@ -0,0 +1,861 @@
+//! Host simulation of the VPP kernel, for the tests of `capsules::vpp`.
+//!
+//! The simulation runs the VPP kernel on a real `Kernel` whose processes are
+//! `MockProcess`es: they keep a Tock state, count their MPU regions and tasks, and
+//! allocate the grants in a heap buffer, so `VppKernel`, `mbox`, `ipc` and
+//! `VppProcess` run unchanged under `cargo test`. A `SimClock` is the virtual clock of
+//! `_mk_Get_Time` and the alarm of the `VppSignalWait`; time only moves with
+//! `SimClock::advance`.
+//!
+//! A `VffBuilder` writes the VPP Firmware headers of the tests.
+//!
+//! The Process at index `i` of the group has the Tock identifier `i`, as the VPP
+//! kernel assumes. The simulations run one at a time, since `debug!` writes to a
+//! global writer.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//!     let sim = SimBuilder::new()
+//!         .process(MK_PROCESS_MAIN_APP_ID, 0, MAIN_MAILBOX)
+//!         .process(CHILD_ID, 0, CHILD_MAILBOX)
+//!         .mailbox(MAIN_MAILBOX, 0, 1)
+//!         .mailbox(CHILD_MAILBOX, 1, 0)
+//!         .build();
+//!     sim.command(1, CMD_SEND_SIGNAL, sim.mailbox_handle(MAIN_MAILBOX) as usize, 0x100);
+//! ```
+
+extern crate std;
+
+use self::std::boxed::Box;
+use self::std::sync::atomic::{AtomicBool, Ordering};
+use self::std::sync::Once;
+use self::std::vec::Vec;
+use crate::vpp::ipc::ipc;
+use crate::vpp::mailbox::mbox;
+use crate::vpp::mloi::*;
+use crate::vpp::process::{VppProcess, VppRestartPolicy};
+use crate::vpp::signal_wait::VppSignalWait;
+use crate::vpp::timer::MonotonicTime;
+use crate::vpp::vff::{
+    FW_DESCRIPTOR_SIZE, IPC_DESCRIPTOR_SIZE, LIB_DESCRIPTOR_SIZE, LLOS_DESCRIPTOR_SIZE,
+    MAILBOX_DESCRIPTOR_SIZE, PROCESS_DESCRIPTOR_SIZE,
+};
+use crate::vpp::vppkernel::{vpp_kernel_driver, VppKernel, NUM_PROCS};
+use core::cell::Cell;
+use core::fmt::Write;
+use core::ptr::{self, NonNull};
+use kernel::capabilities::{
+    ExternalProcessCapability, MemoryAllocationCapability, ProcessManagementCapability,
+};
+use kernel::common::cells::OptionalCell;
+use kernel::common::RingBuffer;
+use kernel::debug::{self, DebugWriter, DebugWriterWrapper};
+use kernel::hil::time::{self, Alarm, Freq1KHz, Ticks, Ticks32, Time};
+use kernel::hil::uart;
+use kernel::procs::{Error, FunctionCall, ProcessEvent, ProcessType, State, Task};
+use kernel::syscall::{ContextSwitchReason, Syscall};
+use kernel::{mpu, AppId, AppSlice, CallbackId, Driver, Grant, Kernel, ReturnCode, Shared};
+
+/// Size of the memory of a `MockProcess`, which holds its grants.
+const SIM_MEMORY_SIZE: usize = 8192;
+/// Number of grants a `MockProcess` can hold.
+const SIM_GRANTS: usize = 32;
+
+struct SimCapability;
+unsafe impl MemoryAllocationCapability for SimCapability {}
+unsafe impl ExternalProcessCapability for SimCapability {}
+unsafe impl ProcessManagementCapability for SimCapability {}
+
+static SIM_LOCK: AtomicBool = AtomicBool::new(false);
+static DEBUG_WRITER: Once = Once::new();
+
+/// Runs the simulations one at a time.
+struct SimLock;
+
+impl SimLock {
+    fn acquire() -> SimLock {
+        while SIM_LOCK
+            .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            std::thread::yield_now();
+        }
+        SimLock
+    }
+}
+
+impl Drop for SimLock {
+    fn drop(&mut self) {
+        SIM_LOCK.store(false, Ordering::Release);
+    }
+}
+
+fn leak<T>(value: T) -> &'static mut T {
+    Box::leak(Box::new(value))
+}
+
+/// Leaks a table of `len` slots holding `items` followed by `None`.
+fn leak_table<T>(mut items: Vec<Option<T>>, len: usize) -> *const Option<T> {
+    assert!(items.len() <= len);
+    items.resize_with(len, || None);
+    Box::leak(items.into_boxed_slice()).as_ptr()
+}
+
+/// UART discarding the output of `debug!`.
+struct SinkUart;
+
+impl<'a> uart::Transmit<'a> for SinkUart {
+    fn set_transmit_client(&self, _client: &'a dyn uart::TransmitClient) {}
+
+    fn transmit_buffer(
+        &self,
+        tx_buffer: &'static mut [u8],
+        _tx_len: usize,
+    ) -> (ReturnCode, Option<&'static mut [u8]>) {
+        (ReturnCode::SUCCESS, Some(tx_buffer))
+    }
+
+    fn transmit_word(&self, _word: u32) -> ReturnCode {
+        ReturnCode::FAIL
+    }
+
+    fn transmit_abort(&self) -> ReturnCode {
+        ReturnCode::SUCCESS
+    }
+}
+
+fn install_debug_writer() {
+    DEBUG_WRITER.call_once(|| unsafe {
+        let ring = leak(RingBuffer::new(Box::leak(Box::new([0u8; 1024]))));
+        let writer = leak(DebugWriter::new(
+            leak(SinkUart),
+            Box::leak(Box::new([0u8; 1024])),
+            ring,
+        ));
+        debug::set_debug_writer_wrapper(leak(DebugWriterWrapper::new(writer)));
+    });
+}
+
+/// Tock process of the simulation. It does not run code: its state only changes
+/// through the `ProcessType` interface, and is reported to the process observers.
+pub(crate) struct MockProcess {
+    kernel: Cell<Option<&'static Kernel>>,
+    appid: Cell<Option<AppId>>,
+    state: Cell<State>,
+    restarts: Cell<usize>,
+    /// Callbacks scheduled for the process.
+    tasks: Cell<usize>,
+    /// MPU regions added and not removed.
+    regions: Cell<usize>,
+    memory: *mut u8,
+    /// Offset of the kernel break in `memory`, the grants being allocated below it.
+    kernel_break: Cell<usize>,
+    grant_ptrs: Vec<Cell<*mut u8>>,
+}
+
+impl MockProcess {
+    fn new() -> MockProcess {
+        MockProcess {
+            kernel: Cell::new(None),
+            appid: Cell::new(None),
+            state: Cell::new(State::Yielded),
+            restarts: Cell::new(0),
+            tasks: Cell::new(0),
+            regions: Cell::new(0),
+            memory: Box::leak(Box::new([0u8; SIM_MEMORY_SIZE])).as_mut_ptr(),
+            kernel_break: Cell::new(SIM_MEMORY_SIZE),
+            grant_ptrs: (0..SIM_GRANTS).map(|_| Cell::new(ptr::null_mut())).collect(),
+        }
+    }
+
+    pub(crate) fn regions(&self) -> usize {
+        self.regions.get()
+    }
+
+    fn event(&self, event: ProcessEvent) {
+        if let Some(kernel) = self.kernel.get() {
+            kernel.process_event_external(self.appid(), event, &SimCapability);
+        }
+    }
+
+    fn update(&self, state: State) {
+        let old_state = self.state.replace(state);
+        if old_state != state {
+            self.event(ProcessEvent::StateChanged(old_state, state));
+        }
+    }
+}
+
+impl ProcessType for MockProcess {
+    fn appid(&self) -> AppId {
+        self.appid.get().expect("MockProcess without AppId")
+    }
+
+    fn enqueue_task(&self, _task: Task) -> bool {
+        self.tasks.set(self.tasks.get() + 1);
+        self.event(ProcessEvent::TaskQueued);
+        true
+    }
+
+    fn ready(&self) -> bool {
+        self.tasks.get() != 0 || self.state.get() == State::Running
+    }
+
+    fn dequeue_task(&self) -> Option<Task> {
+        None
+    }
+
+    fn remove_pending_callbacks(&self, _callback_id: CallbackId) {}
+
+    fn get_state(&self) -> State {
+        self.state.get()
+    }
+
+    fn set_state(&self, state: State) {
+        self.update(state);
+    }
+
+    fn set_yielded_state(&self) {
+        if self.state.get() == State::Running {
+            self.update(State::Yielded);
+        }
+    }
+
+    fn stop(&self) {
+        match self.state.get() {
+            State::Running => self.update(State::StoppedRunning),
+            State::Yielded => self.update(State::StoppedYielded),
+            _ => {}
+        }
+    }
+
+    fn resume(&self) {
+        match self.state.get() {
+            State::StoppedRunning => self.update(State::Running),
+            State::StoppedYielded => self.update(State::Yielded),
+            _ => {}
+        }
+    }
+
+    fn set_fault_state(&self) {
+        self.update(State::Fault);
+        self.event(ProcessEvent::Faulted);
+    }
+
+    fn terminate(&self) {
+        self.tasks.set(0);
+        self.update(State::StoppedFaulted);
+    }
+
+    fn get_restart_count(&self) -> usize {
+        self.restarts.get()
+    }
+
+    fn get_process_name(&self) -> &'static str {
+        "sim"
+    }
+
+    fn check_syscall_permissions(&self, _syscall: &Syscall) -> Result<(), ReturnCode> {
+        Ok(())
+    }
+
+    fn brk(&self, _new_break: *const u8) -> Result<*const u8, Error> {
+        Err(Error::OutOfMemory)
+    }
+
+    fn sbrk(&self, _increment: isize) -> Result<*const u8, Error> {
+        Err(Error::OutOfMemory)
+    }
+
+    fn mem_start(&self) -> *const u8 {
+        self.memory
+    }
+
+    fn mem_end(&self) -> *const u8 {
+        self.memory.wrapping_add(SIM_MEMORY_SIZE)
+    }
+
+    fn flash_start(&self) -> *const u8 {
+        ptr::null()
+    }
+
+    fn flash_end(&self) -> *const u8 {
+        ptr::null()
+    }
+
+    fn kernel_memory_break(&self) -> *const u8 {
+        self.memory.wrapping_add(self.kernel_break.get())
+    }
+
+    fn number_writeable_flash_regions(&self) -> usize {
+        0
+    }
+
+    fn get_writeable_flash_region(&self, _region_index: usize) -> (u32, u32) {
+        (0, 0)
+    }
+
+    fn update_stack_start_pointer(&self, _stack_pointer: *const u8) {}
+
+    fn update_heap_start_pointer(&self, _heap_pointer: *const u8) {}
+
+    fn allow(
+        &self,
+        _buf_start_addr: *const u8,
+        _size: usize,
+    ) -> Result<Option<AppSlice<Shared, u8>>, ReturnCode> {
+        Err(ReturnCode::ENOSUPPORT)
+    }
+
+    fn flash_non_protected_start(&self) -> *const u8 {
+        ptr::null()
+    }
+
+    fn setup_mpu(&self) {}
+
+    fn add_mpu_region(
+        &self,
+        unallocated_memory_start: *const u8,
+        unallocated_memory_size: usize,
+        _min_region_size: usize,
+        _permissions: mpu::Permissions,
+    ) -> Option<mpu::Region> {
+        self.regions.set(self.regions.get() + 1);
+        Some(mpu::Region::new(
+            unallocated_memory_start,
+            unallocated_memory_size,
+        ))
+    }
+
+    fn remove_mpu_region(&self, _region: mpu::Region) -> Result<(), ()> {
+        self.regions.set(self.regions.get() - 1);
+        Ok(())
+    }
+
+    fn alloc(&self, size: usize, align: usize) -> Option<NonNull<u8>> {
+        let base = self.memory as usize;
+        let address = (base + self.kernel_break.get()).checked_sub(size)? & !(align - 1);
+        if address < base {
+            return None;
+        }
+        self.kernel_break.set(address - base);
+        NonNull::new(address as *mut u8)
+    }
+
+    unsafe fn free(&self, _: *mut u8) {}
+
+    fn get_grant_ptr(&self, grant_num: usize) -> Option<*mut u8> {
+        self.grant_ptrs.get(grant_num).map(|grant_ptr| grant_ptr.get())
+    }
+
+    unsafe fn set_grant_ptr(&self, grant_num: usize, grant_ptr: *mut u8) {
+        self.grant_ptrs[grant_num].set(grant_ptr);
+    }
+
+    unsafe fn set_syscall_return_value(&self, _return_value: isize) {}
+
+    unsafe fn set_process_function(&self, _callback: FunctionCall) {}
+
+    unsafe fn switch_to(&self) -> Option<ContextSwitchReason> {
+        None
+    }
+
+    unsafe fn print_memory_map(&self, _writer: &mut dyn Write) {}
+
+    unsafe fn print_full_process(&self, _writer: &mut dyn Write) {}
+
+    fn debug_syscall_count(&self) -> usize {
+        0
+    }
+
+    fn debug_dropped_callback_count(&self) -> usize {
+        0
+    }
+
+    fn debug_timeslice_expiration_count(&self) -> usize {
+        0
+    }
+
+    fn debug_denied_syscall_count(&self) -> usize {
+        0
+    }
+
+    fn debug_cpu_time_us(&self) -> Option<u64> {
+        None
+    }
+
+    fn debug_max_stack_depth(&self) -> usize {
+        0
+    }
+
+    fn debug_grant_memory_size(&self) -> usize {
+        0
+    }
+
+    fn debug_cpu_time_used(&self, _time_us: u32) {}
+
+    fn debug_timeslice_expired(&self) {}
+
+    fn debug_syscall_called(&self, _last_syscall: Syscall) {}
+
+    fn debug_syscall_denied(&self) {}
+}
+
+/// Virtual clock of the simulation, in milliseconds.
+pub(crate) struct SimClock {
+    now: Cell<u32>,
+    /// `(reference, dt)` of the armed alarm.
+    alarm: Cell<Option<(Ticks32, Ticks32)>>,
+    client: OptionalCell<&'static dyn time::AlarmClient>,
+}
+
+impl SimClock {
+    fn new() -> SimClock {
+        SimClock {
+            now: Cell::new(0),
+            alarm: Cell::new(None),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    /// Moves the time forward by `ms` and fires the alarm if it expired.
+    pub(crate) fn advance(&self, ms: u32) {
+        self.now.set(self.now.get().wrapping_add(ms));
+        let now = self.now();
+        if let Some((reference, dt)) = self.alarm.get() {
+            if !now.within_range(reference, reference.wrapping_add(dt)) {
+                self.alarm.set(None);
+                self.client.map(|client| client.alarm());
+            }
+        }
+    }
+}
+
+impl Time for SimClock {
+    type Frequency = Freq1KHz;
+    type Ticks = Ticks32;
+
+    fn now(&self) -> Ticks32 {
+        self.now.get().into()
+    }
+}
+
+impl Alarm<'static> for SimClock {
+    fn set_alarm_client(&'static self, client: &'static dyn time::AlarmClient) {
+        self.client.set(client);
+    }
+
+    fn set_alarm(&self, reference: Ticks32, dt: Ticks32) {
+        self.alarm.set(Some((reference, dt)));
+    }
+
+    fn get_alarm(&self) -> Ticks32 {
+        self.alarm
+            .get()
+            .map_or(0.into(), |(reference, dt)| reference.wrapping_add(dt))
+    }
+
+    fn disarm(&self) -> ReturnCode {
+        self.alarm.set(None);
+        ReturnCode::SUCCESS
+    }
+
+    fn is_armed(&self) -> bool {
+        self.alarm.get().is_some()
+    }
+
+    fn minimum_dt(&self) -> Ticks32 {
+        1.into()
+    }
+}
+
+impl MonotonicTime for SimClock {
+    fn get_time(&self) -> MK_TIME_t {
+        self.now.get() as MK_TIME_t
+    }
+}
+
+/// Group of Processes, Mailboxes and IPCs of a simulation.
+pub(crate) struct SimBuilder {
+    /// `(id, parent, kernel Mailbox)` of the Processes, by index.
+    processes: Vec<(MK_Process_ID_u, MK_Index_t, MK_MAILBOX_ID_u)>,
+    /// `(id, owner, sender)` of the Mailboxes, by slot.
+    mailboxes: Vec<(MK_MAILBOX_ID_u, MK_Index_t, MK_Index_t)>,
+    /// `(id, length, writer, reader)` of the IPCs, by slot.
+    ipcs: Vec<(MK_IPC_ID_u, u16, MK_Index_t, MK_Index_t)>,
+}
+
+impl SimBuilder {
+    pub(crate) fn new() -> SimBuilder {
+        SimBuilder {
+            processes: Vec::new(),
+            mailboxes: Vec::new(),
+            ipcs: Vec::new(),
+        }
+    }
+
+    /// Adds the next Process of the group.
+    pub(crate) fn process(
+        mut self,
+        id: MK_Process_ID_u,
+        parent: MK_Index_t,
+        kernel_mailbox: MK_MAILBOX_ID_u,
+    ) -> SimBuilder {
+        self.processes.push((id, parent, kernel_mailbox));
+        self
+    }
+
+    pub(crate) fn mailbox(
+        mut self,
+        id: MK_MAILBOX_ID_u,
+        owner: MK_Index_t,
+        sender: MK_Index_t,
+    ) -> SimBuilder {
+        self.mailboxes.push((id, owner, sender));
+        self
+    }
+
+    pub(crate) fn ipc(
+        mut self,
+        id: MK_IPC_ID_u,
+        length: u16,
+        writer: MK_Index_t,
+        reader: MK_Index_t,
+    ) -> SimBuilder {
+        self.ipcs.push((id, length, writer, reader));
+        self
+    }
+
+    pub(crate) fn build(self) -> Sim {
+        assert!(self.processes.len() <= NUM_PROCS);
+        let lock = SimLock::acquire();
+        install_debug_writer();
+        let capability = SimCapability;
+
+        let mocks: Vec<&'static MockProcess> =
+            self.processes.iter().map(|_| &*leak(MockProcess::new())).collect();
+        let mut tock_processes: [Option<&'static dyn ProcessType>; NUM_PROCS] = [None; NUM_PROCS];
+        for (index, mock) in mocks.iter().enumerate() {
+            tock_processes[index] = Some(*mock);
+        }
+        let kernel: &'static Kernel = leak(Kernel::new(leak(tock_processes)));
+        for (index, mock) in mocks.iter().enumerate() {
+            mock.kernel.set(Some(kernel));
+            mock.appid
+                .set(Some(AppId::new_external(kernel, index, index, &capability)));
+        }
+
+        let mut vpp_processes: [Option<VppProcess>; NUM_PROCS] = Default::default();
+        for (index, &(id, parent, kernel_mailbox)) in self.processes.iter().enumerate() {
+            let process = VppProcess::create_main_process(Some(mocks[index]));
+            process.vppid.set(id);
+            process.parent.set(parent);
+            process.kernel_mailbox.set(kernel_mailbox);
+            process
+                .restart_policy
+                .set(VppRestartPolicy::for_process(id, index as MK_Index_t, parent));
+            vpp_processes[index] = Some(process);
+        }
+        let mailboxes = self
+            .mailboxes
+            .iter()
+            .map(|&(id, owner, sender)| Some(mbox::new(id, owner, sender, kernel, &capability)))
+            .collect();
+        let ipcs = self
+            .ipcs
+            .iter()
+            .map(|&(id, length, writer, reader)| {
+                Some(ipc::new(id, length, writer, reader, kernel, &capability))
+            })
+            .collect();
+        let mailboxes = leak_table(mailboxes, MK_MAILBOX_LIMIT);
+        let ipcs = leak_table(ipcs, MK_IPC_LIMIT);
+        let vpp_kernel: &'static VppKernel = leak(VppKernel::new(
+            leak(vpp_processes),
+            unsafe { &*(mailboxes as *const [Option<mbox>; MK_MAILBOX_LIMIT]) },
+            unsafe { &*(ipcs as *const [Option<ipc>; MK_IPC_LIMIT]) },
+            kernel,
+        ));
+
+        let clock: &'static SimClock = leak(SimClock::new());
+        vpp_kernel.set_clock(clock);
+        let waiter: &'static VppSignalWait<'static, SimClock> = leak(VppSignalWait::new(
+            vpp_kernel,
+            clock,
+            kernel.create_grant(&capability),
+        ));
+        clock.set_alarm_client(waiter);
+        vpp_kernel.set_signal_waiter(waiter);
+        kernel.add_process_observer(vpp_kernel, &capability);
+
+        Sim {
+            kernel: kernel,
+            vpp_kernel: vpp_kernel,
+            driver: leak(vpp_kernel_driver::new(vpp_kernel)),
+            clock: clock,
+            processes: mocks,
+            _lock: lock,
+        }
+    }
+}
+
+/// A running simulation.
+pub(crate) struct Sim {
+    pub(crate) kernel: &'static Kernel,
+    pub(crate) vpp_kernel: &'static VppKernel,
+    pub(crate) driver: &'static vpp_kernel_driver,
+    pub(crate) clock: &'static SimClock,
+    /// Tock processes, by index in the group.
+    pub(crate) processes: Vec<&'static MockProcess>,
+    _lock: SimLock,
+}
+
+impl Sim {
+    pub(crate) fn appid(&self, index: usize) -> AppId {
+        self.processes[index].appid()
+    }
+
+    /// `AppId` of an app loaded in the slot of the Process at `index` once it is
+    /// unloaded.
+    pub(crate) fn next_appid(&self, index: usize) -> AppId {
+        AppId::new_external(self.kernel, NUM_PROCS + index, index, &SimCapability)
+    }
+
+    /// Creates a grant for a capsule under test.
+    pub(crate) fn create_grant<T: Default>(&self) -> Grant<T> {
+        self.kernel.create_grant(&SimCapability)
+    }
+
+    /// Buffer `data` shared by the Process at `index`, as given to `allow`.
+    pub(crate) fn app_slice(&self, index: usize, data: &'static mut [u8]) -> AppSlice<Shared, u8> {
+        unsafe {
+            AppSlice::new_external(
+                NonNull::new(data.as_mut_ptr()).unwrap(),
+                data.len(),
+                self.appid(index),
+                &SimCapability,
+            )
+        }
+    }
+
+    pub(crate) fn process(&self, index: usize) -> &VppProcess {
+        self.vpp_kernel
+            .get_process_ref_index(index as MK_Index_t)
+            .expect("no such Process")
+    }
+
+    pub(crate) fn process_handle(&self, index: usize) -> MK_HANDLE_t {
+        self.vpp_kernel.process_handle(index).expect("no such Process")
+    }
+
+    pub(crate) fn mailbox_handle(&self, id: MK_MAILBOX_ID_u) -> MK_HANDLE_t {
+        self.vpp_kernel
+            ._mk_Get_Mailbox_Handle(id)
+            .expect("no such Mailbox")
+    }
+
+    pub(crate) fn ipc_handle(&self, id: MK_IPC_ID_u) -> MK_HANDLE_t {
+        self.vpp_kernel._mk_Get_IPC_Handle(id).expect("no such IPC")
+    }
+
+    /// Runs command `command_num` of the VPP kernel driver for the Process at `caller`.
+    pub(crate) fn command(
+        &self,
+        caller: usize,
+        command_num: usize,
+        data: usize,
+        data2: usize,
+    ) -> ReturnCode {
+        self.driver
+            .command(command_num, data, data2, self.appid(caller))
+    }
+}
+
+/// VPP Firmware header of the tests. It starts as a valid header of an App Firmware
+/// with only its Main Process; every field can then be changed, valid or not.
+pub(crate) struct VffBuilder {
+    id: UUID_t,
+    family_id: UUID_t,
+    /// `m_uHeaderLength`, computed from the descriptors if `None`.
+    header_length: Option<u16>,
+    format_version: u16,
+    software_type: u8,
+    scheduling_type: u8,
+    processes: Vec<[u8; PROCESS_DESCRIPTOR_SIZE]>,
+    mailboxes: Vec<[u8; MAILBOX_DESCRIPTOR_SIZE]>,
+    ipcs: Vec<[u8; IPC_DESCRIPTOR_SIZE]>,
+    libs: Vec<[u8; LIB_DESCRIPTOR_SIZE]>,
+    llos: Option<[u8; LLOS_DESCRIPTOR_SIZE]>,
+}
+
+impl VffBuilder {
+    /// Version 4 UUIDs of the RFC 4122 variant.
+    pub(crate) const ID: UUID_t = 0x1234_5678_9ABC_4DEF_8123_4567_89AB_CDEF;
+    pub(crate) const FAMILY_ID: UUID_t = 0x0FED_CBA9_8765_4321_9FED_CBA9_8765_4321;
+    /// Stack of the Processes, the minimum supported.
+    pub(crate) const STACK_SIZE: u16 = 2048;
+
+    pub(crate) fn new() -> VffBuilder {
+        VffBuilder {
+            id: Self::ID,
+            family_id: Self::FAMILY_ID,
+            header_length: None,
+            format_version: 0x0400,
+            software_type: VPP_FRW_TYPE_e::FIRMWARE_SOFTWARE_TYPE_APP.into(),
+            scheduling_type: VPP_SCHEDULING_TYPE_e::MK_SCHEDULING_TYPE_PREEMPTIVE.into(),
+            processes: Vec::new(),
+            mailboxes: Vec::new(),
+            ipcs: Vec::new(),
+            libs: Vec::new(),
+            llos: None,
+        }
+        .process(MK_PROCESS_MAIN_APP_ID, 0, 0)
+    }
+
+    pub(crate) fn id(mut self, id: UUID_t) -> VffBuilder {
+        self.id = id;
+        self
+    }
+
+    pub(crate) fn family_id(mut self, family_id: UUID_t) -> VffBuilder {
+        self.family_id = family_id;
+        self
+    }
+
+    pub(crate) fn header_length(mut self, header_length: u16) -> VffBuilder {
+        self.header_length = Some(header_length);
+        self
+    }
+
+    pub(crate) fn format_version(mut self, format_version: u16) -> VffBuilder {
+        self.format_version = format_version;
+        self
+    }
+
+    pub(crate) fn software_type(mut self, software_type: u8) -> VffBuilder {
+        self.software_type = software_type;
+        self
+    }
+
+    pub(crate) fn scheduling_type(mut self, scheduling_type: u8) -> VffBuilder {
+        self.scheduling_type = scheduling_type;
+        self
+    }
+
+    /// Removes the Process Descriptors, the Main Process included.
+    pub(crate) fn no_processes(mut self) -> VffBuilder {
+        self.processes.clear();
+        self
+    }
+
+    /// Adds a Process Descriptor with 256 bytes of CODE starting with its entry point.
+    pub(crate) fn process(
+        self,
+        id: MK_Process_ID_u,
+        parent: MK_Index_t,
+        kernel_mailbox: MK_MAILBOX_ID_u,
+    ) -> VffBuilder {
+        self.process_with(id, parent, kernel_mailbox, 0, 256, Self::STACK_SIZE)
+    }
+
+    pub(crate) fn process_with(
+        mut self,
+        id: MK_Process_ID_u,
+        parent: MK_Index_t,
+        kernel_mailbox: MK_MAILBOX_ID_u,
+        entry: u32,
+        code_length: u32,
+        stack_size: u16,
+    ) -> VffBuilder {
+        let mut descriptor = [0; PROCESS_DESCRIPTOR_SIZE];
+        descriptor[0..4].copy_from_slice(&entry.to_le_bytes());
+        descriptor[4..8].copy_from_slice(&code_length.to_le_bytes());
+        descriptor[24..26].copy_from_slice(&id.to_le_bytes());
+        descriptor[26..28].copy_from_slice(&kernel_mailbox.to_le_bytes());
+        descriptor[28..30].copy_from_slice(&parent.to_le_bytes());
+        descriptor[30..32].copy_from_slice(&stack_size.to_le_bytes());
+        self.processes.push(descriptor);
+        self
+    }
+
+    pub(crate) fn mailbox(
+        mut self,
+        id: MK_MAILBOX_ID_u,
+        owner: MK_Index_t,
+        sender: MK_Index_t,
+    ) -> VffBuilder {
+        let mut descriptor = [0; MAILBOX_DESCRIPTOR_SIZE];
+        descriptor[0..2].copy_from_slice(&id.to_le_bytes());
+        descriptor[2..4].copy_from_slice(&owner.to_le_bytes());
+        descriptor[4..6].copy_from_slice(&sender.to_le_bytes());
+        self.mailboxes.push(descriptor);
+        self
+    }
+
+    pub(crate) fn ipc(
+        mut self,
+        id: MK_IPC_ID_u,
+        length: u16,
+        writer: MK_Index_t,
+        reader: MK_Index_t,
+    ) -> VffBuilder {
+        let mut descriptor = [0; IPC_DESCRIPTOR_SIZE];
+        descriptor[0..2].copy_from_slice(&id.to_le_bytes());
+        descriptor[2..4].copy_from_slice(&length.to_le_bytes());
+        descriptor[4..6].copy_from_slice(&writer.to_le_bytes());
+        descriptor[6..8].copy_from_slice(&reader.to_le_bytes());
+        self.ipcs.push(descriptor);
+        self
+    }
+
+    pub(crate) fn lib(mut self, id: MK_LIB_ID_u, version: u16, processes: MK_BITMAP_t) -> VffBuilder {
+        let mut descriptor = [0; LIB_DESCRIPTOR_SIZE];
+        descriptor[0..2].copy_from_slice(&id.to_le_bytes());
+        descriptor[2..4].copy_from_slice(&version.to_le_bytes());
+        descriptor[4..8].copy_from_slice(&processes.to_le_bytes());
+        self.libs.push(descriptor);
+        self
+    }
+
+    /// Makes the Firmware a LLOS, removing its Process Descriptors.
+    pub(crate) fn llos(mut self, entry: u32, code_length: u32) -> VffBuilder {
+        let mut descriptor = [0; LLOS_DESCRIPTOR_SIZE];
+        descriptor[0..4].copy_from_slice(&entry.to_le_bytes());
+        descriptor[4..8].copy_from_slice(&code_length.to_le_bytes());
+        self.llos = Some(descriptor);
+        self.software_type = VPP_FRW_TYPE_e::FIRMWARE_SOFTWARE_TYPE_LLOS.into();
+        self.no_processes()
+    }
+
+    pub(crate) fn build(&self) -> Vec<u8> {
+        let mut descriptors = Vec::new();
+        if let Some(llos) = self.llos {
+            descriptors.extend_from_slice(&llos);
+        }
+        descriptors.extend(self.processes.iter().flatten());
+        descriptors.extend(self.mailboxes.iter().flatten());
+        descriptors.extend(self.ipcs.iter().flatten());
+        descriptors.extend(self.libs.iter().flatten());
+        let header_length = self
+            .header_length
+            .unwrap_or((FW_DESCRIPTOR_SIZE + descriptors.len()) as u16);
+
+        let mut header = Vec::new();
+        header.extend_from_slice(&self.id.to_be_bytes());
+        header.extend_from_slice(&self.family_id.to_be_bytes());
+        header.extend_from_slice(&header_length.to_le_bytes());
+        header.extend_from_slice(&self.format_version.to_le_bytes());
+        // m_VersionFirmware
+        header.extend_from_slice(&0x0100u16.to_le_bytes());
+        header.push(self.processes.len() as u8);
+        header.push(self.mailboxes.len() as u8);
+        header.push(self.ipcs.len() as u8);
+        header.push(self.libs.len() as u8);
+        header.push(self.software_type);
+        header.push(self.scheduling_type);
+        header.extend_from_slice(&descriptors);
+        header
+    }
+}
@@ -0,0 +1,466 @@
+//! Scenario tests of the VPP kernel, run on the host simulation of `vpp::sim`.
+//!
+//! The group of Processes of the tests:
+//!
+//! ```text
+//! index 0  MAIN        root        Mailboxes MAIN (from CHILD), MAIN_2 (from OTHER)
+//! index 1  CHILD       of MAIN     Mailbox CHILD (from MAIN)
+//! index 2  GRANDCHILD  of CHILD    Mailbox GRANDCHILD (from CHILD)
+//! index 3  OTHER       root        Mailbox OTHER (from MAIN)
+//! ```
+//!
+//! MAIN writes the IPC read by CHILD.
+
+use crate::vpp::mloi::MK_ERROR_e::*;
+use crate::vpp::mloi::*;
+use crate::vpp::sim::{Sim, SimBuilder};
+use crate::vpp::vppkernel::*;
+use kernel::procs::{ProcessEvent, ProcessObserver, ProcessRestartPolicy, ProcessType, State};
+use kernel::{Driver, ReturnCode};
+
+const MAIN_ID: MK_Process_ID_u = 0x4000;
+const CHILD_ID: MK_Process_ID_u = 0x4001;
+const GRANDCHILD_ID: MK_Process_ID_u = 0x4002;
+const OTHER_ID: MK_Process_ID_u = 0x4003;
+
+const MAIN: usize = 0;
+const CHILD: usize = 1;
+const GRANDCHILD: usize = 2;
+const OTHER: usize = 3;
+
+const MAIN_MAILBOX: MK_MAILBOX_ID_u = 0x4010;
+const MAIN_MAILBOX_2: MK_MAILBOX_ID_u = 0x4011;
+const CHILD_MAILBOX: MK_MAILBOX_ID_u = 0x4012;
+const GRANDCHILD_MAILBOX: MK_MAILBOX_ID_u = 0x4013;
+const OTHER_MAILBOX: MK_MAILBOX_ID_u = 0x4014;
+
+const MAIN_CHILD_IPC: MK_IPC_ID_u = 0x4020;
+const MAIN_CHILD_IPC_LENGTH: u16 = 64;
+
+const SIGNAL_A: MK_BITMAP_t = 0x0000_0100;
+const SIGNAL_B: MK_BITMAP_t = 0x0000_0200;
+
+fn sim() -> Sim {
+    SimBuilder::new()
+        .process(MAIN_ID, MAIN as MK_Index_t, MAIN_MAILBOX)
+        .process(CHILD_ID, MAIN as MK_Index_t, CHILD_MAILBOX)
+        .process(GRANDCHILD_ID, CHILD as MK_Index_t, GRANDCHILD_MAILBOX)
+        .process(OTHER_ID, OTHER as MK_Index_t, OTHER_MAILBOX)
+        .mailbox(MAIN_MAILBOX, MAIN as MK_Index_t, CHILD as MK_Index_t)
+        .mailbox(MAIN_MAILBOX_2, MAIN as MK_Index_t, OTHER as MK_Index_t)
+        .mailbox(CHILD_MAILBOX, CHILD as MK_Index_t, MAIN as MK_Index_t)
+        .mailbox(GRANDCHILD_MAILBOX, GRANDCHILD as MK_Index_t, CHILD as MK_Index_t)
+        .mailbox(OTHER_MAILBOX, OTHER as MK_Index_t, MAIN as MK_Index_t)
+        .ipc(MAIN_CHILD_IPC, MAIN_CHILD_IPC_LENGTH, MAIN as MK_Index_t, CHILD as MK_Index_t)
+        .build()
+}
+
+fn value(value: usize) -> ReturnCode {
+    ReturnCode::SuccessWithValue { value: value }
+}
+
+fn error(error: MK_ERROR_e) -> ReturnCode {
+    value(error.into())
+}
+
+#[test]
+fn driver_check_returns_the_abi_version() {
+    let sim = sim();
+    assert_eq!(sim.command(MAIN, CMD_DRIVER_CHECK, 0, 0), value(VPP_ABI_VERSION));
+    assert_eq!(sim.command(MAIN, 0xFFFF, 0, 0), ReturnCode::ENOSUPPORT);
+}
+
+#[test]
+fn signals_accumulate_until_read_by_the_owner() {
+    let sim = sim();
+    let mailbox = sim.mailbox_handle(MAIN_MAILBOX) as usize;
+    assert_eq!(sim.command(CHILD, CMD_SEND_SIGNAL, mailbox, SIGNAL_A as usize), error(MK_ERROR_NONE));
+    assert_eq!(sim.command(CHILD, CMD_SEND_SIGNAL, mailbox, SIGNAL_B as usize), error(MK_ERROR_NONE));
+    assert_eq!(
+        sim.command(MAIN, CMD_GET_SIGNAL, mailbox, 0),
+        value((SIGNAL_A | SIGNAL_B) as usize)
+    );
+    assert_eq!(sim.command(MAIN, CMD_GET_SIGNAL, mailbox, 0), value(0));
+}
+
+#[test]
+fn only_the_sender_signals_and_only_the_owner_reads() {
+    let sim = sim();
+    let mailbox = sim.mailbox_handle(MAIN_MAILBOX) as usize;
+    assert_eq!(
+        sim.command(GRANDCHILD, CMD_SEND_SIGNAL, mailbox, SIGNAL_A as usize),
+        error(MK_ERROR_ACCESS_DENIED)
+    );
+    assert_eq!(sim.command(CHILD, CMD_SEND_SIGNAL, mailbox, SIGNAL_A as usize), error(MK_ERROR_NONE));
+    assert_eq!(sim.command(CHILD, CMD_GET_SIGNAL, mailbox, 0), ReturnCode::FAIL);
+    assert_eq!(sim.process(CHILD).get_last_generated_error(), MK_ERROR_ACCESS_DENIED);
+    assert_eq!(sim.command(MAIN, CMD_GET_SIGNAL, mailbox, 0), value(SIGNAL_A as usize));
+}
+
+#[test]
+fn mailbox_handles_are_given_to_owner_and_sender() {
+    let sim = sim();
+    let handle = sim.mailbox_handle(MAIN_MAILBOX) as usize;
+    assert_eq!(sim.command(MAIN, CMD_GET_MAILBOX_HANDLE, MAIN_MAILBOX as usize, 0), value(handle));
+    assert_eq!(sim.command(CHILD, CMD_GET_MAILBOX_HANDLE, MAIN_MAILBOX as usize, 0), value(handle));
+    assert_eq!(sim.command(OTHER, CMD_GET_MAILBOX_HANDLE, MAIN_MAILBOX as usize, 0), value(0));
+    assert_eq!(sim.process(OTHER).get_last_generated_error(), MK_ERROR_ACCESS_DENIED);
+    assert_eq!(sim.command(MAIN, CMD_GET_MAILBOX_HANDLE, 0x7FFF, 0), value(0));
+    assert_eq!(sim.process(MAIN).get_last_generated_error(), MK_ERROR_UNKNOWN_ID);
+}
+
+#[test]
+fn handles_are_checked_for_their_type() {
+    let sim = sim();
+    let process = sim.process_handle(CHILD);
+    let mailbox = sim.mailbox_handle(MAIN_MAILBOX);
+    assert_eq!(sim.vpp_kernel._mk_Send_Signal(process, SIGNAL_A), MK_ERROR_ACCESS_DENIED);
+    assert_eq!(sim.vpp_kernel._mk_Send_Signal(0, SIGNAL_A), MK_ERROR_UNKNOWN_HANDLE);
+    assert_eq!(sim.vpp_kernel._mk_Get_Error(mailbox), MK_ERROR_GET_ERROR_HANDLE_NOT_A_PROCESS);
+    assert_eq!(sim.vpp_kernel._mk_Get_Error(0), MK_ERROR_UNKNOWN_HANDLE);
+    assert_eq!(
+        sim.vpp_kernel._mk_set_process_priority(mailbox, MK_PROCESS_PRIORITY_e::MK_PROCESS_PRIORITY_HIGH),
+        MK_ERROR_ACCESS_DENIED
+    );
+}
+
+#[test]
+fn handles_are_stale_once_the_process_restarts() {
+    let sim = sim();
+    let process = sim.process_handle(CHILD);
+    let mailbox = sim.mailbox_handle(CHILD_MAILBOX);
+    sim.vpp_kernel.invalidate_handles(CHILD);
+    assert_eq!(sim.vpp_kernel._mk_Get_Error(process), MK_ERROR_UNKNOWN_HANDLE);
+    assert_eq!(sim.vpp_kernel._mk_Send_Signal(mailbox, SIGNAL_A), MK_ERROR_UNKNOWN_HANDLE);
+    let mailbox = sim.mailbox_handle(CHILD_MAILBOX);
+    assert_eq!(sim.vpp_kernel._mk_Send_Signal(mailbox, SIGNAL_A), MK_ERROR_NONE);
+    assert_eq!(sim.vpp_kernel._mk_Get_Error(sim.process_handle(CHILD)), MK_ERROR_NONE);
+}
+
+#[test]
+fn process_handles_are_given_to_ancestors_only() {
+    let sim = sim();
+    let grandchild = sim.process_handle(GRANDCHILD) as usize;
+    assert_eq!(sim.command(MAIN, CMD_GET_PROCESS_HANDLE, GRANDCHILD_ID as usize, 0), value(grandchild));
+    assert_eq!(sim.command(GRANDCHILD, CMD_GET_PROCESS_HANDLE, GRANDCHILD_ID as usize, 0), value(grandchild));
+    assert_eq!(sim.command(GRANDCHILD, CMD_GET_PROCESS_HANDLE, MAIN_ID as usize, 0), value(0));
+    assert_eq!(sim.process(GRANDCHILD).get_last_generated_error(), MK_ERROR_ACCESS_DENIED);
+    assert_eq!(sim.command(OTHER, CMD_GET_PROCESS_HANDLE, CHILD_ID as usize, 0), value(0));
+    assert_eq!(sim.command(MAIN, CMD_GET_PROCESS_HANDLE, 0x4FFF, 0), value(0));
+    assert_eq!(sim.process(MAIN).get_last_generated_error(), MK_ERROR_UNKNOWN_ID);
+}
+
+#[test]
+fn an_ancestor_suspends_and_resumes_a_process() {
+    let sim = sim();
+    let grandchild = sim.process_handle(GRANDCHILD) as usize;
+    assert_eq!(sim.command(GRANDCHILD, CMD_SUSPEND_PROCESS, grandchild, 0), error(MK_ERROR_ACCESS_DENIED));
+    assert_eq!(sim.command(OTHER, CMD_SUSPEND_PROCESS, grandchild, 0), error(MK_ERROR_ACCESS_DENIED));
+    assert_eq!(
+        sim.command(CHILD, CMD_SUSPEND_PROCESS, sim.process_handle(MAIN) as usize, 0),
+        error(MK_ERROR_ACCESS_DENIED)
+    );
+
+    assert_eq!(sim.command(MAIN, CMD_SUSPEND_PROCESS, grandchild, 0), error(MK_ERROR_NONE));
+    assert_eq!(sim.process(GRANDCHILD).get_vpp_state(), VppState::SUSPENDED_R);
+    assert_eq!(sim.processes[GRANDCHILD].get_state(), State::StoppedYielded);
+
+    assert_eq!(sim.command(CHILD, CMD_RESUME_PROCESS, grandchild, 0), error(MK_ERROR_NONE));
+    assert_eq!(sim.process(GRANDCHILD).get_vpp_state(), VppState::READY);
+    assert_eq!(sim.processes[GRANDCHILD].get_state(), State::Yielded);
+}
+
+#[test]
+fn a_waiting_process_stays_waiting_while_suspended() {
+    let sim = sim();
+    let child = sim.process_handle(CHILD);
+    let mailbox = sim.mailbox_handle(CHILD_MAILBOX) as usize;
+    assert_eq!(
+        sim.command(CHILD, CMD_WAIT_SIGNAL, mailbox, MK_TIME_INFINITE as usize),
+        error(MK_ERROR_NONE)
+    );
+    assert_eq!(sim.vpp_kernel._mk_suspend_process(child), MK_ERROR_NONE);
+    assert_eq!(sim.process(CHILD).get_vpp_state(), VppState::SUSPENDED_W);
+    assert_eq!(sim.vpp_kernel._mk_resume_process(child), MK_ERROR_NONE);
+    assert_eq!(sim.process(CHILD).get_vpp_state(), VppState::WAITING);
+}
+
+#[test]
+fn yield_makes_the_running_process_ready() {
+    let sim = sim();
+    sim.processes[CHILD].set_state(State::Running);
+    assert_eq!(sim.process(CHILD).get_vpp_state(), VppState::RUNNING);
+    assert_eq!(sim.command(CHILD, CMD_YIELD, 0, 0), ReturnCode::SUCCESS);
+    assert_eq!(sim.process(CHILD).get_vpp_state(), VppState::READY);
+    assert_eq!(sim.processes[CHILD].get_state(), State::Yielded);
+}
+
+#[test]
+fn the_vpp_state_follows_the_tock_process() {
+    let sim = sim();
+    let child = sim.processes[CHILD];
+    child.set_state(State::Running);
+    assert_eq!(sim.process(CHILD).get_vpp_state(), VppState::RUNNING);
+    child.stop();
+    assert_eq!(sim.process(CHILD).get_vpp_state(), VppState::SUSPENDED_R);
+    child.resume();
+    assert_eq!(sim.process(CHILD).get_vpp_state(), VppState::RUNNING);
+    child.set_yielded_state();
+    assert_eq!(sim.process(CHILD).get_vpp_state(), VppState::READY);
+
+    // Tock runs the other callbacks of a Process waiting for a Signal.
+    let mailbox = sim.mailbox_handle(CHILD_MAILBOX) as usize;
+    assert_eq!(
+        sim.command(CHILD, CMD_WAIT_SIGNAL, mailbox, MK_TIME_INFINITE as usize),
+        error(MK_ERROR_NONE)
+    );
+    child.set_state(State::Running);
+    child.set_yielded_state();
+    assert_eq!(sim.process(CHILD).get_vpp_state(), VppState::WAITING);
+    child.stop();
+    assert_eq!(sim.process(CHILD).get_vpp_state(), VppState::SUSPENDED_W);
+    child.resume();
+    assert_eq!(sim.process(CHILD).get_vpp_state(), VppState::WAITING);
+
+    child.set_fault_state();
+    assert_eq!(sim.process(CHILD).get_vpp_state(), VppState::DEAD);
+    assert_eq!(sim.process(MAIN).get_vpp_state(), VppState::READY);
+}
+
+#[test]
+fn a_signal_ends_the_wait_and_is_consumed() {
+    let sim = sim();
+    let mailbox = sim.mailbox_handle(MAIN_MAILBOX) as usize;
+    assert_eq!(
+        sim.command(MAIN, CMD_WAIT_SIGNAL, mailbox, MK_TIME_INFINITE as usize),
+        error(MK_ERROR_NONE)
+    );
+    assert_eq!(sim.process(MAIN).get_vpp_state(), VppState::WAITING);
+    sim.clock.advance(1000);
+    assert_eq!(sim.process(MAIN).get_vpp_state(), VppState::WAITING);
+
+    assert_eq!(sim.command(CHILD, CMD_SEND_SIGNAL, mailbox, SIGNAL_A as usize), error(MK_ERROR_NONE));
+    assert_eq!(sim.process(MAIN).get_vpp_state(), VppState::READY);
+    assert_eq!(sim.command(MAIN, CMD_GET_SIGNAL, mailbox, 0), value(0));
+}
+
+#[test]
+fn only_the_owner_waits_on_a_mailbox() {
+    let sim = sim();
+    let mailbox = sim.mailbox_handle(MAIN_MAILBOX) as usize;
+    assert_eq!(
+        sim.command(CHILD, CMD_WAIT_SIGNAL, mailbox, MK_TIME_INFINITE as usize),
+        error(MK_ERROR_ACCESS_DENIED)
+    );
+    assert_eq!(sim.process(CHILD).get_vpp_state(), VppState::READY);
+}
+
+#[test]
+fn a_wait_times_out_on_the_virtual_clock() {
+    let sim = sim();
+    let mailbox = sim.mailbox_handle(MAIN_MAILBOX) as usize;
+    assert_eq!(sim.command(MAIN, CMD_WAIT_SIGNAL, mailbox, 10), error(MK_ERROR_NONE));
+    sim.clock.advance(9);
+    assert_eq!(sim.process(MAIN).get_vpp_state(), VppState::WAITING);
+    sim.clock.advance(1);
+    assert_eq!(sim.process(MAIN).get_vpp_state(), VppState::READY);
+    assert_eq!(sim.command(MAIN, CMD_GET_TIME, 0, 0), value(10));
+    assert_eq!(sim.command(MAIN, CMD_GET_TIME, 1, 0), value(0));
+    assert_eq!(sim.command(MAIN, CMD_GET_TIME, 2, 0), ReturnCode::EINVAL);
+}
+
+#[test]
+fn activated_mailboxes_are_returned_in_round_robin() {
+    let sim = sim();
+    let main = sim.mailbox_handle(MAIN_MAILBOX) as usize;
+    let main_2 = sim.mailbox_handle(MAIN_MAILBOX_2) as usize;
+    assert_eq!(sim.command(MAIN, CMD_GET_MAILBOX_ID_ACTIVATED, 0, 0), ReturnCode::FAIL);
+
+    assert_eq!(sim.command(OTHER, CMD_SEND_SIGNAL, main_2, SIGNAL_A as usize), error(MK_ERROR_NONE));
+    assert_eq!(sim.command(MAIN, CMD_GET_MAILBOX_ID_ACTIVATED, 0, 0), value(MAIN_MAILBOX_2 as usize));
+    assert_eq!(sim.command(MAIN, CMD_GET_MAILBOX_ID_ACTIVATED, 0, 0), ReturnCode::FAIL);
+
+    // Both Mailboxes are activated: the round robin resumes after MAIN_2.
+    assert_eq!(sim.command(OTHER, CMD_SEND_SIGNAL, main_2, SIGNAL_B as usize), error(MK_ERROR_NONE));
+    assert_eq!(sim.command(CHILD, CMD_SEND_SIGNAL, main, SIGNAL_A as usize), error(MK_ERROR_NONE));
+    assert_eq!(sim.command(MAIN, CMD_GET_MAILBOX_ID_ACTIVATED, 0, 0), value(MAIN_MAILBOX as usize));
+    assert_eq!(sim.command(OTHER, CMD_SEND_SIGNAL, main_2, SIGNAL_B as usize), error(MK_ERROR_NONE));
+    assert_eq!(sim.command(CHILD, CMD_SEND_SIGNAL, main, SIGNAL_A as usize), error(MK_ERROR_NONE));
+    assert_eq!(sim.command(MAIN, CMD_GET_MAILBOX_ID_ACTIVATED, 0, 0), value(MAIN_MAILBOX_2 as usize));
+    assert_eq!(sim.command(MAIN, CMD_GET_MAILBOX_ID_ACTIVATED, 0, 0), value(MAIN_MAILBOX as usize));
+
+    // The Signals stay pending until read, which also deactivates the Mailbox.
+    assert_eq!(sim.command(MAIN, CMD_GET_SIGNAL, main_2, 0), value((SIGNAL_A | SIGNAL_B) as usize));
+    assert_eq!(sim.command(CHILD, CMD_SEND_SIGNAL, main, SIGNAL_B as usize), error(MK_ERROR_NONE));
+    assert_eq!(sim.command(MAIN, CMD_GET_SIGNAL, main, 0), value((SIGNAL_A | SIGNAL_B) as usize));
+    assert_eq!(sim.command(MAIN, CMD_GET_MAILBOX_ID_ACTIVATED, 0, 0), ReturnCode::FAIL);
+    // The Mailboxes of other Processes are not reported.
+    assert_eq!(sim.command(CHILD, CMD_GET_MAILBOX_ID_ACTIVATED, 0, 0), ReturnCode::FAIL);
+}
+
+#[test]
+fn a_process_waits_on_all_its_mailboxes() {
+    let sim = sim();
+    let main_2 = sim.mailbox_handle(MAIN_MAILBOX_2) as usize;
+    let other = sim.mailbox_handle(OTHER_MAILBOX) as usize;
+    assert_eq!(
+        sim.command(MAIN, CMD_WAIT_SIGNAL, MK_HANDLE_ANY_MAILBOX as usize, MK_TIME_INFINITE as usize),
+        error(MK_ERROR_NONE)
+    );
+    assert_eq!(sim.process(MAIN).get_vpp_state(), VppState::WAITING);
+    // A Signal to a Mailbox of another Process does not end the wait.
+    assert_eq!(sim.command(MAIN, CMD_SEND_SIGNAL, other, SIGNAL_A as usize), error(MK_ERROR_NONE));
+    assert_eq!(sim.process(MAIN).get_vpp_state(), VppState::WAITING);
+
+    assert_eq!(sim.command(OTHER, CMD_SEND_SIGNAL, main_2, SIGNAL_A as usize), error(MK_ERROR_NONE));
+    assert_eq!(sim.process(MAIN).get_vpp_state(), VppState::READY);
+    assert_eq!(sim.command(MAIN, CMD_GET_MAILBOX_ID_ACTIVATED, 0, 0), value(MAIN_MAILBOX_2 as usize));
+    assert_eq!(sim.command(MAIN, CMD_GET_SIGNAL, main_2, 0), value(SIGNAL_A as usize));
+
+    // An activated Mailbox ends the wait right away.
+    assert_eq!(sim.command(OTHER, CMD_SEND_SIGNAL, main_2, SIGNAL_B as usize), error(MK_ERROR_NONE));
+    assert_eq!(
+        sim.command(MAIN, CMD_WAIT_SIGNAL, MK_HANDLE_ANY_MAILBOX as usize, MK_TIME_INFINITE as usize),
+        error(MK_ERROR_NONE)
+    );
+    assert_eq!(sim.process(MAIN).get_vpp_state(), VppState::READY);
+}
+
+#[test]
+fn a_wait_on_all_mailboxes_times_out_on_the_kernel_mailbox() {
+    let sim = sim();
+    let kernel_mailbox = sim.mailbox_handle(MAIN_MAILBOX) as usize;
+    assert_eq!(
+        sim.command(MAIN, CMD_WAIT_SIGNAL, MK_HANDLE_ANY_MAILBOX as usize, 5),
+        error(MK_ERROR_NONE)
+    );
+    sim.clock.advance(5);
+    assert_eq!(sim.process(MAIN).get_vpp_state(), VppState::READY);
+    assert_eq!(sim.command(MAIN, CMD_GET_MAILBOX_ID_ACTIVATED, 0, 0), value(MAIN_MAILBOX as usize));
+    assert_eq!(
+        sim.command(MAIN, CMD_GET_SIGNAL, kernel_mailbox, 0),
+        value(MK_BITMAP_t::from(MK_SIGNAL_e::MK_SIGNAL_TIME_OUT) as usize)
+    );
+}
+
+#[test]
+fn a_dead_process_keeps_its_error_and_notifies_its_parent() {
+    let sim = sim();
+    let stale = sim.process_handle(CHILD);
+    sim.process(CHILD).error.set(MK_ERROR_INTERNAL);
+    assert_eq!(sim.vpp_kernel.should_restart(sim.processes[CHILD]), false);
+    assert_eq!(sim.process(CHILD).get_vpp_state(), VppState::DEAD);
+
+    assert_eq!(sim.vpp_kernel._mk_Get_Error(stale), MK_ERROR_UNKNOWN_HANDLE);
+    assert_eq!(sim.vpp_kernel._mk_Get_Exception(stale, sim.appid(MAIN)), Err(MK_ERROR_UNKNOWN_HANDLE));
+    let child = sim.process_handle(CHILD);
+    assert_eq!(sim.command(MAIN, CMD_GET_ERROR, child as usize, 0), error(MK_ERROR_INTERNAL));
+    assert_eq!(sim.command(MAIN, CMD_SUSPEND_PROCESS, child as usize, 0), error(MK_ERROR_UNKNOWN_HANDLE));
+    assert_eq!(
+        sim.command(MAIN, CMD_GET_EXCEPTION, child as usize, 0),
+        value(1 << u16::from(MK_EXCEPTION_e::MK_EXCEPTION_SEVERE))
+    );
+
+    let main = sim.process_handle(MAIN) as usize;
+    assert_eq!(
+        sim.command(MAIN, CMD_GET_EXCEPTION, main, 0),
+        value(1 << u16::from(MK_EXCEPTION_e::MK_EXCEPTION_CHILD_PROCESS_DIED))
+    );
+    assert_eq!(
+        sim.command(MAIN, CMD_GET_SIGNAL, sim.mailbox_handle(MAIN_MAILBOX) as usize, 0),
+        value(MK_BITMAP_t::from(MK_SIGNAL_e::MK_SIGNAL_EXCEPTION) as usize)
+    );
+}
+
+#[test]
+fn an_unloaded_process_is_forgotten() {
+    let sim = sim();
+    let appid = sim.processes[CHILD].appid();
+    let stale = sim.process_handle(CHILD);
+    let mailbox = sim.mailbox_handle(CHILD_MAILBOX);
+    sim.vpp_kernel.process_event(appid, ProcessEvent::Unloaded);
+
+    assert!(sim.process(CHILD).tockprocess.get().is_none());
+    assert_eq!(sim.process(CHILD).get_vpp_state(), VppState::DEAD);
+    assert!(sim.vpp_kernel.get_process_ref_appid(appid).is_none());
+    assert_eq!(sim.vpp_kernel._mk_Get_Error(stale), MK_ERROR_UNKNOWN_HANDLE);
+    assert_eq!(sim.vpp_kernel._mk_yield(stale), MK_ERROR_UNKNOWN_HANDLE);
+    assert_eq!(sim.vpp_kernel._mk_Send_Signal(mailbox, SIGNAL_A), MK_ERROR_UNKNOWN_HANDLE);
+    assert_eq!(sim.vpp_kernel._mk_suspend_process(sim.process_handle(CHILD)), MK_ERROR_UNKNOWN_HANDLE);
+}
+
+#[test]
+fn an_app_in_the_slot_of_an_unloaded_process_has_none_of_its_rights() {
+    let sim = sim();
+    sim.vpp_kernel.process_event(sim.appid(CHILD), ProcessEvent::Unloaded);
+    let app = sim.next_appid(CHILD);
+    let main_mailbox = sim.mailbox_handle(MAIN_MAILBOX) as usize;
+
+    let command = |command_num, data, data2| sim.driver.command(command_num, data, data2, app);
+    assert_eq!(command(CMD_GET_MAILBOX_HANDLE, MAIN_MAILBOX as usize, 0), value(0));
+    assert_eq!(command(CMD_SEND_SIGNAL, main_mailbox, SIGNAL_A as usize), error(MK_ERROR_ACCESS_DENIED));
+    assert_eq!(command(CMD_GET_IPC_HANDLE, MAIN_CHILD_IPC as usize, 0), value(0));
+    assert_eq!(
+        command(CMD_GET_PROCESS_PRIORITY, sim.process_handle(GRANDCHILD) as usize, 0),
+        ReturnCode::FAIL
+    );
+    assert_eq!(sim.driver.subscribe(main_mailbox, None, app), ReturnCode::EINVAL);
+    let grandchild = sim.process_handle(GRANDCHILD);
+    sim.process(GRANDCHILD).raise_exception(MK_EXCEPTION_e::MK_EXCEPTION_ERROR);
+    assert_eq!(sim.vpp_kernel._mk_Get_Exception(grandchild, app), Err(MK_ERROR_ACCESS_DENIED));
+    sim.vpp_kernel._mk_Send_Signal(sim.mailbox_handle(CHILD_MAILBOX), SIGNAL_A);
+    assert_eq!(sim.vpp_kernel._mk_Get_Mailbox_ID_Activated(app), None);
+    let ipc = sim.ipc_handle(MAIN_CHILD_IPC);
+    assert_eq!(sim.vpp_kernel._mk_Get_Access_IPC(ipc, app), None);
+    assert!(sim.vpp_kernel.resolve_ipc(ipc).unwrap().get_holder().is_none());
+    assert_eq!(
+        sim.vpp_kernel._mk_Wait_Signal(MK_HANDLE_ANY_MAILBOX, 0, app),
+        MK_ERROR_ACCESS_DENIED
+    );
+    assert_eq!(sim.vpp_kernel._mk_Get_Signal(main_mailbox as MK_HANDLE_t), Some(0));
+}
+
+#[test]
+fn errors_are_read_by_the_process_and_its_ancestors() {
+    let sim = sim();
+    sim.process(GRANDCHILD).error.set(MK_ERROR_TIME_OUT);
+    let grandchild = sim.process_handle(GRANDCHILD) as usize;
+    assert_eq!(sim.command(GRANDCHILD, CMD_GET_ERROR, grandchild, 0), error(MK_ERROR_TIME_OUT));
+    assert_eq!(sim.command(MAIN, CMD_GET_ERROR, grandchild, 0), error(MK_ERROR_TIME_OUT));
+    assert_eq!(sim.command(OTHER, CMD_GET_ERROR, grandchild, 0), error(MK_ERROR_ACCESS_DENIED));
+    assert_eq!(
+        sim.command(OTHER, CMD_GET_ERROR, sim.mailbox_handle(OTHER_MAILBOX) as usize, 0),
+        error(MK_ERROR_GET_ERROR_HANDLE_NOT_A_PROCESS)
+    );
+}
+
+#[test]
+fn priorities_are_validated() {
+    let sim = sim();
+    let child = sim.process_handle(CHILD) as usize;
+    assert_eq!(sim.command(MAIN, CMD_GET_PROCESS_PRIORITY, child, 0), value(0x4));
+    assert_eq!(sim.command(MAIN, CMD_SET_PROCESS_PRIORITY, child, 0x5), error(MK_ERROR_UNKNOWN_PRIORITY));
+    assert_eq!(sim.command(MAIN, CMD_SET_PROCESS_PRIORITY, child, 0x8), error(MK_ERROR_NONE));
+    assert_eq!(sim.command(CHILD, CMD_GET_PROCESS_PRIORITY, child, 0), value(0x8));
+    assert_eq!(sim.command(OTHER, CMD_SET_PROCESS_PRIORITY, child, 0x0), error(MK_ERROR_ACCESS_DENIED));
+    assert_eq!(sim.command(OTHER, CMD_GET_PROCESS_PRIORITY, child, 0), ReturnCode::FAIL);
+}
+
+#[test]
+fn ipcs_are_limited_to_their_writer_and_reader() {
+    let sim = sim();
+    let handle = sim.ipc_handle(MAIN_CHILD_IPC) as usize;
+    assert_eq!(sim.command(CHILD, CMD_GET_IPC_HANDLE, MAIN_CHILD_IPC as usize, 0), value(handle));
+    assert_eq!(sim.command(OTHER, CMD_GET_IPC_HANDLE, MAIN_CHILD_IPC as usize, 0), value(0));
+    assert_eq!(sim.process(OTHER).get_last_generated_error(), MK_ERROR_ACCESS_DENIED);
+    assert_eq!(sim.command(MAIN, CMD_GET_IPC_SIZE, handle, 0), value(MAIN_CHILD_IPC_LENGTH as usize));
+    assert_eq!(sim.command(GRANDCHILD, CMD_GET_IPC_SIZE, handle, 0), ReturnCode::FAIL);
+
+    assert_eq!(sim.command(OTHER, CMD_GET_ACCESS_IPC, handle, 0), ReturnCode::FAIL);
+    assert_eq!(sim.process(OTHER).get_last_generated_error(), MK_ERROR_ACCESS_DENIED);
+    assert_eq!(sim.command(CHILD, CMD_RELEASE_ACCESS_IPC, handle, 0), error(MK_ERROR_HANDLE_NOT_ACCESSED));
+    // The writer did not share its memory yet: the reader gets no MPU region.
+    assert_eq!(sim.command(CHILD, CMD_GET_ACCESS_IPC, handle, 0), ReturnCode::FAIL);
+    assert_eq!(sim.process(CHILD).get_last_generated_error(), MK_ERROR_INTERNAL);
+    assert_eq!(sim.processes[CHILD].regions(), 0);
+}
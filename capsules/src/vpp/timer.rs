@@ -1,39 +1,260 @@
+//! MLOI time service of the VPP: `_mk_Get_Time` and the VPP timers.
+//!
+//! The time is a monotonic 64-bit count of ticks since the Primary Platform start up,
+//! extending the 32-bit ticks (`Ticks32`, or the low 32 bits of wider ticks) of the
+//! underlying alarm across their wraparound. The alarm is always armed at most half
+//! a wrap ahead, so every wrap is observed.
+//!
+//! A VPP Process can arm one-shot and periodic timers on the Mailboxes it owns, one
+//! timer per Mailbox. When a timer expires, `MK_SIGNAL_TIME_OUT` is sent to its
+//! Mailbox, which also resumes a Process blocked in `_mk_Wait_Signal` on it.
+//!
+//! Commands of the timer driver:
+//!
+//! * 0: driver check.
+//! * 1: get the time, returns its low 32 bits and latches its high 32 bits.
+//! * 2: get the latched high 32 bits of the last time read with command 1.
+//! * 3: get the frequency of the ticks, in Hz.
+//! * 4: arm a one-shot timer, `data` is the Mailbox handle and `data2` the delay in ms.
+//! * 5: arm a periodic timer, `data` is the Mailbox handle and `data2` the period in ms.
+//! * 6: cancel the timer of the Mailbox handle `data`.
+//!
+//! Commands 4 to 6 return the `MK_ERROR_e` of the request as value.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//!     let vpp_timer_alarm = static_init!(
+//!         VirtualMuxAlarm<'static, earlgrey::timer::RvTimer>,
+//!         VirtualMuxAlarm::new(mux_alarm)
+//!     );
+//!     let vpp_timer = static_init!(
+//!         capsules::vpp::timer::Timer<'static, VirtualMuxAlarm<'static, RvTimer>>,
+//!         capsules::vpp::timer::Timer::new(
+//!             vpp_kernel,
+//!             vpp_timer_alarm,
+//!             board_kernel.create_grant(&memory_allocation_cap),
+//!         )
+//!     );
+//!     hil::time::Alarm::set_alarm_client(vpp_timer_alarm, vpp_timer);
+//!     vpp_kernel.set_clock(vpp_timer);
+//!     vpp_timer.start();
+//! ```
+
+use crate::driver;
+use crate::vpp::mloi::MK_ERROR_e::*;
+use crate::vpp::mloi::{MK_ERROR_e, MK_HANDLE_t, MK_SIGNAL_e, MK_TIME_t};
+use crate::vpp::vppkernel::VppKernel;
 use core::cell::Cell;
-use kernel::hil::time::{self, Alarm, Frequency, Ticks, Ticks32};
-use kernel::{AppId, Callback, Driver, Grant, ReturnCode};
-use kernel::debug;
+use kernel::hil::time::{self, Alarm, Frequency, Ticks};
+use kernel::{AppId, Driver, Grant, ReturnCode};
+
+pub const DRIVER_NUM: usize = driver::NUM::VppTimerDriver as usize;
+
+/// Number of timers that can be armed at the same time.
+pub const VPP_TIMER_SLOTS: usize = 8;
 
+/// Longest interval, in ticks, the alarm is armed for. Half a wrap of the 32-bit ticks.
+const MAX_ALARM_DT: u64 = 1 << 31;
+
+/// Monotonic time of the Primary Platform, as used by `_mk_Get_Time`.
+pub trait MonotonicTime {
+    /// Ticks since the Primary Platform start up.
+    fn get_time(&self) -> MK_TIME_t;
+}
 
-#[derive(Copy, Clone)]
+#[derive(Default)]
 pub struct TimerData {
-    callback: Option<Callback>
+    time_high: u32,
 }
-impl Default for TimerData {
-    fn default() -> TimerData {
-        TimerData {
-            callback: None,
-        }
-    }
+
+#[derive(Clone, Copy)]
+struct VppTimer {
+    appid: AppId,
+    mailbox: MK_HANDLE_t,
+    deadline: MK_TIME_t,
+    period: Option<MK_TIME_t>,
 }
+
 pub struct Timer<'a, A: Alarm<'a>> {
+    vpp_kernel: &'static VppKernel,
     alarm: &'a A,
-    app_alarms: Grant<TimerData>,
+    /// Number of wraps of the 32-bit ticks.
+    wraps: Cell<u32>,
+    /// Last 32-bit ticks observed.
+    last: Cell<u32>,
+    timers: [Cell<Option<VppTimer>>; VPP_TIMER_SLOTS],
+    apps: Grant<TimerData>,
 }
 
 impl<'a, A: Alarm<'a>> Timer<'a, A> {
-    pub const fn new(alarm: &'a A, grant: Grant<TimerData>) -> Timer<'a, A> {
+    pub fn new(
+        vpp_kernel: &'static VppKernel,
+        alarm: &'a A,
+        grant: Grant<TimerData>,
+    ) -> Timer<'a, A> {
         Timer {
+            vpp_kernel: vpp_kernel,
             alarm: alarm,
-            app_alarms: grant,
+            wraps: Cell::new(0),
+            last: Cell::new(0),
+            timers: Default::default(),
+            apps: grant,
+        }
+    }
+
+    /// Starts observing the wraps of the ticks. Must be called once at boot.
+    pub fn start(&self) {
+        self.last.set(self.alarm.now().into_u32());
+        self.rearm();
+    }
+
+    fn ticks_from_ms(ms: u32) -> MK_TIME_t {
+        ms as u64 * <A::Frequency as Frequency>::frequency() as u64 / 1000
+    }
+
+    /// Arms a timer sending `MK_SIGNAL_TIME_OUT` to `mailbox` after `ms`, then every
+    /// `ms` if `periodic`. A timer already armed on that Mailbox is replaced.
+    pub fn arm(&self, appid: AppId, mailbox: MK_HANDLE_t, ms: u32, periodic: bool) -> MK_ERROR_e {
+        let mbox = match self.vpp_kernel.resolve_mailbox(mailbox) {
+            Ok(mbox) => mbox,
+            Err(error) => return error,
+        };
+        if mbox.get_owner_proc_appid() != Some(appid) {
+            return MK_ERROR_ACCESS_DENIED;
+        }
+        let interval = Self::ticks_from_ms(ms);
+        if periodic && interval == 0 {
+            return MK_ERROR_ILLEGAL_PARAMETER;
+        }
+        let slot = self
+            .timers
+            .iter()
+            .find(|timer| timer.get().map_or(false, |timer| timer.mailbox == mailbox))
+            .or_else(|| self.timers.iter().find(|timer| timer.get().is_none()));
+        match slot {
+            Some(slot) => {
+                slot.set(Some(VppTimer {
+                    appid: appid,
+                    mailbox: mailbox,
+                    deadline: self.get_time() + interval,
+                    period: if periodic { Some(interval) } else { None },
+                }));
+                self.rearm();
+                MK_ERROR_NONE
+            }
+            None => MK_ERROR_INTERNAL,
+        }
+    }
+
+    /// Cancels the timer armed by `appid` on `mailbox`.
+    pub fn cancel(&self, appid: AppId, mailbox: MK_HANDLE_t) -> MK_ERROR_e {
+        let slot = self.timers.iter().find(|timer| {
+            timer
+                .get()
+                .map_or(false, |timer| timer.mailbox == mailbox && timer.appid == appid)
+        });
+        match slot {
+            Some(slot) => {
+                slot.set(None);
+                self.rearm();
+                MK_ERROR_NONE
+            }
+            None => MK_ERROR_UNKNOWN_HANDLE,
         }
     }
+
+    /// Arms the alarm for the closest deadline, or half a wrap ahead.
+    fn rearm(&self) {
+        let now = self.get_time();
+        let dt = self
+            .timers
+            .iter()
+            .filter_map(|timer| timer.get())
+            .map(|timer| timer.deadline.saturating_sub(now))
+            .fold(MAX_ALARM_DT, core::cmp::min);
+        self.alarm
+            .set_alarm(self.alarm.now(), A::Ticks::from(dt as u32));
+    }
+}
+
+impl<'a, A: Alarm<'a>> MonotonicTime for Timer<'a, A> {
+    fn get_time(&self) -> MK_TIME_t {
+        let now = self.alarm.now().into_u32();
+        if now < self.last.get() {
+            self.wraps.set(self.wraps.get().wrapping_add(1));
+        }
+        self.last.set(now);
+        (self.wraps.get() as u64) << 32 | now as u64
+    }
 }
 
 impl<'a, A: Alarm<'a>> time::AlarmClient for Timer<'a, A> {
     fn alarm(&self) {
-        debug!("Got them");
-        self.app_alarms.each(|alarm| {
-            alarm.callback.map(|mut cb| {cb.schedule(0,0,0)});
-        });
+        let now = self.get_time();
+        for slot in self.timers.iter() {
+            let timer = match slot.get() {
+                Some(timer) if timer.deadline <= now => timer,
+                _ => continue,
+            };
+            // The Process owning the timer restarted or terminated.
+            if timer.appid.index().is_none() {
+                slot.set(None);
+                continue;
+            }
+            slot.set(timer.period.map(|period| VppTimer {
+                deadline: if timer.deadline + period > now {
+                    timer.deadline + period
+                } else {
+                    now + period
+                },
+                ..timer
+            }));
+            self.vpp_kernel
+                ._mk_Send_Signal(timer.mailbox, MK_SIGNAL_e::MK_SIGNAL_TIME_OUT.into());
+        }
+        self.rearm();
+    }
+}
+
+impl<'a, A: Alarm<'a>> Driver for Timer<'a, A> {
+    fn command(&self, command_num: usize, data: usize, data2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 => {
+                let time = self.get_time();
+                self.apps
+                    .enter(appid, |app, _| {
+                        app.time_high = (time >> 32) as u32;
+                        ReturnCode::SuccessWithValue {
+                            value: time as u32 as usize,
+                        }
+                    })
+                    .unwrap_or_else(|err| err.into())
+            }
+            2 => self
+                .apps
+                .enter(appid, |app, _| ReturnCode::SuccessWithValue {
+                    value: app.time_high as usize,
+                })
+                .unwrap_or_else(|err| err.into()),
+            3 => ReturnCode::SuccessWithValue {
+                value: <A::Frequency as Frequency>::frequency() as usize,
+            },
+            4 | 5 => {
+                let error = self.arm(appid, data as MK_HANDLE_t, data2 as u32, command_num == 5);
+                ReturnCode::SuccessWithValue {
+                    value: error.into(),
+                }
+            }
+            6 => {
+                let error = self.cancel(appid, data as MK_HANDLE_t);
+                ReturnCode::SuccessWithValue {
+                    value: error.into(),
+                }
+            }
+            _ => ReturnCode::ENOSUPPORT,
+        }
     }
 }
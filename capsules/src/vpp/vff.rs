@@ -6,16 +6,23 @@
 /// to Firmware Management Service.
 
 use crate::vpp::mloi::*;
+use crate::vpp::vppkernel::VppKernel;
 use core::cell::Cell;
+use core::convert::{TryFrom, TryInto};
+use core::marker::PhantomData;
+use kernel::common::cells::OptionalCell;
+use kernel::procs::State;
+use kernel::AppId;
 
 const MK_FORMAT_VERSION : u8 = 0b0100 ;
 
 pub struct FW {
-    pub FwHeader : FwHeader,
+    pub FwHeader : FwHeader<'static>,
     pub FwBody : FwBody,
     pub state: Cell<FwState>,
 }
 /// Firmware State.
+#[derive(Clone, Copy, PartialEq)]
 pub enum FwState {
     Enabled,
     Disabled,
@@ -28,14 +35,81 @@ impl From<FwState> for u8 {
         }
     }
 }
-pub struct FwHeader {
+impl From<u8> for FwState {
+    fn from(original: u8) -> FwState {
+        match original {
+            0x01 => FwState::Enabled,
+            _ => FwState::Disabled,
+        }
+    }
+}
+pub struct FwHeader<'a> {
     pub fw_descriptor: FwDescriptor,
-    pub process_descriptors: ProcessDescriptors,
-    pub mailbox_descriptors: MailboxDescriptors,
-    pub ipc_descriptor: IpcDescriptor,
+    pub process_descriptors: DescriptorTable<'a, ProcessDescriptors>,
+    pub mailbox_descriptors: DescriptorTable<'a, MailboxDescriptors>,
+    pub ipc_descriptors: DescriptorTable<'a, IpcDescriptor>,
+    pub lib_descriptors: DescriptorTable<'a, LibDescriptor>,
+    /// Only present when the Firmware is of type FIRMWARE_SOFTWARE_TYPE_LLOS.
+    pub llos_descriptor: Option<LlosDescriptor>,
 }
 
-impl FwHeader{}
+impl FwHeader<'_>{
+    /// Returns the Process Descriptor of the Main Process, which is always the first
+    /// entry of the Process array.
+    pub fn main_process_descriptor(&self) -> Option<ProcessDescriptors> {
+        self.process_descriptors.get(0)
+    }
+
+    /// Window `(offset, length)` of the Memory Partition the given target may
+    /// access. A Process owns its CODE, CONSTANTS, DATA, NVM and stack, laid out in
+    /// Process Descriptor order. A Library is linked into the Processes using it
+    /// and an LLOS owns the whole partition, so both span the partition.
+    pub fn impersonation_window(&self, target: ImpersonationTarget) -> Option<(usize, usize)> {
+        match target {
+            ImpersonationTarget::Process(index) => {
+                if self.llos_descriptor.is_some() || index as usize >= self.process_descriptors.len() {
+                    return None;
+                }
+                let mut offset = 0usize;
+                for process in self.process_descriptors.iter().take(index as usize) {
+                    offset += Self::process_size(&process) as usize;
+                }
+                let process = self.process_descriptors.get(index as usize)?;
+                Some((offset, Self::process_size(&process) as usize))
+            }
+            ImpersonationTarget::Library(index) => {
+                if index as usize >= self.lib_descriptors.len() {
+                    return None;
+                }
+                Some((0, self.memory_partition_size() as usize))
+            }
+            ImpersonationTarget::Llos => self
+                .llos_descriptor
+                .map(|_| (0, self.memory_partition_size() as usize)),
+        }
+    }
+
+    fn process_size(process: &ProcessDescriptors) -> u32 {
+        process.m_uLength_Process_CODE
+            .saturating_add(process.m_uLength_Process_CONSTANTS)
+            .saturating_add(process.m_uLength_Process_DATA)
+            .saturating_add(process.m_uLength_Process_NVM)
+            .saturating_add(process.m_uSizeStack as u32)
+    }
+
+    /// Size in bytes of the Memory Partition needed by the Firmware: the CODE,
+    /// CONSTANTS, DATA and NVM segments plus the stack of every Process.
+    pub fn memory_partition_size(&self) -> u32 {
+        if let Some(llos) = self.llos_descriptor {
+            return llos.m_uLength_LLOS_CODE;
+        }
+        self.process_descriptors
+            .iter()
+            .fold(0u32, |size, process| size.saturating_add(Self::process_size(&process)))
+    }
+}
+
+#[derive(Clone, Copy)]
 pub struct FwDescriptor {
     /// Firmware Identifier
     pub m_xID: UUID_t,
@@ -63,6 +137,7 @@ pub struct FwDescriptor {
 /// Definition of the Structure of the IPC Descriptor. The IPC array shall be sorted
 /// in ascending order of the IPC identifiers (m_xID). The IPC Descriptor alignment is
 /// 16 bits. The total size of the IPC Descriptor is 8 bytes == 64 bits.
+#[derive(Clone, Copy)]
 pub struct IpcDescriptor {
     /// Identifiers of the IPC.
     pub m_xID : MK_IPC_ID_u,
@@ -77,6 +152,7 @@ pub struct IpcDescriptor {
 /// be sorted in ascending order of mailbox identifiers (m_xID). The Mailbox Descriptor
 /// alignment is 16 bits and there is no padding for the fields smaller than 16 bits.
 /// The total size of the Mailbox Descriptor is 6 bytes == 48 bits.
+#[derive(Clone, Copy)]
 pub struct MailboxDescriptors {
     /// Identifiers of the mailbox
     pub m_xID : MK_MAILBOX_ID_u,
@@ -91,6 +167,7 @@ pub struct MailboxDescriptors {
 /// The Process array shall be sorted in ascending order of Process identifiers (m_xID)
 /// The first entry shall be considered as _Main Process_. The Process Descriptor alignment
 /// is 32 bit. The total size of the Process Descriptor is 32 bytes == 256 bits.
+#[derive(Clone, Copy)]
 pub struct ProcessDescriptors {
     /// Offset within the CODE segment to compute a pointer to
     /// the Process Entry point function
@@ -103,18 +180,222 @@ pub struct ProcessDescriptors {
     pub m_uLength_Process_DATA: v32_u,
     /// Length of the whole segment for NVM
     pub m_uLength_Process_NVM : v32_u,
-    // /// ORing of Mandatory Access Control for VRE
-    // m_eVRE: MK_VRE_e,
+    /// ORing of Mandatory Access Control for VRE
+    pub m_eVRE: MK_BITMAP_t,
     /// Process Identifier
     pub m_xID : MK_Process_ID_u,
-    // /// Identifier of the mailbox receiving signals from the kernel
-    // m_xKernel_Mailbox : MK_MAILBOX_ID_u,
+    /// Identifier of the mailbox receiving signals from the kernel
+    pub m_xKernel_Mailbox : MK_MAILBOX_ID_u,
     ///Index of the parent Process in the group of processes (self-reference for
     /// MK_PROCESS_MAIN_APP_ID Process)
     pub m_uParent_Process : MK_Index_t,
     /// Size of the Stack (in bytes and multiple of 4)
     pub m_uSizeStack : u16,
 }
+
+/// Definition of the structure of the LIB Descriptor. The LIB array shall be sorted in
+/// ascending order of the library identifiers (m_xID). The LIB Descriptor alignment is
+/// 16 bits. The total size of the LIB Descriptor is 8 bytes == 64 bits.
+#[derive(Clone, Copy)]
+pub struct LibDescriptor {
+    /// Identifier of the shared library
+    pub m_xID: MK_LIB_ID_u,
+    /// Major and minor version of the shared library required by the Firmware
+    pub m_uVersion: u16,
+    /// Bitmap of the Processes (by index in the group of processes) importing the library
+    pub m_bProcesses: MK_BITMAP_t,
+}
+
+/// Definition of the structure of the LLOS Descriptor. It replaces the Process, Mailbox,
+/// IPC and LIB Descriptors in a Firmware of type FIRMWARE_SOFTWARE_TYPE_LLOS.
+/// The total size of the LLOS Descriptor is 8 bytes == 64 bits.
+#[derive(Clone, Copy)]
+pub struct LlosDescriptor {
+    /// Offset within the CODE segment to compute a pointer to the LLOS entry point
+    pub m_pvLLOSCode: v32_u,
+    /// Length of the whole segment for CODE
+    pub m_uLength_LLOS_CODE: v32_u,
+}
+
+/// Size in bytes of each descriptor in the Firmware header.
+pub const FW_DESCRIPTOR_SIZE: usize = 44;
+pub const PROCESS_DESCRIPTOR_SIZE: usize = 32;
+pub const MAILBOX_DESCRIPTOR_SIZE: usize = 6;
+pub const IPC_DESCRIPTOR_SIZE: usize = 8;
+pub const LIB_DESCRIPTOR_SIZE: usize = 8;
+pub const LLOS_DESCRIPTOR_SIZE: usize = 8;
+/// Largest Firmware header allowed by the limits of Table 7-9.
+pub const FW_MAX_HEADER_LENGTH: usize = FW_DESCRIPTOR_SIZE
+    + MK_PROCESS_LIMIT as usize * PROCESS_DESCRIPTOR_SIZE
+    + MK_MAILBOX_LIMIT * MAILBOX_DESCRIPTOR_SIZE
+    + MK_IPC_LIMIT * IPC_DESCRIPTOR_SIZE
+    + MK_LIB_LIMIT as usize * LIB_DESCRIPTOR_SIZE;
+
+/// Every descriptor found in the arrays following the Firmware Descriptor
+/// has a fixed size and is decoded from the raw header bytes.
+pub trait Descriptor: Copy + for<'a> TryFrom<&'a [u8], Error = MGT_ERROR_e> {
+    const SIZE: usize;
+}
+impl Descriptor for ProcessDescriptors { const SIZE: usize = PROCESS_DESCRIPTOR_SIZE; }
+impl Descriptor for MailboxDescriptors { const SIZE: usize = MAILBOX_DESCRIPTOR_SIZE; }
+impl Descriptor for IpcDescriptor { const SIZE: usize = IPC_DESCRIPTOR_SIZE; }
+impl Descriptor for LibDescriptor { const SIZE: usize = LIB_DESCRIPTOR_SIZE; }
+
+/// A validated array of descriptors. The descriptors are kept in the Firmware
+/// header (flash) and decoded on access, which avoids copying up to
+/// MK_PROCESS_LIMIT + MK_MAILBOX_LIMIT + MK_IPC_LIMIT descriptors on the kernel stack.
+#[derive(Clone, Copy)]
+pub struct DescriptorTable<'a, T: Descriptor> {
+    raw: &'a [u8],
+    count: usize,
+    _descriptor: PhantomData<T>,
+}
+
+impl<'a, T: Descriptor> DescriptorTable<'a, T> {
+    fn new(raw: &'a [u8], count: usize) -> DescriptorTable<'a, T> {
+        DescriptorTable {
+            raw,
+            count,
+            _descriptor: PhantomData,
+        }
+    }
+    /// Number of descriptors in the array.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+    /// Decode the descriptor at `index`. Returns `None` if `index` is out of bounds.
+    pub fn get(&self, index: usize) -> Option<T> {
+        if index >= self.count {
+            return None;
+        }
+        self.raw
+            .get(index * T::SIZE..(index + 1) * T::SIZE)
+            .and_then(|bytes| T::try_from(bytes).ok())
+    }
+    pub fn iter(&self) -> impl Iterator<Item = T> + '_ {
+        (0..self.count).filter_map(move |i| self.get(i))
+    }
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Result<u16, MGT_ERROR_e> {
+    bytes
+        .get(offset..offset + 2)
+        .and_then(|b| b.try_into().ok())
+        .map(u16::from_le_bytes)
+        .ok_or(MGT_ERROR_e::MGT_ERROR_ILLEGAL_PARAMETER)
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Result<u32, MGT_ERROR_e> {
+    bytes
+        .get(offset..offset + 4)
+        .and_then(|b| b.try_into().ok())
+        .map(u32::from_le_bytes)
+        .ok_or(MGT_ERROR_e::MGT_ERROR_ILLEGAL_PARAMETER)
+}
+
+/// UUIDs are stored in the canonical (big endian) byte order of [UUID].
+fn read_uuid(bytes: &[u8], offset: usize) -> Result<UUID_t, MGT_ERROR_e> {
+    bytes
+        .get(offset..offset + 16)
+        .and_then(|b| b.try_into().ok())
+        .map(u128::from_be_bytes)
+        .ok_or(MGT_ERROR_e::MGT_ERROR_ILLEGAL_PARAMETER)
+}
+
+impl TryFrom<&[u8]> for FwDescriptor {
+    type Error = MGT_ERROR_e;
+
+    fn try_from(b: &[u8]) -> Result<FwDescriptor, MGT_ERROR_e> {
+        let sw_type = *b.get(42).ok_or(MGT_ERROR_e::MGT_ERROR_ILLEGAL_PARAMETER)?;
+        let scheduling = *b.get(43).ok_or(MGT_ERROR_e::MGT_ERROR_ILLEGAL_PARAMETER)?;
+        Ok(FwDescriptor {
+            m_xID: read_uuid(b, 0)?,
+            m_xFamilyID: read_uuid(b, 16)?,
+            m_uHeaderLength: read_u16(b, 32)?,
+            m_VersionFormat: read_u16(b, 34)?,
+            m_VersionFirmware: read_u16(b, 36)?,
+            m_uProcessCount: b[38],
+            m_uMailboxesCount: b[39],
+            m_uIPCCount: b[40],
+            m_uLIBCount: b[41],
+            m_eFirmare_Software_Type: VPP_FRW_TYPE_e::try_from(sw_type)
+                .or(Err(MGT_ERROR_e::MGT_ERROR_ILLEGAL_PARAMETER))?,
+            m_eSchedluingType: VPP_SCHEDULING_TYPE_e::try_from(scheduling)
+                .or(Err(MGT_ERROR_e::MGT_ERROR_ILLEGAL_PARAMETER))?,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for ProcessDescriptors {
+    type Error = MGT_ERROR_e;
+
+    fn try_from(b: &[u8]) -> Result<ProcessDescriptors, MGT_ERROR_e> {
+        Ok(ProcessDescriptors {
+            m_pvProcessCode: read_u32(b, 0)?,
+            m_uLength_Process_CODE: read_u32(b, 4)?,
+            m_uLength_Process_CONSTANTS: read_u32(b, 8)?,
+            m_uLength_Process_DATA: read_u32(b, 12)?,
+            m_uLength_Process_NVM: read_u32(b, 16)?,
+            m_eVRE: read_u32(b, 20)?,
+            m_xID: read_u16(b, 24)?,
+            m_xKernel_Mailbox: read_u16(b, 26)?,
+            m_uParent_Process: read_u16(b, 28)?,
+            m_uSizeStack: read_u16(b, 30)?,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for MailboxDescriptors {
+    type Error = MGT_ERROR_e;
+
+    fn try_from(b: &[u8]) -> Result<MailboxDescriptors, MGT_ERROR_e> {
+        Ok(MailboxDescriptors {
+            m_xID: read_u16(b, 0)?,
+            m_uIX_Owner: read_u16(b, 2)?,
+            m_uIX_Sender: read_u16(b, 4)?,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for IpcDescriptor {
+    type Error = MGT_ERROR_e;
+
+    fn try_from(b: &[u8]) -> Result<IpcDescriptor, MGT_ERROR_e> {
+        Ok(IpcDescriptor {
+            m_xID: read_u16(b, 0)?,
+            m_uLength_IPC: read_u16(b, 2)?,
+            m_uIX_Writer: read_u16(b, 4)?,
+            m_uIX_Reader: read_u16(b, 6)?,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for LibDescriptor {
+    type Error = MGT_ERROR_e;
+
+    fn try_from(b: &[u8]) -> Result<LibDescriptor, MGT_ERROR_e> {
+        Ok(LibDescriptor {
+            m_xID: read_u16(b, 0)?,
+            m_uVersion: read_u16(b, 2)?,
+            m_bProcesses: read_u32(b, 4)?,
+        })
+    }
+}
+
+impl TryFrom<&[u8]> for LlosDescriptor {
+    type Error = MGT_ERROR_e;
+
+    fn try_from(b: &[u8]) -> Result<LlosDescriptor, MGT_ERROR_e> {
+        Ok(LlosDescriptor {
+            m_pvLLOSCode: read_u32(b, 0)?,
+            m_uLength_LLOS_CODE: read_u32(b, 4)?,
+        })
+    }
+}
+
 pub struct FwBody{
     /// binary executable code
     CODE:  &'static [u8],
@@ -136,13 +417,41 @@ impl Default for FwBody {
     }
 }
 
+/// Code a Firmware is impersonated through.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ImpersonationTarget {
+    /// Process at the given index of the Process Descriptor array.
+    Process(MK_Index_t),
+    /// Library at the given index of the LIB Descriptor array.
+    Library(MK_Index_t),
+    /// Low Level OS of a LLOS Firmware.
+    Llos,
+}
+
+/// An open impersonation: the MGT Process `owner` may read and write `length`
+/// bytes of the Memory Partition of `firmware`, starting at `offset`.
+#[derive(Clone, Copy)]
+pub struct ImpersonationSession {
+    pub owner: AppId,
+    pub firmware: UUID_t,
+    pub target: ImpersonationTarget,
+    pub offset: usize,
+    pub length: usize,
+}
+
+/// Kernel object tracking the impersonation session. At most one session is open
+/// at a time. A session is bound to the `AppId` of the MGT Process which opened
+/// it: a restart of that Process invalidates its `AppId`, and a fault stops it,
+/// both of which revoke the session.
 pub struct _mk_Fw_Mgt{
-    Fw: FW,
+    vpp_kernel: &'static VppKernel,
+    session: OptionalCell<ImpersonationSession>,
 }
 impl _mk_Fw_Mgt {
-    pub fn new (fw: FW) -> _mk_Fw_Mgt {
+    pub fn new (vpp_kernel: &'static VppKernel) -> _mk_Fw_Mgt {
         _mk_Fw_Mgt{
-            Fw: fw,
+            vpp_kernel: vpp_kernel,
+            session: OptionalCell::empty(),
         }
     }
     /// Inform the Kernel that the impersonation of a Firmware started.
@@ -150,19 +459,60 @@ impl _mk_Fw_Mgt {
     ///
     /// _mk_Open_Impersonation allows reading and writing in the Memory Partition
     /// of the registered Firmware.
-    pub fn _mk_Open_Impersonation(&self,_uFirmwareID: UUID_t) -> MK_ERROR_e {
-       unimplemented!();
+    pub fn _mk_Open_Impersonation(&self, session: ImpersonationSession) -> MK_ERROR_e {
+        if !self.vpp_kernel.is_mgt_process(session.owner) {
+            return MK_ERROR_e::MK_ERROR_ACCESS_DENIED;
+        }
+        if self._mk_Get_Impersonation().is_some() {
+            return MK_ERROR_e::MK_ERROR_ACCESS_DENIED;
+        }
+        self.session.set(session);
+        MK_ERROR_e::MK_ERROR_NONE
     }
 
     /// Inform the kernel that the impersonation of a Firmware has completed.
     /// This function can only be used by the System VPP application
     ///
     /// Clear UUID Kernel Object
-    pub fn _mk_Close_Impersonation(&self) ->MK_ERROR_e {
-        unimplemented!();
+    pub fn _mk_Close_Impersonation(&self, owner: AppId) -> MK_ERROR_e {
+        match self._mk_Get_Impersonation() {
+            Some(session) if session.owner == owner => {
+                self.session.clear();
+                MK_ERROR_e::MK_ERROR_NONE
+            }
+            Some(_) => MK_ERROR_e::MK_ERROR_ACCESS_DENIED,
+            None => MK_ERROR_e::MK_ERROR_ILLEGAL_PARAMETER,
+        }
+    }
+
+    /// Returns the open session, revoking it first if its owner restarted or
+    /// faulted since it was opened.
+    pub fn _mk_Get_Impersonation(&self) -> Option<ImpersonationSession> {
+        let session = self.session.map(|session| *session)?;
+        let alive = self
+            .vpp_kernel
+            .get_process_ref_appid(session.owner)
+            .and_then(|process| process.tockprocess.get())
+            .map_or(false, |process| match process.get_state() {
+                State::Fault | State::StoppedFaulted => false,
+                _ => true,
+            });
+        if alive {
+            Some(session)
+        } else {
+            self.session.clear();
+            None
+        }
+    }
+
+    /// Returns the session opened by `owner`, if any.
+    pub fn impersonation_of(&self, owner: AppId) -> Option<ImpersonationSession> {
+        self._mk_Get_Impersonation()
+            .filter(|session| session.owner == owner)
     }
 
 }
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum MGT_ERROR_e{
     MGT_ERROR_NONE,
     MGT_ERROR_ILLEGAL_PARAMETER,
@@ -171,105 +521,519 @@ pub enum MGT_ERROR_e{
     MGT_ERROR_COMMAND_NOK,
 }
 
-pub fn parse_vpp_header(firmware: &'static [u8]) -> FwHeader{//-> Result<FwHeader,MGT_ERROR_e>{
-    // let id_bytes = (0..15).map(|byte|firmware[byte]).collect::<[u8;16]>();
-    // let id_bytes = (0..15).map(|byte|firmware[byte]).collect::<Vec<_>>();
-    //
-    // // let id_byt = firmware.get(0..15)?;
-    // let firmware_id = u128::from_le_bytes(id_bytes) as UUID_t;
-    // // use from_ne_bytes ?
-    // let family_id_bytes = (16..31).map(|byte|firmware[byte]).collect::<[u8;16]>();
-    // let firmware_family_id = u128::from_le_bytes(family_id_bytes) as UUID_t;
+/// Parse and validate a Firmware header. `firmware` must start with the Firmware
+/// Descriptor and contain at least `m_uHeaderLength` bytes. All consistency rules
+/// (see `firmware_descriptor_rules`) are evaluated before the header is returned.
+pub fn parse_vpp_header(firmware: &[u8]) -> Result<FwHeader, MGT_ERROR_e> {
+    let fw_descriptor = FwDescriptor::try_from(
+        firmware
+            .get(0..FW_DESCRIPTOR_SIZE)
+            .ok_or(MGT_ERROR_e::MGT_ERROR_ILLEGAL_PARAMETER)?,
+    )?;
+    let header = firmware
+        .get(0..fw_descriptor.m_uHeaderLength as usize)
+        .ok_or(MGT_ERROR_e::MGT_ERROR_ILLEGAL_PARAMETER)?;
+    // Everything after the Firmware Descriptor is a descriptor array.
+    let mut remaining = header
+        .get(FW_DESCRIPTOR_SIZE..)
+        .ok_or(MGT_ERROR_e::MGT_ERROR_ILLEGAL_PARAMETER)?;
 
+    let mut llos_descriptor = None;
+    if let VPP_FRW_TYPE_e::FIRMWARE_SOFTWARE_TYPE_LLOS = fw_descriptor.m_eFirmare_Software_Type {
+        llos_descriptor = Some(LlosDescriptor::try_from(remaining)?);
+        remaining = &remaining[remaining.len()..];
+    }
+    let process_descriptors = take_table(&mut remaining, fw_descriptor.m_uProcessCount)?;
+    let mailbox_descriptors = take_table(&mut remaining, fw_descriptor.m_uMailboxesCount)?;
+    let ipc_descriptors = take_table(&mut remaining, fw_descriptor.m_uIPCCount)?;
+    let lib_descriptors = take_table(&mut remaining, fw_descriptor.m_uLIBCount)?;
 
-    let header_length = u16::from_le_bytes([firmware[32],firmware[33]]) ;
-    let version_format = u16::from_le_bytes([firmware[34],firmware[35]]);
-    let version_firmware = u16::from_le_bytes([firmware[36],firmware[37]]);
-
-    let process_count = u8::from_le_bytes([firmware[38]]);
-    let mailbox_count = u8::from_le_bytes([firmware[39]]);
-    let ipc_count = u8::from_le_bytes([firmware[40]]);
-    let lib_count = u8::from_le_bytes([firmware[41]]);
-
-    // let fw_sw_type = firmware.get(42).ok_or(MGT_ERROR_ILLEGAL_PARAMETER)?;
-    // let fw_sw_type : VPP_FRW_TYPE_e = firmware.get(42).from();
-    // let scheduling :VPP_SCHEDULING_TYPE_e = firmware.get(43).into();
     let firmware_header = FwHeader {
-        fw_descriptor: FwDescriptor {
-            m_xID: 0,
-            m_xFamilyID: 0,
-            m_uHeaderLength: header_length,
-            m_VersionFormat: version_format,
-            m_VersionFirmware: version_firmware,
-            m_uProcessCount: process_count,
-            m_uMailboxesCount: mailbox_count,
-            m_uIPCCount: ipc_count,
-            m_uLIBCount: lib_count,
-            m_eFirmare_Software_Type: VPP_FRW_TYPE_e::FIRMWARE_SOFTWARE_TYPE_APP,
-            m_eSchedluingType: VPP_SCHEDULING_TYPE_e::MK_SCHEDULING_TYPE_COLLABORATIVE
-        },
-        process_descriptors: ProcessDescriptors {
-            m_pvProcessCode: 0,
-            m_uLength_Process_CODE: 0,
-            m_uLength_Process_CONSTANTS: 0,
-            m_uLength_Process_DATA: 0,
-            m_uLength_Process_NVM: 0,
-            m_xID: 0,
-            m_uParent_Process: 0,
-            m_uSizeStack: 0
-        },
-        mailbox_descriptors: MailboxDescriptors {
-            m_xID: 0,
-            m_uIX_Owner: 0,
-            m_uIX_Sender: 0
-        },
-        ipc_descriptor: IpcDescriptor {
-            m_xID: 0,
-            m_uLength_IPC: 0,
-            m_uIX_Writer: 0,
-            m_uIX_Reader: 0
-        }
+        fw_descriptor,
+        process_descriptors,
+        mailbox_descriptors,
+        ipc_descriptors,
+        lib_descriptors,
+        llos_descriptor,
     };
-    firmware_header
+    firmware_descriptor_rules(&firmware_header)?;
+    Ok(firmware_header)
+}
 
+/// Split `count` descriptors of type `T` off the front of `remaining`.
+fn take_table<'a, T: Descriptor>(
+    remaining: &mut &'a [u8],
+    count: u8,
+) -> Result<DescriptorTable<'a, T>, MGT_ERROR_e> {
+    let len = count as usize * T::SIZE;
+    let raw = remaining
+        .get(0..len)
+        .ok_or(MGT_ERROR_e::MGT_ERROR_ILLEGAL_PARAMETER)?;
+    *remaining = &remaining[len..];
+    Ok(DescriptorTable::new(raw, count as usize))
+}
+
+/// Returns true if `uuid` is a [UUID] of version 4 (random) and of the RFC 4122 variant.
+fn is_uuid_v4(uuid: UUID_t) -> bool {
+    let bytes = uuid.to_be_bytes();
+    (bytes[6] >> 4) == 0x4 && (bytes[8] >> 6) == 0b10
+}
 
+/// Enumerated part of a composite identifier (Table 7-2).
+fn enumerated_id(id: u16) -> u16 {
+    id & 0x3FFF
 }
 
 /// Consistency Rules:
 /// The rules below can be used to evaluate the correctness and validity of the Firmware
 /// Format. To assume correctness, all rules shall be evaluated.
+pub fn firmware_descriptor_rules(header: &FwHeader) -> Result<(), MGT_ERROR_e> {
+    use MGT_ERROR_e::MGT_ERROR_ILLEGAL_PARAMETER;
+    let fw_descriptor = &header.fw_descriptor;
 
-pub fn firmware_descriptor_rules(firmware : FW) -> bool {
-    let fw_descriptor : FwDescriptor = firmware.FwHeader.fw_descriptor;
     // 1. m_xID and m_xFamilyID should be according to [UUID] of type 4
-    // ??
+    if !is_uuid_v4(fw_descriptor.m_xID) || !is_uuid_v4(fw_descriptor.m_xFamilyID) {
+        return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+    }
 
     // 2. m_uHeaderLength shall be equal to:
     // * size of Firmware Descriptor + (m_uProcessCount x size of Firmware Descriptor) +
     // (m_uMailboxesCount x size of Mailbox Descriptor) + (m_uIPCCount x size of IPC Descriptor) +
     // (m_uLIBCount x size of LIB Descriptor), or
     // * size of Firmware Descriptor + size of LLOS Descriptor.
-    let _expected_length = fw_descriptor.m_uHeaderLength;
-    // double check what the size_of function does.
-    //let _size_fw_descriptor = mem::size_of::<fw_descriptor>() as u16 ;
-   // let size_processes = fw_descriptor.
-   // let calculated_length = ;
-
-    false
-}
-
-
-
-/*
-            let (process_option, unused_memory) = unsafe {
-                Process::create(
-                    kernel,
-                    chip,
-                    entry_flash,
-                    header_length as usize,
-                    version,
-                    remaining_memory,
-                    fault_response,
-                    i,
-                )?
-            };*/
\ No newline at end of file
+    let expected_length = fw_descriptor.m_uHeaderLength as usize;
+    let calculated_length = if header.llos_descriptor.is_some() {
+        if fw_descriptor.m_uProcessCount != 0
+            || fw_descriptor.m_uMailboxesCount != 0
+            || fw_descriptor.m_uIPCCount != 0
+            || fw_descriptor.m_uLIBCount != 0
+        {
+            return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+        }
+        FW_DESCRIPTOR_SIZE + LLOS_DESCRIPTOR_SIZE
+    } else {
+        FW_DESCRIPTOR_SIZE
+            + fw_descriptor.m_uProcessCount as usize * PROCESS_DESCRIPTOR_SIZE
+            + fw_descriptor.m_uMailboxesCount as usize * MAILBOX_DESCRIPTOR_SIZE
+            + fw_descriptor.m_uIPCCount as usize * IPC_DESCRIPTOR_SIZE
+            + fw_descriptor.m_uLIBCount as usize * LIB_DESCRIPTOR_SIZE
+    };
+    if expected_length != calculated_length {
+        return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+    }
+
+    // 3. The major version of the Firmware Format shall be supported.
+    if (fw_descriptor.m_VersionFormat >> 8) as u8 != MK_FORMAT_VERSION {
+        return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+    }
+
+    if let Some(llos) = header.llos_descriptor {
+        // The LLOS entry point shall be inside its CODE segment.
+        if llos.m_pvLLOSCode >= llos.m_uLength_LLOS_CODE {
+            return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+        }
+        return Ok(());
+    }
+
+    // 4. The number of descriptors shall be within the limits of Table 7-9.
+    let process_count = fw_descriptor.m_uProcessCount as MK_Index_t;
+    if fw_descriptor.m_uProcessCount < MK_MIN_APP_PROCESSS
+        || fw_descriptor.m_uProcessCount > MK_PROCESS_LIMIT
+        || fw_descriptor.m_uMailboxesCount as usize > MK_MAILBOX_LIMIT
+        || fw_descriptor.m_uIPCCount as usize > MK_IPC_LIMIT
+        || fw_descriptor.m_uLIBCount > MK_LIB_LIMIT
+    {
+        return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+    }
+
+    // 5. Process Descriptors.
+    let mut previous_id: Option<MK_Process_ID_u> = None;
+    for (index, process) in header.process_descriptors.iter().enumerate() {
+        // Sorted in ascending order of identifiers (hence unique).
+        if previous_id.map_or(false, |id| process.m_xID <= id) {
+            return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+        }
+        previous_id = Some(process.m_xID);
+        if index == 0 {
+            // The Main Process is its own parent.
+            if process.m_uParent_Process != 0 {
+                return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+            }
+            if let VPP_FRW_TYPE_e::FIRMWARE_SOFTWARE_TYPE_APP =
+                fw_descriptor.m_eFirmare_Software_Type
+            {
+                if process.m_xID != MK_PROCESS_MAIN_APP_ID {
+                    return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+                }
+            }
+        } else {
+            if process.m_uParent_Process >= process_count {
+                return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+            }
+            if enumerated_id(process.m_xID) < MK_PROCESS_DOMAIN_BASE_ID as u16
+                || enumerated_id(process.m_xID) > MK_MAX_PROCESS_ID
+            {
+                return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+            }
+        }
+        // The entry point shall be inside the CODE segment.
+        if process.m_pvProcessCode >= process.m_uLength_Process_CODE {
+            return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+        }
+        // Stack size in bytes, multiple of 4 and at least the minimal supported stack.
+        if process.m_uSizeStack % 4 != 0
+            || (process.m_uSizeStack as usize)
+                < MK_MIN_SUPPORTED_STACK as usize * core::mem::size_of::<XLEN>()
+        {
+            return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+        }
+        // The kernel Mailbox, if any, shall be a Mailbox owned by the Process.
+        if process.m_xKernel_Mailbox != 0
+            && !header.mailbox_descriptors.iter().any(|mailbox| {
+                mailbox.m_xID == process.m_xKernel_Mailbox
+                    && mailbox.m_uIX_Owner == index as MK_Index_t
+            })
+        {
+            return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+        }
+    }
+
+    // 6. Mailbox Descriptors. Cross-Execution-Domain Mailboxes are instantiated by the
+    // kernel and cannot be defined in a Firmware.
+    let mut previous_id: Option<MK_MAILBOX_ID_u> = None;
+    for mailbox in header.mailbox_descriptors.iter() {
+        if previous_id.map_or(false, |id| mailbox.m_xID <= id) {
+            return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+        }
+        previous_id = Some(mailbox.m_xID);
+        if enumerated_id(mailbox.m_xID) < MK_MAILBOX_DOMAIN_BASE_ID as u16
+            || enumerated_id(mailbox.m_xID) > MK_MAILBOX_MAX_ID
+            || mailbox.m_uIX_Owner >= process_count
+            || mailbox.m_uIX_Sender >= process_count
+        {
+            return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+        }
+    }
+
+    // 7. IPC Descriptors. Same restriction as for Mailboxes on the identifiers.
+    let mut previous_id: Option<MK_IPC_ID_u> = None;
+    for ipc in header.ipc_descriptors.iter() {
+        if previous_id.map_or(false, |id| ipc.m_xID <= id) {
+            return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+        }
+        previous_id = Some(ipc.m_xID);
+        if enumerated_id(ipc.m_xID) < MK_IPC_DOMAIN_BASE_ID as u16
+            || enumerated_id(ipc.m_xID) > MK_IPC_MAX_ID
+            || ipc.m_uLength_IPC == 0
+            || ipc.m_uLength_IPC > MK_IPC_SIZE_LIMIT
+            || ipc.m_uIX_Writer >= process_count
+            || ipc.m_uIX_Reader >= process_count
+            || ipc.m_uIX_Writer == ipc.m_uIX_Reader
+        {
+            return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+        }
+    }
+
+    // 8. LIB Descriptors. Every library shall be imported by at least one existing Process.
+    let process_mask: MK_BITMAP_t = if process_count as u32 >= 32 {
+        !0
+    } else {
+        (1 << process_count) - 1
+    };
+    let mut previous_id: Option<MK_LIB_ID_u> = None;
+    for lib in header.lib_descriptors.iter() {
+        if previous_id.map_or(false, |id| lib.m_xID <= id) {
+            return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+        }
+        previous_id = Some(lib.m_xID);
+        if enumerated_id(lib.m_xID) > MK_LIB_MAX_ID
+            || lib.m_bProcesses == 0
+            || lib.m_bProcesses & !process_mask != 0
+        {
+            return Err(MGT_ERROR_ILLEGAL_PARAMETER);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::vpp::sim::VffBuilder;
+
+    use MGT_ERROR_e::MGT_ERROR_ILLEGAL_PARAMETER;
+
+    /// Identifiers of the APP domain, the first enumerated identifier of a Firmware.
+    const WORKER: MK_Process_ID_u = 0x4064;
+    const MAILBOX: MK_MAILBOX_ID_u = 0x4064;
+    const IPC: MK_IPC_ID_u = 0x4064;
+    const LIB: MK_LIB_ID_u = 0x4001;
+
+    /// A Main Process and its child.
+    fn firmware() -> VffBuilder {
+        VffBuilder::new().process(WORKER, 0, 0)
+    }
+
+    fn parse(builder: &VffBuilder) -> Result<(), MGT_ERROR_e> {
+        parse_vpp_header(&builder.build()).map(|_| ())
+    }
+
+    fn rejected(builder: &VffBuilder) -> bool {
+        parse(builder) == Err(MGT_ERROR_ILLEGAL_PARAMETER)
+    }
+
+    #[test]
+    fn a_firmware_header_is_decoded() {
+        let bytes = firmware()
+            .process(WORKER + 1, 1, MAILBOX)
+            .mailbox(MAILBOX, 2, 1)
+            .ipc(IPC, 64, 1, 2)
+            .lib(LIB, 0x0102, 0b110)
+            .build();
+        let header = parse_vpp_header(&bytes).unwrap();
+
+        let fw = &header.fw_descriptor;
+        assert_eq!(fw.m_xID, VffBuilder::ID);
+        assert_eq!(fw.m_xFamilyID, VffBuilder::FAMILY_ID);
+        assert_eq!(fw.m_uHeaderLength as usize, bytes.len());
+        assert_eq!(
+            bytes.len(),
+            FW_DESCRIPTOR_SIZE
+                + 3 * PROCESS_DESCRIPTOR_SIZE
+                + MAILBOX_DESCRIPTOR_SIZE
+                + IPC_DESCRIPTOR_SIZE
+                + LIB_DESCRIPTOR_SIZE
+        );
+        assert_eq!(fw.m_VersionFormat, 0x0400);
+        assert_eq!(header.process_descriptors.len(), 3);
+
+        let worker = header.process_descriptors.get(2).unwrap();
+        assert_eq!(worker.m_xID, WORKER + 1);
+        assert_eq!(worker.m_uParent_Process, 1);
+        assert_eq!(worker.m_xKernel_Mailbox, MAILBOX);
+        assert_eq!(worker.m_uLength_Process_CODE, 256);
+        assert_eq!(worker.m_uSizeStack, VffBuilder::STACK_SIZE);
+        let mailbox = header.mailbox_descriptors.get(0).unwrap();
+        assert_eq!(
+            (mailbox.m_xID, mailbox.m_uIX_Owner, mailbox.m_uIX_Sender),
+            (MAILBOX, 2, 1)
+        );
+        let ipc = header.ipc_descriptors.get(0).unwrap();
+        assert_eq!(
+            (
+                ipc.m_xID,
+                ipc.m_uLength_IPC,
+                ipc.m_uIX_Writer,
+                ipc.m_uIX_Reader
+            ),
+            (IPC, 64, 1, 2)
+        );
+        let lib = header.lib_descriptors.get(0).unwrap();
+        assert_eq!(
+            (lib.m_xID, lib.m_uVersion, lib.m_bProcesses),
+            (LIB, 0x0102, 0b110)
+        );
+        assert!(header.llos_descriptor.is_none());
+        assert_eq!(
+            header.memory_partition_size(),
+            3 * (256 + VffBuilder::STACK_SIZE as u32)
+        );
+    }
+
+    #[test]
+    fn the_identifiers_are_version_4_uuids() {
+        assert_eq!(parse(&firmware()), Ok(()));
+        // Version 1.
+        assert!(rejected(
+            &firmware().id(0x1234_5678_9ABC_1DEF_8123_4567_89AB_CDEF)
+        ));
+        assert!(rejected(
+            &firmware().family_id(0x1234_5678_9ABC_1DEF_8123_4567_89AB_CDEF)
+        ));
+        // Microsoft variant.
+        assert!(rejected(
+            &firmware().id(0x1234_5678_9ABC_4DEF_C123_4567_89AB_CDEF)
+        ));
+    }
+
+    #[test]
+    fn the_header_length_matches_the_descriptors() {
+        let length = firmware().build().len() as u16;
+        assert!(rejected(
+            &firmware().header_length(length - PROCESS_DESCRIPTOR_SIZE as u16)
+        ));
+        assert!(rejected(
+            &firmware().header_length(FW_DESCRIPTOR_SIZE as u16)
+        ));
+        // Longer than the header given.
+        assert!(rejected(&firmware().header_length(length + 4)));
+
+        let mut truncated = firmware().build();
+        truncated.pop();
+        assert_eq!(
+            parse_vpp_header(&truncated).err(),
+            Some(MGT_ERROR_ILLEGAL_PARAMETER)
+        );
+        assert_eq!(
+            parse_vpp_header(&truncated[..FW_DESCRIPTOR_SIZE - 1]).err(),
+            Some(MGT_ERROR_ILLEGAL_PARAMETER)
+        );
+    }
+
+    #[test]
+    fn only_the_major_format_version_4_is_supported() {
+        assert_eq!(parse(&firmware().format_version(0x0401)), Ok(()));
+        assert!(rejected(&firmware().format_version(0x0300)));
+        assert!(rejected(&firmware().format_version(0x0500)));
+    }
+
+    #[test]
+    fn unknown_software_and_scheduling_types_are_rejected() {
+        assert!(rejected(&firmware().software_type(0xFF)));
+        assert!(rejected(&firmware().scheduling_type(0xFF)));
+    }
+
+    #[test]
+    fn the_number_of_processes_is_limited() {
+        assert!(rejected(&VffBuilder::new().no_processes()));
+
+        let mut builder = VffBuilder::new();
+        for index in 1..MK_PROCESS_LIMIT as u16 {
+            builder = builder.process(WORKER + index, 0, 0);
+        }
+        assert_eq!(parse(&builder), Ok(()));
+        assert!(rejected(&builder.process(
+            WORKER + MK_PROCESS_LIMIT as u16,
+            0,
+            0
+        )));
+    }
+
+    #[test]
+    fn the_processes_are_sorted_by_identifier() {
+        assert!(rejected(&firmware().process(WORKER, 0, 0)));
+        assert!(rejected(&firmware().process(WORKER - 1, 0, 0)));
+        assert_eq!(parse(&firmware().process(WORKER + 1, 0, 0)), Ok(()));
+    }
+
+    #[test]
+    fn the_main_process_is_its_own_parent() {
+        let main = VffBuilder::new().no_processes();
+        assert!(rejected(
+            &main
+                .process(MK_PROCESS_MAIN_APP_ID, 1, 0)
+                .process(WORKER, 0, 0)
+        ));
+        // The Main Process of an App Firmware has the MAIN identifier.
+        let main = VffBuilder::new().no_processes();
+        assert!(rejected(&main.process(MK_PROCESS_MAIN_APP_ID + 1, 0, 0)));
+        let main = VffBuilder::new().no_processes();
+        let vpp = main.software_type(VPP_FRW_TYPE_e::FIRMWARE_SOFTWARE_TYPE_VPP.into());
+        assert_eq!(parse(&vpp.process(0x8064, 0, 0)), Ok(()));
+    }
+
+    #[test]
+    fn the_other_processes_have_a_parent_and_an_enumerated_identifier() {
+        assert_eq!(parse(&firmware().process(WORKER + 1, 1, 0)), Ok(()));
+        assert!(rejected(&firmware().process(WORKER + 1, 3, 0)));
+        // Below MK_PROCESS_DOMAIN_BASE_ID.
+        assert!(rejected(&VffBuilder::new().process(0x4063, 0, 0)));
+    }
+
+    #[test]
+    fn the_entry_point_is_in_the_code_and_the_stack_is_large_enough() {
+        let stack = VffBuilder::STACK_SIZE;
+        assert_eq!(
+            parse(&VffBuilder::new().process_with(WORKER, 0, 0, 255, 256, stack)),
+            Ok(())
+        );
+        assert!(rejected(
+            &VffBuilder::new().process_with(WORKER, 0, 0, 256, 256, stack)
+        ));
+        assert_eq!(
+            parse(&VffBuilder::new().process_with(WORKER, 0, 0, 0, 256, stack + 4)),
+            Ok(())
+        );
+        assert!(rejected(&VffBuilder::new().process_with(
+            WORKER,
+            0,
+            0,
+            0,
+            256,
+            stack + 2
+        )));
+        assert!(rejected(&VffBuilder::new().process_with(
+            WORKER,
+            0,
+            0,
+            0,
+            256,
+            stack - 4
+        )));
+    }
+
+    #[test]
+    fn the_kernel_mailbox_is_owned_by_its_process() {
+        let worker = VffBuilder::new().process(WORKER, 0, MAILBOX);
+        assert_eq!(parse(&worker.mailbox(MAILBOX, 1, 0)), Ok(()));
+        let worker = VffBuilder::new().process(WORKER, 0, MAILBOX);
+        assert!(rejected(&worker.mailbox(MAILBOX, 0, 1)));
+        assert!(rejected(&VffBuilder::new().process(WORKER, 0, MAILBOX)));
+    }
+
+    #[test]
+    fn the_mailboxes_are_sorted_and_link_existing_processes() {
+        assert_eq!(
+            parse(&firmware().mailbox(MAILBOX, 0, 1).mailbox(MAILBOX + 1, 1, 0)),
+            Ok(())
+        );
+        assert!(rejected(
+            &firmware().mailbox(MAILBOX, 0, 1).mailbox(MAILBOX, 1, 0)
+        ));
+        // Below MK_MAILBOX_DOMAIN_BASE_ID.
+        assert!(rejected(&firmware().mailbox(MAILBOX - 1, 0, 1)));
+        assert!(rejected(&firmware().mailbox(MAILBOX, 2, 1)));
+        assert!(rejected(&firmware().mailbox(MAILBOX, 0, 2)));
+    }
+
+    #[test]
+    fn the_ipcs_are_sorted_and_link_two_existing_processes() {
+        assert_eq!(parse(&firmware().ipc(IPC, MK_IPC_SIZE_LIMIT, 0, 1)), Ok(()));
+        assert!(rejected(&firmware().ipc(IPC, 64, 0, 1).ipc(IPC, 64, 1, 0)));
+        // Below MK_IPC_DOMAIN_BASE_ID.
+        assert!(rejected(&firmware().ipc(IPC - 1, 64, 0, 1)));
+        assert!(rejected(&firmware().ipc(IPC, 0, 0, 1)));
+        assert!(rejected(&firmware().ipc(IPC, MK_IPC_SIZE_LIMIT + 1, 0, 1)));
+        assert!(rejected(&firmware().ipc(IPC, 64, 1, 1)));
+        assert!(rejected(&firmware().ipc(IPC, 64, 2, 1)));
+        assert!(rejected(&firmware().ipc(IPC, 64, 0, 2)));
+    }
+
+    #[test]
+    fn the_libraries_are_sorted_and_imported_by_existing_processes() {
+        assert_eq!(
+            parse(&firmware().lib(LIB, 0x0100, 0b01).lib(LIB + 1, 0x0100, 0b10)),
+            Ok(())
+        );
+        assert!(rejected(
+            &firmware().lib(LIB, 0x0100, 0b01).lib(LIB, 0x0100, 0b10)
+        ));
+        assert!(rejected(&firmware().lib(LIB, 0x0100, 0)));
+        assert!(rejected(&firmware().lib(LIB, 0x0100, 0b100)));
+    }
+
+    #[test]
+    fn a_llos_has_only_its_descriptor() {
+        let bytes = VffBuilder::new().llos(0, 1024).build();
+        assert_eq!(bytes.len(), FW_DESCRIPTOR_SIZE + LLOS_DESCRIPTOR_SIZE);
+        let header = parse_vpp_header(&bytes).unwrap();
+        assert!(header.process_descriptors.is_empty());
+        assert_eq!(header.memory_partition_size(), 1024);
+
+        assert!(rejected(&VffBuilder::new().llos(1024, 1024)));
+        assert!(rejected(
+            &VffBuilder::new().llos(0, 1024).process(WORKER, 0, 0)
+        ));
+    }
+}
@@ -12,29 +12,36 @@ use core::str;
 use crate::vpp::mloi::MK_ERROR_e::*;
 use crate::vpp::mloi::MK_PROCESS_PRIORITY_e::*;
 use crate::vpp::mloi::*;
-use crate::vpp::process::*;
+use crate::vpp::process::VppRestartPolicy;
 use crate::vpp::mloi::VppState::*;
 use crate::vpp::process;
-use kernel::{Kernel, capabilities, Chip, AppSlice, Shared};
+use kernel::{Kernel, capabilities, Chip, AppSlice, Shared, mpu};
 use kernel::introspection::KernelInfo;
-use kernel::common::cells::TakeCell;
-use kernel::procs::{ProcessType, Process, FaultResponse, ProcessLoadError};
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::procs::{ProcessType, Process, FaultResponse, ProcessLoadError, ProcessRestartPolicy};
+use kernel::procs::{ProcessEvent, ProcessObserver};
+use kernel::common::list::ListLink;
 use kernel::capabilities::ProcessManagementCapability;
 use kernel::debug;
 use kernel::hil::uart;
 use kernel::ReturnCode;
 use crate::vpp::process::VppProcess;
-use kernel::{ListenerType,LISTENER};
 use kernel::{Callback, Driver,Grant,AppId};
 use crate::virtual_uart::{MuxUart, UartDevice};
 use kernel::static_init;
 use kernel::hil;
 use kernel::procs::{FunctionCall,FunctionCallSource,Task};
 use crate::vpp::mloi::VppState::RUNNING;
-use core::convert::TryInto;
+use core::convert::{TryFrom, TryInto};
 use crate::vpp::mailbox::mbox;
 use crate::vpp::mloi::MK_SIGNAL_e::MK_SIGNAL_ERROR;
-use crate::vpp::ipc::ipc;
+use crate::vpp::ipc::{ipc, MK_SIGNAL_IPC_RELEASED};
+use crate::vpp::nvm::NvmTransaction;
+use crate::vpp::signal_wait::SignalWaiter;
+use crate::vpp::timer::MonotonicTime;
+use crate::vpp::handle::{Handle, HandleType};
+use crate::vpp::library::VppLibrary;
+use crate::vpp::com_service::ComBridge;
 use crate::driver;
 use kernel::hil::time::Alarm;
 
@@ -54,12 +61,54 @@ macro_rules! store_vpp_sec {
 
 pub const NUM_PROCS: usize = 4 ; // Number of allowed vpp processes.
 pub const DRIVER_NUM: usize = driver::NUM::VppDriver as usize;
+/// Subscribe number of the callback resuming a Process after `_mk_Wait_Signal`.
+/// It is never a valid Mailbox handle (its type is 0), Mailbox handles being also
+/// subscribe numbers.
+pub const WAIT_SIGNAL_SUBSCRIBE: usize = 0x1_0000;
+
+/// Version of the `vpp_kernel_driver` ABI, returned by `CMD_DRIVER_CHECK`. The major
+/// version is in the high half-word and changes when a command is removed or changes
+/// meaning, the minor version is in the low half-word and changes when one is added.
+pub const VPP_ABI_VERSION: usize = 0x0001_0001;
+
+// Command numbers of the `vpp_kernel_driver`, documented in
+// `doc/syscalls/90100_vpp_kernel.md`.
+pub const CMD_DRIVER_CHECK: usize = 0;
+pub const CMD_GET_PROCESS_HANDLE: usize = 1;
+pub const CMD_SUSPEND_PROCESS: usize = 2;
+pub const CMD_RESUME_PROCESS: usize = 3;
+pub const CMD_YIELD: usize = 4;
+pub const CMD_GET_MAILBOX_HANDLE: usize = 5;
+pub const CMD_SEND_SIGNAL: usize = 6;
+pub const CMD_GET_SIGNAL: usize = 7;
+pub const CMD_COMMIT: usize = 8;
+pub const CMD_ROLLBACK: usize = 9;
+pub const CMD_WAIT_SIGNAL: usize = 10;
+pub const CMD_GET_EXCEPTION: usize = 11;
+pub const CMD_GET_PROCESS_PRIORITY: usize = 12;
+pub const CMD_SET_PROCESS_PRIORITY: usize = 13;
+pub const CMD_GET_TIME: usize = 14;
+pub const CMD_GET_ACCESS_IPC: usize = 15;
+pub const CMD_RELEASE_ACCESS_IPC: usize = 16;
+pub const CMD_GET_MAILBOX_ID_ACTIVATED: usize = 17;
+pub const CMD_GET_IPC_SIZE: usize = 20;
+pub const CMD_GET_IPC_HANDLE: usize = 21;
+pub const CMD_GET_LIBRARY_ENTRY_TABLE: usize = 22;
+pub const CMD_GET_ERROR: usize = 100;
 
 pub struct VppKernel{
     pub(crate) vpp_processes: &'static [Option<VppProcess>;NUM_PROCS],
     pub(crate) kernel: &'static Kernel,
     pub(crate) mailboxes: &'static [Option<mbox>;MK_MAILBOX_LIMIT],
     pub(crate) ipcs: &'static [Option<ipc>; MK_IPC_LIMIT],
+    pub(crate) nvm: OptionalCell<&'static dyn NvmTransaction>,
+    pub(crate) waiter: OptionalCell<&'static dyn SignalWaiter>,
+    pub(crate) clock: OptionalCell<&'static dyn MonotonicTime>,
+    /// Shared libraries found in the app flash by `load_vpp_processes`.
+    pub(crate) libraries: OptionalCell<&'static [Option<VppLibrary>]>,
+    pub(crate) com: OptionalCell<&'static dyn ComBridge>,
+    /// Link of the VPP kernel in the process observers of the Tock kernel.
+    observer_link: ListLink<'static, dyn ProcessObserver<'static>>,
 }
 
 impl  VppKernel {
@@ -79,12 +128,64 @@ impl  VppKernel {
             kernel: tock_kernel,
             mailboxes: mbs,
             ipcs:ipcs,
+            nvm: OptionalCell::empty(),
+            waiter: OptionalCell::empty(),
+            clock: OptionalCell::empty(),
+            libraries: OptionalCell::empty(),
+            com: OptionalCell::empty(),
+            observer_link: ListLink::empty(),
             // timer:timer,
         }
     }
+    /// Set the transactional NVM used by `_mk_Commit` and `_mk_Rollback`.
+    pub fn set_nvm(&self, nvm: &'static dyn NvmTransaction) {
+        self.nvm.set(nvm);
+    }
+    /// Set the component blocking the Processes in `_mk_Wait_Signal`.
+    pub fn set_signal_waiter(&self, waiter: &'static dyn SignalWaiter) {
+        self.waiter.set(waiter);
+    }
+    /// Set the monotonic time source of `_mk_Get_Time`.
+    pub fn set_clock(&self, clock: &'static dyn MonotonicTime) {
+        self.clock.set(clock);
+    }
+    /// Set the COM service bridging the host link to the MAIN Process.
+    pub fn set_com_bridge(&self, com: &'static dyn ComBridge) {
+        self.com.set(com);
+    }
+    /// Set the shared libraries filled by `load_vpp_processes`.
+    pub fn set_libraries(&self, libraries: &'static [Option<VppLibrary>]) {
+        self.libraries.set(libraries);
+    }
+    /// Address of the entry table of the shared library `id`, if it is mapped into the
+    /// Process `appid`.
+    pub(crate) fn get_library_entry_table(&self, id: MK_LIB_ID_u, appid: AppId) -> Option<usize> {
+        let imported = self.get_process_ref_appid(appid)?.libraries.get();
+        self.libraries.and_then(|libraries| {
+            libraries
+                .iter()
+                .enumerate()
+                .filter(|(slot, _)| imported & 1 << slot != 0)
+                .filter_map(|(_, library)| library.as_ref())
+                .find(|library| library.get_id() == id)
+                .map(|library| library.entry_table())
+        })
+    }
     // 1) Generic Functions
-    pub (crate) fn _mk_Get_Exception(&self){
-        unimplemented!()
+    /// Get the Exceptions raised by a Process, as a bitmap of `MK_EXCEPTION_e` ranks.
+    /// A Process can retrieve its own Exceptions or those of its children, even when
+    /// they are dead. The pending Exceptions are cleared once they have been read.
+    /// `MK_ERROR_ACCESS_DENIED` if the caller is not a Process or is neither the
+    /// Process nor its parent, the error of `resolve_process` if the handle is wrong.
+    pub (crate) fn _mk_Get_Exception(&self, _hProcess: MK_HANDLE_t,
+                                     _appid_caller: AppId) -> Result<MK_BITMAP_t, MK_ERROR_e> {
+        let caller = self.process_index(_appid_caller).ok_or(MK_ERROR_ACCESS_DENIED)? as usize;
+        let (index, process) = self.resolve_process(_hProcess)?;
+        if index == caller || process.parent.get() as usize == caller {
+            Ok(process.take_exceptions())
+        } else {
+            Err(MK_ERROR_ACCESS_DENIED)
+        }
     }
 
     /// Get the most recent error generated through the execution of a function within a given Process
@@ -94,132 +195,122 @@ impl  VppKernel {
     /// modified by a state transition. Power off cycle and other reset will clear the stored error.
     /// mk_Get_Error function does not modify the last error stored for this process during its invocation.
     pub (crate) fn _mk_Get_Error(&self,_hProcess: MK_HANDLE_t) -> MK_ERROR_e{
-        let process_ref = self.get_process_ref_handle(_hProcess);
-        let mut error_retrieved: MK_ERROR_e = MK_ERROR_NONE;
-        if process_ref.is_some(){
-             error_retrieved = process_ref.unwrap().get_last_generated_error();
-        } else {
-            error_retrieved = MK_ERROR_HANDLE_NOT_ACCESSED;
+        match self.resolve_process(_hProcess) {
+            Ok((_, process)) => process.get_last_generated_error(),
+            Err(MK_ERROR_ACCESS_DENIED) => MK_ERROR_GET_ERROR_HANDLE_NOT_A_PROCESS,
+            Err(error) => error,
         }
-        error_retrieved
     }
     /// Get the absolute time (in ticks) since the Primary Platfrom start up
-    /// The return value is  bits in length.
+    /// The return value is 64 bits in length.
     pub (crate) fn _mk_Get_Time(&self) -> MK_TIME_t {
-        unimplemented!()
+        self.clock.map_or(0, |clock| clock.get_time())
     }
 
     // 2) Process Management
     /// Get the process kernel Handle for itself or for one of its descendants through its
     /// Process Identifier. The Proces retrieving the Process Handle does not inherit the rights
     /// of its owner. I
+    ///
+    /// A handle can be obtained for a dead Process, to retrieve its error and its
+    /// Exceptions. The handles obtained before its death are stale.
     pub (crate)  fn _mk_Get_Process_Handle(& self, _eProcess_ID: MK_Process_ID_u)
                                            -> Option<MK_HANDLE_t> {
-        let handle = convert_to_handle(_eProcess_ID);
-        let process =self.get_process_ref_handle(handle);
-        if process.is_some() {Some(handle)}  else { None }
+        let index = self.vpp_processes.iter()
+            .position(|process| process.as_ref().map_or(false, |process| process.get_vpp_id() == _eProcess_ID))?;
+        self.process_handle(index)
     }
 
 
     pub (crate) fn _mk_get_process_priority(& self, _hProcess: MK_HANDLE_t) -> MK_PROCESS_PRIORITY_e {
-        let process = self.get_process_ref_handle(_hProcess);
-        if process.is_some(){
-            let prio = process.unwrap().get_vpp_priority();
-            debug!("Process Priority is {:?}", prio );
-            prio
-        }
-        else {
-            //self.last_error.set(MK_ERROR_UNKNOWN_HANDLE);
-            MK_PROCESS_PRIORITY_ERROR
-
+        match self.get_process_ref_handle(_hProcess) {
+            Some(process) => process.get_vpp_priority(),
+            None => MK_PROCESS_PRIORITY_ERROR,
         }
     }
 
-    // Concerning Priorities there is another missing function that needs to be implemented.
-    // Based on the index of Tock Processes, Vpp Priorities are mapped accordingly.
+    /// Set the priority of a Process. The `VppScheduler` applies it from its next
+    /// scheduling decision.
     pub (crate) fn _mk_set_process_priority(&self, _hProcess: MK_HANDLE_t,_xPriority: MK_PROCESS_PRIORITY_e) -> MK_ERROR_e {
-
-        // Check for UNKNOWN_PRIORITY by figuring out the encoding of the enum in rust
-        // Check for the value _xPriority if different from those 4 values
-        // TO-DO
-
-        let process =self.get_process_ref_handle(_hProcess);
-        if process.is_some() {
-            process.unwrap().set_vpp_priority(_xPriority);
-            debug!("Process Priority set to {:?}",_xPriority );
-            MK_ERROR_NONE
-        } else {
-            MK_ERROR_UNKNOWN_HANDLE
+        if _xPriority == MK_PROCESS_PRIORITY_ERROR {
+            return MK_ERROR_UNKNOWN_PRIORITY;
+        }
+        match self.live_process(_hProcess) {
+            Ok(process) => {
+                process.set_vpp_priority(_xPriority);
+                MK_ERROR_NONE
+            }
+            Err(error) => error,
         }
-
-        // TO DO
-        // Depending of the Scheduler Type, this can be implemented as follows:
-        // Based on the index on the PROCESSES Array, priorities can be defined
-        // index 0: MK_PROCESS_PRIORITY_HIGH
-        // index 1: MK_PROCESS_PRIORITY_NORMAL
-        // index 2: MK_PROCESS_PRIORITY_LOW
-        // match _xPriority {
-        //     // check for the index of the PROCESSES ARRAY and change accordingly
-        //     MK_PROCESS_PRIORITY_e::MK_PROCESS_PRIORITY_LOW => {
-        //         // _hProcess.tockprocess.appid.index changes
-        //     }
-        //     MK_PROCESS_PRIORITY_e::MK_PROCESS_PRIORITY_NORMAL => {
-        //     }
-        //     MK_PROCESS_PRIORITY_e::MK_PROCESS_PRIORITY_HIGH => {
-        //     }
-        //     MK_PROCESS_PRIORITY_e::MK_PROCESS_PRIORITY_ERROR => {
-        //         MK_ERROR_UNKNOWN_PRIORITY
-        //         // Is this the right use case ?
-        //     }
-        // }
-
     }
 
+    /// Suspend a Process and its Tock process. A Process without a Tock process is
+    /// left untouched and `MK_ERROR_INTERNAL` is returned.
     pub (crate) fn _mk_suspend_process(&self, mut _hProcess: MK_HANDLE_t) -> MK_ERROR_e {
-        let vppprocess = self.get_process_ref_handle(_hProcess);
-        if vppprocess.is_some() {
-            let process = vppprocess.unwrap();
-            process.suspend_vpp_process();
-            process.tockprocess.unwrap().stop();
-            MK_ERROR_NONE
-        } else {
-            MK_ERROR_UNKNOWN_HANDLE
+        match self.live_process(_hProcess) {
+            Ok(process) => match process.tockprocess.get() {
+                Some(tockprocess) => {
+                    process.suspend_vpp_process();
+                    tockprocess.stop();
+                    MK_ERROR_NONE
+                }
+                None => MK_ERROR_INTERNAL,
+            },
+            Err(error) => error,
         }
     }
 
+    /// Resume a Process and its Tock process, with the errors of `_mk_suspend_process`.
     pub fn _mk_resume_process(&self, mut _hProcess: MK_HANDLE_t) -> MK_ERROR_e {
-        let vppprocess = self.get_process_ref_handle(_hProcess);
-        if vppprocess.is_some() {
-            let process = vppprocess.unwrap();
-            process.resume_vpp_process();
-            process.tockprocess.unwrap().resume();
-            // this is totally wrong !!!
-            // process.vppstate.set(RUNNING);
-            MK_ERROR_NONE
-        } else {
-            MK_ERROR_UNKNOWN_HANDLE
+        match self.live_process(_hProcess) {
+            Ok(process) => match process.tockprocess.get() {
+                Some(tockprocess) => {
+                    process.resume_vpp_process();
+                    tockprocess.resume();
+                    // this is totally wrong !!!
+                    // process.vppstate.set(RUNNING);
+                    MK_ERROR_NONE
+                }
+                None => MK_ERROR_INTERNAL,
+            },
+            Err(error) => error,
         }
     }
 
-    pub (crate) fn _mk_Commit(&self) {
-        unimplemented!()
+    /// Make the NVM writes of the calling Process since its last commit persistent,
+    /// atomically. The completion is signaled through the NVM driver callback.
+    pub (crate) fn _mk_Commit(&self, _appid_caller: AppId) -> MK_ERROR_e {
+        self.nvm.map_or(MK_ERROR_INTERNAL, |nvm| match nvm.commit(_appid_caller) {
+            ReturnCode::SUCCESS => MK_ERROR_NONE,
+            ReturnCode::EINVAL => MK_ERROR_ACCESS_DENIED,
+            _ => MK_ERROR_INTERNAL,
+        })
     }
 
-    pub (crate) fn _mk_Rollback(&self) {
-        unimplemented!()
+    /// Discard the NVM writes of the calling Process since its last commit.
+    pub (crate) fn _mk_Rollback(&self, _appid_caller: AppId) -> MK_ERROR_e {
+        self.nvm.map_or(MK_ERROR_INTERNAL, |nvm| match nvm.rollback(_appid_caller) {
+            ReturnCode::SUCCESS => MK_ERROR_NONE,
+            ReturnCode::EINVAL => MK_ERROR_ACCESS_DENIED,
+            _ => MK_ERROR_INTERNAL,
+        })
     }
 
-    pub (crate) fn  _mk_yield (&self,_hProcess: MK_HANDLE_t) {
-        // Change state of VppState
-        let vpp_process = self.get_process_ref_handle(_hProcess);
-        if vpp_process.is_some(){
-            vpp_process.unwrap().yield_vpp_process();
-            // Change Tock State
-            vpp_process.unwrap().tockprocess.unwrap().set_yielded_state();
-            //vpp_process.unwrap().snyc_tock_vpp_states();
-            debug!("VPP Process state {:?}", vpp_process.unwrap().get_vpp_state());
-
-        }
+    /// Yield a Process: it is ready again and runs on its next callback.
+    /// `MK_ERROR_INTERNAL` if the Tock process of the Process was unloaded.
+    pub (crate) fn  _mk_yield (&self,_hProcess: MK_HANDLE_t) -> MK_ERROR_e {
+        let vpp_process = match self.live_process(_hProcess) {
+            Ok(vpp_process) => vpp_process,
+            Err(error) => return error,
+        };
+        let tockprocess = match vpp_process.tockprocess.get() {
+            Some(tockprocess) => tockprocess,
+            None => return MK_ERROR_INTERNAL,
+        };
+        // Change state of VppState, then the Tock State
+        vpp_process.yield_vpp_process();
+        tockprocess.set_yielded_state();
+        MK_ERROR_NONE
     }
 
     // 3) Mailbox Management
@@ -230,31 +321,55 @@ impl  VppKernel {
         // to a Mailbox. Needed a caller id and check if that id is the same in the mailbox struct
         // leave for later
         // ACCESS_DENIED if caller Process is not defined as the sender Process of the Mailbox
-        let handle = convert_mbid_to_handle(_eMailboxID);
-        let mailbox = self.Get_Mailbox_ref_internal(handle);
-        // None is the equivalent of NULL in rust, that is why i am wrapping this with
-        // the Option Box
-        if mailbox.is_some() {Some(handle)} else {None}
+        let slot = self.mailboxes.iter()
+            .position(|mailbox| mailbox.as_ref().map_or(false, |mailbox| mailbox.get_mb_id() == _eMailboxID))?;
+        self.mailbox_handle(slot)
     }
 
     /// When waiting for Signal on any Mailbox owned by the caller Process, get the Mailbox
     /// identifier of a Process that has a pending Signal.
     /// This function retrieves the identifier of a Mailbox with a pending signal when the
     /// Process waits on any Mailbox of the caller Process.
-    pub fn _mk_Get_Mailbox_ID_Activated(&self)-> Option<MK_MAILBOX_ID_u>{
-        unimplemented!();
+    ///
+    /// A Mailbox is activated when it receives Signals, until it is returned here or its
+    /// Signals are read. The activated Mailboxes are returned in round robin, so a
+    /// Mailbox signaled often does not hide the others. The Signals stay pending and are
+    /// read with `_mk_Get_Signal`. `None` if no Mailbox of the caller is activated.
+    pub fn _mk_Get_Mailbox_ID_Activated(&self, _appid_caller: AppId) -> Option<MK_MAILBOX_ID_u> {
+        let (caller, process) = self.find_process_appid(_appid_caller)?;
+        let caller = caller as MK_Index_t;
+        let start = process.mailbox_cursor.get() + 1;
+        let slot = (start..start + MK_MAILBOX_LIMIT)
+            .map(|slot| slot % MK_MAILBOX_LIMIT)
+            .find(|&slot| self.mailboxes[slot].as_ref().map_or(false, |mailbox| {
+                mailbox.get_owner_proc_i() == caller && mailbox.is_activated()
+            }))?;
+        process.mailbox_cursor.set(slot);
+        self.mailboxes[slot].as_ref().map(|mailbox| {
+            mailbox.clear_activated();
+            mailbox.get_mb_id()
+        })
+    }
+
+    /// Returns `true` if a Mailbox owned by the Process at `index` is activated.
+    pub(crate) fn has_activated_mailbox(&self, index: MK_Index_t) -> bool {
+        self.mailboxes.iter().flatten().any(|mailbox| {
+            mailbox.get_owner_proc_i() == index && mailbox.is_activated()
+        })
     }
     /// This function sends Signals to a Mailbox. The Signals sent are represented as a bitmap
     /// of Signal values and there is no priority among Signals as to the order of their arrival
     /// within the Mailbox.
     pub fn _mk_Send_Signal(&self,_hMailbox: MK_HANDLE_t,_eSignal: MK_BITMAP_t) ->  MK_ERROR_e{
-        let  mailbox = self.Get_Mailbox_ref_internal(_hMailbox);
-
-        if mailbox.is_some() {
-            mailbox.unwrap().add_sig(_eSignal);
-            MK_ERROR_NONE
-
-        } else { MK_ERROR_UNKNOWN_HANDLE }
+        match self.resolve_mailbox(_hMailbox) {
+            Ok(mailbox) => {
+                mailbox.add_sig(_eSignal);
+                self.waiter.map(|waiter| waiter.signal(_hMailbox));
+                self.com.map(|com| com.signal(mailbox.get_mb_id(), _eSignal));
+                MK_ERROR_NONE
+            }
+            Err(error) => error,
+        }
         // the case of access denied is not yet handled.
         //IF the caller Process is not defined as the sender Process of the Mailox throw an error
     }
@@ -270,25 +385,28 @@ impl  VppKernel {
     /// MK_SIGNAL_ERROR, and MK_SIGNAL_EXCEPTION are sent to that Mailbox.
     ///
     /// * Only the owner of the Mailbox can wait on it.
-    pub fn _mk_Wait_Signal(&self, _hMailbox: MK_HANDLE_t, _uTime: u32) -> MK_ERROR_e {
-    //How to change the state of the Process ?!!!!!!!!!!!!!!!!!!!!!!!!!!!!
-      /*  let mbox = self.Get_Mailbox_ref_internal(hMailbox);
-        if mbox.is_some(){
-
-            if _uTime == 0 {
-             // If the value is 0, the function will not wait for a Signal
-             // amd will return control to the called Process immediately
-                return MK_ERROR_NONE;
-            }
-            let proc_owner_index = mbox.unwrap().get_owner_proc_i();
-            let process = self.get_process_ref_index(proc_owner_index as usize)?;
-            // put the process that owns the mailbox in a Waiting State (TBD how can this be achieved in Tock)
-            process.waiting_vpp_process();
-            MK_ERROR_NONE
-        } else {
-            MK_ERROR_UNKNOWN_HANDLE
-        }*/
-        unimplemented!()
+    ///
+    /// `_uTime` is in milliseconds, `MK_TIME_INFINITE` waits without a time limit.
+    /// The Process is resumed through the `WAIT_SIGNAL_SUBSCRIBE` callback. With
+    /// `MK_HANDLE_ANY_MAILBOX`, it waits until one of its Mailboxes is activated, and
+    /// finds which ones with `_mk_Get_Mailbox_ID_Activated`.
+    pub fn _mk_Wait_Signal(&self, _hMailbox: MK_HANDLE_t, _uTime: u32,
+                           _appid_caller: AppId) -> MK_ERROR_e {
+        if _hMailbox == MK_HANDLE_ANY_MAILBOX {
+            return self.waiter.map_or(MK_ERROR_INTERNAL, |waiter| {
+                waiter.wait(_appid_caller, _hMailbox, _uTime)
+            });
+        }
+        let mailbox = match self.resolve_mailbox(_hMailbox) {
+            Ok(mailbox) => mailbox,
+            Err(error) => return error,
+        };
+        if mailbox.get_owner_proc_appid() != Some(_appid_caller) {
+            return MK_ERROR_ACCESS_DENIED;
+        }
+        self.waiter.map_or(MK_ERROR_INTERNAL, |waiter| {
+            waiter.wait(_appid_caller, _hMailbox, _uTime)
+        })
     }
 
     /// Get a Signal from a Mailbox.
@@ -313,177 +431,251 @@ impl  VppKernel {
     /// The granted access Process (reader) has read-only access.
     pub(crate) fn _mk_Get_IPC_Handle(&self, _eIPC_ID: MK_IPC_ID_u) -> Option<MK_HANDLE_t> {
         // Control Access is still missing
-        let handle = convert_ipcID_to_handle(_eIPC_ID);
-        let ipc = self.Get_IPC_ref_internal(handle);
-        if ipc.is_some() {Some(handle)} else {None}
+        let slot = self.ipcs.iter()
+            .position(|ipc| ipc.as_ref().map_or(false, |ipc| ipc.get_ipc_id() == _eIPC_ID))?;
+        self.ipc_handle(slot)
     }
 
 
     /// Get access to a shared memory area used by an IPC.
     /// This function returns the virtual memory address of the IPC. Since no MMU is provided.
     /// This will returns the physical memory address of the shared memory.
-     pub(crate) fn _mk_Get_Access_IPC(&self, _hIPC: MK_HANDLE_t, _appid_caller: AppId) -> Option<u8>{
-        // let _ipc = self.Get_IPC_ref_internal(handle)?;
-        // let _reader_index = _ipc.get_reader_proc_i();
-        // let _writer_index = _ipc.get_writer_proc_i();
-        // let process_reader_id = self.get_process_ref_index(_reader_index)?;
-        // let slice = process_reader_id.tockprocess.unwrap().
-        //     allow(base_address, _ipc.get_ipc_len() as usize);
-        // let process_writer_id = self.get_process_ref_index(_writer_index)?;
-        // let process_reader_appid= process_reader_id.tockprocess.unwrap().appid();
-        // let process_writer_appid = process_writer_id.tockprocess.unwrap().appid();
-        // _ipc.share_slice(process_reader_appid,process_writer_appid);
-
-        unimplemented!()
+    ///
+    /// Only the writer and the reader of the IPC can access it, one at a time. The reader
+    /// is granted an MPU region on the memory shared by the writer for as long as it holds
+    /// the IPC. On failure, `None` is returned and the error is stored for the caller.
+    pub(crate) fn _mk_Get_Access_IPC(&self, _hIPC: MK_HANDLE_t, _appid_caller: AppId) -> Option<*const u8> {
+        let result = self.get_access_ipc(_hIPC, _appid_caller);
+        self.get_process_ref_appid(_appid_caller).map(|process| {
+            process.error.set(result.err().unwrap_or(MK_ERROR_NONE))
+        });
+        result.ok()
+    }
 
+    fn get_access_ipc(&self, handle: MK_HANDLE_t, caller: AppId) -> Result<*const u8, MK_ERROR_e> {
+        let ipc = self.resolve_ipc(handle)?;
+        let caller_index = self.process_index(caller).ok_or(MK_ERROR_ACCESS_DENIED)?;
+        if caller_index != ipc.get_writer_proc_i() && caller_index != ipc.get_reader_proc_i() {
+            return Err(MK_ERROR_ACCESS_DENIED);
+        }
+        match ipc.get_holder() {
+            Some(holder) if holder == caller => {}
+            Some(_) => return Err(MK_ERROR_ACCESS_DENIED),
+            // The previous holder, if any, died without releasing the IPC.
+            None => self.revoke_ipc(ipc),
+        }
+        let writer = self.process_appid(ipc.get_writer_proc_i()).ok_or(MK_ERROR_INTERNAL)?;
+        let (buffer, length) = ipc.data.enter(writer, |data, _| {
+            data.shared_memory.as_ref().map(|slice| (slice.ptr(), slice.len()))
+        }).ok().flatten().ok_or(MK_ERROR_INTERNAL)?;
+        if ipc.get_holder().is_none() {
+            let reader_region = if caller_index == ipc.get_writer_proc_i() {
+                None
+            } else {
+                let reader = self.get_process_ref_index(caller_index)
+                    .and_then(|process| process.tockprocess.get())
+                    .ok_or(MK_ERROR_INTERNAL)?;
+                Some(reader.add_mpu_region(buffer, length, length, mpu::Permissions::ReadOnly).ok_or(MK_ERROR_INTERNAL)?)
+            };
+            ipc.set_holder(caller, reader_region);
+        }
+        Ok(buffer)
     }
+
     /// Release access to the IPC. This function allows releasing the access to the IPC.
     /// The Process can no longer access the shared memory. The IPC is a scarce resource,
     /// thus the number of access of IPC is limited (MK_IPC_LIMIT) at run time.
-    pub (crate) fn _mk_Release_Access_IPC(&self, _hIPC: MK_HANDLE_t) -> MK_ERROR_e {
-        unimplemented!( )
+    ///
+    /// The MPU region of the reader is revoked, and the other side of the IPC receives
+    /// `MK_SIGNAL_IPC_RELEASED` on the Mailbox it shares with the caller.
+    pub (crate) fn _mk_Release_Access_IPC(&self, _hIPC: MK_HANDLE_t, _appid_caller: AppId) -> MK_ERROR_e {
+        let ipc = match self.resolve_ipc(_hIPC) {
+            Ok(ipc) => ipc,
+            Err(error) => return error,
+        };
+        if ipc.get_holder() != Some(_appid_caller) {
+            return MK_ERROR_HANDLE_NOT_ACCESSED;
+        }
+        self.revoke_ipc(ipc);
+
+        let caller_index = match self.process_index(_appid_caller) {
+            Some(index) => index,
+            None => return MK_ERROR_NONE,
+        };
+        let peer_index = if caller_index == ipc.get_writer_proc_i() {
+            ipc.get_reader_proc_i()
+        } else {
+            ipc.get_writer_proc_i()
+        };
+        let shared_mailbox = self.mailboxes.iter().flatten().find(|mailbox| {
+            mailbox.get_owner_proc_i() == peer_index && mailbox.get_sender_proc_i() == caller_index
+        });
+        let mailbox_id = match shared_mailbox {
+            Some(mailbox) => Some(mailbox.get_mb_id()),
+            None => self.get_process_ref_index(peer_index).map(|peer| peer.kernel_mailbox.get()),
+        };
+        mailbox_id
+            .and_then(|id| self._mk_Get_Mailbox_Handle(id))
+            .map(|handle| self._mk_Send_Signal(handle, MK_SIGNAL_IPC_RELEASED));
+        MK_ERROR_NONE
+    }
+
+    /// Ends the access to `ipc` and revokes the MPU region of its reader.
+    fn revoke_ipc(&self, ipc: &ipc) {
+        if let Some(region) = ipc.clear_holder() {
+            self.get_process_ref_index(ipc.get_reader_proc_i())
+                .and_then(|process| process.tockprocess.get())
+                .map(|reader| reader.remove_mpu_region(region));
+        }
     }
 
     // 5) VRE Management
     // 6) Firmware Management
     // 7) Misc
     pub (crate) fn get_process_ref_index(&self, index: MK_Index_t) -> Option<&VppProcess> {
-        let mut return_pointer: Option<&VppProcess> = None;
-        for (i, process ) in self.vpp_processes.iter().enumerate(){
-            if i == index as usize {
-                return_pointer = Option::from(process);
-            } else {
-                return_pointer = None;
-            }
-        }
-        return_pointer
+        self.vpp_processes.get(index as usize).and_then(|process| process.as_ref())
     }
     /// Helper function to get a reference to a valid process based on a handle. It returns
     /// `None` if the process is in dead state or if the handle is not found.
-
     pub (crate) fn get_process_ref_handle(&self, handle: MK_HANDLE_t) -> Option<&VppProcess> {
-        // Mapping id to handle. For the time being,
-        // we consider the handle as the id but in 32 bits. This will probably be changed later.
-        let id = convert_to_id(handle);
-        let mut return_pointer: Option<&VppProcess>  = None;
-        for process in self.vpp_processes.iter() {
-            match process {
-                Some(proc) => {
-                    if proc.get_vpp_id() == id {
-                        // even if id found, the Process must not be in "DEAD" state
-                        if proc.get_vpp_state() == VppState::DEAD {
-                            proc.error.set(MK_ERROR_e::MK_ERROR_UNKNOWN_ID);
-                            return_pointer =  None;
-                        }
-                        // if the Process in any other state, a pointer to
-                        // that process is delivered with a success flag and
-                        // break of the loop
-                        else {
-                            proc.error.set(MK_ERROR_e::MK_ERROR_NONE);
-                            return_pointer = Some(proc);
-                            break;
-                        }
-                    }
-                    else {
-                        proc.error.set(MK_ERROR_UNKNOWN_ID);
-                        return_pointer = None;
-                    }
-                }
-
-                None => {
-                    return_pointer = None;
-                }
-            }
-        }
-        return_pointer
-        //Missing ACCESS DENIED!!!!!!!!!!!!!!!!!!!!!!!!!
+        self.live_process(handle).ok()
     }
-    /// Helper function to get a reference to a valid Mailbox  based on a handle. It returns
-    /// `None` if the handle is not found.
-
     /// Helper function to get a reference to a valid IPC  based on a handle. It returns
     /// `None` if the handle is not found.
     pub fn Get_IPC_ref_internal(&self, handle: MK_HANDLE_t) -> Option<&ipc> {
-        let IPC_ID = convert_handle_to_ipcID(handle);
-        let mut return_pointer: Option<&ipc> = None ;
-        for ipc in self.ipcs.iter(){
-            match ipc {
-                Some(ipc_struct) => { if ipc_struct.get_ipc_id() == IPC_ID {
-                    // self.last_error.set(MK_ERROR_NONE);
-                    return_pointer = Some(ipc_struct);
-                    break;
-                } else {
-                    // self.last_error.set(MK_ERROR_UNKNOWN_ID);
-                    return_pointer = None;
-                }},
-                None =>  {
-                    // self.last_error.set(MK_ERROR_UNKNOWN_ID);
-                    return_pointer = None;
-                }
-            }
-        }
-        return_pointer
+        self.resolve_ipc(handle).ok()
     }
+    /// First IPC read by the Process `reader_appid`.
     pub fn Get_IPC_from_reader_appid(&self, reader_appid: AppId) -> Option<&ipc> {
-        let mut return_pointer: Option<&ipc> = None ;
-        for ipc in self.ipcs.iter(){
-            match ipc {
-                Some(ipc_struct) => {
-                    if ipc_struct.get_reader_proc_i() == reader_appid.index().unwrap() as u16 {
-                    return_pointer = Some(ipc_struct);
-                    break;
-                } else {
-                    return_pointer = None;
-                }},
-                None =>  {
-                    return_pointer = None;
-                }
-            }
-        }
-        return_pointer
-
+        let reader = self.process_index(reader_appid)?;
+        self.ipcs.iter().flatten().find(|ipc| ipc.get_reader_proc_i() == reader)
     }
+    /// First IPC written by the Process `writer_appid`.
     pub fn Get_IPC_from_writer_appid(&self, writer_appid: AppId) -> Option<&ipc> {
-        let mut return_pointer: Option<&ipc> = None ;
-        for ipc in self.ipcs.iter(){
-            match ipc {
-                Some(ipc_struct) => {
-                    if ipc_struct.get_writer_proc_i() == writer_appid.index().unwrap() as u16 {
-                        return_pointer = Some(ipc_struct);
-                        break;
-                    } else {
-                        return_pointer = None;
-                    }},
-                None =>  {
-                    return_pointer = None;
-                }
-            }
-        }
-        return_pointer
-
+        let writer = self.process_index(writer_appid)?;
+        self.ipcs.iter().flatten().find(|ipc| ipc.get_writer_proc_i() == writer)
     }
-    /// Helper function to get a reference to a valid process based on the index of the process
-    /// in the group (array) of Processes. The function return `None` if there is no Process with
-    /// such index--.
+    /// Helper function to get a reference to a valid Mailbox  based on a handle. It returns
+    /// `None` if the handle is not found.
     pub fn Get_Mailbox_ref_internal(&self, handle: MK_HANDLE_t) -> Option<&mbox> {
-        let MailboxID = convert_handle_to_mbid(handle);
-        let mut return_pointer: Option<&mbox> = None ;
-        for mailbox in self.mailboxes.iter(){
-            match mailbox {
-                Some(mb) => { if mb.get_mb_id() == MailboxID {
-                    // self.last_error.set(MK_ERROR_NONE);
-                    return_pointer = Some(mb);
-                    break;
-                } else {
-                    // self.last_error.set(MK_ERROR_UNKNOWN_ID);
-                    return_pointer = None;
-                }},
-                None =>  {
-                    return_pointer = None;
-                }
+        self.resolve_mailbox(handle).ok()
+    }
+
+    // Handles
+    /// Handle of the Process at `index` in the group of Processes.
+    pub(crate) fn process_handle(&self, index: usize) -> Option<MK_HANDLE_t> {
+        self.get_process_ref_index(index as MK_Index_t).map(|process| {
+            Handle::new(HandleType::Process, process.get_generation(), index).into()
+        })
+    }
+    /// Handle of the Mailbox in `slot` of the Mailboxes.
+    pub(crate) fn mailbox_handle(&self, slot: usize) -> Option<MK_HANDLE_t> {
+        self.mailboxes.get(slot).and_then(|mailbox| mailbox.as_ref()).map(|mailbox| {
+            Handle::new(HandleType::Mailbox, mailbox.get_generation(), slot).into()
+        })
+    }
+    /// Handle of the IPC in `slot` of the IPCs.
+    pub(crate) fn ipc_handle(&self, slot: usize) -> Option<MK_HANDLE_t> {
+        self.ipcs.get(slot).and_then(|ipc| ipc.as_ref()).map(|ipc| {
+            Handle::new(HandleType::Ipc, ipc.get_generation(), slot).into()
+        })
+    }
+    /// Index and reference of the Process of `handle`, dead or alive.
+    /// `MK_ERROR_UNKNOWN_HANDLE` if the handle is stale, `MK_ERROR_ACCESS_DENIED` if it
+    /// is not the handle of a Process.
+    pub(crate) fn resolve_process(&self, handle: MK_HANDLE_t) -> Result<(usize, &VppProcess), MK_ERROR_e> {
+        let handle = Handle::decode(handle, HandleType::Process)?;
+        self.get_process_ref_index(handle.slot as MK_Index_t)
+            .filter(|process| process.get_generation() == handle.generation)
+            .map(|process| (handle.slot, process))
+            .ok_or(MK_ERROR_UNKNOWN_HANDLE)
+    }
+    /// Process of `handle`, which must not be dead.
+    pub(crate) fn live_process(&self, handle: MK_HANDLE_t) -> Result<&VppProcess, MK_ERROR_e> {
+        match self.resolve_process(handle)? {
+            (_, process) if process.get_vpp_state() == VppState::DEAD => Err(MK_ERROR_UNKNOWN_HANDLE),
+            (_, process) => Ok(process),
+        }
+    }
+    /// Mailbox of `handle`, with the errors of `resolve_process`.
+    pub(crate) fn resolve_mailbox(&self, handle: MK_HANDLE_t) -> Result<&mbox, MK_ERROR_e> {
+        let handle = Handle::decode(handle, HandleType::Mailbox)?;
+        self.mailboxes.get(handle.slot)
+            .and_then(|mailbox| mailbox.as_ref())
+            .filter(|mailbox| mailbox.get_generation() == handle.generation)
+            .ok_or(MK_ERROR_UNKNOWN_HANDLE)
+    }
+    /// IPC of `handle`, with the errors of `resolve_process`.
+    pub(crate) fn resolve_ipc(&self, handle: MK_HANDLE_t) -> Result<&ipc, MK_ERROR_e> {
+        let handle = Handle::decode(handle, HandleType::Ipc)?;
+        self.ipcs.get(handle.slot)
+            .and_then(|ipc| ipc.as_ref())
+            .filter(|ipc| ipc.get_generation() == handle.generation)
+            .ok_or(MK_ERROR_UNKNOWN_HANDLE)
+    }
+    /// Raises `exception` on `process` and sends `MK_SIGNAL_EXCEPTION` to its kernel Mailbox.
+    pub(crate) fn notify_exception(&self, process: &VppProcess, exception: MK_EXCEPTION_e) {
+        process.raise_exception(exception);
+        self._mk_Get_Mailbox_Handle(process.kernel_mailbox.get())
+            .map(|handle| self._mk_Send_Signal(handle, MK_SIGNAL_e::MK_SIGNAL_EXCEPTION.into()));
+    }
+    /// Invalidates the handles of the Process at `index` and of the objects it owns: its
+    /// Mailboxes and the IPCs it writes.
+    pub(crate) fn invalidate_handles(&self, index: usize) {
+        self.get_process_ref_index(index as MK_Index_t)
+            .map(|process| process.invalidate_handles());
+        self.mailboxes.iter().flatten()
+            .filter(|mailbox| mailbox.get_owner_proc_i() as usize == index)
+            .for_each(|mailbox| mailbox.invalidate_handles());
+        self.ipcs.iter().flatten()
+            .filter(|ipc| ipc.get_writer_proc_i() as usize == index)
+            .for_each(|ipc| ipc.invalidate_handles());
+    }
+    /// Returns `true` if the Process at `index` is the Process at `ancestor` or one of
+    /// its descendants.
+    pub(crate) fn is_descendant(&self, index: MK_Index_t, ancestor: MK_Index_t) -> bool {
+        let mut current = index;
+        // A group has no cycle other than the root being its own parent.
+        for _ in 0..NUM_PROCS {
+            if current == ancestor {
+                return true;
+            }
+            match self.get_process_ref_index(current) {
+                Some(process) if process.parent.get() != current => current = process.parent.get(),
+                _ => return false,
             }
         }
-        return_pointer
+        false
+    }
+    /// Helper function to get a reference to the Vpp Process backed by the Tock Process `appid`.
+    pub (crate) fn get_process_ref_appid(&self, appid: AppId) -> Option<&VppProcess> {
+        self.find_process_appid(appid).map(|(_, process)| process)
+    }
+    /// Helper function to get the index in the group and a reference to the Process
+    /// whose Tock process is `appid`.
+    fn find_process_appid(&self, appid: AppId) -> Option<(usize, &VppProcess)> {
+        self.vpp_processes.iter().enumerate()
+            .filter_map(|(index, process)| process.as_ref().map(|process| (index, process)))
+            .find(|(_, process)| {
+                process.tockprocess.get().map_or(false, |tockprocess| tockprocess.appid() == appid)
+            })
+    }
+    /// Index in the group of the Process whose Tock process is `appid`. The whole
+    /// `AppId` is compared, so an app loaded in the slot of an unloaded Process is not
+    /// taken for it and gets `None`.
+    pub (crate) fn process_index(&self, appid: AppId) -> Option<MK_Index_t> {
+        self.find_process_appid(appid).map(|(index, _)| index as MK_Index_t)
+    }
+    /// Helper function to get the current `AppId` of the Process at `index` in the group.
+    pub (crate) fn process_appid(&self, index: MK_Index_t) -> Option<AppId> {
+        self.get_process_ref_index(index)
+            .and_then(|process| process.tockprocess.get())
+            .map(|tockprocess| tockprocess.appid())
+    }
+    /// Returns `true` if `appid` is the MGT Process of the VPP.
+    pub fn is_mgt_process(&self, appid: AppId) -> bool {
+        self.get_process_ref_appid(appid)
+            .map_or(false, |process| process.get_vpp_id() == MK_PROCESS_MGT_VPP_ID)
     }
     pub fn Convert_Appid_to_Index(&self, appid: AppId) -> MK_Index_t {
         appid.index().unwrap() as u16
@@ -499,6 +691,72 @@ impl  VppKernel {
     }
 }
 
+/// Exception model of the VPP. The kernel calls this policy when a Process faulted,
+/// whether by an MPU violation, an invalid system call or a stack overflow, so the
+/// board uses `FaultResponse::Restart(vpp_kernel)`.
+///
+/// The faulted Process raises `MK_EXCEPTION_SEVERE`. Its parent raises
+/// `MK_EXCEPTION_ERROR` if the Process is restarted, `MK_EXCEPTION_CHILD_PROCESS_DIED`
+/// otherwise, and receives `MK_SIGNAL_EXCEPTION` on its kernel Mailbox. A root Process
+/// is its own parent and finds the Signal once restarted.
+impl ProcessRestartPolicy for VppKernel {
+    fn should_restart(&self, process: &dyn ProcessType) -> bool {
+        let (index, vpp_process) = match self.find_process_appid(process.appid()) {
+            Some(found) => found,
+            None => return false,
+        };
+        vpp_process.raise_exception(MK_EXCEPTION_e::MK_EXCEPTION_SEVERE);
+        let restart = match vpp_process.restart_policy.get() {
+            VppRestartPolicy::Always => true,
+            VppRestartPolicy::Threshold(threshold) => process.get_restart_count() <= threshold,
+            VppRestartPolicy::Never => false,
+        };
+        vpp_process.vppstate.set(if restart { VppState::READY } else { VppState::DEAD });
+        self.invalidate_handles(index);
+
+        if let Some(parent) = self.get_process_ref_index(vpp_process.parent.get()) {
+            if vpp_process.parent.get() as usize != index {
+                parent.raise_exception(if restart {
+                    MK_EXCEPTION_e::MK_EXCEPTION_ERROR
+                } else {
+                    MK_EXCEPTION_e::MK_EXCEPTION_CHILD_PROCESS_DIED
+                });
+            }
+            self._mk_Get_Mailbox_Handle(parent.kernel_mailbox.get())
+                .map(|handle| self._mk_Send_Signal(handle, MK_SIGNAL_e::MK_SIGNAL_EXCEPTION.into()));
+        }
+        restart
+    }
+}
+
+/// Keeps the `VppState` of the Processes in step with their Tock process. The board
+/// registers the VPP kernel with `Kernel::add_process_observer`.
+///
+/// An unloaded Tock process is forgotten before its memory is reused: its Process is
+/// `DEAD` and the handles to it, its Mailboxes and its IPCs are invalid.
+impl ProcessObserver<'static> for VppKernel {
+    fn process_event(&self, appid: AppId, event: ProcessEvent) {
+        match event {
+            ProcessEvent::StateChanged(from, to) => {
+                self.get_process_ref_appid(appid)
+                    .map(|process| process.follow_tock_state(from, to));
+            }
+            ProcessEvent::Unloaded => {
+                if let Some((index, process)) = self.find_process_appid(appid) {
+                    process.tockprocess.set(None);
+                    process.vppstate.set(VppState::DEAD);
+                    self.invalidate_handles(index);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn next_observer(&'static self) -> &'static ListLink<'static, dyn ProcessObserver<'static>> {
+        &self.observer_link
+    }
+}
+
 /// Syscall Driver for VPP ABI/API Kernel functions
 pub struct vpp_kernel_driver {
     vpp_kernel: &'static VppKernel,
@@ -506,131 +764,250 @@ pub struct vpp_kernel_driver {
 
 impl vpp_kernel_driver{
     pub fn new(vpp_kernel:  &'static VppKernel) -> vpp_kernel_driver{vpp_kernel_driver{vpp_kernel}}
+
+    /// Stores `error` as the last error of the calling Process.
+    fn set_error(&self, appid: AppId, error: MK_ERROR_e) {
+        self.vpp_kernel.get_process_ref_appid(appid).map(|process| process.error.set(error));
+    }
+
+    /// Checks that the caller manages the Process of `handle`: it is one of its
+    /// ancestors or, if `itself` is set, that Process.
+    fn check_process(&self, appid: AppId, handle: MK_HANDLE_t, itself: bool) -> Result<(), MK_ERROR_e> {
+        let caller = self.vpp_kernel.process_index(appid).ok_or(MK_ERROR_ACCESS_DENIED)?;
+        let (index, _) = self.vpp_kernel.resolve_process(handle)?;
+        let index = index as MK_Index_t;
+        if (itself || index != caller) && self.vpp_kernel.is_descendant(index, caller) {
+            Ok(())
+        } else {
+            Err(MK_ERROR_ACCESS_DENIED)
+        }
+    }
+
+    /// Checks that the caller is the owner of the Mailbox of `handle` or, if `sender` is
+    /// set, its sender.
+    fn check_mailbox(&self, appid: AppId, handle: MK_HANDLE_t, sender: bool) -> Result<(), MK_ERROR_e> {
+        let caller = self.vpp_kernel.process_index(appid).ok_or(MK_ERROR_ACCESS_DENIED)?;
+        let mailbox = self.vpp_kernel.resolve_mailbox(handle)?;
+        if mailbox.get_owner_proc_i() == caller || (sender && mailbox.get_sender_proc_i() == caller) {
+            Ok(())
+        } else {
+            Err(MK_ERROR_ACCESS_DENIED)
+        }
+    }
+
+    /// Checks that the caller is the writer or the reader of the IPC of `handle`.
+    fn check_ipc(&self, appid: AppId, handle: MK_HANDLE_t) -> Result<&ipc, MK_ERROR_e> {
+        let caller = self.vpp_kernel.process_index(appid).ok_or(MK_ERROR_ACCESS_DENIED)?;
+        let ipc = self.vpp_kernel.resolve_ipc(handle)?;
+        if ipc.get_writer_proc_i() == caller || ipc.get_reader_proc_i() == caller {
+            Ok(ipc)
+        } else {
+            Err(MK_ERROR_ACCESS_DENIED)
+        }
+    }
+
+    /// Returns the handle, or 0 after storing the error for the caller.
+    fn handle_value(&self, appid: AppId, handle: Result<MK_HANDLE_t, MK_ERROR_e>) -> ReturnCode {
+        match handle {
+            Ok(handle) => ReturnCode::SuccessWithValue { value: handle as usize },
+            Err(error) => {
+                self.set_error(appid, error);
+                ReturnCode::SuccessWithValue { value: 0 }
+            }
+        }
+    }
+
+    /// Returns the value, or `FAIL` after storing the error for the caller.
+    fn value_or_fail(&self, appid: AppId, value: Result<usize, MK_ERROR_e>) -> ReturnCode {
+        match value {
+            Ok(value) => ReturnCode::SuccessWithValue { value: value },
+            Err(error) => {
+                self.set_error(appid, error);
+                ReturnCode::FAIL
+            }
+        }
+    }
 }
 
+/// Userspace interface, versioned by `VPP_ABI_VERSION`. The commands returning an
+/// `MK_ERROR_e` return it as value; the others return 0 (handles) or `FAIL` on
+/// failure, and store the error for `CMD_GET_ERROR`.
+///
+/// A Process manages itself and its descendants: it gets their handles, priorities
+/// and errors. Only an ancestor suspends or resumes a Process. A Process gets the
+/// handle of the Mailboxes it owns or sends to, sends Signals to the Mailboxes it is
+/// the sender of, and gets the Signals of the Mailboxes it owns. Only the writer and
+/// the reader of an IPC get its handle and size.
+///
+/// The caller is identified by its whole `AppId`. An app which is not a Process, such
+/// as one loaded in the slot of an unloaded Process, has none of these rights.
+///
+/// - allow: shared memory of the IPC written by the caller.
+/// - subscribe `WAIT_SIGNAL_SUBSCRIBE`: resume callback of `CMD_WAIT_SIGNAL`.
+/// - subscribe Mailbox handle: callback of the sender when the Mailbox is signaled.
 impl Driver  for vpp_kernel_driver {
     fn command(&self,
                command_num: usize,
                data: usize,
                data2: usize,
-               _appid: AppId) -> ReturnCode {
+               appid: AppId) -> ReturnCode {
         match command_num {
-            0 => ReturnCode::SUCCESS,
-            /*1 =>
-                {
-                    debug!("Suspending Process");
-                    //debug!("Data is {:?}", data);
-                    let handle = convert_to_handle(data as u16);
-                    //let handle = self.vpm._mk_get_process_handle(0);
-                    let error_result = self.vpm._mk_suspend_process(handle);
-                    let ret = MK_ERROR_e::into(error_result);
-                    self.vpm._mk_Get_Error();
-                    debug!("Last Error {:?}", self.vpm.last_error.get());
-                    ReturnCode::SuccessWithValue {
-                        value: ret
-                    }
-                },
-            2 =>
-                {
-                    debug!("Resuming Process");
-                    let handle = convert_to_handle(data as u16);
-                    let error_result = self.vpm._mk_resume_process(handle);
-                    let ret = MK_ERROR_e::into(error_result);
-                    self.vpm._mk_Get_Error();
-                    ReturnCode::SuccessWithValue {
-                        value: ret
+            CMD_DRIVER_CHECK => ReturnCode::SuccessWithValue { value: VPP_ABI_VERSION },
+            CMD_GET_PROCESS_HANDLE => {
+                let handle = self.vpp_kernel
+                    ._mk_Get_Process_Handle(data as MK_Process_ID_u)
+                    .ok_or(MK_ERROR_UNKNOWN_ID)
+                    .and_then(|handle| self.check_process(appid, handle, true).map(|_| handle));
+                self.handle_value(appid, handle)
+            },
+            CMD_SUSPEND_PROCESS => {
+                let error = match self.check_process(appid, data as MK_HANDLE_t, false) {
+                    Ok(()) => self.vpp_kernel._mk_suspend_process(data as MK_HANDLE_t),
+                    Err(error) => error,
+                };
+                ReturnCode::SuccessWithValue {value: error.into()}
+            },
+            CMD_RESUME_PROCESS => {
+                let error = match self.check_process(appid, data as MK_HANDLE_t, false) {
+                    Ok(()) => self.vpp_kernel._mk_resume_process(data as MK_HANDLE_t),
+                    Err(error) => error,
+                };
+                ReturnCode::SuccessWithValue {value: error.into()}
+            },
+            CMD_YIELD => {
+                // The caller runs again on its next callback. `mk_Yield` reports
+                // no error.
+                let _ = self.vpp_kernel.process_index(appid)
+                    .and_then(|index| self.vpp_kernel.process_handle(index as usize))
+                    .map(|handle| self.vpp_kernel._mk_yield(handle));
+                ReturnCode::SUCCESS
+            },
+            CMD_GET_MAILBOX_HANDLE => {
+                let handle = self.vpp_kernel
+                    ._mk_Get_Mailbox_Handle(data as MK_MAILBOX_ID_u)
+                    .ok_or(MK_ERROR_UNKNOWN_ID)
+                    .and_then(|handle| self.check_mailbox(appid, handle, true).map(|_| handle));
+                self.handle_value(appid, handle)
+            },
+            CMD_SEND_SIGNAL => {
+                let error = match self.vpp_kernel.resolve_mailbox(data as MK_HANDLE_t) {
+                    Ok(mailbox) if self.vpp_kernel.process_index(appid) == Some(mailbox.get_sender_proc_i()) => {
+                        self.vpp_kernel._mk_Send_Signal(data as MK_HANDLE_t, data2 as MK_BITMAP_t)
                     }
-                },
-            3 =>
-                {
-                    debug!("Yielding  Process");
-                    let handle = convert_to_handle(appid.id() as u16  );
-                    debug!("ID of process {:?}",appid);
-                    self.vpm._mk_yield(handle);
-                    self.vpm._mk_Get_Error();
-                    ReturnCode::SUCCESS
-                },*/
-            1 =>
-                {
-                    let handle =
-                        self
-                            .vpp_kernel
-                            ._mk_Get_Process_Handle(data as MK_Process_ID_u);
-                    if handle.is_some() {ReturnCode::SuccessWithValue {value: handle.unwrap() as usize} }
-                    else { ReturnCode::SuccessWithValue {value: 0}}
-                },
-            5 => {
-                let mb_handle =
-                    self
-                        .vpp_kernel
-                        ._mk_Get_Mailbox_Handle(data as MK_MAILBOX_ID_u);
-                if mb_handle.is_some() {ReturnCode::SuccessWithValue {value: mb_handle.unwrap() as usize} }
-                else { ReturnCode::SuccessWithValue {value: 0}}
+                    Ok(_) => MK_ERROR_ACCESS_DENIED,
+                    Err(error) => error,
+                };
+                ReturnCode::SuccessWithValue {value: error.into()}
             },
-            6 => {
-                let error = self.vpp_kernel.
-                    _mk_Send_Signal( data as MK_HANDLE_t,
-                                    data2 as MK_BITMAP_t
-
-                ).into();
-
-                ReturnCode::SuccessWithValue {value:error}
+            CMD_GET_SIGNAL => {
+                let bitmap = self.check_mailbox(appid, data as MK_HANDLE_t, false).and_then(|_| {
+                    self.vpp_kernel
+                        ._mk_Get_Signal(data as MK_HANDLE_t)
+                        .ok_or(MK_ERROR_UNKNOWN_HANDLE)
+                });
+                self.value_or_fail(appid, bitmap.map(|bitmap| bitmap as usize))
             },
-            7 => {
-                // debug!("Retrieving last  Signal");
-                let bitmap = self.vpp_kernel
-                    ._mk_Get_Signal(data as MK_HANDLE_t);
-                if bitmap.is_some(){
-                    ReturnCode::SuccessWithValue {value: bitmap.unwrap() as usize }
-                }
-                else { ReturnCode::FAIL}
+            CMD_COMMIT => {
+                let error = self.vpp_kernel._mk_Commit(appid);
+                ReturnCode::SuccessWithValue {value: error.into()}
+            },
+            CMD_ROLLBACK => {
+                let error = self.vpp_kernel._mk_Rollback(appid);
+                ReturnCode::SuccessWithValue {value: error.into()}
+            },
+            CMD_WAIT_SIGNAL => {
+                let error = self.vpp_kernel
+                    ._mk_Wait_Signal(data as MK_HANDLE_t, data2 as u32, appid);
+                ReturnCode::SuccessWithValue {value: error.into()}
             },
-            15 => {
-                let ipc = self.vpp_kernel.Get_IPC_ref_internal(data as u32);
-                if ipc.is_some(){
-                    let writer_proc_i = ipc.unwrap().get_writer_proc_i();
-                    let writer_appid = self
-                        .vpp_kernel.Convert_Index_to_Appid(writer_proc_i);
-                    if writer_appid.is_some() {
-                        ipc.unwrap().data.enter(writer_appid.unwrap(), |data, _| {
-                            if data.shared_memory.as_ref().is_some() {
-                                let shared_buffer = data.shared_memory.as_ref().unwrap().ptr();
-                                debug!("Shared Buffer {:?}", shared_buffer);
-                                ReturnCode::SuccessWithValue { value: shared_buffer as usize }
-                            } else {
-                                ReturnCode::FAIL
-                            }
-                        }).unwrap_or(ReturnCode::EBUSY)
-                    }else {
-                        ReturnCode::FAIL
+            CMD_GET_EXCEPTION => {
+                let exceptions = self.vpp_kernel
+                    ._mk_Get_Exception(data as MK_HANDLE_t, appid);
+                self.value_or_fail(appid, exceptions.map(|exceptions| exceptions as usize))
+            },
+            CMD_GET_PROCESS_PRIORITY => {
+                let priority = self.check_process(appid, data as MK_HANDLE_t, true).map(|_| {
+                    u16::from(self.vpp_kernel._mk_get_process_priority(data as MK_HANDLE_t)) as usize
+                });
+                self.value_or_fail(appid, priority)
+            },
+            CMD_SET_PROCESS_PRIORITY => {
+                let error = match (
+                    self.check_process(appid, data as MK_HANDLE_t, true),
+                    MK_PROCESS_PRIORITY_e::try_from(data2 as u16),
+                ) {
+                    (Err(error), _) => error,
+                    (Ok(()), Err(())) => MK_ERROR_UNKNOWN_PRIORITY,
+                    (Ok(()), Ok(priority)) => {
+                        self.vpp_kernel._mk_set_process_priority(data as MK_HANDLE_t, priority)
                     }
+                };
+                ReturnCode::SuccessWithValue {value: error.into()}
+            },
+            CMD_GET_TIME => {
+                // The time does not fit in the value: `data` selects its low (0) or high
+                // (1) word. The caller reads high, low, high and retries if the high word
+                // changed.
+                let time = self.vpp_kernel._mk_Get_Time();
+                match data {
+                    0 => ReturnCode::SuccessWithValue { value: time as u32 as usize },
+                    1 => ReturnCode::SuccessWithValue { value: (time >> 32) as u32 as usize },
+                    _ => ReturnCode::EINVAL,
+                }
+            },
+            CMD_GET_ACCESS_IPC => {
+                let buffer = self.vpp_kernel._mk_Get_Access_IPC(data as MK_HANDLE_t, appid);
+                if buffer.is_some() {
+                    ReturnCode::SuccessWithValue { value: buffer.unwrap() as usize }
                 } else {
                     ReturnCode::FAIL
                 }
             },
-            20 => {
-                let ipc = self.vpp_kernel.Get_IPC_ref_internal(data as u32);
-                // Error Handling Missing
-                if ipc.is_some() {
-                    let size = ipc.unwrap().get_ipc_len();
-                    ReturnCode::SuccessWithValue { value: size as usize }
+            CMD_RELEASE_ACCESS_IPC => {
+                let error = self.vpp_kernel._mk_Release_Access_IPC(data as MK_HANDLE_t, appid);
+                ReturnCode::SuccessWithValue {value: error.into()}
+            },
+            CMD_GET_MAILBOX_ID_ACTIVATED => {
+                let mailbox_id = self.vpp_kernel._mk_Get_Mailbox_ID_Activated(appid);
+                if mailbox_id.is_some() {
+                    ReturnCode::SuccessWithValue { value: mailbox_id.unwrap() as usize }
                 } else {
                     ReturnCode::FAIL
                 }
             },
-            21 => {
-                let ipc_handle = self.vpp_kernel._mk_Get_IPC_Handle(data as u16);
-                // Error Handling Missing
-                if ipc_handle.is_some() {ReturnCode::SuccessWithValue {value: ipc_handle.unwrap() as usize} }
-                else { ReturnCode::SuccessWithValue {value: 0}}
+            CMD_GET_IPC_SIZE => {
+                let size = self.check_ipc(appid, data as MK_HANDLE_t)
+                    .map(|ipc| ipc.get_ipc_len() as usize);
+                self.value_or_fail(appid, size)
+            },
+            CMD_GET_IPC_HANDLE => {
+                let handle = self.vpp_kernel
+                    ._mk_Get_IPC_Handle(data as MK_IPC_ID_u)
+                    .ok_or(MK_ERROR_UNKNOWN_ID)
+                    .and_then(|handle| self.check_ipc(appid, handle).map(|_| handle));
+                self.handle_value(appid, handle)
+            },
+            CMD_GET_LIBRARY_ENTRY_TABLE => {
+                let entry_table = self.vpp_kernel
+                    .get_library_entry_table(data as MK_LIB_ID_u, appid);
+                if entry_table.is_some() {
+                    ReturnCode::SuccessWithValue { value: entry_table.unwrap() }
+                } else {
+                    ReturnCode::FAIL
+                }
             },
             30 => {
                 store_vpp_sec!(buff,500);
                 ReturnCode::SUCCESS
 
             },
-            100 => {
-                let error = self.vpp_kernel._mk_Get_Error(data as u32);
+            CMD_GET_ERROR => {
+                let error = match self.check_process(appid, data as MK_HANDLE_t, true) {
+                    Err(MK_ERROR_ACCESS_DENIED) if self.vpp_kernel.resolve_process(data as MK_HANDLE_t).is_ok() => {
+                        MK_ERROR_ACCESS_DENIED
+                    }
+                    _ => self.vpp_kernel._mk_Get_Error(data as MK_HANDLE_t),
+                };
                 ReturnCode::SuccessWithValue {value: error.into() }
 
             }
@@ -645,18 +1022,14 @@ impl Driver  for vpp_kernel_driver {
     ) -> ReturnCode {
         let owner_ipc = self.vpp_kernel.Get_IPC_from_writer_appid(appid);
         if owner_ipc.is_some(){
-            let reader_ipc = owner_ipc.unwrap().get_reader_proc_i();
-            let reader_appid =
-                self.vpp_kernel.
-                    Convert_Index_to_Appid(reader_ipc);
+            // The shared memory cannot move while the reader accesses it.
+            if owner_ipc.unwrap().get_holder().map_or(false, |holder| holder != appid) {
+                return ReturnCode::EBUSY;
+            }
             owner_ipc.unwrap().data.enter(appid,|data,_|{
-                // Register the shared memory in the Grant region of the writer/owner Process
+                // Register the shared memory in the Grant region of the writer/owner Process.
+                // The reader is given access to it by _mk_Get_Access_IPC.
                 data.shared_memory= shared_mem;
-                // Expose that shared memory the reader_appid from the IPC struct
-                if reader_appid.is_some(){
-                    debug!("Exposing");
-                    unsafe {data.shared_memory.as_ref().unwrap().expose_to(reader_appid.unwrap())};
-                 }
                 ReturnCode::SUCCESS
             }).unwrap_or(ReturnCode::EBUSY);
             ReturnCode::SUCCESS
@@ -671,11 +1044,16 @@ impl Driver  for vpp_kernel_driver {
         callback: Option<Callback>,
         app_id: AppId,
     ) -> ReturnCode {
-        //TODO: Missing change the state of the process to waiting
-
+        if handle == WAIT_SIGNAL_SUBSCRIBE {
+            return self.vpp_kernel.waiter
+                .map_or(ReturnCode::ENOSUPPORT, |waiter| waiter.subscribe(callback, app_id));
+        }
         // This is the mailbox that is being sent to. The app_id is however the sender process.
-        let mailbox= self.vpp_kernel.Get_Mailbox_ref_internal(handle as MK_HANDLE_t);
-        let ret = mailbox.unwrap().data.enter(app_id, |data,_| {
+        let mailbox = match self.vpp_kernel.resolve_mailbox(handle as MK_HANDLE_t) {
+            Ok(mailbox) if self.vpp_kernel.process_index(app_id) == Some(mailbox.get_sender_proc_i()) => mailbox,
+            _ => return ReturnCode::EINVAL,
+        };
+        let ret = mailbox.data.enter(app_id, |data,_| {
             data.callback = callback;
             ReturnCode::SuccessWithValue {value: 0x007}
         }).unwrap_or(ReturnCode::EBUSY);
@@ -0,0 +1,568 @@
+//! VRE broker of the VPP: access of the VPP Processes to the hardware functions of the
+//! Execution Domain.
+//!
+//! A VPP Process attaches to a VRE (`MK_VRE_e`) before using it. Attaching is only
+//! granted if the firmware descriptor of the Process lists the VRE in its `m_eVRE`.
+//! The VREs are backed by the Tock crypto HILs:
+//!
+//! * `MK_VRE_AES`: `hil::symmetric_encryption`, AES-128 ECB.
+//! * `MK_VRE_HASH`: `hil::digest`, SHA-256.
+//! * `MK_VRE_RNG`: `hil::rng`.
+//!
+//! The other VREs have no backend and cannot be attached.
+//!
+//! A VRE is revoked by the MGT Process, or when its hardware reports an error. The
+//! Processes attached to a revoked VRE are detached, raise `MK_EXCEPTION_VRE_DETACHED`
+//! and receive `MK_SIGNAL_EXCEPTION` on their kernel Mailbox. A revoked VRE stays
+//! unavailable until the Primary Platform restarts. Hardware too busy to start a
+//! request is not an error: the request fails with `EBUSY` and may be retried.
+//!
+//! Userspace interface:
+//!
+//! - allow 0: input buffer.
+//! - allow 1: output buffer.
+//! - allow 2: key buffer, 16 bytes.
+//! - subscribe 0: completion callback `(VRE, MK_ERROR_e, length)`.
+//! - command 0: driver check.
+//! - command 1: attach to the VRE `data`.
+//! - command 2: detach from the VRE `data`.
+//! - command 3: AES-128 ECB of `data2` bytes of the input, a multiple of 16, into the
+//!   output. `data` is 1 to encrypt, 0 to decrypt.
+//! - command 4: SHA-256 of `data` bytes of the input into the output.
+//! - command 5: `data` random bytes into the output.
+//! - command 6: revoke the VRE `data`, MGT Process only.
+//!
+//! The commands return the `MK_ERROR_e` of the request as value, or `EBUSY` while
+//! another request is in progress.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//!     let vre_hmac = static_init!(
+//!         VirtualMuxHmac<'static, lowrisc::hmac::Hmac, [u8; 32]>,
+//!         VirtualMuxHmac::new(mux_hmac)
+//!     );
+//!     let vre = static_init!(
+//!         capsules::vpp::vre::VreBroker<
+//!             earlgrey::aes::Aes<'static>,
+//!             VirtualMuxHmac<'static, lowrisc::hmac::Hmac, [u8; 32]>,
+//!         >,
+//!         capsules::vpp::vre::VreBroker::new(
+//!             vpp_kernel,
+//!             Some(&earlgrey::aes::AES),
+//!             Some(vre_hmac),
+//!             None,
+//!             &mut VRE_BUFFER,
+//!             &mut VRE_DIGEST,
+//!             board_kernel.create_grant(&memory_allocation_cap),
+//!         )
+//!     );
+//!     hil::symmetric_encryption::AES128::set_client(&earlgrey::aes::AES, vre);
+//!     hil::digest::Digest::set_client(vre_hmac, vre);
+//! ```
+
+use crate::driver;
+use crate::vpp::mloi::MK_ERROR_e::*;
+use crate::vpp::mloi::{MK_BITMAP_t, MK_ERROR_e, MK_EXCEPTION_e, MK_VRE_e};
+use crate::vpp::vppkernel::VppKernel;
+use core::cell::Cell;
+use core::cmp;
+use core::convert::TryFrom;
+use kernel::common::cells::{OptionalCell, TakeCell};
+use kernel::common::leasable_buffer::LeasableBuffer;
+use kernel::hil::digest::{self, Digest};
+use kernel::hil::rng::{self, Rng};
+use kernel::hil::symmetric_encryption::{self, AES128, AES128ECB, AES128_KEY_SIZE};
+use kernel::{AppId, AppSlice, Callback, Driver, Grant, ReturnCode, Shared};
+
+pub const DRIVER_NUM: usize = driver::NUM::VppVreDriver as usize;
+
+/// Size of the kernel buffer of the broker, the longest AES or SHA-256 input.
+pub const VRE_BUFFER_SIZE: usize = 128;
+
+/// Length of a SHA-256 digest.
+const DIGEST_SIZE: usize = 32;
+
+#[derive(Default)]
+pub struct App {
+    /// VREs the Process is attached to, ORing of `MK_VRE_e`.
+    attached: MK_BITMAP_t,
+    callback: Option<Callback>,
+    input: Option<AppSlice<Shared, u8>>,
+    output: Option<AppSlice<Shared, u8>>,
+    key: Option<AppSlice<Shared, u8>>,
+}
+
+/// Request in progress on a VRE.
+#[derive(Clone, Copy)]
+struct Request {
+    appid: AppId,
+    vre: MK_VRE_e,
+    length: usize,
+    /// Bytes produced so far, for the RNG.
+    done: usize,
+}
+
+pub struct VreBroker<
+    A: 'static + AES128<'static> + AES128ECB,
+    D: 'static + Digest<'static, [u8; DIGEST_SIZE]>,
+> {
+    vpp_kernel: &'static VppKernel,
+    aes: Option<&'static A>,
+    digest: Option<&'static D>,
+    rng: Option<&'static dyn Rng<'static>>,
+    buffer: TakeCell<'static, [u8]>,
+    digest_buffer: TakeCell<'static, [u8; DIGEST_SIZE]>,
+    current: OptionalCell<Request>,
+    /// Revoked VREs, ORing of `MK_VRE_e`.
+    revoked: Cell<MK_BITMAP_t>,
+    apps: Grant<App>,
+}
+
+impl<A: 'static + AES128<'static> + AES128ECB, D: 'static + Digest<'static, [u8; DIGEST_SIZE]>>
+    VreBroker<A, D>
+{
+    pub fn new(
+        vpp_kernel: &'static VppKernel,
+        aes: Option<&'static A>,
+        digest: Option<&'static D>,
+        rng: Option<&'static dyn Rng<'static>>,
+        buffer: &'static mut [u8; VRE_BUFFER_SIZE],
+        digest_buffer: &'static mut [u8; DIGEST_SIZE],
+        grant: Grant<App>,
+    ) -> VreBroker<A, D> {
+        VreBroker {
+            vpp_kernel: vpp_kernel,
+            aes: aes,
+            digest: digest,
+            rng: rng,
+            buffer: TakeCell::new(buffer),
+            digest_buffer: TakeCell::new(digest_buffer),
+            current: OptionalCell::empty(),
+            revoked: Cell::new(0),
+            apps: grant,
+        }
+    }
+
+    /// Returns `true` if `vre` has a backend and has not been revoked.
+    fn available(&self, vre: MK_VRE_e) -> bool {
+        let backed = match vre {
+            MK_VRE_e::MK_VRE_AES => self.aes.is_some(),
+            MK_VRE_e::MK_VRE_HASH => self.digest.is_some(),
+            MK_VRE_e::MK_VRE_RNG => self.rng.is_some(),
+            _ => false,
+        };
+        backed && self.revoked.get() & u32::from(vre) == 0
+    }
+
+    /// Attaches `appid` to `vre`, if its firmware descriptor lists it.
+    fn attach(&self, appid: AppId, vre: MK_VRE_e) -> MK_ERROR_e {
+        let rights = match self.vpp_kernel.get_process_ref_appid(appid) {
+            Some(process) => process.get_vre_rights(),
+            None => return MK_ERROR_ACCESS_DENIED,
+        };
+        if rights & u32::from(vre) == 0 {
+            return MK_ERROR_ACCESS_DENIED;
+        }
+        if !self.available(vre) {
+            return MK_ERROR_UNKNOWN_ID;
+        }
+        self.apps
+            .enter(appid, |app, _| {
+                app.attached |= u32::from(vre);
+                MK_ERROR_NONE
+            })
+            .unwrap_or(MK_ERROR_INTERNAL)
+    }
+
+    fn detach(&self, appid: AppId, vre: MK_VRE_e) -> MK_ERROR_e {
+        self.apps
+            .enter(appid, |app, _| {
+                app.attached &= !u32::from(vre);
+                MK_ERROR_NONE
+            })
+            .unwrap_or(MK_ERROR_INTERNAL)
+    }
+
+    /// Revokes `vre`: detaches the attached Processes and raises
+    /// `MK_EXCEPTION_VRE_DETACHED` on them.
+    pub fn revoke(&self, vre: MK_VRE_e) {
+        self.revoked.set(self.revoked.get() | u32::from(vre));
+        self.apps.each(|app| {
+            if app.attached & u32::from(vre) == 0 {
+                return;
+            }
+            app.attached &= !u32::from(vre);
+            self.vpp_kernel
+                .get_process_ref_appid(app.appid())
+                .map(|process| {
+                    self.vpp_kernel
+                        .notify_exception(process, MK_EXCEPTION_e::MK_EXCEPTION_VRE_DETACHED)
+                });
+        });
+    }
+
+    /// Completes the request in progress and notifies its Process.
+    fn complete(&self, error: MK_ERROR_e) {
+        let request = match self.current.take() {
+            Some(request) => request,
+            None => return,
+        };
+        let _ = self.apps.enter(request.appid, |app, _| {
+            let length = match (error, app.output.as_mut()) {
+                (MK_ERROR_NONE, Some(output)) => {
+                    let length = cmp::min(request.length, output.len());
+                    self.buffer
+                        .map(|buffer| output.as_mut()[..length].copy_from_slice(&buffer[..length]));
+                    length
+                }
+                _ => 0,
+            };
+            app.callback.map(|mut callback| {
+                callback.schedule(u32::from(request.vre) as usize, error.into(), length)
+            });
+        });
+        self.clear_buffer();
+    }
+
+    fn clear_buffer(&self) {
+        self.buffer.map(|buffer| {
+            for byte in buffer.iter_mut() {
+                *byte = 0;
+            }
+        });
+    }
+
+    /// Completes the request in progress with `MK_ERROR_INTERNAL` and revokes its VRE,
+    /// its hardware reported an error.
+    fn hardware_failed(&self, vre: MK_VRE_e) {
+        self.complete(MK_ERROR_INTERNAL);
+        self.revoke(vre);
+    }
+
+    /// Ends the request in progress, which the hardware of `vre` refused to start with
+    /// `ret`. Busy hardware, shared with other users, drops the request for the Process
+    /// to retry it; any other error is a hardware failure.
+    fn start_failed(&self, vre: MK_VRE_e, ret: ReturnCode) -> ReturnCode {
+        if ret == ReturnCode::EBUSY {
+            self.current.clear();
+            self.clear_buffer();
+            ReturnCode::EBUSY
+        } else {
+            self.hardware_failed(vre);
+            mk_return(MK_ERROR_INTERNAL)
+        }
+    }
+
+    /// Checks that `appid` is attached to `vre`, that its output holds `output` bytes and
+    /// that no request is in progress, then copies `input` bytes of its input into the
+    /// kernel buffer.
+    fn start(
+        &self,
+        appid: AppId,
+        vre: MK_VRE_e,
+        input: usize,
+        output: usize,
+    ) -> Result<(), ReturnCode> {
+        if self.current.is_some() {
+            return Err(ReturnCode::EBUSY);
+        }
+        let buffer_length = self.buffer.map_or(0, |buffer| buffer.len());
+        let error = self
+            .apps
+            .enter(appid, |app, _| {
+                if app.attached & u32::from(vre) == 0 {
+                    return MK_ERROR_ACCESS_DENIED;
+                }
+                if output > buffer_length
+                    || app
+                        .output
+                        .as_ref()
+                        .map_or(true, |slice| slice.len() < output)
+                {
+                    return MK_ERROR_ILLEGAL_PARAMETER;
+                }
+                if input == 0 {
+                    return MK_ERROR_NONE;
+                }
+                match app.input.as_ref() {
+                    Some(slice) if input <= slice.len() && input <= buffer_length => {
+                        self.buffer.map(|buffer| {
+                            buffer[..input].copy_from_slice(&slice.as_ref()[..input])
+                        });
+                        MK_ERROR_NONE
+                    }
+                    _ => MK_ERROR_ILLEGAL_PARAMETER,
+                }
+            })
+            .unwrap_or(MK_ERROR_INTERNAL);
+        if error != MK_ERROR_NONE {
+            return Err(ReturnCode::SuccessWithValue {
+                value: error.into(),
+            });
+        }
+        self.current.set(Request {
+            appid: appid,
+            vre: vre,
+            length: output,
+            done: 0,
+        });
+        Ok(())
+    }
+
+    fn crypt(&self, appid: AppId, encrypting: bool, length: usize) -> ReturnCode {
+        let aes = match self.aes {
+            Some(aes) => aes,
+            None => return mk_return(MK_ERROR_UNKNOWN_ID),
+        };
+        if length % symmetric_encryption::AES128_BLOCK_SIZE != 0 {
+            return mk_return(MK_ERROR_ILLEGAL_PARAMETER);
+        }
+        let mut key = [0; AES128_KEY_SIZE];
+        let has_key = self
+            .apps
+            .enter(appid, |app, _| match app.key.as_ref() {
+                Some(slice) if slice.len() >= AES128_KEY_SIZE => {
+                    key.copy_from_slice(&slice.as_ref()[..AES128_KEY_SIZE]);
+                    true
+                }
+                _ => false,
+            })
+            .unwrap_or(false);
+        if !has_key {
+            return mk_return(MK_ERROR_ILLEGAL_PARAMETER);
+        }
+        if let Err(ret) = self.start(appid, MK_VRE_e::MK_VRE_AES, length, length) {
+            return ret;
+        }
+        aes.enable();
+        aes.set_mode_aes128ecb(encrypting);
+        let ret = aes.set_key(&key);
+        if ret != ReturnCode::SUCCESS {
+            aes.disable();
+            return self.start_failed(MK_VRE_e::MK_VRE_AES, ret);
+        }
+        aes.start_message();
+        let buffer = match self.buffer.take() {
+            Some(buffer) => buffer,
+            None => {
+                aes.disable();
+                return self.start_failed(MK_VRE_e::MK_VRE_AES, ReturnCode::EBUSY);
+            }
+        };
+        // The request may complete synchronously, `crypt_done` is then already called.
+        if let Some((ret, _, buffer)) = aes.crypt(None, buffer, 0, length) {
+            self.buffer.replace(buffer);
+            aes.disable();
+            return self.start_failed(MK_VRE_e::MK_VRE_AES, ret);
+        }
+        mk_return(MK_ERROR_NONE)
+    }
+
+    fn hash(&self, appid: AppId, length: usize) -> ReturnCode {
+        let digest = match self.digest {
+            Some(digest) => digest,
+            None => return mk_return(MK_ERROR_UNKNOWN_ID),
+        };
+        if self.digest_buffer.is_none() {
+            return ReturnCode::EBUSY;
+        }
+        if let Err(ret) = self.start(appid, MK_VRE_e::MK_VRE_HASH, length, DIGEST_SIZE) {
+            return ret;
+        }
+        let buffer = match self.buffer.take() {
+            Some(buffer) => buffer,
+            None => return self.start_failed(MK_VRE_e::MK_VRE_HASH, ReturnCode::EBUSY),
+        };
+        let mut data = LeasableBuffer::new(buffer);
+        data.slice(0..length);
+        if let Err((ret, buffer)) = digest.add_data(data) {
+            self.buffer.replace(buffer);
+            return self.start_failed(MK_VRE_e::MK_VRE_HASH, ret);
+        }
+        mk_return(MK_ERROR_NONE)
+    }
+
+    fn random(&self, appid: AppId, length: usize) -> ReturnCode {
+        let rng = match self.rng {
+            Some(rng) => rng,
+            None => return mk_return(MK_ERROR_UNKNOWN_ID),
+        };
+        if let Err(ret) = self.start(appid, MK_VRE_e::MK_VRE_RNG, 0, length) {
+            return ret;
+        }
+        let ret = rng.get();
+        if ret != ReturnCode::SUCCESS {
+            return self.start_failed(MK_VRE_e::MK_VRE_RNG, ret);
+        }
+        mk_return(MK_ERROR_NONE)
+    }
+}
+
+/// Returns `error` as the value of a command.
+fn mk_return(error: MK_ERROR_e) -> ReturnCode {
+    ReturnCode::SuccessWithValue {
+        value: error.into(),
+    }
+}
+
+impl<A: 'static + AES128<'static> + AES128ECB, D: 'static + Digest<'static, [u8; DIGEST_SIZE]>>
+    symmetric_encryption::Client<'static> for VreBroker<A, D>
+{
+    fn crypt_done(&'static self, _source: Option<&'static mut [u8]>, dest: &'static mut [u8]) {
+        self.buffer.replace(dest);
+        self.aes.map(|aes| aes.disable());
+        self.complete(MK_ERROR_NONE);
+    }
+}
+
+impl<A: 'static + AES128<'static> + AES128ECB, D: 'static + Digest<'static, [u8; DIGEST_SIZE]>>
+    digest::Client<'static, [u8; DIGEST_SIZE]> for VreBroker<A, D>
+{
+    fn add_data_done(&'static self, result: Result<(), ReturnCode>, data: &'static mut [u8]) {
+        self.buffer.replace(data);
+        let digest = match self.digest {
+            Some(digest) => digest,
+            None => return,
+        };
+        if result.is_err() {
+            digest.clear_data();
+            self.hardware_failed(MK_VRE_e::MK_VRE_HASH);
+            return;
+        }
+        if let Some(digest_buffer) = self.digest_buffer.take() {
+            if let Err((_, digest_buffer)) = digest.run(digest_buffer) {
+                self.digest_buffer.replace(digest_buffer);
+                digest.clear_data();
+                self.hardware_failed(MK_VRE_e::MK_VRE_HASH);
+            }
+        }
+    }
+
+    fn hash_done(
+        &'static self,
+        result: Result<(), ReturnCode>,
+        digest: &'static mut [u8; DIGEST_SIZE],
+    ) {
+        // Releases the digest for the other users of the mux.
+        self.digest.map(|digest| digest.clear_data());
+        if result.is_err() {
+            self.digest_buffer.replace(digest);
+            self.hardware_failed(MK_VRE_e::MK_VRE_HASH);
+            return;
+        }
+        self.buffer
+            .map(|buffer| buffer[..DIGEST_SIZE].copy_from_slice(&digest[..]));
+        self.digest_buffer.replace(digest);
+        self.complete(MK_ERROR_NONE);
+    }
+}
+
+impl<A: 'static + AES128<'static> + AES128ECB, D: 'static + Digest<'static, [u8; DIGEST_SIZE]>>
+    rng::Client for VreBroker<A, D>
+{
+    fn randomness_available(
+        &self,
+        randomness: &mut dyn Iterator<Item = u32>,
+        error: ReturnCode,
+    ) -> rng::Continue {
+        let mut request = match self.current.take() {
+            Some(request) if request.vre == MK_VRE_e::MK_VRE_RNG => request,
+            other => {
+                other.map(|request| self.current.set(request));
+                return rng::Continue::Done;
+            }
+        };
+        if error != ReturnCode::SUCCESS {
+            self.current.set(request);
+            self.hardware_failed(MK_VRE_e::MK_VRE_RNG);
+            return rng::Continue::Done;
+        }
+        self.buffer.map(|buffer| {
+            while request.done < request.length {
+                let word = match randomness.next() {
+                    Some(word) => word.to_le_bytes(),
+                    None => break,
+                };
+                let count = cmp::min(word.len(), request.length - request.done);
+                buffer[request.done..request.done + count].copy_from_slice(&word[..count]);
+                request.done += count;
+            }
+        });
+        self.current.set(request);
+        if request.done < request.length {
+            rng::Continue::More
+        } else {
+            self.complete(MK_ERROR_NONE);
+            rng::Continue::Done
+        }
+    }
+}
+
+impl<A: 'static + AES128<'static> + AES128ECB, D: 'static + Digest<'static, [u8; DIGEST_SIZE]>>
+    Driver for VreBroker<A, D>
+{
+    fn command(&self, command_num: usize, data: usize, data2: usize, appid: AppId) -> ReturnCode {
+        match command_num {
+            0 => ReturnCode::SUCCESS,
+            1 | 2 | 6 => {
+                let vre = match MK_VRE_e::try_from(data as u32) {
+                    Ok(vre) => vre,
+                    Err(()) => return mk_return(MK_ERROR_UNKNOWN_ID),
+                };
+                let error = match command_num {
+                    1 => self.attach(appid, vre),
+                    2 => self.detach(appid, vre),
+                    _ if self.vpp_kernel.is_mgt_process(appid) => {
+                        self.revoke(vre);
+                        MK_ERROR_NONE
+                    }
+                    _ => MK_ERROR_ACCESS_DENIED,
+                };
+                mk_return(error)
+            }
+            3 => self.crypt(appid, data == 1, data2),
+            4 => self.hash(appid, data),
+            5 => self.random(appid, data),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+
+    fn allow(
+        &self,
+        appid: AppId,
+        allow_num: usize,
+        slice: Option<AppSlice<Shared, u8>>,
+    ) -> ReturnCode {
+        self.apps
+            .enter(appid, |app, _| {
+                match allow_num {
+                    0 => app.input = slice,
+                    1 => app.output = slice,
+                    2 => app.key = slice,
+                    _ => return ReturnCode::ENOSUPPORT,
+                }
+                ReturnCode::SUCCESS
+            })
+            .unwrap_or_else(|err| err.into())
+    }
+
+    fn subscribe(
+        &self,
+        subscribe_num: usize,
+        callback: Option<Callback>,
+        app_id: AppId,
+    ) -> ReturnCode {
+        match subscribe_num {
+            0 => self
+                .apps
+                .enter(app_id, |app, _| {
+                    app.callback = callback;
+                    ReturnCode::SUCCESS
+                })
+                .unwrap_or_else(|err| err.into()),
+            _ => ReturnCode::ENOSUPPORT,
+        }
+    }
+}
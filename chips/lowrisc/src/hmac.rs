@@ -74,6 +74,11 @@ pub struct Hmac<'a> {
     data_index: Cell<usize>,
 
     digest: Cell<Option<&'static mut [u8; 32]>>,
+
+    /// Whether `set_mode_hmacsha256` was called since the last `clear_data`.
+    hmac_mode: Cell<bool>,
+    /// Whether the hash of the current message was started.
+    started: Cell<bool>,
 }
 
 impl Hmac<'_> {
@@ -85,9 +90,22 @@ impl Hmac<'_> {
             data_len: Cell::new(0),
             data_index: Cell::new(0),
             digest: Cell::new(None),
+            hmac_mode: Cell::new(false),
+            started: Cell::new(false),
         }
     }
 
+    /// Configures the HMAC for the current mode, keeping the HMAC enabled
+    /// after `set_mode_hmacsha256`.
+    fn configure(&self) {
+        self.registers.cfg.write(
+            CFG::ENDIAN_SWAP::SET
+                + CFG::SHA_EN::SET
+                + CFG::DIGEST_SWAP::SET
+                + CFG::HMAC_EN.val(self.hmac_mode.get() as u32),
+        );
+    }
+
     fn data_progress(&self) {
         let regs = self.registers;
         let idx = self.data_index.get();
@@ -198,11 +216,13 @@ impl<'a> hil::digest::Digest<'a, [u8; 32]> for Hmac<'a> {
     ) -> Result<usize, (ReturnCode, &'static mut [u8])> {
         let regs = self.registers;
 
-        // Ensure the HMAC is setup
-        regs.cfg
-            .write(CFG::ENDIAN_SWAP::SET + CFG::SHA_EN::SET + CFG::DIGEST_SWAP::SET);
-
-        regs.cmd.modify(CMD::START::SET);
+        // Start the hash with the first data of the message only, starting
+        // again would drop the data added before.
+        if !self.started.get() {
+            self.configure();
+            regs.cmd.modify(CMD::START::SET);
+            self.started.set(true);
+        }
 
         // Clear the FIFO empty interrupt
         regs.intr_state.modify(INTR_STATE::FIFO_EMPTY::SET);
@@ -230,6 +250,7 @@ impl<'a> hil::digest::Digest<'a, [u8; 32]> for Hmac<'a> {
 
         // Start the process
         regs.cmd.modify(CMD::PROCESS::SET);
+        self.started.set(false);
 
         self.digest.set(Some(digest));
 
@@ -241,6 +262,8 @@ impl<'a> hil::digest::Digest<'a, [u8; 32]> for Hmac<'a> {
 
         regs.cmd.modify(CMD::START::CLEAR);
         regs.wipe_secret.set(1 as u32);
+        self.hmac_mode.set(false);
+        self.started.set(false);
     }
 }
 
@@ -248,9 +271,12 @@ impl hil::digest::HMACSha256 for Hmac<'_> {
     fn set_mode_hmacsha256(&self, key: &[u8; 32]) -> Result<(), ReturnCode> {
         let regs = self.registers;
 
-        // Ensure the HMAC is setup
-        regs.cfg
-            .write(CFG::ENDIAN_SWAP::SET + CFG::SHA_EN::SET + CFG::DIGEST_SWAP::SET);
+        // The mode cannot change in the middle of a message.
+        if self.started.get() {
+            return Err(ReturnCode::EBUSY);
+        }
+        self.hmac_mode.set(true);
+        self.configure();
 
         for i in 0..8 {
             let idx = i * 4;
@@ -4,8 +4,6 @@ use core::fmt;
 use core::ptr::NonNull;
 
 use crate::capabilities;
-use crate::config;
-use crate::debug;
 use crate::process;
 use crate::sched::Kernel;
 
@@ -212,20 +210,6 @@ impl Callback {
                 }))
             });
         // debug!("r0 {:#x},r1 {:#x},r2 {:#x}", r0,r1,r2);
-        if config::CONFIG.trace_syscalls {
-            debug!(
-                "[{:?}] schedule[{:#x}:{:#x}] @{:#x}({:#x}, {:#x}, {:#x}, {:#x}) = {}",
-                self.app_id,
-                self.callback_id.driver_num,
-                self.callback_id.subscribe_num,
-                self.fn_ptr.as_ptr() as usize,
-                r0,
-                r1,
-                r2,
-                self.appdata,
-                res
-            );
-        }
         res
     }
 }
@@ -28,10 +28,12 @@
 /// To change the configuration, modify the relevant values in the `CONFIG` constant object defined
 /// at the end of this file.
 pub struct Config {
-    /// Whether the kernel should trace syscalls to the debug output.
+    /// Whether the kernel should trace syscalls.
     ///
-    /// If enabled, the kernel will print a message in the debug output for each system call and
-    /// callback, with details including the application ID, and system call or callback parameters.
+    /// If enabled, the kernel records each system call and its result, and each callback and IPC
+    /// notification delivered to a process, in the `syscall_trace::SyscallTrace` registered with
+    /// the `Kernel`, with details including the application ID, and system call or callback
+    /// parameters.
     pub trace_syscalls: bool,
 
     /// Whether the kernel should show debugging output when loading processes.
@@ -45,6 +47,7 @@ pub struct Config {
 /// A unique instance of `Config` where compile-time configuration options are defined. These
 /// options are available in the kernel crate to be used for relevant configuration.
 pub const CONFIG: Config = Config {
-    trace_syscalls: true,
-    debug_load_processes: true,
+    trace_syscalls: false,
+    // Unit tests run without a board, hence without a debug writer.
+    debug_load_processes: !cfg!(test),
 };
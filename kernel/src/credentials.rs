@@ -0,0 +1,167 @@
+//! Credentials of apps, checked before their process is created.
+//!
+//! An app carries credentials with a `Credentials` TLV in its TBF header,
+//! which gives the format of a footer stored in the last bytes of the TBF. The
+//! footer holds a SHA-256 hash of the rest of the TBF, the header included, and
+//! an HMAC or a signature of that hash.
+//!
+//! A board decides which apps may run by registering an `AppCredentialsChecker`
+//! with the `Kernel`. Without a checker, every app runs. An app the checker
+//! rejects is not created, and is reported by `introspection::KernelInfo`.
+//!
+//! The verdicts on the apps found at boot do not hold for the apps loaded
+//! afterwards by a `ProcessLoader`, whose image may have been rewritten in
+//! place. Such an app must be checked again with
+//! `ProcessLoader::verify()` before each load.
+
+use core::fmt;
+
+use crate::returncode::ReturnCode;
+
+/// Length of the SHA-256 hash at the start of the footer.
+pub const CREDENTIALS_HASH_LENGTH: usize = 32;
+
+/// Formats of the credentials footer.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum CredentialsFormat {
+    /// SHA-256 hash followed by the HMAC-SHA256 of the hash.
+    HmacSha256,
+    /// SHA-256 hash followed by the ECDSA NIST P-256 signature (`r` then `s`)
+    /// of the hash.
+    EcdsaNistP256,
+    /// A format this kernel does not know, with no footer.
+    Unknown(u32),
+}
+
+impl From<u32> for CredentialsFormat {
+    fn from(format: u32) -> CredentialsFormat {
+        match format {
+            1 => CredentialsFormat::HmacSha256,
+            2 => CredentialsFormat::EcdsaNistP256,
+            other => CredentialsFormat::Unknown(other),
+        }
+    }
+}
+
+impl CredentialsFormat {
+    /// Length in bytes of the footer of this format.
+    pub fn footer_length(&self) -> usize {
+        match self {
+            CredentialsFormat::HmacSha256 => CREDENTIALS_HASH_LENGTH + 32,
+            CredentialsFormat::EcdsaNistP256 => CREDENTIALS_HASH_LENGTH + 64,
+            CredentialsFormat::Unknown(_) => 0,
+        }
+    }
+}
+
+/// The credentials of an app, as found in its TBF.
+#[derive(Clone, Copy)]
+pub struct AppCredentials {
+    pub format: CredentialsFormat,
+    /// The part of the TBF the credentials cover: everything but the footer.
+    pub covered: &'static [u8],
+    /// SHA-256 hash of `covered`.
+    pub hash: &'static [u8],
+    /// HMAC or signature of `hash`.
+    pub tag: &'static [u8],
+}
+
+impl AppCredentials {
+    /// Split the footer of `format` off the end of `app`, the whole TBF of an
+    /// app. Returns `None` if the footer does not fit after `header_length`.
+    pub(crate) fn new(
+        app: &'static [u8],
+        header_length: usize,
+        format: CredentialsFormat,
+    ) -> Option<AppCredentials> {
+        let covered_length = app.len().checked_sub(format.footer_length())?;
+        if covered_length < header_length {
+            return None;
+        }
+        let (covered, footer) = app.split_at(covered_length);
+        let (hash, tag) = footer.split_at(footer.len().min(CREDENTIALS_HASH_LENGTH));
+        Some(AppCredentials {
+            format,
+            covered,
+            hash,
+            tag,
+        })
+    }
+}
+
+/// Reasons for an app to be rejected.
+#[derive(Clone, Copy, Eq, PartialEq)]
+pub enum CredentialsError {
+    /// The app has no credentials and the checker requires them.
+    Missing,
+
+    /// The checker does not support the format of the credentials.
+    UnsupportedFormat(CredentialsFormat),
+
+    /// The hash of the app does not match its credentials.
+    HashMismatch,
+
+    /// The HMAC or signature of the credentials is not valid.
+    InvalidTag,
+
+    /// The checker has not checked the app.
+    Unchecked,
+}
+
+impl fmt::Debug for CredentialsError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CredentialsError::Missing => write!(f, "App has no credentials"),
+            CredentialsError::UnsupportedFormat(format) => {
+                write!(f, "Credentials format {:?} unsupported", format)
+            }
+            CredentialsError::HashMismatch => write!(f, "App hash does not match credentials"),
+            CredentialsError::InvalidTag => write!(f, "Credentials HMAC or signature invalid"),
+            CredentialsError::Unchecked => write!(f, "App credentials were not checked"),
+        }
+    }
+}
+
+/// Decides whether an app may run.
+pub trait AppCredentialsChecker {
+    /// Called for every enabled app before its process is created. `app` is
+    /// the whole TBF of the app and `credentials` are its credentials, if any.
+    /// The process is only created if this returns `Ok(())`.
+    fn check_credentials(
+        &self,
+        app: &'static [u8],
+        credentials: Option<AppCredentials>,
+    ) -> Result<(), CredentialsError>;
+
+    /// Starts checking again the credentials of `app`, a TBF written to flash
+    /// after boot. The checker reports the verdict to its client, and keeps
+    /// it for the next `check_loaded_credentials()` of the app. Returns
+    /// `SUCCESS` if the check started.
+    fn recheck_credentials(&self, _app: &'static [u8]) -> ReturnCode {
+        ReturnCode::ENOSUPPORT
+    }
+
+    /// Called for an app loaded after boot, before its process is created.
+    /// The credentials of the app must have been checked again since it was
+    /// written: a verdict from before, such as the one made at boot, is
+    /// stale. A verdict is used by one load only.
+    fn check_loaded_credentials(
+        &self,
+        _app: &'static [u8],
+        _credentials: Option<AppCredentials>,
+    ) -> Result<(), CredentialsError> {
+        Err(CredentialsError::Unchecked)
+    }
+}
+
+/// Told the verdict of `AppCredentialsChecker::recheck_credentials()`.
+pub trait AppCredentialsClient {
+    fn credentials_checked(&self, app: &'static [u8], result: Result<(), CredentialsError>);
+}
+
+/// An app rejected by the `AppCredentialsChecker` while loading processes.
+#[derive(Clone, Copy)]
+pub(crate) struct RejectedApp {
+    pub(crate) name: Option<&'static str>,
+    pub(crate) error: CredentialsError,
+}
@@ -96,7 +96,7 @@ pub unsafe fn panic<L: hil::led::Led, W: Write + IoWrite, C: Chip>(
     writer: &mut W,
     panic_info: &PanicInfo,
     nop: &dyn Fn(),
-    processes: &'static [Option<&'static dyn ProcessType>],
+    processes: &'static Option<&'static [Cell<Option<&'static dyn ProcessType>>]>,
     chip: &'static Option<&'static C>,
 ) -> ! {
     panic_begin(nop);
@@ -149,16 +149,18 @@ pub unsafe fn panic_cpu_state<W: Write, C: Chip>(
 ///
 /// **NOTE:** The supplied `writer` must be synchronous.
 pub unsafe fn panic_process_info<W: Write>(
-    procs: &'static [Option<&'static dyn ProcessType>],
+    procs: &'static Option<&'static [Cell<Option<&'static dyn ProcessType>>]>,
     writer: &mut W,
 ) {
     // print data about each process
     let _ = writer.write_fmt(format_args!("\r\n---| App Status |---\r\n"));
-    for idx in 0..procs.len() {
-        procs[idx].as_ref().map(|process| {
-            process.print_full_process(writer);
-        });
-    }
+    procs.map(|procs| {
+        for slot in procs.iter() {
+            slot.get().map(|process| {
+                process.print_full_process(writer);
+            });
+        }
+    });
 }
 
 /// Blinks a recognizable pattern forever.
@@ -71,6 +71,32 @@ impl KernelInfo {
         count.get()
     }
 
+    /// Returns how many apps were rejected by the credentials checker of the
+    /// kernel when loading processes. No process was created for them, so they
+    /// are not counted by the functions above.
+    pub fn number_rejected_processes(
+        &self,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> usize {
+        self.kernel.rejected_app_count()
+    }
+
+    /// Returns the name of the `index`-th rejected app and the error it was
+    /// rejected with. Only the first few rejected apps are kept, `None` is
+    /// returned for the others.
+    pub fn rejected_process(
+        &self,
+        index: usize,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> Option<(&'static str, process::ProcessLoadError)> {
+        self.kernel.rejected_app(index).map(|app| {
+            (
+                app.name.unwrap_or("unknown"),
+                process::ProcessLoadError::CredentialsRejected(app.error),
+            )
+        })
+    }
+
     /// Get the name of the process.
     pub fn process_name(
         &self,
@@ -91,6 +117,17 @@ impl KernelInfo {
             .process_map_or(0, app, |process| process.debug_syscall_count())
     }
 
+    /// Returns the number of syscalls of the app denied because it did not
+    /// declare their driver or command in its permissions.
+    pub fn number_app_denied_syscalls(
+        &self,
+        app: AppId,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> usize {
+        self.kernel
+            .process_map_or(0, app, |process| process.debug_denied_syscall_count())
+    }
+
     /// Returns the number of dropped callbacks the app has experience.
     /// Callbacks can be dropped if the queue for the app is full when a capsule
     /// tries to schedule a callback.
@@ -123,6 +160,39 @@ impl KernelInfo {
             .process_map_or(0, app, |process| process.debug_timeslice_expiration_count())
     }
 
+    /// Returns how long the app has executed, in microseconds. Only the time
+    /// the app runs with a timeslice is counted, `None` if it never ran with
+    /// one.
+    pub fn app_cpu_time_us(
+        &self,
+        app: AppId,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> Option<u64> {
+        self.kernel
+            .process_map_or(None, app, |process| process.debug_cpu_time_us())
+    }
+
+    /// Returns the deepest the stack of the app went, in bytes, since it
+    /// started.
+    pub fn app_max_stack_depth(
+        &self,
+        app: AppId,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> usize {
+        self.kernel
+            .process_map_or(0, app, |process| process.debug_max_stack_depth())
+    }
+
+    /// Returns how many bytes of grant memory the app holds.
+    pub fn app_grant_memory_size(
+        &self,
+        app: AppId,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> usize {
+        self.kernel
+            .process_map_or(0, app, |process| process.debug_grant_memory_size())
+    }
+
     /// Returns a tuple of the (the number of grants in the grant region this
     /// app has allocated, total number of grants that exist in the system).
     pub fn number_app_grant_uses(
@@ -159,4 +229,26 @@ impl KernelInfo {
         });
         count.get()
     }
+
+    /// Returns how long all the processes have executed, in microseconds,
+    /// `None` if none of them ran with a timeslice.
+    pub fn cpu_time_us(&self, _capability: &dyn ProcessManagementCapability) -> Option<u64> {
+        let time: Cell<Option<u64>> = Cell::new(None);
+        self.kernel.process_each(|proc| {
+            if let Some(us) = proc.debug_cpu_time_us() {
+                time.set(Some(time.get().unwrap_or(0) + us));
+            }
+        });
+        time.get()
+    }
+
+    /// Returns the total number of syscalls denied by the permissions of the
+    /// processes.
+    pub fn denied_syscalls(&self, _capability: &dyn ProcessManagementCapability) -> usize {
+        let count: Cell<usize> = Cell::new(0);
+        self.kernel.process_each(|proc| {
+            count.add(proc.debug_denied_syscall_count());
+        });
+        count.get()
+    }
 }
@@ -97,22 +97,26 @@ pub mod hil;
 pub mod introspection;
 pub mod ipc;
 pub mod syscall;
+pub mod syscall_trace;
 
 mod callback;
 pub mod config;
+mod credentials;
 mod driver;
 mod grant;
 mod mem;
 mod memop;
+mod observer;
 mod platform;
 mod process;
+mod process_loader;
 mod returncode;
 mod sched;
+#[cfg(test)]
+mod sim;
 pub mod tbfheader;
 
-pub use crate::sched::ListenerType;
-pub use crate::sched::LISTENER;
-pub use crate::callback::{AppId, Callback};
+pub use crate::callback::{AppId, Callback, CallbackId};
 pub use crate::driver::Driver;
 pub use crate::grant::Grant;
 pub use crate::mem::{AppSlice, Private, Shared};
@@ -125,7 +129,7 @@ pub use crate::sched::cooperative::{CoopProcessNode, CooperativeSched};
 pub use crate::sched::mlfq::{MLFQProcessNode, MLFQSched};
 pub use crate::sched::priority::PrioritySched;
 pub use crate::sched::round_robin::{RoundRobinProcessNode, RoundRobinSched};
-pub use crate::sched::{Kernel, Scheduler};
+pub use crate::sched::{Kernel, Scheduler, SchedulingDecision, StoppedExecutingReason};
 
 // Export only select items from the process module. To remove the name conflict
 // this cannot be called `process`, so we use a shortened version. These
@@ -134,9 +138,15 @@ pub use crate::sched::{Kernel, Scheduler};
 /// Publicly available process-related objects.
 pub mod procs {
     pub use crate::process::{
-        load_processes, AlwaysRestart, Error, FaultResponse, FunctionCall, FunctionCallSource,
-        Process, ProcessLoadError, ProcessRestartPolicy, ProcessType, State, Task,
-        ThresholdRestart, ThresholdRestartThenPanic,
+        check_process_credentials, load_processes, AlwaysRestart, Error, FaultResponse,
+        FunctionCall, FunctionCallSource, Process, ProcessLoadError, ProcessRestartPolicy,
+        ProcessType, State, Task, ThresholdRestart, ThresholdRestartThenPanic,
     };
+    pub use crate::credentials::{
+        AppCredentials, AppCredentialsChecker, AppCredentialsClient, CredentialsError,
+        CredentialsFormat, CREDENTIALS_HASH_LENGTH,
+    };
+    pub use crate::observer::{ProcessEvent, ProcessObserver};
+    pub use crate::process_loader::ProcessLoader;
 }
 
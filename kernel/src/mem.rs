@@ -7,6 +7,7 @@ use core::slice;
 use crate::callback::AppId;
 use crate::capabilities;
 use crate::debug;
+use crate::platform::mpu;
 /// Type for specifying an AppSlice is hidden from the kernel.
 #[derive(Debug)]
 pub struct Private;
@@ -100,7 +101,12 @@ impl<L, T> AppSlice<L, T> {
                 .kernel
                 .process_map_or(false, appid, |process| {
                     process
-                        .add_mpu_region(self.ptr() as *const u8, self.len(), self.len())
+                        .add_mpu_region(
+                            self.ptr() as *const u8,
+                            self.len(),
+                            self.len(),
+                            mpu::Permissions::ReadWriteOnly,
+                        )
                         .is_some()
                 })
         } else {
@@ -0,0 +1,62 @@
+//! Observers of the lifecycle of processes.
+//!
+//! Capsules that keep per-process state alongside the kernel, such as a mirror
+//! of the process state, register a `ProcessObserver` with the `Kernel` and are
+//! told about every event below as it happens, rather than polling the
+//! processes.
+//!
+//! Observers are called synchronously from the kernel, often in the middle of
+//! handling a system call or a fault of the process. They must only update
+//! their own state and must not call back into the process.
+
+use crate::callback::AppId;
+use crate::common::list::{ListLink, ListNode};
+use crate::process::State;
+
+/// An event in the lifecycle of a process.
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum ProcessEvent {
+    /// The process moved from the first state to the second.
+    StateChanged(State, State),
+
+    /// The process faulted. The kernel applies the fault response of the
+    /// process next, which is reported by the following events.
+    Faulted,
+
+    /// The process was restarted and runs its `_start` function next. The
+    /// `AppId` of the event is the new identifier of the process.
+    Restarted,
+
+    /// A task, a callback or an IPC notification, was queued for the process.
+    TaskQueued,
+
+    /// The process called `yield`.
+    Yielded,
+
+    /// The process was preempted because its timeslice expired.
+    TimesliceExpired,
+
+    /// The process was loaded while the kernel runs, by a `ProcessLoader`.
+    Loaded,
+
+    /// The process was stopped and is being removed. Its `AppId` is no longer
+    /// valid after this event and its memory is reused for other processes.
+    /// Observers must drop any `&dyn ProcessType` they keep to the process:
+    /// it points to memory the next process loaded is created in.
+    Unloaded,
+}
+
+/// A client of the process events of a `Kernel`.
+pub trait ProcessObserver<'a>: 'a {
+    /// Called for every `event` of the process `appid`.
+    fn process_event(&self, appid: AppId, event: ProcessEvent);
+
+    /// Link to the next observer registered with the kernel.
+    fn next_observer(&'a self) -> &'a ListLink<'a, dyn ProcessObserver<'a>>;
+}
+
+impl<'a> ListNode<'a, dyn ProcessObserver<'a>> for dyn ProcessObserver<'a> {
+    fn next(&'a self) -> &'a ListLink<'a, dyn ProcessObserver<'a>> {
+        self.next_observer()
+    }
+}
@@ -17,7 +17,7 @@ pub enum Permissions {
 /// MPU region.
 ///
 /// This is one contiguous address space protected by the MPU.
-#[derive(Copy, Clone)]
+#[derive(Copy, Clone, PartialEq)]
 pub struct Region {
     /// The memory address where the region starts.
     ///
@@ -158,6 +158,27 @@ pub trait MPU {
         }
     }
 
+    /// Removes an MPU region previously allocated with `allocate_region`.
+    ///
+    /// An implementation must remove the MPU region stored in `config` that
+    /// matches `region` exactly. The region covering app-owned memory must not
+    /// be removed.
+    ///
+    /// # Arguments
+    ///
+    /// - `region`: the region to remove
+    /// - `config`: MPU region configuration
+    ///
+    /// # Return Value
+    ///
+    /// Returns an error if no region of `config` matches `region`, or if
+    /// `region` is the app-owned memory region. The default implementation
+    /// keeps no regions, so it always returns an error.
+    #[allow(unused_variables)]
+    fn remove_memory_region(&self, region: Region, config: &mut Self::MpuConfig) -> Result<(), ()> {
+        Err(())
+    }
+
     /// Chooses the location for a process's memory, and allocates an MPU region
     /// covering the app-owned part.
     ///
@@ -266,7 +287,17 @@ pub trait MPU {
 }
 
 /// Implement default MPU trait for unit.
-impl MPU for () {}
+impl MPU for () {
+    /// Without an MPU, the regions are not enforced and removing one always
+    /// succeeds.
+    fn remove_memory_region(
+        &self,
+        _region: Region,
+        _config: &mut MpuConfigDefault,
+    ) -> Result<(), ()> {
+        Ok(())
+    }
+}
 
 /// The generic trait that particular kernel level memory protection unit
 /// implementations need to implement.
@@ -11,9 +11,11 @@ use crate::capabilities::ProcessManagementCapability;
 use crate::common::cells::{MapCell, NumericCellExt};
 use crate::common::{Queue, RingBuffer};
 use crate::config;
+use crate::credentials::CredentialsError;
 use crate::debug;
 use crate::ipc;
 use crate::mem::{AppSlice, Shared};
+use crate::observer::ProcessEvent;
 use crate::platform::mpu::{self, MPU};
 use crate::platform::Chip;
 use crate::returncode::ReturnCode;
@@ -55,6 +57,24 @@ pub enum ProcessLoadError {
         expected_address: u32,
     },
 
+    /// The credentials checker of the kernel rejected the app, its process
+    /// was not created. `load_processes()` still loads the other apps and
+    /// returns the first rejection once done.
+    CredentialsRejected(CredentialsError),
+
+    /// The TBF is padding or a disabled app, no process was created.
+    NotEnabled,
+
+    /// All the slots of the processes array are taken.
+    NoFreeSlot,
+
+    /// The process to replace does not exist anymore.
+    UnknownProcess,
+
+    /// The process to replace is running, or its system call is being
+    /// handled: a process cannot replace itself.
+    ProcessRunning,
+
     /// Process loading error due (likely) to a bug in the kernel. If you get
     /// this error please open a bug report.
     InternalError,
@@ -108,11 +128,42 @@ impl fmt::Debug for ProcessLoadError {
                 actual_address, expected_address
             ),
 
+            ProcessLoadError::CredentialsRejected(credentials_error) => {
+                write!(f, "App credentials rejected\n")?;
+                write!(f, "{:?}", credentials_error)
+            }
+
+            ProcessLoadError::NotEnabled => write!(f, "App is padding or not enabled"),
+
+            ProcessLoadError::NoFreeSlot => write!(f, "No free slot for another process"),
+
+            ProcessLoadError::UnknownProcess => write!(f, "Process to replace does not exist"),
+
+            ProcessLoadError::ProcessRunning => write!(f, "Process to replace is running"),
+
             ProcessLoadError::InternalError => write!(f, "Error in kernel. Likely a bug."),
         }
     }
 }
 
+/// Word written over the initial stack of the processes when they start. The
+/// deepest word of the stack that no longer holds it gives how deep the stack
+/// went.
+const STACK_PAINT: u32 = 0xDEAD_BEEF;
+
+/// Paint the stack of a process from `start` up to `end`, both word aligned.
+unsafe fn paint_stack(start: *const u8, end: *const u8) {
+    // The stack starts at the start of the process memory, which the MPU
+    // aligns on at least a word boundary, so `start` is word aligned.
+    debug_assert_eq!(start as usize % mem::align_of::<u32>(), 0);
+    #[allow(clippy::cast_ptr_alignment)]
+    let mut word = start as *mut u32;
+    while (word as usize) < end as usize {
+        write_volatile(word, STACK_PAINT);
+        word = word.add(1);
+    }
+}
+
 /// Helper function to load processes from flash into an array of active
 /// processes. This is the default template for loading processes, but a board
 /// is able to create its own `load_processes()` function and use that instead.
@@ -120,8 +171,9 @@ impl fmt::Debug for ProcessLoadError {
 /// Processes are found in flash starting from the given address and iterating
 /// through Tock Binary Format (TBF) headers. Processes are given memory out of
 /// the `app_memory` buffer until either the memory is exhausted or the
-/// allocated number of processes are created. A reference to each process is
-/// stored in the provided `procs` array. How process faults are handled by the
+/// allocated number of processes are created. Each process is installed in
+/// the processes array of the kernel, and the memory left unused is given to
+/// the kernel for a `ProcessLoader`. How process faults are handled by the
 /// kernel must be provided and is assigned to every created process.
 ///
 /// This function is made `pub` so that board files can use it, but loading
@@ -130,15 +182,16 @@ impl fmt::Debug for ProcessLoadError {
 ///
 /// Returns `Ok(())` if process discovery went as expected. Returns a
 /// `ProcessLoadError` if something goes wrong during TBF parsing or process
-/// creation.
+/// creation. Apps rejected by the credentials checker of the kernel are
+/// skipped, the other apps are loaded and the first rejection is returned as
+/// `ProcessLoadError::CredentialsRejected`.
 pub fn load_processes<C: Chip>(
     kernel: &'static Kernel,
     chip: &'static C,
     app_flash: &'static [u8],
     app_memory: &'static mut [u8],
-    procs: &'static mut [Option<&'static dyn ProcessType>],
     fault_response: FaultResponse,
-    _capability: &dyn ProcessManagementCapability,
+    capability: &dyn ProcessManagementCapability,
 ) -> Result<(), ProcessLoadError> {
     if config::CONFIG.debug_load_processes {
         debug!(
@@ -152,9 +205,10 @@ pub fn load_processes<C: Chip>(
 
     let mut remaining_flash = app_flash;
     let mut remaining_memory = app_memory;
+    let mut rejected = None;
 
-    // Try to discover up to `procs.len()` processes in flash.
-    for i in 0..procs.len() {
+    // Try to discover up to `kernel.processes.len()` processes in flash.
+    for i in 0..kernel.processes.len() {
         // Get the first eight bytes of flash to check if there is another
         // app.
         let test_header_slice = match remaining_flash.get(0..8) {
@@ -163,7 +217,7 @@ pub fn load_processes<C: Chip>(
                 // Not enough flash to test for another app. This just means
                 // we are at the end of flash, and there are no more apps to
                 // load.
-                return Ok(());
+                break;
             }
         };
 
@@ -186,7 +240,7 @@ pub fn load_processes<C: Chip>(
                 // header we started to parse is intentionally invalid to signal
                 // the end of apps. This is ok and just means we have finished
                 // loading apps.
-                return Ok(());
+                break;
             }
         };
 
@@ -212,6 +266,15 @@ pub fn load_processes<C: Chip>(
             // based on whatever is assigned to the new process if one is
             // created.
 
+            // Skip an app rejected by the credentials checker, keeping the
+            // memory for the next apps.
+            if let Err(error) =
+                check_process_credentials(kernel, entry_flash, header_length as usize, version)
+            {
+                rejected = rejected.or(Some(error));
+                continue;
+            }
+
             // Try to create a process object from that app slice. If we don't
             // get a process and we didn't get a loading error (aka we got to
             // this point), then the app is a disabled process or just padding.
@@ -241,7 +304,7 @@ pub fn load_processes<C: Chip>(
                 }
 
                 // Save the reference to this process in the processes array.
-                procs[i] = Some(process);
+                kernel.processes[i].set(Some(process));
             });
             unused_memory
         } else {
@@ -251,7 +314,40 @@ pub fn load_processes<C: Chip>(
         };
     }
 
-    Ok(())
+    kernel.set_unused_app_memory(remaining_memory, capability);
+    rejected.map_or(Ok(()), |error| {
+        Err(ProcessLoadError::CredentialsRejected(error))
+    })
+}
+
+/// Asks the credentials checker of `kernel` whether the app in `app_flash` may
+/// run, before creating its process. `Process::create()` rejects the app as
+/// well, but a loader checking first keeps the memory it would have given to
+/// the process for the next apps.
+///
+/// Padding, disabled apps and headers that do not parse are left to
+/// `Process::create()`.
+pub fn check_process_credentials(
+    kernel: &'static Kernel,
+    app_flash: &'static [u8],
+    header_length: usize,
+    app_version: u16,
+) -> Result<(), CredentialsError> {
+    let tbf_header = match app_flash
+        .get(0..header_length)
+        .and_then(|header| tbfheader::parse_tbf_header(header, app_version).ok())
+    {
+        Some(tbf_header) => tbf_header,
+        None => return Ok(()),
+    };
+    if !tbf_header.is_app() || !tbf_header.enabled() {
+        return Ok(());
+    }
+    kernel.check_credentials(
+        app_flash,
+        tbf_header.get_package_name(),
+        tbf_header.get_credentials(app_flash),
+    )
 }
 
 /// This trait is implemented by process structs.
@@ -314,12 +410,22 @@ pub trait ProcessType {
     /// `FaultResponse` for this process to occur.
     fn set_fault_state(&self);
 
+    /// Stop the process and free its grants and queued tasks. The process
+    /// does not run again unless it is restarted.
+    fn terminate(&self);
+
     /// Returns how many times this process has been restarted.
     fn get_restart_count(&self) -> usize;
 
     /// Get the name of the process. Used for IPC.
     fn get_process_name(&self) -> &'static str;
 
+    /// Check that the process declared the driver and command of `syscall`
+    /// in the `Permissions` TLV of its TBF header. Returns `ENOSUPPORT` for a
+    /// driver the process did not declare and `ERESERVE` for a command outside
+    /// of the declared ranges. A process without the TLV may call any driver.
+    fn check_syscall_permissions(&self, syscall: &Syscall) -> Result<(), ReturnCode>;
+
     // memop operations
 
     /// Change the location of the program break and reallocate the MPU region
@@ -410,8 +516,9 @@ pub trait ProcessType {
     fn setup_mpu(&self);
 
     /// Allocate a new MPU region for the process that is at least
-    /// `min_region_size` bytes and lies within the specified stretch of
-    /// unallocated memory.
+    /// `min_region_size` bytes, lies within the specified stretch of
+    /// unallocated memory and is accessible with `permissions`. Memory shared
+    /// with the process is `ReadWriteOnly`, shared code is `ExecuteOnly`.
     ///
     /// It is not valid to call this function when the process is inactive (i.e.
     /// the process will not run again).
@@ -420,8 +527,16 @@ pub trait ProcessType {
         unallocated_memory_start: *const u8,
         unallocated_memory_size: usize,
         min_region_size: usize,
+        permissions: mpu::Permissions,
     ) -> Option<mpu::Region>;
 
+    /// Removes an MPU region from the process that has been previously added
+    /// with `add_mpu_region`.
+    ///
+    /// Returns an error if `region` is not one of the regions added to the
+    /// process, or if the process is no longer active.
+    fn remove_mpu_region(&self, region: mpu::Region) -> Result<(), ()>;
+
     // grants
 
     /// Create new memory in the grant region, and check that the MPU region
@@ -488,12 +603,36 @@ pub trait ProcessType {
     /// Returns how many times this process has exceeded its timeslice.
     fn debug_timeslice_expiration_count(&self) -> usize;
 
+    /// Returns how many syscalls of this process were denied by its
+    /// permissions.
+    fn debug_denied_syscall_count(&self) -> usize;
+
+    /// Returns how long this process has executed, in microseconds. Only the
+    /// time the process runs with a timeslice is counted, so this is `None`
+    /// until it first runs with one, for example with a cooperative scheduler.
+    fn debug_cpu_time_us(&self) -> Option<u64>;
+
+    /// Returns the deepest the stack of this process went, in bytes. The stack
+    /// is painted when the process starts, this is how much of the paint the
+    /// process overwrote.
+    fn debug_max_stack_depth(&self) -> usize;
+
+    /// Returns how many bytes of grant memory this process holds.
+    fn debug_grant_memory_size(&self) -> usize;
+
+    /// Add `time_us` to how long the process has executed.
+    fn debug_cpu_time_used(&self, time_us: u32);
+
     /// Increment the number of times the process has exceeded its timeslice.
     fn debug_timeslice_expired(&self);
 
     /// Increment the number of times the process called a syscall and record
     /// the last syscall that was called.
     fn debug_syscall_called(&self, last_syscall: Syscall);
+
+    /// Increment the number of syscalls of the process denied by its
+    /// permissions.
+    fn debug_syscall_denied(&self);
 }
 
 /// Generic trait for implementing process restart policies.
@@ -652,7 +791,9 @@ impl<'a> ProcessStateCell<'a> {
         self.state.get()
     }
 
-    fn update(&self, new_state: State) {
+    /// Moves the process `appid` to `new_state` and tells the process
+    /// observers about the change.
+    fn update(&self, appid: AppId, new_state: State) {
         let old_state = self.state.get();
 
         if old_state == State::Running && new_state != State::Running {
@@ -661,6 +802,10 @@ impl<'a> ProcessStateCell<'a> {
             self.kernel.increment_work()
         }
         self.state.set(new_state);
+        if old_state != new_state {
+            self.kernel
+                .process_event(appid, ProcessEvent::StateChanged(old_state, new_state));
+        }
     }
 }
 
@@ -772,6 +917,13 @@ struct ProcessDebug {
     /// How many times this process has been paused because it exceeded its
     /// timeslice.
     timeslice_expiration_count: usize,
+
+    /// How many syscalls were denied by the permissions of the process.
+    denied_syscall_count: usize,
+
+    /// How long the process has executed, in microseconds, `None` until it
+    /// runs with a timeslice.
+    cpu_time_us: Option<u64>,
 }
 
 /// A type for userspace processes in Tock.
@@ -912,6 +1064,7 @@ impl<C: Chip> ProcessType for Process<'_, C> {
             });
         } else {
             self.kernel.increment_work();
+            self.kernel.process_event(self.appid(), ProcessEvent::TaskQueued);
         }
 
         ret
@@ -925,7 +1078,6 @@ impl<C: Chip> ProcessType for Process<'_, C> {
 
     fn remove_pending_callbacks(&self, callback_id: CallbackId) {
         self.tasks.map(|tasks| {
-            let count_before = tasks.len();
             tasks.retain(|task| match task {
                 // Remove only tasks that are function calls with an id equal
                 // to `callback_id`.
@@ -942,16 +1094,6 @@ impl<C: Chip> ProcessType for Process<'_, C> {
                 },
                 _ => true,
             });
-            if config::CONFIG.trace_syscalls {
-                let count_after = tasks.len();
-                debug!(
-                    "[{:?}] remove_pending_callbacks[{:#x}:{}] = {} callback(s) removed",
-                    self.appid(),
-                    callback_id.driver_num,
-                    callback_id.subscribe_num,
-                    count_before - count_after,
-                );
-            }
         });
     }
 
@@ -963,31 +1105,32 @@ impl<C: Chip> ProcessType for Process<'_, C> {
 
     fn set_yielded_state(&self) {
         if self.state.get() == State::Running {
-            self.state.update(State::Yielded);
+            self.state.update(self.appid(), State::Yielded);
         }
     }
     fn set_state(&self,state: State) {
-        self.state.update(state);
+        self.state.update(self.appid(), state);
     }
 
     fn stop(&self) {
         match self.state.get() {
-            State::Running => self.state.update(State::StoppedRunning),
-            State::Yielded => self.state.update(State::StoppedYielded),
+            State::Running => self.state.update(self.appid(), State::StoppedRunning),
+            State::Yielded => self.state.update(self.appid(), State::StoppedYielded),
             _ => {} // Do nothing
         }
     }
 
     fn resume(&self) {
         match self.state.get() {
-            State::StoppedRunning => self.state.update(State::Running),
-            State::StoppedYielded => self.state.update(State::Yielded),
+            State::StoppedRunning => self.state.update(self.appid(), State::Running),
+            State::StoppedYielded => self.state.update(self.appid(), State::Yielded),
             _ => {} // Do nothing
         }
     }
 
     fn set_fault_state(&self) {
-        self.state.update(State::Fault);
+        self.state.update(self.appid(), State::Fault);
+        self.kernel.process_event(self.appid(), ProcessEvent::Faulted);
 
         match self.fault_response {
             FaultResponse::Panic => {
@@ -1008,6 +1151,34 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         }
     }
 
+    /// Stop and clear a process's state.
+    ///
+    /// This will end the process, but does not reset it such that it could be
+    /// restarted and run again. This function instead frees grants and any
+    /// queued tasks for this process, but leaves the debug information about
+    /// the process and other state intact.
+    fn terminate(&self) {
+        // Remove the tasks that were scheduled for the app from the
+        // amount of work queue.
+        let tasks_len = self.tasks.map_or(0, |tasks| tasks.len());
+        for _ in 0..tasks_len {
+            self.kernel.decrement_work();
+        }
+
+        // And remove those tasks
+        self.tasks.map(|tasks| {
+            tasks.empty();
+        });
+
+        // Clear any grant regions this app has setup with any capsules.
+        unsafe {
+            self.grant_ptrs_reset();
+        }
+
+        // Mark the app as stopped so the scheduler won't try to run it.
+        self.state.update(self.appid(), State::StoppedFaulted);
+    }
+
     fn get_restart_count(&self) -> usize {
         self.restart_count.get()
     }
@@ -1086,13 +1257,14 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         unallocated_memory_start: *const u8,
         unallocated_memory_size: usize,
         min_region_size: usize,
+        permissions: mpu::Permissions,
     ) -> Option<mpu::Region> {
         self.mpu_config.and_then(|mut config| {
             let new_region = self.chip.mpu().allocate_region(
                 unallocated_memory_start,
                 unallocated_memory_size,
                 min_region_size,
-                mpu::Permissions::ReadWriteOnly,
+                permissions,
                 &mut config,
             );
 
@@ -1112,6 +1284,19 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         })
     }
 
+    fn remove_mpu_region(&self, region: mpu::Region) -> Result<(), ()> {
+        self.mpu_config.map_or(Err(()), |mut config| {
+            let stored = self
+                .mpu_regions
+                .iter()
+                .find(|stored| stored.get() == Some(region))
+                .ok_or(())?;
+            self.chip.mpu().remove_memory_region(region, &mut config)?;
+            stored.set(None);
+            Ok(())
+        })
+    }
+
     fn sbrk(&self, increment: isize) -> Result<*const u8, Error> {
         // Do not modify an inactive process.
         if !self.is_active() {
@@ -1285,6 +1470,40 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         self.process_name
     }
 
+    fn check_syscall_permissions(&self, syscall: &Syscall) -> Result<(), ReturnCode> {
+        let (driver_number, command_number) = match *syscall {
+            Syscall::SUBSCRIBE { driver_number, .. } | Syscall::ALLOW { driver_number, .. } => {
+                (driver_number, None)
+            }
+            Syscall::COMMAND {
+                driver_number,
+                subdriver_number,
+                ..
+            } => (driver_number, Some(subdriver_number)),
+            Syscall::YIELD | Syscall::MEMOP { .. } => return Ok(()),
+        };
+        let permissions = match self.header.get_permissions() {
+            Some(permissions) => permissions,
+            None => return Ok(()),
+        };
+
+        let mut declared = false;
+        for permission in permissions.filter(|p| p.is_for_driver(driver_number)) {
+            declared = true;
+            // Command 0 only checks that the driver exists, it is allowed for
+            // all the declared drivers.
+            match command_number {
+                Some(command) if command != 0 && !permission.allows_command(command) => {}
+                _ => return Ok(()),
+            }
+        }
+        if declared {
+            Err(ReturnCode::ERESERVE)
+        } else {
+            Err(ReturnCode::ENOSUPPORT)
+        }
+    }
+
     unsafe fn set_syscall_return_value(&self, return_value: isize) {
         self.stored_state.map(|stored_state| {
             self.chip
@@ -1318,7 +1537,7 @@ impl<C: Chip> ProcessType for Process<'_, C> {
 
                 // Move this process to the "running" state so the scheduler
                 // will schedule it.
-                self.state.update(State::Running);
+                self.state.update(self.appid(), State::Running);
 
                 // Update helpful debugging metadata.
                 self.current_stack_pointer.set(stack_bottom as *mut u8);
@@ -1388,6 +1607,41 @@ impl<C: Chip> ProcessType for Process<'_, C> {
             .map_or(0, |debug| debug.timeslice_expiration_count)
     }
 
+    fn debug_denied_syscall_count(&self) -> usize {
+        self.debug.map_or(0, |debug| debug.denied_syscall_count)
+    }
+
+    fn debug_cpu_time_us(&self) -> Option<u64> {
+        self.debug.map_or(None, |debug| debug.cpu_time_us)
+    }
+
+    fn debug_max_stack_depth(&self) -> usize {
+        // The stack grows down from its start towards the start of the memory
+        // of the process. Only the initial stack is painted.
+        let painted_end = self.original_stack_pointer as usize;
+        let stack_start = self
+            .debug
+            .map_or(None, |debug| debug.app_stack_start_pointer)
+            .map_or(painted_end, |pointer| pointer as usize);
+        let mut deepest = self.mem_start() as usize;
+        while deepest < painted_end
+            && unsafe { ptr::read_volatile(deepest as *const u32) } == STACK_PAINT
+        {
+            deepest += mem::size_of::<u32>();
+        }
+        stack_start.saturating_sub(deepest)
+    }
+
+    fn debug_grant_memory_size(&self) -> usize {
+        self.original_kernel_memory_break as usize - self.kernel_memory_break.get() as usize
+    }
+
+    fn debug_cpu_time_used(&self, time_us: u32) {
+        self.debug.map(|debug| {
+            debug.cpu_time_us = Some(debug.cpu_time_us.unwrap_or(0) + time_us as u64)
+        });
+    }
+
     fn debug_timeslice_expired(&self) {
         self.debug
             .map(|debug| debug.timeslice_expiration_count += 1);
@@ -1400,6 +1654,10 @@ impl<C: Chip> ProcessType for Process<'_, C> {
         });
     }
 
+    fn debug_syscall_denied(&self) {
+        self.debug.map(|debug| debug.denied_syscall_count += 1);
+    }
+
     unsafe fn print_memory_map(&self, writer: &mut dyn Write) {
         // Flash
         let flash_end = self.flash.as_ptr().add(self.flash.len()) as usize;
@@ -1677,6 +1935,25 @@ impl<C: 'static + Chip> Process<'_, C> {
             return Ok((None, remaining_memory));
         }
 
+        // Only create the process if the credentials checker of the kernel
+        // accepts the app. The kernel keeps the rejection for introspection.
+        if let Err(error) = kernel.check_credentials(
+            app_flash,
+            process_name,
+            tbf_header.get_credentials(app_flash),
+        ) {
+            if config::CONFIG.debug_load_processes {
+                debug!(
+                    "Process rejected flash={:#010X}-{:#010X} process={:?} - {:?}",
+                    app_flash.as_ptr() as usize,
+                    app_flash.as_ptr() as usize + app_flash.len() - 1,
+                    process_name,
+                    ProcessLoadError::CredentialsRejected(error)
+                );
+            }
+            return Err(ProcessLoadError::CredentialsRejected(error));
+        }
+
         // Otherwise, actually load the app.
         let mut min_app_ram_size = tbf_header.get_minimum_app_ram_size() as usize;
         // debug!("min_app_ram_size {:#010X}", min_app_ram_size);
@@ -1896,6 +2173,9 @@ impl<C: 'static + Chip> Process<'_, C> {
         let initial_stack_pointer = app_memory.as_ptr().add(initial_app_memory_size);
         let initial_sbrk_pointer = app_memory.as_ptr().add(initial_app_memory_size);
 
+        // Paint the initial stack to measure how deep it goes.
+        paint_stack(app_memory.as_ptr(), initial_stack_pointer);
+
         // Set up initial grant region.
         let mut kernel_memory_break = app_memory.as_mut_ptr().add(app_memory.len());
         // debug!("initial_stack_pointer  {:?}", initial_stack_pointer);
@@ -2015,6 +2295,8 @@ impl<C: 'static + Chip> Process<'_, C> {
             last_syscall: None,
             dropped_callback_count: 0,
             timeslice_expiration_count: 0,
+            denied_syscall_count: 0,
+            cpu_time_us: None,
         });
 
         let flash_protected_size = process.header.get_protected_size() as usize;
@@ -2090,7 +2372,7 @@ impl<C: 'static + Chip> Process<'_, C> {
         self.terminate();
 
         // Set the state the process will be in if it cannot be restarted.
-        self.state.update(failure_state);
+        self.state.update(self.appid(), failure_state);
 
         // Check if the restart policy for this app allows us to continue with
         // the restart.
@@ -2132,6 +2414,8 @@ impl<C: 'static + Chip> Process<'_, C> {
             debug.last_syscall = None;
             debug.dropped_callback_count = 0;
             debug.timeslice_expiration_count = 0;
+            debug.denied_syscall_count = 0;
+            debug.cpu_time_us = None;
         });
 
         // We are going to start this process over again, so need the init_fn
@@ -2151,6 +2435,9 @@ impl<C: 'static + Chip> Process<'_, C> {
         self.current_stack_pointer.set(self.original_stack_pointer);
         self.allow_high_water_mark
             .set(self.original_allow_high_water_mark);
+        unsafe {
+            paint_stack(self.mem_start(), self.original_stack_pointer);
+        }
 
         // Handle any architecture-specific requirements for a process when it
         // first starts (as it would when it is new).
@@ -2182,10 +2469,11 @@ impl<C: 'static + Chip> Process<'_, C> {
         let flash_app_start = app_flash_address as usize + flash_protected_size;
 
         // Mark the state as `Unstarted` for the scheduler.
-        self.state.update(State::Unstarted);
+        self.state.update(self.appid(), State::Unstarted);
 
         // Mark that we restarted this process.
         self.restart_count.increment();
+        self.kernel.process_event(self.appid(), ProcessEvent::Restarted);
 
         // Enqueue the initial function.
         self.tasks.map(|tasks| {
@@ -2203,34 +2491,6 @@ impl<C: 'static + Chip> Process<'_, C> {
         self.kernel.increment_work();
     }
 
-    /// Stop and clear a process's state.
-    ///
-    /// This will end the process, but does not reset it such that it could be
-    /// restarted and run again. This function instead frees grants and any
-    /// queued tasks for this process, but leaves the debug information about
-    /// the process and other state intact.
-    fn terminate(&self) {
-        // Remove the tasks that were scheduled for the app from the
-        // amount of work queue.
-        let tasks_len = self.tasks.map_or(0, |tasks| tasks.len());
-        for _ in 0..tasks_len {
-            self.kernel.decrement_work();
-        }
-
-        // And remove those tasks
-        self.tasks.map(|tasks| {
-            tasks.empty();
-        });
-
-        // Clear any grant regions this app has setup with any capsules.
-        unsafe {
-            self.grant_ptrs_reset();
-        }
-
-        // Mark the app as stopped so the scheduler won't try to run it.
-        self.state.update(State::StoppedFaulted);
-    }
-
     /// Get the current stack pointer as a pointer.
     // This is currently safe as the the userspace/kernel boundary
     // implementations of both Risc-V and ARM would fault on context switch if
@@ -2297,3 +2557,152 @@ impl<C: 'static + Chip> Process<'_, C> {
     }
 }
 
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use crate::sim::{loader, tbf, tbf_with, Cap};
+    use std::vec::Vec;
+
+    const DRIVER: usize = 0x8000_0000;
+    const OTHER_DRIVER: usize = 0x3;
+
+    /// A `Permissions` TLV with one `(driver, first command, last command)`
+    /// entry per element of `entries`.
+    fn permissions(entries: &[(u32, u16, u16)]) -> Vec<u8> {
+        let mut tlv = Vec::new();
+        tlv.extend_from_slice(&131u16.to_le_bytes());
+        tlv.extend_from_slice(&(8 * entries.len() as u16).to_le_bytes());
+        for &(driver, first, last) in entries {
+            tlv.extend_from_slice(&driver.to_le_bytes());
+            tlv.extend_from_slice(&first.to_le_bytes());
+            tlv.extend_from_slice(&last.to_le_bytes());
+        }
+        tlv
+    }
+
+    fn process(app: &'static [u8]) -> &'static dyn ProcessType {
+        let loader = loader(64 * 1024);
+        let appid = loader.load(app, &Cap).unwrap();
+        appid.kernel.processes[appid.index].get().unwrap()
+    }
+
+    fn command(driver_number: usize, subdriver_number: usize) -> Syscall {
+        Syscall::COMMAND {
+            driver_number,
+            subdriver_number,
+            arg0: 0,
+            arg1: 0,
+        }
+    }
+
+    fn subscribe(driver_number: usize) -> Syscall {
+        Syscall::SUBSCRIBE {
+            driver_number,
+            subdriver_number: 0,
+            callback_ptr: ptr::null_mut(),
+            appdata: 0,
+        }
+    }
+
+    fn allow(driver_number: usize) -> Syscall {
+        Syscall::ALLOW {
+            driver_number,
+            subdriver_number: 0,
+            allow_address: ptr::null_mut(),
+            allow_size: 0,
+        }
+    }
+
+    #[test]
+    fn an_app_without_permissions_may_call_any_driver() {
+        let process = process(tbf(0));
+        assert_eq!(
+            process.check_syscall_permissions(&command(DRIVER, 7)),
+            Ok(())
+        );
+        assert_eq!(
+            process.check_syscall_permissions(&subscribe(OTHER_DRIVER)),
+            Ok(())
+        );
+        assert_eq!(
+            process.check_syscall_permissions(&allow(OTHER_DRIVER)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn an_app_may_only_call_the_declared_drivers() {
+        let process = process(tbf_with(0, &permissions(&[(DRIVER as u32, 1, 4)])));
+        assert_eq!(
+            process.check_syscall_permissions(&subscribe(DRIVER)),
+            Ok(())
+        );
+        assert_eq!(process.check_syscall_permissions(&allow(DRIVER)), Ok(()));
+        assert_eq!(
+            process.check_syscall_permissions(&command(OTHER_DRIVER, 0)),
+            Err(ReturnCode::ENOSUPPORT)
+        );
+        assert_eq!(
+            process.check_syscall_permissions(&subscribe(OTHER_DRIVER)),
+            Err(ReturnCode::ENOSUPPORT)
+        );
+        assert_eq!(
+            process.check_syscall_permissions(&allow(OTHER_DRIVER)),
+            Err(ReturnCode::ENOSUPPORT)
+        );
+        // Yield and memop are not driver calls.
+        assert_eq!(process.check_syscall_permissions(&Syscall::YIELD), Ok(()));
+        assert_eq!(
+            process.check_syscall_permissions(&Syscall::MEMOP {
+                operand: 0,
+                arg0: 0
+            }),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn an_app_may_only_call_the_declared_commands() {
+        let process = process(tbf_with(0, &permissions(&[(DRIVER as u32, 1, 4)])));
+        for &command_number in &[1, 2, 4] {
+            assert_eq!(
+                process.check_syscall_permissions(&command(DRIVER, command_number)),
+                Ok(())
+            );
+        }
+        for &command_number in &[5, 0xFFFF, 0x10000] {
+            assert_eq!(
+                process.check_syscall_permissions(&command(DRIVER, command_number)),
+                Err(ReturnCode::ERESERVE)
+            );
+        }
+        // Command 0 checks that the driver exists.
+        assert_eq!(
+            process.check_syscall_permissions(&command(DRIVER, 0)),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn the_ranges_of_a_driver_add_up() {
+        let process = process(tbf_with(
+            0,
+            &permissions(&[(DRIVER as u32, 1, 2), (DRIVER as u32, 10, 0xFFFF)]),
+        ));
+        for &command_number in &[1, 2, 10, 11, 0xFFFF, 0x10000] {
+            assert_eq!(
+                process.check_syscall_permissions(&command(DRIVER, command_number)),
+                Ok(())
+            );
+        }
+        for &command_number in &[3, 9] {
+            assert_eq!(
+                process.check_syscall_permissions(&command(DRIVER, command_number)),
+                Err(ReturnCode::ERESERVE)
+            );
+        }
+    }
+}
@@ -0,0 +1,542 @@
+//! Loading, replacing and unloading processes while the kernel runs.
+//!
+//! `load_processes()` creates the processes of the apps found in flash at
+//! boot. A `ProcessLoader` creates more processes afterwards, from TBF images
+//! written to flash by the board, for example with
+//! `capsules::app_flash_driver`. It takes the free slots of the processes
+//! array of the kernel, and the RAM of the apps `load_processes()` left
+//! unused. Unloading a process frees its slot, and the RAM of a process the
+//! loader created, grants included, for the next process loaded. The RAM of
+//! the processes loaded at boot is not reused.
+//!
+//! As the memory of a process is reused, no capsule may keep a `&dyn
+//! ProcessType` across the unloading of the process. Capsules keeping one
+//! register a `ProcessObserver` with the kernel and drop it on
+//! `ProcessEvent::Unloaded`, or look the process up again by its `AppId`.
+//!
+//! Usage
+//! -----
+//!
+//! ```ignore
+//! let process_loader = static_init!(
+//!     kernel::procs::ProcessLoader<earlgrey::chip::EarlGrey>,
+//!     kernel::procs::ProcessLoader::new(
+//!         board_kernel,
+//!         chip,
+//!         kernel::procs::FaultResponse::Panic,
+//!     )
+//! );
+//! let appid = process_loader.load(app_flash, &process_mgmt_cap)?;
+//! ```
+
+use core::convert::TryInto;
+
+use crate::callback::AppId;
+use crate::capabilities::ProcessManagementCapability;
+use crate::config;
+use crate::debug;
+use crate::observer::ProcessEvent;
+use crate::platform::Chip;
+use crate::process::{FaultResponse, Process, ProcessLoadError, ProcessType, State};
+use crate::returncode::ReturnCode;
+use crate::sched::Kernel;
+use crate::tbfheader;
+
+/// Creates and removes processes after boot.
+pub struct ProcessLoader<C: 'static + Chip> {
+    kernel: &'static Kernel,
+    chip: &'static C,
+    /// Start and length of the RAM the processes are created in.
+    memory_start: *mut u8,
+    memory_len: usize,
+    fault_response: FaultResponse,
+}
+
+impl<C: 'static + Chip> ProcessLoader<C> {
+    /// Takes the RAM of the apps that `load_processes()` left unused. It must
+    /// be created after the processes are loaded, and there must be only one.
+    pub fn new(
+        kernel: &'static Kernel,
+        chip: &'static C,
+        fault_response: FaultResponse,
+    ) -> ProcessLoader<C> {
+        let app_memory = kernel.take_unused_app_memory().unwrap_or(&mut []);
+        ProcessLoader {
+            kernel,
+            chip,
+            memory_start: app_memory.as_mut_ptr(),
+            memory_len: app_memory.len(),
+            fault_response,
+        }
+    }
+
+    /// Starts checking the credentials of the TBF image `app_flash`, written
+    /// to flash since boot, with the credentials checker of the kernel. The
+    /// checker tells its client once done, the image may then be loaded once.
+    ///
+    /// Returns `ENODEVICE` if the kernel has no credentials checker, every
+    /// image may then be loaded.
+    pub fn verify(
+        &self,
+        app_flash: &'static [u8],
+        _capability: &dyn ProcessManagementCapability,
+    ) -> ReturnCode {
+        self.kernel.recheck_credentials(app_flash)
+    }
+
+    /// Creates a process from the TBF image `app_flash` in a free slot and
+    /// starts it. Returns the `AppId` of the new process.
+    ///
+    /// If the kernel has a credentials checker, the image must have been
+    /// checked with `verify()` since it was written.
+    pub fn load(
+        &self,
+        app_flash: &'static [u8],
+        _capability: &dyn ProcessManagementCapability,
+    ) -> Result<AppId, ProcessLoadError> {
+        let index = self
+            .kernel
+            .processes
+            .iter()
+            .position(|p| p.get().is_none())
+            .ok_or(ProcessLoadError::NoFreeSlot)?;
+        let process = self.create(app_flash, index)?;
+        self.install(index, process);
+        Ok(process.appid())
+    }
+
+    /// Stops the process `appid` and removes it. Its slot is reused by the next
+    /// processes loaded, and so is its memory if this loader created it. The
+    /// RAM of a process loaded at boot by `load_processes()` is never
+    /// reclaimed: only its slot is freed.
+    ///
+    /// Fails with `EBUSY` if the process is running or its system call is
+    /// being handled: a process cannot unload itself.
+    pub fn unload(
+        &self,
+        appid: AppId,
+        _capability: &dyn ProcessManagementCapability,
+    ) -> Result<(), ReturnCode> {
+        let process = self.find(appid).ok_or(ReturnCode::EINVAL)?;
+        if self.is_running(process) {
+            return Err(ReturnCode::EBUSY);
+        }
+        self.remove(appid.index, process);
+        Ok(())
+    }
+
+    /// Replaces the process `appid` with a process created from the TBF
+    /// image `app_flash`, a newer version of the app, in the same slot.
+    ///
+    /// The new process is created in free memory while the old one still
+    /// runs, and the old one is removed only once the new one is created. On
+    /// any error the old process keeps running untouched. If the free memory
+    /// cannot hold both, this fails with `NotEnoughMemory`: the app must then
+    /// be unloaded first and its new version loaded, losing its state. As
+    /// with `unload()`, a running process cannot be replaced, this fails with
+    /// `ProcessRunning`.
+    ///
+    /// If the kernel has a credentials checker, the image must have been
+    /// checked with `verify()` since it was written.
+    pub fn replace(
+        &self,
+        appid: AppId,
+        app_flash: &'static [u8],
+        _capability: &dyn ProcessManagementCapability,
+    ) -> Result<AppId, ProcessLoadError> {
+        let old = self.find(appid).ok_or(ProcessLoadError::UnknownProcess)?;
+        if self.is_running(old) {
+            return Err(ProcessLoadError::ProcessRunning);
+        }
+        let new = self.create(app_flash, appid.index)?;
+        self.remove(appid.index, old);
+        self.install(appid.index, new);
+        Ok(new.appid())
+    }
+
+    fn find(&self, appid: AppId) -> Option<&'static dyn ProcessType> {
+        self.kernel
+            .processes
+            .get(appid.index)
+            .and_then(|p| p.get())
+            .filter(|process| process.appid() == appid)
+    }
+
+    /// Whether `process` runs, or is the process whose system call is being
+    /// handled. The kernel loop still uses it once its system call returns.
+    fn is_running(&self, process: &dyn ProcessType) -> bool {
+        process.get_state() == State::Running || self.kernel.is_current_process(process.appid())
+    }
+
+    fn install(&self, index: usize, process: &'static dyn ProcessType) {
+        self.kernel.processes[index].set(Some(process));
+        self.kernel
+            .process_event(process.appid(), ProcessEvent::Loaded);
+    }
+
+    fn remove(&self, index: usize, process: &'static dyn ProcessType) {
+        process.terminate();
+        self.kernel
+            .process_event(process.appid(), ProcessEvent::Unloaded);
+        self.kernel.processes[index].set(None);
+        if config::CONFIG.debug_load_processes {
+            debug!(
+                "Unloaded process[{}] {:?} from sram={:#010X}-{:#010X}",
+                index,
+                process.get_process_name(),
+                process.mem_start() as usize,
+                process.mem_end() as usize - 1
+            );
+        }
+    }
+
+    /// Creates the process of the app `app_flash` for the slot `index` in the
+    /// first gap of free memory it fits in. The process is not installed in
+    /// the processes array.
+    fn create(
+        &self,
+        app_flash: &'static [u8],
+        index: usize,
+    ) -> Result<&'static dyn ProcessType, ProcessLoadError> {
+        let lengths = app_flash
+            .get(0..8)
+            .ok_or(ProcessLoadError::NotEnoughFlash)?;
+        let (version, header_length, entry_length) = match tbfheader::parse_tbf_header_lengths(
+            lengths
+                .try_into()
+                .or(Err(ProcessLoadError::InternalError))?,
+        ) {
+            Ok(lengths) => lengths,
+            Err(tbfheader::InitialTbfParseError::UnableToParse) => {
+                let version = u16::from_le_bytes([lengths[0], lengths[1]]);
+                return Err(tbfheader::TbfParseError::UnsupportedVersion(version).into());
+            }
+            Err(tbfheader::InitialTbfParseError::InvalidHeader(_)) => {
+                return Err(tbfheader::TbfParseError::NotEnoughFlash.into());
+            }
+        };
+        let app_flash = app_flash
+            .get(0..entry_length as usize)
+            .ok_or(ProcessLoadError::NotEnoughFlash)?;
+
+        // Report disabled and rejected apps before looking for memory.
+        let header = app_flash
+            .get(0..header_length as usize)
+            .ok_or(ProcessLoadError::NotEnoughFlash)?;
+        let tbf_header = tbfheader::parse_tbf_header(header, version)?;
+        if !tbf_header.is_app() || !tbf_header.enabled() {
+            return Err(ProcessLoadError::NotEnabled);
+        }
+        self.kernel
+            .check_loaded_credentials(
+                app_flash,
+                tbf_header.get_package_name(),
+                tbf_header.get_credentials(app_flash),
+            )
+            .map_err(ProcessLoadError::CredentialsRejected)?;
+
+        let mut gap_start = self.memory_start as usize;
+        while let Some((start, end)) = self.next_gap(gap_start) {
+            gap_start = end;
+            let memory = unsafe { core::slice::from_raw_parts_mut(start as *mut u8, end - start) };
+            let created = unsafe {
+                Process::create(
+                    self.kernel,
+                    self.chip,
+                    app_flash,
+                    header_length as usize,
+                    version,
+                    memory,
+                    self.fault_response,
+                    index,
+                )
+            };
+            match created {
+                Ok((Some(process), _)) => {
+                    if config::CONFIG.debug_load_processes {
+                        debug!(
+                            "Loaded process[{}] from flash={:#010X}-{:#010X} into sram={:#010X}-{:#010X} = {:?}",
+                            index,
+                            app_flash.as_ptr() as usize,
+                            app_flash.as_ptr() as usize + app_flash.len() - 1,
+                            process.mem_start() as usize,
+                            process.mem_end() as usize - 1,
+                            process.get_process_name()
+                        );
+                    }
+                    return Ok(process);
+                }
+                Ok((None, _)) => return Err(ProcessLoadError::InternalError),
+                // Try the next gap.
+                Err(ProcessLoadError::NotEnoughMemory)
+                | Err(ProcessLoadError::MemoryAddressMismatch { .. }) => {}
+                Err(error) => return Err(error),
+            }
+        }
+        Err(ProcessLoadError::NotEnoughMemory)
+    }
+
+    /// Finds the first gap of the memory of the loader starting at or after
+    /// `from` that no process uses. Returns its start and end addresses.
+    fn next_gap(&self, from: usize) -> Option<(usize, usize)> {
+        let memory_end = self.memory_start as usize + self.memory_len;
+        let in_memory =
+            |address: usize| address >= self.memory_start as usize && address <= memory_end;
+        let used = || {
+            self.kernel
+                .processes
+                .iter()
+                .filter_map(|p| p.get())
+                .map(|p| (p.mem_start() as usize, p.mem_end() as usize))
+                .filter(move |&(start, end)| in_memory(start) && in_memory(end))
+        };
+
+        // A gap starts at the start of the memory or at the end of a
+        // process, and ends at the start of the next process.
+        let mut gap_start = from;
+        loop {
+            if let Some((_, end)) =
+                used().find(|&(start, end)| start <= gap_start && gap_start < end)
+            {
+                gap_start = end;
+                continue;
+            }
+            let gap_end = used()
+                .map(|(start, _)| start)
+                .filter(|&start| start > gap_start)
+                .min()
+                .unwrap_or(memory_end);
+            if gap_start >= memory_end {
+                return None;
+            }
+            return Some((gap_start, gap_end));
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use crate::common::list::ListLink;
+    use crate::observer::ProcessObserver;
+    use crate::sim::{loader, tbf, Cap, MockChip};
+    use core::cell::Cell;
+    use std::boxed::Box;
+
+    /// The RAM a process gets when it asks for less than the initial app
+    /// memory of `Process::create()`.
+    const APP_RAM: usize = 3 * 1024;
+
+    /// The RAM `Process::create()` takes for an app asking for `APP_RAM`.
+    fn process_size() -> usize {
+        let loader = loader(64 * 1024);
+        let appid = loader.load(tbf(0), &Cap).unwrap();
+        let process = loader.find(appid).unwrap();
+        process.mem_end() as usize - process.mem_start() as usize
+    }
+
+    fn process(loader: &ProcessLoader<MockChip>, index: usize) -> &'static dyn ProcessType {
+        loader.kernel.processes[index].get().unwrap()
+    }
+
+    /// An observer keeping the last process loaded, as the capsules mirroring
+    /// the processes do, until it is unloaded.
+    struct Mirror {
+        kernel: &'static Kernel,
+        process: Cell<Option<&'static dyn ProcessType>>,
+        link: ListLink<'static, dyn ProcessObserver<'static>>,
+    }
+
+    impl ProcessObserver<'static> for Mirror {
+        fn process_event(&self, appid: AppId, event: ProcessEvent) {
+            match event {
+                ProcessEvent::Loaded => self.process.set(self.kernel.processes[appid.index].get()),
+                ProcessEvent::Unloaded => {
+                    if self.process.get().map_or(false, |p| p.appid() == appid) {
+                        self.process.set(None);
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        fn next_observer(
+            &'static self,
+        ) -> &'static ListLink<'static, dyn ProcessObserver<'static>> {
+            &self.link
+        }
+    }
+
+    #[test]
+    fn load_fills_slots_and_memory_in_order() {
+        let size = process_size();
+        let loader = loader(2 * size);
+
+        let first = loader.load(tbf(0), &Cap).unwrap();
+        let second = loader.load(tbf(0), &Cap).unwrap();
+        assert_eq!(first.index, 0);
+        assert_eq!(second.index, 1);
+        assert_eq!(
+            process(loader, 0).mem_start(),
+            loader.memory_start as *const u8
+        );
+        assert_eq!(process(loader, 1).mem_start(), process(loader, 0).mem_end());
+        assert_eq!(process(loader, 0).get_state(), State::Unstarted);
+
+        assert!(matches!(
+            loader.load(tbf(0), &Cap),
+            Err(ProcessLoadError::NotEnoughMemory)
+        ));
+        assert!(matches!(
+            loader.load(tbf(2 * APP_RAM as u32), &Cap),
+            Err(ProcessLoadError::NotEnoughMemory)
+        ));
+    }
+
+    #[test]
+    fn load_rejects_bad_images() {
+        let loader = loader(process_size());
+
+        assert!(matches!(
+            loader.load(&[], &Cap),
+            Err(ProcessLoadError::NotEnoughFlash)
+        ));
+        let app = tbf(0);
+        assert!(matches!(
+            loader.load(&app[..32], &Cap),
+            Err(ProcessLoadError::NotEnoughFlash)
+        ));
+        assert!(loader.kernel.processes.iter().all(|p| p.get().is_none()));
+    }
+
+    #[test]
+    fn unload_frees_the_slot() {
+        let loader = loader(process_size());
+
+        let appid = loader.load(tbf(0), &Cap).unwrap();
+        let process = process(loader, 0);
+        assert_eq!(loader.unload(appid, &Cap), Ok(()));
+        assert!(loader.kernel.processes[0].get().is_none());
+        assert_eq!(process.get_state(), State::StoppedFaulted);
+        assert_eq!(loader.unload(appid, &Cap), Err(ReturnCode::EINVAL));
+
+        let appid = loader.load(tbf(0), &Cap).unwrap();
+        assert_eq!(appid.index, 0);
+    }
+
+    #[test]
+    fn a_running_process_is_not_unloaded_or_replaced() {
+        let size = process_size();
+        let loader = loader(2 * size);
+
+        let appid = loader.load(tbf(0), &Cap).unwrap();
+        process(loader, 0).set_state(State::Running);
+        assert_eq!(loader.unload(appid, &Cap), Err(ReturnCode::EBUSY));
+        assert!(matches!(
+            loader.replace(appid, tbf(0), &Cap),
+            Err(ProcessLoadError::ProcessRunning)
+        ));
+        assert_eq!(process(loader, 0).appid(), appid);
+        assert_eq!(process(loader, 0).get_state(), State::Running);
+
+        // The kernel handles a system call of the process, which may have
+        // yielded already.
+        process(loader, 0).set_state(State::Yielded);
+        loader.kernel.current_process.set(appid);
+        assert_eq!(loader.unload(appid, &Cap), Err(ReturnCode::EBUSY));
+        assert!(matches!(
+            loader.replace(appid, tbf(0), &Cap),
+            Err(ProcessLoadError::ProcessRunning)
+        ));
+
+        loader.kernel.current_process.clear();
+        assert_eq!(loader.unload(appid, &Cap), Ok(()));
+    }
+
+    #[test]
+    fn load_reuses_the_gap_of_an_unloaded_process() {
+        let size = process_size();
+        let loader = loader(3 * size);
+
+        let first = loader.load(tbf(0), &Cap).unwrap();
+        loader.load(tbf(0), &Cap).unwrap();
+        loader.load(tbf(0), &Cap).unwrap();
+        let gap = process(loader, 0).mem_start();
+        loader.unload(first, &Cap).unwrap();
+
+        // A larger app does not fit the gap.
+        assert!(matches!(
+            loader.load(tbf(2 * APP_RAM as u32), &Cap),
+            Err(ProcessLoadError::NotEnoughMemory)
+        ));
+        let appid = loader.load(tbf(0), &Cap).unwrap();
+        assert_eq!(appid.index, 0);
+        assert_ne!(appid, first);
+        assert_eq!(process(loader, 0).mem_start(), gap);
+    }
+
+    #[test]
+    fn observers_drop_an_unloaded_process_before_its_memory_is_reused() {
+        let loader = loader(process_size());
+        let mirror: &'static Mirror = Box::leak(Box::new(Mirror {
+            kernel: loader.kernel,
+            process: Cell::new(None),
+            link: ListLink::empty(),
+        }));
+        loader.kernel.add_process_observer(mirror, &Cap);
+
+        let old = loader.load(tbf(0), &Cap).unwrap();
+        let gap = process(loader, 0).mem_start();
+        assert_eq!(mirror.process.get().map(|p| p.appid()), Some(old));
+        loader.unload(old, &Cap).unwrap();
+        assert!(mirror.process.get().is_none());
+
+        // The new process is created where the old one was.
+        let new = loader.load(tbf(0), &Cap).unwrap();
+        assert_eq!(process(loader, 0).mem_start(), gap);
+        let mirrored = mirror.process.get().unwrap();
+        assert_eq!(mirrored.appid(), new);
+        assert_eq!(mirrored.get_state(), State::Unstarted);
+        assert_eq!(mirrored.mem_start(), gap);
+    }
+
+    #[test]
+    fn replace_swaps_the_process_in_its_slot() {
+        let size = process_size();
+        let loader = loader(2 * size);
+
+        let old = loader.load(tbf(0), &Cap).unwrap();
+        let old_process = process(loader, 0);
+        let new = loader.replace(old, tbf(0), &Cap).unwrap();
+        assert_eq!(new.index, old.index);
+        assert_ne!(new, old);
+        assert_eq!(old_process.get_state(), State::StoppedFaulted);
+        assert_eq!(process(loader, 0).appid(), new);
+        assert_eq!(process(loader, 0).mem_start(), old_process.mem_end());
+        assert!(matches!(
+            loader.replace(old, tbf(0), &Cap),
+            Err(ProcessLoadError::UnknownProcess)
+        ));
+    }
+
+    #[test]
+    fn replace_keeps_the_old_process_without_memory() {
+        let size = process_size();
+        let loader = loader(size);
+
+        let old = loader.load(tbf(0), &Cap).unwrap();
+        assert!(matches!(
+            loader.replace(old, tbf(0), &Cap),
+            Err(ProcessLoadError::NotEnoughMemory)
+        ));
+        assert_eq!(process(loader, 0).appid(), old);
+        assert_eq!(process(loader, 0).get_state(), State::Unstarted);
+
+        // Unloading first makes room for the new version.
+        loader.unload(old, &Cap).unwrap();
+        let new = loader.load(tbf(0), &Cap).unwrap();
+        assert_eq!(new.index, old.index);
+    }
+}
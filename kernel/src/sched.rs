@@ -13,13 +13,15 @@ use core::ptr::NonNull;
 
 use crate::callback::{AppId, Callback, CallbackId};
 use crate::capabilities;
-use crate::common::cells::NumericCellExt;
+use crate::common::cells::{NumericCellExt, OptionalCell, TakeCell};
+use crate::common::list::List;
 use crate::common::dynamic_deferred_call::DynamicDeferredCall;
 use crate::config;
-use crate::debug;
+use crate::credentials::{AppCredentials, AppCredentialsChecker, CredentialsError, RejectedApp};
 use crate::grant::Grant;
 use crate::ipc;
 use crate::memop;
+use crate::observer::{ProcessEvent, ProcessObserver};
 use crate::platform::mpu::MPU;
 use crate::platform::scheduler_timer::SchedulerTimer;
 use crate::platform::watchdog::WatchDog;
@@ -27,21 +29,13 @@ use crate::platform::{Chip, Platform};
 use crate::process::{self, Task};
 use crate::returncode::ReturnCode;
 use crate::syscall::{ContextSwitchReason, Syscall};
-use crate::sched::ListenerType::TaskQueued;
+use crate::syscall_trace::{SyscallTrace, SyscallTraceRecord, TraceClass, FLAG_DENIED};
 
 /// Threshold in microseconds to consider a process's timeslice to be exhausted.
 /// That is, Tock will skip re-scheduling a process if its remaining timeslice
 /// is less than this threshold.
 pub(crate) const MIN_QUANTA_THRESHOLD_US: u32 = 500;
 
-/// A listener when key events take place such as JobAdded, JobScheduled ..
-#[derive(Clone,Copy,PartialEq)]
-pub enum ListenerType {
-    Idle,
-    TaskQueued,
-}
-pub static mut LISTENER: Cell<ListenerType> = Cell::new(ListenerType::Idle);
-
 /// Trait which any scheduler must implement.
 pub trait Scheduler<C: Chip> {
     /// Decide which process to run next.
@@ -133,8 +127,14 @@ pub struct Kernel {
     /// outstanding callbacks and processes in the Running state.
     work: Cell<usize>,
 
-    /// This holds a pointer to the static array of Process pointers.
-    pub processes: &'static [Option<&'static dyn process::ProcessType>],
+    /// This holds a pointer to the static array of Process pointers. The
+    /// kernel owns the slots: processes are installed and removed through
+    /// `set_process()`.
+    pub(crate) processes: &'static [Cell<Option<&'static dyn process::ProcessType>>],
+
+    /// The RAM of the apps no process was given by `load_processes()`, kept
+    /// for the processes loaded while the kernel runs.
+    unused_app_memory: TakeCell<'static, [u8]>,
 
     /// A counter which keeps track of how many process identifiers have been
     /// created. This is used to create new unique identifiers for processes.
@@ -151,8 +151,27 @@ pub struct Kernel {
     /// established.
     grants_finalized: Cell<bool>,
 
+    /// Clients told about the lifecycle events of the processes.
+    observers: List<'static, dyn ProcessObserver<'static>>,
+
+    /// Decides which apps may run, every app runs if there is none.
+    credentials_checker: OptionalCell<&'static dyn AppCredentialsChecker>,
+
+    /// The first apps rejected by the credentials checker, and how many were
+    /// rejected in total.
+    rejected_apps: [Cell<Option<RejectedApp>>; MAX_REJECTED_APPS],
+    rejected_app_count: Cell<usize>,
+
+    /// Where the system calls are recorded if `trace_syscalls` is set.
+    syscall_trace: OptionalCell<&'static dyn SyscallTrace>,
+
+    /// The process `do_process()` runs, whose system calls are being handled.
+    pub(crate) current_process: OptionalCell<AppId>,
 }
 
+/// How many rejected apps the kernel remembers for introspection.
+const MAX_REJECTED_APPS: usize = 4;
+
 /// Enum used to inform scheduler why a process stopped executing (aka why
 /// `do_process()` returned).
 #[derive(PartialEq, Eq)]
@@ -179,16 +198,213 @@ pub enum StoppedExecutingReason {
 }
 
 impl Kernel {
-    pub fn new(processes: &'static [Option<&'static dyn process::ProcessType>]) -> Kernel {
+    /// The kernel takes the processes array of the board, which must not be
+    /// used afterwards: `load_processes()` and the `ProcessLoader` install the
+    /// processes through the kernel, and the board reads them through
+    /// `process_slots()`.
+    pub fn new(processes: &'static mut [Option<&'static dyn process::ProcessType>]) -> Kernel {
         Kernel {
             work: Cell::new(0),
-            processes,
+            processes: Cell::from_mut(processes).as_slice_of_cells(),
+            unused_app_memory: TakeCell::empty(),
             process_identifier_max: Cell::new(0),
             grant_counter: Cell::new(0),
             grants_finalized: Cell::new(false),
+            observers: List::new(),
+            credentials_checker: OptionalCell::empty(),
+            rejected_apps: Default::default(),
+            rejected_app_count: Cell::new(0),
+            syscall_trace: OptionalCell::empty(),
+            current_process: OptionalCell::empty(),
         }
     }
 
+    /// The slots of the processes array. The processes in them change while
+    /// the kernel runs, the schedulers and the panic handler of the board
+    /// read them through these cells instead of the array given to `new()`.
+    ///
+    /// This is restricted with a capability as the slots can be changed.
+    pub fn process_slots(
+        &self,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) -> &'static [Cell<Option<&'static dyn process::ProcessType>>] {
+        self.processes
+    }
+
+    /// Puts `process` in the slot `index` of the processes array, or empties
+    /// the slot with `None`. Fails with `NoFreeSlot` if there is no such slot.
+    ///
+    /// This is restricted with a capability as it decides which processes
+    /// run. The process removed from the slot, if any, must be terminated.
+    pub fn set_process(
+        &self,
+        index: usize,
+        process: Option<&'static dyn process::ProcessType>,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) -> Result<(), process::ProcessLoadError> {
+        self.processes
+            .get(index)
+            .ok_or(process::ProcessLoadError::NoFreeSlot)?
+            .set(process);
+        Ok(())
+    }
+
+    /// Gives the kernel the RAM of the apps that no process uses, for the
+    /// processes loaded while the kernel runs by a `ProcessLoader`.
+    pub fn set_unused_app_memory(
+        &self,
+        memory: &'static mut [u8],
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) {
+        self.unused_app_memory.replace(memory);
+    }
+
+    /// Takes the RAM of the apps that no process uses, if no one did yet.
+    pub(crate) fn take_unused_app_memory(&self) -> Option<&'static mut [u8]> {
+        self.unused_app_memory.take()
+    }
+
+    /// Whether `appid` is the process `do_process()` runs, including while its
+    /// system calls are being handled.
+    pub(crate) fn is_current_process(&self, appid: AppId) -> bool {
+        self.current_process.contains(&appid)
+    }
+
+    /// Registers the checker deciding which apps may run. It must be set
+    /// before the processes are loaded.
+    ///
+    /// This is restricted with a capability as it decides what code runs on
+    /// the board.
+    pub fn set_credentials_checker(
+        &self,
+        checker: &'static dyn AppCredentialsChecker,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) {
+        self.credentials_checker.set(checker);
+    }
+
+    /// Asks the credentials checker whether the app `app` may run. A rejected
+    /// app is remembered under `name`.
+    pub(crate) fn check_credentials(
+        &self,
+        app: &'static [u8],
+        name: Option<&'static str>,
+        credentials: Option<AppCredentials>,
+    ) -> Result<(), CredentialsError> {
+        let result = self
+            .credentials_checker
+            .map_or(Ok(()), |checker| checker.check_credentials(app, credentials));
+        self.record_rejection(name, result);
+        result
+    }
+
+    /// Asks the credentials checker whether the app `app`, loaded after boot,
+    /// may run. A rejected app is remembered under `name`.
+    pub(crate) fn check_loaded_credentials(
+        &self,
+        app: &'static [u8],
+        name: Option<&'static str>,
+        credentials: Option<AppCredentials>,
+    ) -> Result<(), CredentialsError> {
+        let result = self.credentials_checker.map_or(Ok(()), |checker| {
+            checker.check_loaded_credentials(app, credentials)
+        });
+        self.record_rejection(name, result);
+        result
+    }
+
+    /// Asks the credentials checker to check again the app `app`, written to
+    /// flash after boot. Returns `ENODEVICE` if there is no checker: every app
+    /// may run.
+    pub(crate) fn recheck_credentials(&self, app: &'static [u8]) -> ReturnCode {
+        self.credentials_checker
+            .map_or(ReturnCode::ENODEVICE, |checker| {
+                checker.recheck_credentials(app)
+            })
+    }
+
+    fn record_rejection(&self, name: Option<&'static str>, result: Result<(), CredentialsError>) {
+        if let Err(error) = result {
+            if let Some(slot) = self.rejected_apps.get(self.rejected_app_count.get()) {
+                slot.set(Some(RejectedApp { name, error }));
+            }
+            self.rejected_app_count.increment();
+        }
+    }
+
+    /// How many apps the credentials checker rejected.
+    pub(crate) fn rejected_app_count(&self) -> usize {
+        self.rejected_app_count.get()
+    }
+
+    /// The app rejected `index`-th by the credentials checker, if the kernel
+    /// remembers it.
+    pub(crate) fn rejected_app(&self, index: usize) -> Option<RejectedApp> {
+        self.rejected_apps.get(index).and_then(|slot| slot.get())
+    }
+
+    /// Registers the trace the system calls are recorded in when
+    /// `config::CONFIG.trace_syscalls` is set.
+    ///
+    /// This is restricted with a capability as the trace sees the system
+    /// calls of every process.
+    pub fn set_syscall_trace(
+        &self,
+        trace: &'static dyn SyscallTrace,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) {
+        self.syscall_trace.set(trace);
+    }
+
+    /// Records `record` in the syscall trace, if tracing is enabled.
+    fn trace(&self, record: SyscallTraceRecord) {
+        if config::CONFIG.trace_syscalls {
+            self.syscall_trace.map(|trace| trace.record(record));
+        }
+    }
+
+    /// Records the system call `syscall` the kernel refused to run.
+    fn trace_denied(&self, appid: AppId, syscall: &Syscall, response: ReturnCode) {
+        let mut record = SyscallTraceRecord::syscall(appid, syscall, response);
+        record.flags |= FLAG_DENIED;
+        self.trace(record);
+    }
+
+    /// Registers `observer` for the events of all processes. Observers are
+    /// called in the order they were registered.
+    ///
+    /// This is restricted with a capability as observers see every process.
+    pub fn add_process_observer(
+        &self,
+        observer: &'static dyn ProcessObserver<'static>,
+        _capability: &dyn capabilities::ProcessManagementCapability,
+    ) {
+        self.observers.push_tail(observer);
+    }
+
+    /// Tells the registered observers about an `event` of the process `appid`.
+    ///
+    /// This is only exposed in the core kernel crate.
+    pub(crate) fn process_event(&self, appid: AppId, event: ProcessEvent) {
+        for observer in self.observers.iter() {
+            observer.process_event(appid, event);
+        }
+    }
+
+    /// Tells the registered observers about an `event` of the process `appid`.
+    ///
+    /// This is exposed publicly, but restricted with a capability. The intent
+    /// is that external implementations of `ProcessType` need to be able to
+    /// report their events like the kernel's `Process` does.
+    pub fn process_event_external(
+        &self,
+        appid: AppId,
+        event: ProcessEvent,
+        _capability: &dyn capabilities::ExternalProcessCapability,
+    ) {
+        self.process_event(appid, event);
+    }
+
     /// Something was scheduled for a process, so there is more work to do.
     ///
     /// This is only exposed in the core kernel crate.
@@ -232,7 +448,7 @@ impl Kernel {
 
     /// Helper function for determining if we should service processes or go to
     /// sleep.
-    fn processes_blocked(&self) -> bool {
+    pub fn processes_blocked(&self) -> bool {
         self.work.get() == 0
     }
 
@@ -263,7 +479,7 @@ impl Kernel {
             .map_or(None, |process_entry| {
                 // Check if there is any process state here, or if the entry is
                 // `None`.
-                process_entry.map_or(None, |process| {
+                process_entry.get().map_or(None, |process| {
                     // Check that the process stored here matches the identifier
                     // in the `appid`.
                     if process.appid() == appid {
@@ -283,9 +499,9 @@ impl Kernel {
         F: Fn(&dyn process::ProcessType),
     {
         for process in self.processes.iter() {
-            match process {
+            match process.get() {
                 Some(p) => {
-                    closure(*p);
+                    closure(p);
                 }
                 None => {}
             }
@@ -293,16 +509,18 @@ impl Kernel {
     }
 
     /// Returns an iterator over all processes loaded by the kernel
-    pub(crate) fn get_process_iter(
+    pub fn get_process_iter(
         &self,
     ) -> core::iter::FilterMap<
-        core::slice::Iter<Option<&dyn process::ProcessType>>,
-        fn(&Option<&'static dyn process::ProcessType>) -> Option<&'static dyn process::ProcessType>,
+        core::slice::Iter<Cell<Option<&'static dyn process::ProcessType>>>,
+        fn(
+            &Cell<Option<&'static dyn process::ProcessType>>,
+        ) -> Option<&'static dyn process::ProcessType>,
     > {
         fn keep_some(
-            &x: &Option<&'static dyn process::ProcessType>,
+            x: &Cell<Option<&'static dyn process::ProcessType>>,
         ) -> Option<&'static dyn process::ProcessType> {
-            x
+            x.get()
         }
         self.processes.iter().filter_map(keep_some)
     }
@@ -321,9 +539,9 @@ impl Kernel {
         F: Fn(&dyn process::ProcessType),
     {
         for process in self.processes.iter() {
-            match process {
+            match process.get() {
                 Some(p) => {
-                    closure(*p);
+                    closure(p);
                 }
                 None => {}
             }
@@ -339,9 +557,9 @@ impl Kernel {
         F: Fn(&dyn process::ProcessType) -> ReturnCode,
     {
         for process in self.processes.iter() {
-            match process {
+            match process.get() {
                 Some(p) => {
-                    let ret = closure(*p);
+                    let ret = closure(p);
                     if ret != ReturnCode::FAIL {
                         return ret;
                     }
@@ -357,8 +575,8 @@ impl Kernel {
     /// as from userspace) and needs to be expanded to a full `AppId` for use
     /// with other APIs.
     pub fn lookup_app_by_identifier(&self, identifier: usize) -> Option<AppId> {
-        self.processes.iter().find_map(|&p| {
-            p.map_or(None, |p2| {
+        self.processes.iter().find_map(|p| {
+            p.get().map_or(None, |p2| {
                 if p2.appid().id() == identifier {
                     Some(p2.appid())
                 } else {
@@ -376,7 +594,8 @@ impl Kernel {
     /// verify that the referenced app is still at the correct index.
     pub(crate) fn appid_is_valid(&self, appid: &AppId) -> bool {
         self.processes.get(appid.index).map_or(false, |p| {
-            p.map_or(false, |process| process.appid().id() == appid.id())
+            p.get()
+                .map_or(false, |process| process.appid().id() == appid.id())
         })
     }
 
@@ -455,7 +674,7 @@ impl Kernel {
     /// apps.
     pub fn hardfault_all_apps<C: capabilities::ProcessManagementCapability>(&self, _c: &C) {
         for p in self.processes.iter() {
-            p.map(|process| {
+            p.get().map(|process| {
                 process.set_fault_state();
             });
         }
@@ -494,6 +713,7 @@ impl Kernel {
                             SchedulingDecision::RunProcess((appid, timeslice_us)) => {
                                 self.process_map_or((), appid, |process| {
                                     //debug!(" Process {:?} [{:?}]",process.get_state(), appid.id());
+                                    self.current_process.set(appid);
                                     let (reason, time_executed) = self.do_process(
                                         platform,
                                         chip,
@@ -502,6 +722,7 @@ impl Kernel {
                                         ipc,
                                         timeslice_us,
                                     );
+                                    self.current_process.clear();
                                     scheduler.result(reason, time_executed);
                                 });
                                 //debug!("Ended Running");
@@ -660,6 +881,16 @@ impl Kernel {
                             // decide how to handle the error.
                             if syscall != Syscall::YIELD {
                                 if let Err(response) = platform.filter_syscall(process, &syscall) {
+                                    self.trace_denied(process.appid(), &syscall, response);
+                                    process.set_syscall_return_value(response.into());
+                                    continue;
+                                }
+
+                                // The process may only use the drivers and
+                                // commands it declared in its TBF header.
+                                if let Err(response) = process.check_syscall_permissions(&syscall) {
+                                    process.debug_syscall_denied();
+                                    self.trace_denied(process.appid(), &syscall, response);
                                     process.set_syscall_return_value(response.into());
                                     continue;
                                 }
@@ -669,23 +900,21 @@ impl Kernel {
                             match syscall {
                                 Syscall::MEMOP { operand, arg0 } => {
                                     let res = memop::memop(process, operand, arg0);
-                                    if config::CONFIG.trace_syscalls {
-                                        debug!(
-                                            "[{:?}] memop({}, {:#x}) = {:#x} = {:?}",
-                                            process.appid(),
-                                            operand,
-                                            arg0,
-                                            usize::from(res),
-                                            res
-                                        );
-                                    }
+                                    self.trace(SyscallTraceRecord::syscall(
+                                        process.appid(),
+                                        &syscall,
+                                        res,
+                                    ));
                                     process.set_syscall_return_value(res.into());
                                 }
                                 Syscall::YIELD => {
-                                    if config::CONFIG.trace_syscalls {
-                                        debug!("[{:?}] yield", process.appid());
-                                    }
+                                    self.trace(SyscallTraceRecord::syscall(
+                                        process.appid(),
+                                        &syscall,
+                                        ReturnCode::SUCCESS,
+                                    ));
                                     process.set_yielded_state();
+                                    self.process_event(process.appid(), ProcessEvent::Yielded);
                                     // There might be already enqueued callbacks
                                     continue;
                                 }
@@ -730,18 +959,11 @@ impl Kernel {
                                                 None => ReturnCode::ENODEVICE,
                                             },
                                         );
-                                    if config::CONFIG.trace_syscalls {
-                                        debug!(
-                                            "[{:?}] subscribe({:#x}, {}, @{:#x}, {:#x}) = {:#x} = {:?}",
-                                            process.appid(),
-                                            driver_number,
-                                            subdriver_number,
-                                            callback_ptr as usize,
-                                            appdata,
-                                            usize::from(res),
-                                            res
-                                        );
-                                    }
+                                    self.trace(SyscallTraceRecord::syscall(
+                                        process.appid(),
+                                        &syscall,
+                                        res,
+                                    ));
                                     process.set_syscall_return_value(res.into());
                                 }
                                 Syscall::COMMAND {
@@ -763,18 +985,11 @@ impl Kernel {
                                                 None => ReturnCode::ENODEVICE,
                                             },
                                         );
-                                    if config::CONFIG.trace_syscalls {
-                                        debug!(
-                                            "[{:?}] cmd({:#x}, {}, {:#x}, {:#x}) = {:#x} = {:?}",
-                                            process.appid(),
-                                            driver_number,
-                                            subdriver_number,
-                                            arg0,
-                                            arg1,
-                                            usize::from(res),
-                                            res
-                                        );
-                                    }
+                                    self.trace(SyscallTraceRecord::syscall(
+                                        process.appid(),
+                                        &syscall,
+                                        res,
+                                    ));
                                     process.set_syscall_return_value(res.into());
                                 }
                                 Syscall::ALLOW {
@@ -798,18 +1013,11 @@ impl Kernel {
                                             None => ReturnCode::ENODEVICE,
                                         }
                                     });
-                                    if config::CONFIG.trace_syscalls {
-                                        debug!(
-                                            "[{:?}] allow({:#x}, {}, @{:#x}, {:#x}) = {:#x} = {:?}",
-                                            process.appid(),
-                                            driver_number,
-                                            subdriver_number,
-                                            allow_address as usize,
-                                            allow_size,
-                                            usize::from(res),
-                                            res
-                                        );
-                                    }
+                                    self.trace(SyscallTraceRecord::syscall(
+                                        process.appid(),
+                                        &syscall,
+                                        res,
+                                    ));
                                     process.set_syscall_return_value(res.into());
                                 }
                             }
@@ -818,6 +1026,10 @@ impl Kernel {
                             if scheduler_timer.get_remaining_us().is_none() {
                                 // This interrupt was a timeslice expiration.
                                 process.debug_timeslice_expired();
+                                self.process_event(
+                                    process.appid(),
+                                    ProcessEvent::TimesliceExpired,
+                                );
                                 return_reason = StoppedExecutingReason::TimesliceExpired;
                                 break;
                             }
@@ -843,20 +1055,18 @@ impl Kernel {
                         None => break,
                         Some(cb) => match cb {
                             Task::FunctionCall(ccb) => {
-                                if config::CONFIG.trace_syscalls {
-                                    debug!(
-                                        "[{:?}] function_call @{:#x}({:#x}, {:#x}, {:#x}, {:#x})",
-                                        process.appid(),
-                                        ccb.pc,
-                                        ccb.argument0,
-                                        ccb.argument1,
-                                        ccb.argument2,
-                                        ccb.argument3,
-                                    );
-                                }
+                                let driver = match ccb.source {
+                                    process::FunctionCallSource::Kernel => usize::MAX,
+                                    process::FunctionCallSource::Driver(id) => id.driver_num,
+                                };
+                                self.trace(SyscallTraceRecord::upcall(
+                                    process.appid(),
+                                    TraceClass::Upcall,
+                                    driver,
+                                    [ccb.argument0, ccb.argument1, ccb.argument2, ccb.pc],
+                                ));
 
                                 process.set_process_function(ccb);
-                                LISTENER.set(TaskQueued);
                             }
                             Task::IPC((otherapp, ipc_type)) => {
                                 ipc.map_or_else(
@@ -868,14 +1078,12 @@ impl Kernel {
                                     },
                                     |ipc| {
                                         ipc.schedule_callback(process.appid(), otherapp, ipc_type);
-                                        if config::CONFIG.trace_syscalls {
-                                            debug!(
-                                                "[{:?}] ipc_schedule with type [{:?}] from app[{:?}] ",
-                                                process.appid(),
-                                                ipc_type,
-                                                otherapp,
-                                            );
-                                        }
+                                        self.trace(SyscallTraceRecord::upcall(
+                                            process.appid(),
+                                            TraceClass::Ipc,
+                                            otherapp.id(),
+                                            [ipc_type as usize, 0, 0, 0],
+                                        ));
                                     },
                                 );
                             }
@@ -922,6 +1130,11 @@ impl Kernel {
         // chip is sleeping, for example.
         scheduler_timer.reset();
 
+        // Charge the time to the process for introspection.
+        if let Some(time) = time_executed_us {
+            process.debug_cpu_time_used(time);
+        }
+
         (return_reason, time_executed_us)
     }
 }
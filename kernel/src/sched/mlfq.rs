@@ -16,11 +16,17 @@
 //!           reduced (i.e., it moves down one queue).
 //! - Rule 5: After some time period S, move all the jobs in the system to the
 //!           topmost queue.
+//!
+//! The queue state is kept per process slot. The scheduler must be registered
+//! with `Kernel::add_process_observer()`, so that a process loaded in a slot
+//! starts in the topmost queue rather than where the previous process of the
+//! slot was.
 
 use crate::callback::AppId;
 use crate::common::list::{List, ListLink, ListNode};
 use crate::hil::time;
 use crate::hil::time::Ticks;
+use crate::observer::{ProcessEvent, ProcessObserver};
 use crate::platform::Chip;
 use crate::process::ProcessType;
 use crate::sched::{Kernel, Scheduler, SchedulingDecision, StoppedExecutingReason};
@@ -34,13 +40,13 @@ struct MfProcState {
 
 /// Nodes store per-process state
 pub struct MLFQProcessNode<'a> {
-    proc: &'static Option<&'static dyn ProcessType>,
+    proc: &'static Cell<Option<&'static dyn ProcessType>>,
     state: MfProcState,
     next: ListLink<'a, MLFQProcessNode<'a>>,
 }
 
 impl<'a> MLFQProcessNode<'a> {
-    pub fn new(proc: &'static Option<&'static dyn ProcessType>) -> MLFQProcessNode<'a> {
+    pub fn new(proc: &'static Cell<Option<&'static dyn ProcessType>>) -> MLFQProcessNode<'a> {
         MLFQProcessNode {
             proc,
             state: MfProcState::default(),
@@ -62,6 +68,7 @@ pub struct MLFQSched<'a, A: 'static + time::Alarm<'static>> {
     last_reset_check: Cell<A::Ticks>,
     last_timeslice: Cell<u32>,
     last_queue_idx: Cell<usize>,
+    observer_link: ListLink<'a, dyn ProcessObserver<'a>>,
 }
 
 impl<'a, A: 'static + time::Alarm<'static>> MLFQSched<'a, A> {
@@ -77,6 +84,7 @@ impl<'a, A: 'static + time::Alarm<'static>> MLFQSched<'a, A> {
             last_reset_check: Cell::new(A::Ticks::from(0)),
             last_timeslice: Cell::new(0),
             last_queue_idx: Cell::new(0),
+            observer_link: ListLink::empty(),
         }
     }
 
@@ -110,7 +118,7 @@ impl<'a, A: 'static + time::Alarm<'static>> MLFQSched<'a, A> {
         for (idx, queue) in self.processes.iter().enumerate() {
             let next = queue
                 .iter()
-                .find(|node_ref| node_ref.proc.map_or(false, |proc| proc.ready()));
+                .find(|node_ref| node_ref.proc.get().map_or(false, |proc| proc.ready()));
             if next.is_some() {
                 // pop procs to back until we get to match
                 loop {
@@ -132,6 +140,42 @@ impl<'a, A: 'static + time::Alarm<'static>> MLFQSched<'a, A> {
         }
         (None, 0)
     }
+
+    /// Moves the node of the process `appid` to the tail of the topmost queue
+    /// with a fresh time allotment, as a new job (Rule 3). The other nodes keep
+    /// their order, so the last executed node stays at the head of its queue.
+    fn reset_process_node(&self, appid: AppId) {
+        for queue in self.processes.iter() {
+            let mut found = None;
+            for _ in 0..queue.iter().count() {
+                match queue.pop_head() {
+                    Some(node) if node.proc.get().map_or(false, |p| p.appid() == appid) => {
+                        found = Some(node)
+                    }
+                    Some(node) => queue.push_tail(node),
+                    None => {}
+                }
+            }
+            if let Some(node) = found {
+                node.state.us_used_this_queue.set(0);
+                self.processes[0].push_tail(node);
+                return;
+            }
+        }
+    }
+}
+
+impl<'a, A: 'static + time::Alarm<'static>> ProcessObserver<'a> for MLFQSched<'a, A> {
+    fn process_event(&self, appid: AppId, event: ProcessEvent) {
+        match event {
+            ProcessEvent::Loaded | ProcessEvent::Unloaded => self.reset_process_node(appid),
+            _ => {}
+        }
+    }
+
+    fn next_observer(&'a self) -> &'a ListLink<'a, dyn ProcessObserver<'a>> {
+        &self.observer_link
+    }
 }
 
 impl<'a, A: 'static + time::Alarm<'static>, C: Chip> Scheduler<C> for MLFQSched<'a, A> {
@@ -157,7 +201,7 @@ impl<'a, A: 'static + time::Alarm<'static>, C: Chip> Scheduler<C> for MLFQSched<
             let node_ref = node_ref_opt.unwrap(); // Panic if fail bc processes_blocked()!
             let timeslice =
                 self.get_timeslice_us(queue_idx) - node_ref.state.us_used_this_queue.get();
-            let next = node_ref.proc.unwrap().appid(); // Panic if fail bc processes_blocked()!
+            let next = node_ref.proc.get().unwrap().appid(); // Panic if fail bc processes_blocked()!
             self.last_queue_idx.set(queue_idx);
             self.last_timeslice.set(timeslice);
 
@@ -194,3 +238,82 @@ impl<'a, A: 'static + time::Alarm<'static>, C: Chip> Scheduler<C> for MLFQSched<
         true
     }
 }
+
+#[cfg(test)]
+mod test {
+    extern crate std;
+
+    use super::*;
+    use crate::hil::time::{Freq1MHz, Ticks32};
+    use crate::returncode::ReturnCode;
+    use crate::sim::{kernel, loader_for, tbf, Cap, MockChip};
+    use std::boxed::Box;
+
+    /// An alarm whose time never moves.
+    struct MockAlarm;
+
+    impl time::Time for MockAlarm {
+        type Frequency = Freq1MHz;
+        type Ticks = Ticks32;
+
+        fn now(&self) -> Ticks32 {
+            Ticks32::from(0)
+        }
+    }
+
+    impl<'a> time::Alarm<'a> for MockAlarm {
+        fn set_alarm_client(&'a self, _client: &'a dyn time::AlarmClient) {}
+
+        fn set_alarm(&self, _reference: Ticks32, _dt: Ticks32) {}
+
+        fn get_alarm(&self) -> Ticks32 {
+            Ticks32::from(0)
+        }
+
+        fn disarm(&self) -> ReturnCode {
+            ReturnCode::SUCCESS
+        }
+
+        fn is_armed(&self) -> bool {
+            false
+        }
+
+        fn minimum_dt(&self) -> Ticks32 {
+            Ticks32::from(1)
+        }
+    }
+
+    /// Queue of the node of the process `appid`, and the time it used there.
+    fn queue_of(sched: &MLFQSched<MockAlarm>, appid: AppId) -> Option<(usize, u32)> {
+        sched.processes.iter().enumerate().find_map(|(idx, queue)| {
+            queue
+                .iter()
+                .find(|node| node.proc.get().map_or(false, |p| p.appid() == appid))
+                .map(|node| (idx, node.state.us_used_this_queue.get()))
+        })
+    }
+
+    #[test]
+    fn a_process_loaded_in_a_freed_slot_starts_in_the_topmost_queue() {
+        let kernel = kernel(64 * 1024);
+        let sched: &'static MLFQSched<MockAlarm> = Box::leak(Box::new(MLFQSched::new(&MockAlarm)));
+        for slot in kernel.processes.iter() {
+            sched.processes[0].push_tail(Box::leak(Box::new(MLFQProcessNode::new(slot))));
+        }
+        kernel.add_process_observer(sched, &Cap);
+        let loader = loader_for(kernel);
+
+        let old = loader.load(tbf(0), &Cap).unwrap();
+        // The process uses up its first timeslice, then part of the second.
+        Scheduler::<MockChip>::next(sched, kernel);
+        Scheduler::<MockChip>::result(sched, StoppedExecutingReason::TimesliceExpired, Some(0));
+        Scheduler::<MockChip>::next(sched, kernel);
+        Scheduler::<MockChip>::result(sched, StoppedExecutingReason::KernelPreemption, Some(5000));
+        assert_eq!(queue_of(sched, old), Some((1, 15000)));
+
+        loader.unload(old, &Cap).unwrap();
+        let new = loader.load(tbf(0), &Cap).unwrap();
+        assert_eq!(new.index, old.index);
+        assert_eq!(queue_of(sched, new), Some((0, 0)));
+    }
+}
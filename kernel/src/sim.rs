@@ -0,0 +1,193 @@
+//! Host simulation of a chip, for the tests of the kernel.
+//!
+//! A `MockChip` has no MPU, no timer and no watchdog, and its `MockBoundary`
+//! never runs the processes, so `Process` and `ProcessLoader` run unchanged
+//! under `cargo test`. `tbf` and `tbf_with` write the TBF headers of the apps
+//! of the tests.
+//!
+//! Usage
+//! -----
+//!
+//! ```
+//!     let loader = loader(64 * 1024);
+//!     let appid = loader.load(tbf(0), &Cap).unwrap();
+//! ```
+
+extern crate std;
+
+use self::std::boxed::Box;
+use self::std::vec;
+use self::std::vec::Vec;
+use crate::capabilities::ProcessManagementCapability;
+use crate::platform::Chip;
+use crate::process::{self, FaultResponse};
+use crate::process_loader::ProcessLoader;
+use crate::sched::Kernel;
+use crate::syscall::{ContextSwitchReason, UserspaceKernelBoundary};
+use core::convert::TryInto;
+use core::fmt::Write;
+
+pub(crate) struct Cap;
+unsafe impl ProcessManagementCapability for Cap {}
+
+pub(crate) struct MockBoundary;
+
+impl UserspaceKernelBoundary for MockBoundary {
+    type StoredState = ();
+
+    unsafe fn initialize_process(
+        &self,
+        stack_pointer: *const usize,
+        _stack_size: usize,
+        _state: &mut (),
+    ) -> Result<*const usize, ()> {
+        Ok(stack_pointer)
+    }
+
+    unsafe fn set_syscall_return_value(
+        &self,
+        _stack_pointer: *const usize,
+        _state: &mut (),
+        _return_value: isize,
+    ) {
+    }
+
+    unsafe fn set_process_function(
+        &self,
+        stack_pointer: *const usize,
+        _remaining_stack_memory: usize,
+        _state: &mut (),
+        _callback: process::FunctionCall,
+    ) -> Result<*mut usize, *mut usize> {
+        Ok(stack_pointer as *mut usize)
+    }
+
+    unsafe fn switch_to_process(
+        &self,
+        stack_pointer: *const usize,
+        _state: &mut (),
+    ) -> (*mut usize, ContextSwitchReason) {
+        (
+            stack_pointer as *mut usize,
+            ContextSwitchReason::Interrupted,
+        )
+    }
+
+    unsafe fn print_context(
+        &self,
+        _stack_pointer: *const usize,
+        _state: &(),
+        _writer: &mut dyn Write,
+    ) {
+    }
+}
+
+pub(crate) struct MockChip {
+    boundary: MockBoundary,
+}
+
+impl Chip for MockChip {
+    type MPU = ();
+    type UserspaceKernelBoundary = MockBoundary;
+    type SchedulerTimer = ();
+    type WatchDog = ();
+
+    fn service_pending_interrupts(&self) {}
+
+    fn has_pending_interrupts(&self) -> bool {
+        false
+    }
+
+    fn mpu(&self) -> &() {
+        &()
+    }
+
+    fn scheduler_timer(&self) -> &() {
+        &()
+    }
+
+    fn watchdog(&self) -> &() {
+        &()
+    }
+
+    fn userspace_kernel_boundary(&self) -> &MockBoundary {
+        &self.boundary
+    }
+
+    fn sleep(&self) {}
+
+    unsafe fn atomic<F, R>(&self, f: F) -> R
+    where
+        F: FnOnce() -> R,
+    {
+        f()
+    }
+
+    unsafe fn print_state(&self, _writer: &mut dyn Write) {}
+}
+
+/// A loader with `memory_size` bytes of RAM and four slots.
+pub(crate) fn loader(memory_size: usize) -> &'static ProcessLoader<MockChip> {
+    loader_for(kernel(memory_size))
+}
+
+/// A kernel with `memory_size` bytes of unused app RAM and four slots.
+pub(crate) fn kernel(memory_size: usize) -> &'static Kernel {
+    let processes = Box::leak(Box::new([None; 4]));
+    let kernel: &'static Kernel = Box::leak(Box::new(Kernel::new(processes)));
+    // Process memory must be word aligned.
+    let words = Box::leak(vec![0u64; memory_size / 8].into_boxed_slice());
+    let memory =
+        unsafe { core::slice::from_raw_parts_mut(words.as_mut_ptr() as *mut u8, memory_size) };
+    kernel.set_unused_app_memory(memory, &Cap);
+    kernel
+}
+
+/// A loader taking the unused app RAM of `kernel`.
+pub(crate) fn loader_for(kernel: &'static Kernel) -> &'static ProcessLoader<MockChip> {
+    let chip = Box::leak(Box::new(MockChip {
+        boundary: MockBoundary,
+    }));
+    Box::leak(Box::new(ProcessLoader::new(
+        kernel,
+        chip,
+        FaultResponse::Panic,
+    )))
+}
+
+/// The TBF of an enabled app asking for `minimum_ram_size` bytes of RAM.
+pub(crate) fn tbf(minimum_ram_size: u32) -> &'static [u8] {
+    tbf_with(minimum_ram_size, &[])
+}
+
+/// The TBF of an enabled app asking for `minimum_ram_size` bytes of RAM, with
+/// the TLVs `tlvs` after its Main TLV.
+pub(crate) fn tbf_with(minimum_ram_size: u32, tlvs: &[u8]) -> &'static [u8] {
+    let header_size = 32 + tlvs.len() as u16;
+    let total_size = header_size as u32 + 32;
+
+    let mut app = Vec::new();
+    app.extend_from_slice(&2u16.to_le_bytes());
+    app.extend_from_slice(&header_size.to_le_bytes());
+    app.extend_from_slice(&total_size.to_le_bytes());
+    // Enabled.
+    app.extend_from_slice(&1u32.to_le_bytes());
+    app.extend_from_slice(&0u32.to_le_bytes());
+    // Main TLV: init_fn_offset, protected_size, minimum_ram_size.
+    app.extend_from_slice(&1u16.to_le_bytes());
+    app.extend_from_slice(&12u16.to_le_bytes());
+    app.extend_from_slice(&0u32.to_le_bytes());
+    app.extend_from_slice(&0u32.to_le_bytes());
+    app.extend_from_slice(&minimum_ram_size.to_le_bytes());
+    app.extend_from_slice(tlvs);
+    let checksum = app
+        .chunks_exact(4)
+        .enumerate()
+        .filter(|&(i, _)| i != 3)
+        .fold(0, |checksum, (_, word)| {
+            checksum ^ u32::from_le_bytes(word.try_into().unwrap())
+        });
+    app[12..16].copy_from_slice(&checksum.to_le_bytes());
+    app.resize(total_size as usize, 0);
+    Box::leak(app.into_boxed_slice())
+}
@@ -0,0 +1,351 @@
+//! Binary trace of the system calls of the processes.
+//!
+//! When `config::CONFIG.trace_syscalls` is set, the kernel records every
+//! system call, its result, and every callback and IPC notification delivered
+//! to a process in the `SyscallTrace` registered with the `Kernel`. Records are
+//! small and fixed-size, so tracing does not change much the timing of the
+//! processes, unlike formatting them with `debug!`.
+//!
+//! `SyscallTraceBuffer` keeps the records in a ring buffer until a client, such
+//! as `capsules::syscall_trace::SyscallTraceUart`, sends them to the host,
+//! where `tools/syscall_trace_decode` prints them.
+//!
+//! Record format
+//! -------------
+//!
+//! A record is `RECORD_LENGTH` bytes, all fields being little-endian:
+//!
+//! | Offset | Size | Field                                           |
+//! |--------|------|-------------------------------------------------|
+//! | 0      | 4    | timestamp, in ticks of the clock of the buffer  |
+//! | 4      | 2    | identifier of the process (`AppId::id()`)       |
+//! | 6      | 1    | `TraceClass`                                    |
+//! | 7      | 1    | flags, `FLAG_DENIED`                            |
+//! | 8      | 4    | driver number                                   |
+//! | 12     | 16   | four arguments                                  |
+//! | 28     | 4    | return code, as returned to the process         |
+//!
+//! The driver and the arguments depend on the class:
+//!
+//! - `Subscribe`: subdriver, callback address and appdata.
+//! - `Command`: subdriver, `arg0` and `arg1`.
+//! - `Allow`: subdriver, buffer address and size.
+//! - `Memop`: no driver, the operand and `arg0`.
+//! - `Upcall`: the driver of the callback (`0xFFFFFFFF` for the kernel), the
+//!   first three arguments of the callback and its address.
+//! - `Ipc`: the identifier of the other process and the type of notification,
+//!   `0` for a service and `1` for a client notification.
+//! - `Start`: the frequency of the clock in Hz as driver, and `START_MAGIC`.
+//! - `Lost`: the number of records dropped because the buffer was full.
+//!
+//! Values wider than 32 bits are truncated.
+
+use core::cell::Cell;
+use core::convert::TryInto;
+
+use crate::callback::AppId;
+use crate::common::cells::{MapCell, NumericCellExt, OptionalCell};
+use crate::common::{Queue, RingBuffer};
+use crate::hil::time::{Frequency, Ticks, Time};
+use crate::returncode::ReturnCode;
+use crate::syscall::Syscall;
+
+/// Length in bytes of an encoded record.
+pub const RECORD_LENGTH: usize = 32;
+
+/// First argument of a `Start` record, which lets a decoder find the start of
+/// the records in a stream.
+pub const START_MAGIC: u32 = 0x4543_5254;
+
+/// Flag of the records of the system calls the kernel refused to run, because
+/// of the platform syscall filter or of the permissions of the process.
+pub const FLAG_DENIED: u8 = 1;
+
+/// What a record is about. The system calls use their SVC number.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum TraceClass {
+    Yield = 0,
+    Subscribe = 1,
+    Command = 2,
+    Allow = 3,
+    Memop = 4,
+    /// A callback was delivered to the process.
+    Upcall = 5,
+    /// An IPC notification was delivered to the process.
+    Ipc = 6,
+    /// Records were dropped before this one.
+    Lost = 0xFE,
+    /// The start of a trace stream.
+    Start = 0xFF,
+}
+
+impl TraceClass {
+    pub fn from_u8(class: u8) -> Option<TraceClass> {
+        match class {
+            0 => Some(TraceClass::Yield),
+            1 => Some(TraceClass::Subscribe),
+            2 => Some(TraceClass::Command),
+            3 => Some(TraceClass::Allow),
+            4 => Some(TraceClass::Memop),
+            5 => Some(TraceClass::Upcall),
+            6 => Some(TraceClass::Ipc),
+            0xFE => Some(TraceClass::Lost),
+            0xFF => Some(TraceClass::Start),
+            _ => None,
+        }
+    }
+}
+
+/// A record of the trace.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct SyscallTraceRecord {
+    pub timestamp: u32,
+    pub app: u16,
+    pub class: TraceClass,
+    pub flags: u8,
+    pub driver: u32,
+    pub args: [u32; 4],
+    pub return_code: i32,
+}
+
+impl SyscallTraceRecord {
+    /// An empty record, to initialize the storage of a `SyscallTraceBuffer`.
+    pub const EMPTY: SyscallTraceRecord = SyscallTraceRecord {
+        timestamp: 0,
+        app: 0,
+        class: TraceClass::Yield,
+        flags: 0,
+        driver: 0,
+        args: [0; 4],
+        return_code: 0,
+    };
+
+    fn new(
+        appid: AppId,
+        class: TraceClass,
+        driver: usize,
+        args: [usize; 4],
+        return_code: isize,
+    ) -> SyscallTraceRecord {
+        SyscallTraceRecord {
+            timestamp: 0,
+            app: appid.id() as u16,
+            class,
+            flags: 0,
+            driver: driver as u32,
+            args: [
+                args[0] as u32,
+                args[1] as u32,
+                args[2] as u32,
+                args[3] as u32,
+            ],
+            return_code: return_code as i32,
+        }
+    }
+
+    /// The record of `syscall` made by the process `appid`, which returned
+    /// `result`.
+    pub(crate) fn syscall(
+        appid: AppId,
+        syscall: &Syscall,
+        result: ReturnCode,
+    ) -> SyscallTraceRecord {
+        let (class, driver, args) = match *syscall {
+            Syscall::YIELD => (TraceClass::Yield, 0, [0; 4]),
+            Syscall::SUBSCRIBE {
+                driver_number,
+                subdriver_number,
+                callback_ptr,
+                appdata,
+            } => (
+                TraceClass::Subscribe,
+                driver_number,
+                [subdriver_number, callback_ptr as usize, appdata, 0],
+            ),
+            Syscall::COMMAND {
+                driver_number,
+                subdriver_number,
+                arg0,
+                arg1,
+            } => (
+                TraceClass::Command,
+                driver_number,
+                [subdriver_number, arg0, arg1, 0],
+            ),
+            Syscall::ALLOW {
+                driver_number,
+                subdriver_number,
+                allow_address,
+                allow_size,
+            } => (
+                TraceClass::Allow,
+                driver_number,
+                [subdriver_number, allow_address as usize, allow_size, 0],
+            ),
+            Syscall::MEMOP { operand, arg0 } => (TraceClass::Memop, 0, [operand, arg0, 0, 0]),
+        };
+        SyscallTraceRecord::new(appid, class, driver, args, result.into())
+    }
+
+    /// The record of a callback or IPC notification delivered to the process
+    /// `appid`.
+    pub(crate) fn upcall(
+        appid: AppId,
+        class: TraceClass,
+        driver: usize,
+        args: [usize; 4],
+    ) -> SyscallTraceRecord {
+        SyscallTraceRecord::new(appid, class, driver, args, 0)
+    }
+
+    /// The record starting a stream of records timestamped by a clock of
+    /// `frequency` Hz.
+    pub fn start(frequency: u32) -> SyscallTraceRecord {
+        SyscallTraceRecord {
+            class: TraceClass::Start,
+            driver: frequency,
+            args: [START_MAGIC, 0, 0, 0],
+            ..SyscallTraceRecord::EMPTY
+        }
+    }
+
+    /// The record telling that `count` records were dropped.
+    pub fn lost(count: usize) -> SyscallTraceRecord {
+        SyscallTraceRecord {
+            class: TraceClass::Lost,
+            args: [count as u32, 0, 0, 0],
+            ..SyscallTraceRecord::EMPTY
+        }
+    }
+
+    /// Whether the kernel refused to run the system call.
+    pub fn denied(&self) -> bool {
+        self.flags & FLAG_DENIED != 0
+    }
+
+    pub fn encode(&self, buffer: &mut [u8; RECORD_LENGTH]) {
+        buffer[0..4].copy_from_slice(&self.timestamp.to_le_bytes());
+        buffer[4..6].copy_from_slice(&self.app.to_le_bytes());
+        buffer[6] = self.class as u8;
+        buffer[7] = self.flags;
+        buffer[8..12].copy_from_slice(&self.driver.to_le_bytes());
+        for (i, arg) in self.args.iter().enumerate() {
+            buffer[12 + 4 * i..16 + 4 * i].copy_from_slice(&arg.to_le_bytes());
+        }
+        buffer[28..32].copy_from_slice(&self.return_code.to_le_bytes());
+    }
+
+    /// Decodes an encoded record. Returns `None` if its class is unknown.
+    pub fn decode(buffer: &[u8; RECORD_LENGTH]) -> Option<SyscallTraceRecord> {
+        let word = |offset: usize| {
+            u32::from_le_bytes(buffer[offset..offset + 4].try_into().unwrap_or([0; 4]))
+        };
+        Some(SyscallTraceRecord {
+            timestamp: word(0),
+            app: u16::from_le_bytes([buffer[4], buffer[5]]),
+            class: TraceClass::from_u8(buffer[6])?,
+            flags: buffer[7],
+            driver: word(8),
+            args: [word(12), word(16), word(20), word(24)],
+            return_code: word(28) as i32,
+        })
+    }
+}
+
+/// Where the kernel records the trace.
+pub trait SyscallTrace {
+    /// Called by the kernel for every record, in the middle of handling the
+    /// system call. The timestamp of `record` is left to the trace.
+    fn record(&self, record: SyscallTraceRecord);
+}
+
+/// A client of a `SyscallTraceBuffer`, which sends the records somewhere.
+pub trait SyscallTraceClient {
+    /// A record was added to the buffer. Called for every record, so that a
+    /// client which could not send the previous ones tries again.
+    fn records_available(&self);
+}
+
+/// Keeps the records in a ring buffer until its client takes them, and
+/// timestamps them with `time`. New records are dropped while the buffer is
+/// full.
+pub struct SyscallTraceBuffer<'a, T: Time> {
+    time: &'a T,
+    records: MapCell<RingBuffer<'static, SyscallTraceRecord>>,
+    /// How many records were dropped since the last call to `take_lost()`.
+    lost: Cell<usize>,
+    client: OptionalCell<&'a dyn SyscallTraceClient>,
+}
+
+impl<'a, T: Time> SyscallTraceBuffer<'a, T> {
+    /// The buffer holds one record less than `records`.
+    pub fn new(
+        time: &'a T,
+        records: &'static mut [SyscallTraceRecord],
+    ) -> SyscallTraceBuffer<'a, T> {
+        SyscallTraceBuffer {
+            time,
+            records: MapCell::new(RingBuffer::new(records)),
+            lost: Cell::new(0),
+            client: OptionalCell::empty(),
+        }
+    }
+
+    pub fn set_client(&self, client: &'a dyn SyscallTraceClient) {
+        self.client.set(client);
+    }
+
+    /// The frequency in Hz of the timestamps of the records.
+    pub fn frequency(&self) -> u32 {
+        T::Frequency::frequency()
+    }
+
+    /// Takes the oldest record of the buffer.
+    pub fn dequeue(&self) -> Option<SyscallTraceRecord> {
+        self.records.map_or(None, |records| records.dequeue())
+    }
+
+    /// How many records were dropped since the last call.
+    pub fn take_lost(&self) -> usize {
+        self.lost.replace(0)
+    }
+}
+
+impl<'a, T: Time> SyscallTrace for SyscallTraceBuffer<'a, T> {
+    fn record(&self, mut record: SyscallTraceRecord) {
+        record.timestamp = self.time.now().into_u32();
+        let queued = self
+            .records
+            .map_or(false, |records| records.enqueue(record));
+        if !queued {
+            self.lost.increment();
+        } else {
+            self.client.map(|client| client.records_available());
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn encode_decode() {
+        let record = SyscallTraceRecord {
+            timestamp: 0x1234_5678,
+            app: 3,
+            class: TraceClass::Command,
+            flags: FLAG_DENIED,
+            driver: 0x4_0001,
+            args: [2, 0xFFFF_FFFF, 0, 7],
+            return_code: -10,
+        };
+        let mut buffer = [0; RECORD_LENGTH];
+        record.encode(&mut buffer);
+        assert_eq!(buffer[0..8], [0x78, 0x56, 0x34, 0x12, 3, 0, 2, 1]);
+        assert_eq!(SyscallTraceRecord::decode(&buffer), Some(record));
+
+        buffer[6] = 0x80;
+        assert_eq!(SyscallTraceRecord::decode(&buffer), None);
+    }
+}
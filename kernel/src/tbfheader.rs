@@ -8,6 +8,8 @@ use core::fmt;
 use core::iter::Iterator;
 use core::{mem, str};
 
+use crate::credentials::{AppCredentials, CredentialsFormat};
+
 /// Takes a value and rounds it up to be aligned % 4
 macro_rules! align4 {
     ($e:expr) => {
@@ -117,6 +119,10 @@ pub(crate) enum TbfHeaderTypes {
     TbfHeaderWriteableFlashRegions = 2,
     TbfHeaderPackageName = 3,
     TbfHeaderFixedAddresses = 5,
+    TbfHeaderVppFirmware = 128,
+    TbfHeaderVppLibrary = 129,
+    TbfHeaderCredentials = 130,
+    TbfHeaderPermissions = 131,
 
     /// Some field in the header that we do not understand. Since the TLV format
     /// specifies the length of each section, if we get a field we do not
@@ -221,6 +227,10 @@ impl core::convert::TryFrom<u16> for TbfHeaderTypes {
             2 => Ok(TbfHeaderTypes::TbfHeaderWriteableFlashRegions),
             3 => Ok(TbfHeaderTypes::TbfHeaderPackageName),
             5 => Ok(TbfHeaderTypes::TbfHeaderFixedAddresses),
+            128 => Ok(TbfHeaderTypes::TbfHeaderVppFirmware),
+            129 => Ok(TbfHeaderTypes::TbfHeaderVppLibrary),
+            130 => Ok(TbfHeaderTypes::TbfHeaderCredentials),
+            131 => Ok(TbfHeaderTypes::TbfHeaderPermissions),
             _ => Ok(TbfHeaderTypes::Unknown),
         }
     }
@@ -308,6 +318,53 @@ impl core::convert::TryFrom<&[u8]> for TbfHeaderV2FixedAddresses {
     }
 }
 
+/// A driver an app may use, and the range of its commands the app may call.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct TbfHeaderV2Permission {
+    driver_number: u32,
+    first_command: u16,
+    /// Last command allowed. `0xFFFF` allows all the commands from
+    /// `first_command` on.
+    last_command: u16,
+}
+
+impl core::convert::TryFrom<&[u8]> for TbfHeaderV2Permission {
+    type Error = TbfParseError;
+
+    fn try_from(b: &[u8]) -> Result<TbfHeaderV2Permission, Self::Error> {
+        Ok(TbfHeaderV2Permission {
+            driver_number: u32::from_le_bytes(
+                b.get(0..4)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+            first_command: u16::from_le_bytes(
+                b.get(4..6)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+            last_command: u16::from_le_bytes(
+                b.get(6..8)
+                    .ok_or(TbfParseError::InternalError)?
+                    .try_into()?,
+            ),
+        })
+    }
+}
+
+impl TbfHeaderV2Permission {
+    /// Whether this entry is about the driver `driver_number`.
+    pub(crate) fn is_for_driver(&self, driver_number: usize) -> bool {
+        self.driver_number as usize == driver_number
+    }
+
+    /// Whether this entry allows the command `command_number`.
+    pub(crate) fn allows_command(&self, command_number: usize) -> bool {
+        command_number >= self.first_command as usize
+            && (self.last_command == 0xFFFF || command_number <= self.last_command as usize)
+    }
+}
+
 /// Single header that can contain all parts of a v2 header.
 ///
 /// Note, this struct limits the number of writeable regions an app can have to
@@ -320,6 +377,10 @@ pub(crate) struct TbfHeaderV2 {
     package_name: Option<&'static str>,
     writeable_regions: Option<[Option<TbfHeaderV2WriteableFlashRegion>; 4]>,
     fixed_addresses: Option<TbfHeaderV2FixedAddresses>,
+    vpp_firmware: Option<&'static [u8]>,
+    vpp_library: Option<&'static [u8]>,
+    credentials_format: Option<CredentialsFormat>,
+    permissions: Option<&'static [u8]>,
 }
 
 /// Type that represents the fields of the Tock Binary Format header.
@@ -446,6 +507,51 @@ impl TbfHeader {
             start => Some(start),
         }
     }
+
+    /// Get the VPP Firmware header (VFF) carried by the app, if any.
+    pub(crate) fn get_vpp_firmware_header(&self) -> Option<&'static [u8]> {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd.vpp_firmware,
+            _ => None,
+        }
+    }
+
+    /// Get the VPP shared library header carried by the app, if any.
+    pub(crate) fn get_vpp_library_header(&self) -> Option<&'static [u8]> {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd.vpp_library,
+            _ => None,
+        }
+    }
+
+    /// Get the drivers and commands the app declared it uses, if the header
+    /// has a `Permissions` TLV. Without it, the app may use any driver.
+    pub(crate) fn get_permissions(
+        &self,
+    ) -> Option<impl Iterator<Item = TbfHeaderV2Permission>> {
+        let permissions = match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd.permissions?,
+            _ => return None,
+        };
+        // The length of the TLV was checked when parsing the header.
+        Some(
+            permissions
+                .chunks_exact(8)
+                .filter_map(|entry| entry.try_into().ok()),
+        )
+    }
+
+    /// Get the credentials of the app from its footer, if the header has a
+    /// `Credentials` TLV. `app` must be the whole TBF of the app.
+    pub(crate) fn get_credentials(&self, app: &'static [u8]) -> Option<AppCredentials> {
+        match *self {
+            TbfHeader::TbfHeaderV2(hd) => hd.credentials_format.and_then(|format| {
+                let length = app.len().min(hd.base.total_size as usize);
+                AppCredentials::new(&app[..length], hd.base.header_size as usize, format)
+            }),
+            _ => None,
+        }
+    }
 }
 
 /// Parse the TBF header length and the entire length of the TBF binary.
@@ -563,6 +669,10 @@ pub(crate) fn parse_tbf_header(
                     Default::default();
                 let mut app_name_str = "";
                 let mut fixed_address_pointer: Option<TbfHeaderV2FixedAddresses> = None;
+                let mut vpp_firmware: Option<&'static [u8]> = None;
+                let mut vpp_library: Option<&'static [u8]> = None;
+                let mut credentials_format: Option<CredentialsFormat> = None;
+                let mut permissions: Option<&'static [u8]> = None;
 
                 // Iterate the remainder of the header looking for TLV entries.
                 while remaining.len() > 0 {
@@ -643,6 +753,58 @@ pub(crate) fn parse_tbf_header(
                             }
                         }
 
+                        TbfHeaderTypes::TbfHeaderVppFirmware => {
+                            vpp_firmware = Some(
+                                remaining
+                                    .get(0..tlv_header.length as usize)
+                                    .ok_or(TbfParseError::NotEnoughFlash)?,
+                            );
+                        }
+
+                        TbfHeaderTypes::TbfHeaderVppLibrary => {
+                            vpp_library = Some(
+                                remaining
+                                    .get(0..tlv_header.length as usize)
+                                    .ok_or(TbfParseError::NotEnoughFlash)?,
+                            );
+                        }
+
+                        TbfHeaderTypes::TbfHeaderCredentials => {
+                            // The footer described by the TLV must fit between
+                            // the header and the end of the app.
+                            if tlv_header.length as usize == 4 {
+                                let format = CredentialsFormat::from(u32::from_le_bytes(
+                                    remaining
+                                        .get(0..4)
+                                        .ok_or(TbfParseError::NotEnoughFlash)?
+                                        .try_into()?,
+                                ));
+                                if (tbf_header_base.total_size as usize)
+                                    < tbf_header_base.header_size as usize + format.footer_length()
+                                {
+                                    return Err(TbfParseError::BadTlvEntry(
+                                        tlv_header.tipe as usize,
+                                    ));
+                                }
+                                credentials_format = Some(format);
+                            } else {
+                                return Err(TbfParseError::BadTlvEntry(tlv_header.tipe as usize));
+                            }
+                        }
+
+                        TbfHeaderTypes::TbfHeaderPermissions => {
+                            let entry_len = 8;
+                            if tlv_header.length as usize % entry_len == 0 {
+                                permissions = Some(
+                                    remaining
+                                        .get(0..tlv_header.length as usize)
+                                        .ok_or(TbfParseError::NotEnoughFlash)?,
+                                );
+                            } else {
+                                return Err(TbfParseError::BadTlvEntry(tlv_header.tipe as usize));
+                            }
+                        }
+
                         _ => {}
                     }
 
@@ -660,6 +822,10 @@ pub(crate) fn parse_tbf_header(
                     package_name: Some(app_name_str),
                     writeable_regions: Some(wfr_pointer),
                     fixed_addresses: fixed_address_pointer,
+                    vpp_firmware: vpp_firmware,
+                    vpp_library: vpp_library,
+                    credentials_format: credentials_format,
+                    permissions: permissions,
                 };
 
                 Ok(TbfHeader::TbfHeaderV2(tbf_header))
@@ -668,3 +834,41 @@ pub(crate) fn parse_tbf_header(
         _ => Err(TbfParseError::UnsupportedVersion(version)),
     }
 }
+
+/// Find the VPP Firmware header (VFF) carried by an app in its TBF header.
+///
+/// The `header` must be a slice that only contains the TBF header, as for
+/// `parse_tbf_header()`. Returns `Ok(None)` if the app has no VPP Firmware TLV,
+/// or if the header is only padding.
+pub fn parse_tbf_header_vpp_firmware(
+    header: &'static [u8],
+    version: u16,
+) -> Result<Option<&'static [u8]>, TbfParseError> {
+    parse_tbf_header(header, version).map(|tbf_header| tbf_header.get_vpp_firmware_header())
+}
+
+/// Find the VPP shared library header carried by an app in its TBF header.
+///
+/// The `header` must be a slice that only contains the TBF header, as for
+/// `parse_tbf_header()`. Returns `Ok(None)` if the app is not a VPP shared
+/// library, or if the header is only padding.
+pub fn parse_tbf_header_vpp_library(
+    header: &'static [u8],
+    version: u16,
+) -> Result<Option<&'static [u8]>, TbfParseError> {
+    parse_tbf_header(header, version).map(|tbf_header| tbf_header.get_vpp_library_header())
+}
+
+/// Find the credentials of an app.
+///
+/// The `app` must be the whole TBF of the app, starting with its header of
+/// `header_length` bytes. Returns `Ok(None)` if the app has no `Credentials`
+/// TLV, or if the header is only padding.
+pub fn parse_tbf_header_credentials(
+    app: &'static [u8],
+    header_length: usize,
+    version: u16,
+) -> Result<Option<AppCredentials>, TbfParseError> {
+    let header = app.get(0..header_length).ok_or(TbfParseError::NotEnoughFlash)?;
+    parse_tbf_header(header, version).map(|tbf_header| tbf_header.get_credentials(app))
+}
@@ -0,0 +1,298 @@
+//! Decodes the binary syscall trace of the kernel, as sent by
+//! `capsules::syscall_trace::SyscallTraceUart`, into a readable trace.
+//!
+//! Records are decoded with the kernel, `SyscallTraceRecord::decode`, so the
+//! decoder always follows the record format of the kernel it is built with.
+
+use kernel::syscall_trace::{SyscallTraceRecord, TraceClass, RECORD_LENGTH, START_MAGIC};
+use std::convert::TryInto;
+use std::fs;
+use std::io::{self, Read};
+use std::process;
+
+/// Prints an error message and usage string. Used to report command line
+/// argument errors.
+fn usage_error(message: &str) {
+    println!(
+        "{}
+
+Usage:
+  syscall_trace_decode [<trace>] [--frequency <Hz>]
+
+Reads the binary syscall trace captured from the UART or RTT channel of the
+trace, or the standard input without <trace>, and prints one line per record.
+
+Timestamps are printed in seconds since the first record, using the frequency
+of the Start record of the trace or --frequency, and in ticks without either.",
+        message
+    );
+}
+
+/// Names of the return codes of the kernel, indexed by their negated value.
+const RETURN_CODES: [&str; 14] = [
+    "SUCCESS",
+    "FAIL",
+    "EBUSY",
+    "EALREADY",
+    "EOFF",
+    "ERESERVE",
+    "EINVAL",
+    "ESIZE",
+    "ECANCEL",
+    "ENOMEM",
+    "ENOSUPPORT",
+    "ENODEVICE",
+    "EUNINSTALLED",
+    "ENOACK",
+];
+
+fn return_code(code: i32) -> String {
+    if code > 0 {
+        return format!("{:#x}", code);
+    }
+    match RETURN_CODES.get(code.wrapping_neg() as usize) {
+        Some(name) => name.to_string(),
+        None => format!("{}", code),
+    }
+}
+
+/// The offset of the first `Start` record of `trace`, where the records of a
+/// stream captured from its beginning start.
+fn find_start(trace: &[u8]) -> Option<usize> {
+    trace.windows(RECORD_LENGTH).position(|window| {
+        window[6] == TraceClass::Start as u8 && window[12..16] == START_MAGIC.to_le_bytes()
+    })
+}
+
+/// Formats the part of a record specific to its class.
+fn describe(record: &SyscallTraceRecord) -> String {
+    let [a0, a1, a2, a3] = record.args;
+    let result = format!(
+        "= {:#x} = {}",
+        record.return_code,
+        return_code(record.return_code)
+    );
+    let call = match record.class {
+        TraceClass::Yield => "yield".to_string(),
+        TraceClass::Subscribe => format!(
+            "subscribe({:#x}, {}, @{:#x}, {:#x}) {}",
+            record.driver, a0, a1, a2, result
+        ),
+        TraceClass::Command => format!(
+            "cmd({:#x}, {}, {:#x}, {:#x}) {}",
+            record.driver, a0, a1, a2, result
+        ),
+        TraceClass::Allow => format!(
+            "allow({:#x}, {}, @{:#x}, {:#x}) {}",
+            record.driver, a0, a1, a2, result
+        ),
+        TraceClass::Memop => format!("memop({}, {:#x}) {}", a0, a1, result),
+        TraceClass::Upcall if record.driver == u32::MAX => {
+            format!("function_call @{:#x}({:#x}, {:#x}, {:#x})", a3, a0, a1, a2)
+        }
+        TraceClass::Upcall => format!(
+            "callback[{:#x}] @{:#x}({:#x}, {:#x}, {:#x})",
+            record.driver, a3, a0, a1, a2
+        ),
+        TraceClass::Ipc => format!(
+            "ipc {} from app {}",
+            if a0 == 0 { "service" } else { "client" },
+            record.driver
+        ),
+        TraceClass::Lost => format!("{} records lost", a0),
+        TraceClass::Start => format!("trace start, {} Hz", record.driver),
+    };
+    if record.denied() {
+        format!("{} denied", call)
+    } else {
+        call
+    }
+}
+
+/// Decodes `trace` into one line per record. `frequency` overrides the one
+/// of the `Start` record.
+fn decode(trace: &[u8], frequency: Option<u32>) -> Vec<String> {
+    let start = find_start(trace).unwrap_or(0);
+    let mut frequency = frequency;
+    // Ticks since the first record, counting the wraps of the timestamps.
+    let mut ticks: u64 = 0;
+    let mut last: Option<u32> = None;
+    let mut lines = Vec::new();
+
+    for (index, chunk) in trace[start..].chunks_exact(RECORD_LENGTH).enumerate() {
+        let offset = start + index * RECORD_LENGTH;
+        let record = match chunk.try_into().ok().and_then(SyscallTraceRecord::decode) {
+            Some(record) => record,
+            None => {
+                lines.push(format!("{:#08x}: invalid record", offset));
+                continue;
+            }
+        };
+        match record.class {
+            TraceClass::Start => {
+                frequency = frequency.or(Some(record.driver));
+                lines.push(describe(&record));
+                continue;
+            }
+            TraceClass::Lost => {
+                lines.push(describe(&record));
+                continue;
+            }
+            _ => {}
+        }
+
+        ticks += u64::from(last.map_or(0, |last| record.timestamp.wrapping_sub(last)));
+        last = Some(record.timestamp);
+        let time = match frequency {
+            Some(hz) if hz > 0 => format!("{:.6}", ticks as f64 / f64::from(hz)),
+            _ => format!("{}", ticks),
+        };
+        lines.push(format!(
+            "{:>12} [{}] {}",
+            time,
+            record.app,
+            describe(&record)
+        ));
+    }
+
+    let remainder = (trace.len() - start) % RECORD_LENGTH;
+    if remainder != 0 {
+        eprintln!("warning: {} trailing bytes ignored", remainder);
+    }
+    lines
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let mut path = None;
+    let mut frequency = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--frequency" => match args.next().and_then(|hz| hz.parse::<u32>().ok()) {
+                Some(hz) => frequency = Some(hz),
+                None => {
+                    usage_error("--frequency expects a frequency in Hz");
+                    process::exit(2);
+                }
+            },
+            _ if arg.starts_with("--") || path.is_some() => {
+                usage_error("Incorrect arguments");
+                process::exit(2);
+            }
+            _ => path = Some(arg),
+        }
+    }
+
+    let trace = match path {
+        Some(path) => fs::read(path).map_err(|e| format!("cannot read {}: {}", path, e)),
+        None => {
+            let mut trace = Vec::new();
+            io::stdin()
+                .read_to_end(&mut trace)
+                .map(|_| trace)
+                .map_err(|e| format!("cannot read the standard input: {}", e))
+        }
+    };
+    match trace {
+        Ok(trace) => {
+            for line in decode(&trace, frequency) {
+                println!("{}", line);
+            }
+        }
+        Err(message) => {
+            eprintln!("error: {}", message);
+            process::exit(1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn encode(records: &[SyscallTraceRecord]) -> Vec<u8> {
+        let mut trace = Vec::new();
+        for record in records {
+            let mut buffer = [0; RECORD_LENGTH];
+            record.encode(&mut buffer);
+            trace.extend_from_slice(&buffer);
+        }
+        trace
+    }
+
+    fn command(timestamp: u32) -> SyscallTraceRecord {
+        SyscallTraceRecord {
+            timestamp,
+            app: 1,
+            class: TraceClass::Command,
+            driver: 0x1,
+            args: [2, 0x10, 0, 0],
+            ..SyscallTraceRecord::EMPTY
+        }
+    }
+
+    #[test]
+    fn find_start_skips_a_partial_record() {
+        let mut trace = vec![0xFF; 5];
+        trace.extend(encode(&[SyscallTraceRecord::start(1000), command(0)]));
+        assert_eq!(find_start(&trace), Some(5));
+
+        let lines = decode(&trace, None);
+        assert_eq!(lines.len(), 2);
+        assert_eq!(
+            lines[1].trim_start(),
+            "0.000000 [1] cmd(0x1, 2, 0x10, 0x0) = 0x0 = SUCCESS"
+        );
+    }
+
+    #[test]
+    fn find_start_needs_the_magic() {
+        let mut start = SyscallTraceRecord::start(1000);
+        start.args[0] = 0;
+        assert_eq!(find_start(&encode(&[start, command(0)])), None);
+    }
+
+    #[test]
+    fn timestamps_count_the_wraps() {
+        let trace = encode(&[
+            SyscallTraceRecord::start(1000),
+            command(u32::MAX - 499),
+            command(500),
+            command(1500),
+        ]);
+        let times: Vec<String> = decode(&trace, None)
+            .iter()
+            .skip(1)
+            .map(|line| line.split_whitespace().next().unwrap().to_string())
+            .collect();
+        assert_eq!(times, ["0.000000", "1.000000", "2.000000"]);
+    }
+
+    #[test]
+    fn frequency_overrides_the_start_record() {
+        let trace = encode(&[SyscallTraceRecord::start(1000), command(0), command(4000)]);
+        assert!(decode(&trace, Some(2000))[2].starts_with("    2.000000 "));
+    }
+
+    #[test]
+    fn ticks_without_frequency() {
+        let trace = encode(&[command(10), command(15)]);
+        assert_eq!(
+            decode(&trace, None)[1].trim_start(),
+            "5 [1] cmd(0x1, 2, 0x10, 0x0) = 0x0 = SUCCESS"
+        );
+    }
+
+    #[test]
+    fn start_and_lost_records() {
+        let trace = encode(&[
+            SyscallTraceRecord::start(32768),
+            SyscallTraceRecord::lost(7),
+        ]);
+        assert_eq!(
+            decode(&trace, None),
+            ["trace start, 32768 Hz", "7 records lost"]
+        );
+    }
+}
@@ -0,0 +1,141 @@
+//! Extraction of the segments of a Process from its binary.
+//!
+//! For an ELF file, the allocated sections are sorted by their flags: the
+//! executable ones make the CODE segment, the read-only ones the CONSTANTS
+//! segment and the writable ones the DATA segment. Each segment spans its
+//! sections by address, gaps and `.bss` being filled with zeros. The entry
+//! point is the offset of the ELF entry in the CODE segment.
+//!
+//! A TBF binary is position independent and is kept whole as the CODE
+//! segment, its entry point being the `_start` offset of its Main TLV. It has
+//! no CONSTANTS nor DATA segment.
+
+use elf::types::{SHF_ALLOC, SHF_EXECINSTR, SHF_WRITE, SHT_NOBITS};
+use std::io::Cursor;
+
+const ELF_MAGIC: &[u8] = b"\x7fELF";
+const TBF_HEADER_MAIN: u16 = 1;
+
+/// The CODE, CONSTANTS and DATA segments of a Process.
+pub struct App {
+    pub entry: u32,
+    pub code: Vec<u8>,
+    pub constants: Vec<u8>,
+    pub data: Vec<u8>,
+}
+
+pub fn parse(binary: &[u8]) -> Result<App, String> {
+    if binary.starts_with(ELF_MAGIC) {
+        parse_elf(binary)
+    } else {
+        parse_tbf(binary)
+    }
+}
+
+/// A segment being assembled from sections.
+#[derive(Default)]
+struct Segment {
+    start: u64,
+    bytes: Vec<u8>,
+}
+
+impl Segment {
+    fn add(&mut self, address: u64, bytes: &[u8]) -> Result<(), String> {
+        if self.bytes.is_empty() {
+            self.start = address;
+        }
+        let offset = address
+            .checked_sub(self.start)
+            .ok_or("sections of a segment are not sorted")? as usize;
+        if offset < self.bytes.len() {
+            return Err(format!(
+                "section at {:#x} overlaps the previous one",
+                address
+            ));
+        }
+        self.bytes.resize(offset, 0);
+        self.bytes.extend_from_slice(bytes);
+        Ok(())
+    }
+}
+
+fn parse_elf(binary: &[u8]) -> Result<App, String> {
+    let file = elf::File::open_stream(&mut Cursor::new(binary))
+        .map_err(|e| format!("invalid ELF file: {:?}", e))?;
+    let mut sections: Vec<&elf::Section> = file
+        .sections
+        .iter()
+        .filter(|section| section.shdr.flags.0 & SHF_ALLOC.0 != 0 && section.shdr.size != 0)
+        .collect();
+    sections.sort_by_key(|section| section.shdr.addr);
+
+    let mut code = Segment::default();
+    let mut constants = Segment::default();
+    let mut data = Segment::default();
+    for section in sections {
+        let flags = section.shdr.flags.0;
+        let segment = if flags & SHF_EXECINSTR.0 != 0 {
+            &mut code
+        } else if flags & SHF_WRITE.0 != 0 {
+            &mut data
+        } else {
+            &mut constants
+        };
+        if section.shdr.shtype == SHT_NOBITS {
+            segment.add(section.shdr.addr, &vec![0; section.shdr.size as usize])?;
+        } else {
+            segment.add(section.shdr.addr, &section.data)?;
+        }
+    }
+
+    let entry = file
+        .ehdr
+        .entry
+        .checked_sub(code.start)
+        .filter(|&entry| entry < code.bytes.len() as u64)
+        .ok_or_else(|| format!("entry point {:#x} is not in the code", file.ehdr.entry))?;
+    Ok(App {
+        entry: entry as u32,
+        code: code.bytes,
+        constants: constants.bytes,
+        data: data.bytes,
+    })
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> Option<u16> {
+    let b = bytes.get(offset..offset + 2)?;
+    Some(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> Option<u32> {
+    let b = bytes.get(offset..offset + 4)?;
+    Some(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn parse_tbf(binary: &[u8]) -> Result<App, String> {
+    let invalid = || "neither an ELF nor a TBF binary".to_string();
+    if read_u16(binary, 0).ok_or_else(invalid)? != 2 {
+        return Err(invalid());
+    }
+    let header_size = read_u16(binary, 2).ok_or_else(invalid)? as usize;
+    let total_size = read_u32(binary, 4).ok_or_else(invalid)? as usize;
+    let code = binary.get(0..total_size).ok_or_else(invalid)?;
+
+    // Walk the TLVs following the 16 byte base header, each padded to 4 bytes.
+    let mut offset = 16;
+    while offset + 4 <= header_size {
+        let tlv_type = read_u16(binary, offset).ok_or_else(invalid)?;
+        let tlv_length = read_u16(binary, offset + 2).ok_or_else(invalid)? as usize;
+        if tlv_type == TBF_HEADER_MAIN {
+            let init_fn_offset = read_u32(binary, offset + 4).ok_or_else(invalid)?;
+            return Ok(App {
+                entry: header_size as u32 + init_fn_offset,
+                code: code.to_vec(),
+                constants: Vec::new(),
+                data: Vec::new(),
+            });
+        }
+        offset += 4 + ((tlv_length + 3) & !3);
+    }
+    Err("the TBF binary has no Main TLV".to_string())
+}
@@ -0,0 +1,130 @@
+//! Encoding of a VFF image: the Firmware header followed by the Memory
+//! Partition of the Firmware.
+//!
+//! The header is the Firmware Descriptor followed by the Process, Mailbox, IPC
+//! and LIB Descriptor arrays, in the layout decoded by `vff::parse_vpp_header`.
+//! The Memory Partition holds the CODE, CONSTANTS, DATA, NVM and stack of each
+//! Process in descriptor order, which are the windows the MGT service gives to
+//! an impersonation of the Process.
+
+use crate::app::App;
+use crate::manifest::{self, Manifest, Process, Scheduling, SoftwareType};
+use capsules::vpp::mloi::{VPP_FRW_TYPE_e, VPP_SCHEDULING_TYPE_e};
+use capsules::vpp::vff::{
+    FW_DESCRIPTOR_SIZE, IPC_DESCRIPTOR_SIZE, LIB_DESCRIPTOR_SIZE, MAILBOX_DESCRIPTOR_SIZE,
+    PROCESS_DESCRIPTOR_SIZE,
+};
+
+/// Version 4.0 of the Firmware Format.
+const FORMAT_VERSION: u16 = 0x0400;
+
+/// Segment lengths are rounded up to keep every segment word aligned.
+fn align(length: usize) -> u32 {
+    ((length + 3) & !3) as u32
+}
+
+/// The DATA segment holds the data of the binary, and is extended to the
+/// length given by the manifest.
+fn data_length(process: &Process, app: &App) -> u32 {
+    process.data.unwrap_or(0).max(align(app.data.len()))
+}
+
+fn count(name: &str, length: usize) -> Result<u8, String> {
+    if length > u8::max_value() as usize {
+        return Err(format!("too many {} descriptors", name));
+    }
+    Ok(length as u8)
+}
+
+/// Encode the header of the Firmware described by `manifest`, `apps` being
+/// the binaries of its Processes in order.
+pub fn header(manifest: &Manifest, apps: &[App]) -> Result<Vec<u8>, String> {
+    let firmware = &manifest.firmware;
+    let length = FW_DESCRIPTOR_SIZE
+        + manifest.process.len() * PROCESS_DESCRIPTOR_SIZE
+        + manifest.mailbox.len() * MAILBOX_DESCRIPTOR_SIZE
+        + manifest.ipc.len() * IPC_DESCRIPTOR_SIZE
+        + manifest.library.len() * LIB_DESCRIPTOR_SIZE;
+    let software_type = match firmware.software_type {
+        SoftwareType::App => VPP_FRW_TYPE_e::FIRMWARE_SOFTWARE_TYPE_APP,
+        SoftwareType::Vpp => VPP_FRW_TYPE_e::FIRMWARE_SOFTWARE_TYPE_VPP,
+        SoftwareType::Sysapp => VPP_FRW_TYPE_e::FIRMWARE_SOFTWARE_TYPE_SYSAPP,
+    };
+    let scheduling = match firmware.scheduling {
+        Scheduling::Collaborative => VPP_SCHEDULING_TYPE_e::MK_SCHEDULING_TYPE_COLLABORATIVE,
+        Scheduling::Preemptive => VPP_SCHEDULING_TYPE_e::MK_SCHEDULING_TYPE_PREEMPTIVE,
+    };
+
+    let mut header = Vec::with_capacity(length);
+    header.extend_from_slice(&manifest::parse_uuid(&firmware.id)?.to_be_bytes());
+    header.extend_from_slice(&manifest::parse_uuid(&firmware.family)?.to_be_bytes());
+    header.extend_from_slice(&(length as u16).to_le_bytes());
+    header.extend_from_slice(&FORMAT_VERSION.to_le_bytes());
+    header.extend_from_slice(&firmware.version.to_le_bytes());
+    header.push(count("Process", manifest.process.len())?);
+    header.push(count("Mailbox", manifest.mailbox.len())?);
+    header.push(count("IPC", manifest.ipc.len())?);
+    header.push(count("LIB", manifest.library.len())?);
+    header.push(u8::from(software_type));
+    header.push(u8::from(scheduling));
+
+    for (process, app) in manifest.process.iter().zip(apps) {
+        header.extend_from_slice(&app.entry.to_le_bytes());
+        header.extend_from_slice(&align(app.code.len()).to_le_bytes());
+        header.extend_from_slice(&align(app.constants.len()).to_le_bytes());
+        header.extend_from_slice(&data_length(process, app).to_le_bytes());
+        header.extend_from_slice(&process.nvm.to_le_bytes());
+        header.extend_from_slice(&process.vre.to_le_bytes());
+        header.extend_from_slice(&process.id.to_le_bytes());
+        header.extend_from_slice(&process.kernel_mailbox.to_le_bytes());
+        header.extend_from_slice(&process.parent.to_le_bytes());
+        header.extend_from_slice(&process.stack.to_le_bytes());
+    }
+    for mailbox in &manifest.mailbox {
+        header.extend_from_slice(&mailbox.id.to_le_bytes());
+        header.extend_from_slice(&mailbox.owner.to_le_bytes());
+        header.extend_from_slice(&mailbox.sender.to_le_bytes());
+    }
+    for ipc in &manifest.ipc {
+        header.extend_from_slice(&ipc.id.to_le_bytes());
+        header.extend_from_slice(&ipc.length.to_le_bytes());
+        header.extend_from_slice(&ipc.writer.to_le_bytes());
+        header.extend_from_slice(&ipc.reader.to_le_bytes());
+    }
+    for library in &manifest.library {
+        let mut processes = 0u32;
+        for &index in &library.processes {
+            if index as usize >= manifest.process.len() || index >= 32 {
+                return Err(format!(
+                    "library {:#x} imported by unknown Process {}",
+                    library.id, index
+                ));
+            }
+            processes |= 1 << index;
+        }
+        header.extend_from_slice(&library.id.to_le_bytes());
+        header.extend_from_slice(&library.version.to_le_bytes());
+        header.extend_from_slice(&processes.to_le_bytes());
+    }
+    Ok(header)
+}
+
+/// Encode the Memory Partition of the Firmware, with the segment lengths of
+/// its Process Descriptors.
+pub fn partition(manifest: &Manifest, apps: &[App]) -> Vec<u8> {
+    let mut partition = Vec::new();
+    for (process, app) in manifest.process.iter().zip(apps) {
+        for (segment, length) in &[
+            (&app.code, align(app.code.len())),
+            (&app.constants, align(app.constants.len())),
+            (&app.data, data_length(process, app)),
+        ] {
+            let end = partition.len() + *length as usize;
+            partition.extend_from_slice(segment);
+            partition.resize(end, 0);
+        }
+        let end = partition.len() + process.nvm as usize + process.stack as usize;
+        partition.resize(end, 0);
+    }
+    partition
+}
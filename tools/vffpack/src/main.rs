@@ -0,0 +1,274 @@
+//! Packages the binaries of the Processes of a Firmware into a VPP Firmware
+//! Format (VFF) image, and verifies VFF images.
+//!
+//! The header of a packed image is checked with the parser of the kernel,
+//! `vff::parse_vpp_header`, so an image accepted by `verify` passes the
+//! consistency rules applied when the Firmware is loaded.
+
+mod app;
+mod image;
+mod manifest;
+
+use capsules::vpp::vff::{self, FwHeader};
+use std::fs;
+use std::path::Path;
+use std::process;
+
+/// Prints an error message and usage string. Used to report command line
+/// argument errors.
+fn usage_error(message: &str) {
+    println!(
+        "{}
+
+Usage:
+  vffpack pack <manifest> <image> [--header-only]
+  vffpack verify <image> [--header-only]
+
+pack reads a TOML (or .json) manifest and the ELF or TBF binaries of its
+Processes, and writes the VFF image of the Firmware. With --header-only, only
+the Firmware header is written, as carried by the VPP Firmware TLV of a TBF.
+
+verify checks the header of an image against the consistency rules of the
+kernel, and that the image holds the whole Memory Partition of the Firmware.",
+        message
+    );
+}
+
+fn read(path: &Path) -> Result<Vec<u8>, String> {
+    fs::read(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))
+}
+
+/// Parse the header at the start of `image` with the kernel parser.
+fn check_header(image: &[u8]) -> Result<FwHeader, String> {
+    vff::parse_vpp_header(image).map_err(|e| format!("invalid Firmware header: {:?}", e))
+}
+
+fn pack(manifest_path: &Path, image_path: &Path, header_only: bool) -> Result<(), String> {
+    let manifest = manifest::load(manifest_path)?;
+    let directory = manifest_path.parent().unwrap_or_else(|| Path::new(""));
+    let mut apps = Vec::new();
+    for process in &manifest.process {
+        let path = directory.join(&process.binary);
+        let app = app::parse(&read(&path)?).map_err(|e| format!("{}: {}", path.display(), e))?;
+        apps.push(app);
+    }
+
+    let mut image = image::header(&manifest, &apps)?;
+    check_header(&image)?;
+    if !header_only {
+        image.extend(image::partition(&manifest, &apps));
+    }
+    fs::write(image_path, &image)
+        .map_err(|e| format!("cannot write {}: {}", image_path.display(), e))?;
+    println!("{}: {} bytes", image_path.display(), image.len());
+    Ok(())
+}
+
+fn verify(image_path: &Path, header_only: bool) -> Result<(), String> {
+    let image = read(image_path)?;
+    let header = check_header(&image)?;
+    let header_length = header.fw_descriptor.m_uHeaderLength as usize;
+    let expected = if header_only {
+        header_length
+    } else {
+        header_length + header.memory_partition_size() as usize
+    };
+    if image.len() != expected {
+        return Err(format!(
+            "the image is {} bytes long, the header describes {} bytes",
+            image.len(),
+            expected
+        ));
+    }
+
+    let fw = &header.fw_descriptor;
+    println!(
+        "Firmware {:032x} (family {:032x}), version {:#06x}",
+        fw.m_xID, fw.m_xFamilyID, fw.m_VersionFirmware
+    );
+    println!(
+        "{}, {} Processes, {} Mailboxes, {} IPCs, {} LIBs",
+        format!("{:?}", fw.m_eFirmare_Software_Type).trim(),
+        fw.m_uProcessCount,
+        fw.m_uMailboxesCount,
+        fw.m_uIPCCount,
+        fw.m_uLIBCount
+    );
+    println!("  ID      entry     CODE  CONSTS    DATA     NVM  stack  parent");
+    for process in header.process_descriptors.iter() {
+        println!(
+            "  {:#06x} {:#08x} {:>8} {:>7} {:>7} {:>7} {:>6} {:>7}",
+            process.m_xID,
+            process.m_pvProcessCode,
+            process.m_uLength_Process_CODE,
+            process.m_uLength_Process_CONSTANTS,
+            process.m_uLength_Process_DATA,
+            process.m_uLength_Process_NVM,
+            process.m_uSizeStack,
+            process.m_uParent_Process
+        );
+    }
+    println!("Memory Partition: {} bytes", header.memory_partition_size());
+    Ok(())
+}
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let header_only = args.iter().any(|arg| arg == "--header-only");
+    let paths: Vec<&Path> = args[1.min(args.len())..]
+        .iter()
+        .filter(|arg| !arg.starts_with("--"))
+        .map(Path::new)
+        .collect();
+    let result = match (args.first().map(String::as_str), paths.as_slice()) {
+        (Some("pack"), [manifest, image]) => pack(manifest, image, header_only),
+        (Some("verify"), [image]) => verify(image, header_only),
+        _ => {
+            usage_error("Incorrect arguments");
+            process::exit(2);
+        }
+    };
+    if let Err(message) = result {
+        eprintln!("error: {}", message);
+        process::exit(1);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use capsules::vpp::mloi::VPP_SCHEDULING_TYPE_e;
+    use std::env;
+    use std::path::PathBuf;
+
+    const MANIFEST: &str = r#"
+[firmware]
+id = "3f2a7c1e-8b4d-4e6f-9a1b-2c3d4e5f6071"
+family = "0b8e6f2a-1c3d-4a5b-8c7d-9e0f1a2b3c4d"
+version = 0x0102
+scheduling = "collaborative"
+
+[[process]]
+id = 0x4000
+binary = "app.tbf"
+stack = 2048
+kernel_mailbox = 0x4064
+
+[[process]]
+id = 0x4064
+binary = "app.tbf"
+stack = 2048
+nvm = 256
+data = 512
+
+[[mailbox]]
+id = 0x4064
+owner = 0
+sender = 1
+
+[[ipc]]
+id = 0x4064
+length = 64
+writer = 0
+reader = 1
+
+[[library]]
+id = 0x4064
+version = 0x0100
+processes = [1]
+"#;
+
+    /// A TBF binary of 64 bytes: the base header and a Main TLV, with `_start` 8
+    /// bytes after the header.
+    fn tbf() -> Vec<u8> {
+        let mut tbf = vec![0; 64];
+        tbf[0..2].copy_from_slice(&2u16.to_le_bytes());
+        tbf[2..4].copy_from_slice(&32u16.to_le_bytes());
+        tbf[4..8].copy_from_slice(&64u32.to_le_bytes());
+        tbf[16..18].copy_from_slice(&1u16.to_le_bytes());
+        tbf[18..20].copy_from_slice(&12u16.to_le_bytes());
+        tbf[20..24].copy_from_slice(&8u32.to_le_bytes());
+        tbf
+    }
+
+    /// Writes the manifest and its binary to a fresh directory.
+    fn workspace(name: &str) -> PathBuf {
+        let directory = env::temp_dir().join(format!("vffpack-{}-{}", name, process::id()));
+        fs::create_dir_all(&directory).unwrap();
+        fs::write(directory.join("firmware.toml"), MANIFEST).unwrap();
+        fs::write(directory.join("app.tbf"), tbf()).unwrap();
+        directory
+    }
+
+    #[test]
+    fn a_packed_image_is_verified_by_the_kernel_parser() {
+        let directory = workspace("image");
+        let image_path = directory.join("firmware.vff");
+        pack(&directory.join("firmware.toml"), &image_path, false).unwrap();
+        verify(&image_path, false).unwrap();
+
+        let image = fs::read(&image_path).unwrap();
+        let header = vff::parse_vpp_header(&image).unwrap();
+        let fw = &header.fw_descriptor;
+        assert_eq!(fw.m_xID, 0x3f2a7c1e_8b4d_4e6f_9a1b_2c3d4e5f6071);
+        assert_eq!(fw.m_xFamilyID, 0x0b8e6f2a_1c3d_4a5b_8c7d_9e0f1a2b3c4d);
+        assert_eq!(fw.m_VersionFirmware, 0x0102);
+        assert!(fw.m_eSchedluingType == VPP_SCHEDULING_TYPE_e::MK_SCHEDULING_TYPE_COLLABORATIVE);
+        assert_eq!(
+            (
+                fw.m_uProcessCount,
+                fw.m_uMailboxesCount,
+                fw.m_uIPCCount,
+                fw.m_uLIBCount
+            ),
+            (2, 1, 1, 1)
+        );
+
+        let main = header.process_descriptors.get(0).unwrap();
+        assert_eq!(main.m_xID, 0x4000);
+        assert_eq!(main.m_pvProcessCode, 40);
+        assert_eq!(main.m_uLength_Process_CODE, 64);
+        assert_eq!(main.m_xKernel_Mailbox, 0x4064);
+        let worker = header.process_descriptors.get(1).unwrap();
+        assert_eq!(worker.m_xID, 0x4064);
+        assert_eq!(worker.m_uLength_Process_DATA, 512);
+        assert_eq!(worker.m_uLength_Process_NVM, 256);
+        assert_eq!(worker.m_uSizeStack, 2048);
+
+        let mailbox = header.mailbox_descriptors.get(0).unwrap();
+        assert_eq!(
+            (mailbox.m_xID, mailbox.m_uIX_Owner, mailbox.m_uIX_Sender),
+            (0x4064, 0, 1)
+        );
+        let ipc = header.ipc_descriptors.get(0).unwrap();
+        assert_eq!(
+            (ipc.m_xID, ipc.m_uLength_IPC, ipc.m_uIX_Writer),
+            (0x4064, 64, 0)
+        );
+        let library = header.lib_descriptors.get(0).unwrap();
+        assert_eq!(
+            (library.m_xID, library.m_uVersion, library.m_bProcesses),
+            (0x4064, 0x0100, 0b10)
+        );
+
+        // The Memory Partition starts with the CODE of the Main Process.
+        let partition = &image[fw.m_uHeaderLength as usize..];
+        assert_eq!(partition.len(), header.memory_partition_size() as usize);
+        assert_eq!(&partition[..64], &tbf()[..]);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+
+    #[test]
+    fn a_packed_header_is_verified_by_the_kernel_parser() {
+        let directory = workspace("header");
+        let header_path = directory.join("firmware.vffh");
+        pack(&directory.join("firmware.toml"), &header_path, true).unwrap();
+        verify(&header_path, true).unwrap();
+        assert!(verify(&header_path, false).is_err());
+
+        let header = fs::read(&header_path).unwrap();
+        let fw = vff::parse_vpp_header(&header).unwrap().fw_descriptor;
+        assert_eq!(header.len(), fw.m_uHeaderLength as usize);
+        fs::remove_dir_all(&directory).unwrap();
+    }
+}
@@ -0,0 +1,138 @@
+//! The manifest describing a Firmware: its identity and one table per kind of
+//! descriptor. Processes, Mailboxes, IPCs and libraries refer to Processes by
+//! their index in the `process` table, as the descriptors do.
+
+use serde::Deserialize;
+use std::fs;
+use std::path::Path;
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Manifest {
+    pub firmware: Firmware,
+    #[serde(default)]
+    pub process: Vec<Process>,
+    #[serde(default)]
+    pub mailbox: Vec<Mailbox>,
+    #[serde(default)]
+    pub ipc: Vec<Ipc>,
+    #[serde(default)]
+    pub library: Vec<Library>,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Firmware {
+    /// Firmware identifier, a version 4 UUID.
+    pub id: String,
+    /// Firmware family identifier, a version 4 UUID.
+    pub family: String,
+    /// Major and minor version of the Firmware.
+    #[serde(default)]
+    pub version: u16,
+    #[serde(rename = "type", default)]
+    pub software_type: SoftwareType,
+    #[serde(default)]
+    pub scheduling: Scheduling,
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SoftwareType {
+    App,
+    Vpp,
+    Sysapp,
+}
+
+impl Default for SoftwareType {
+    fn default() -> SoftwareType {
+        SoftwareType::App
+    }
+}
+
+#[derive(Clone, Copy, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheduling {
+    Collaborative,
+    Preemptive,
+}
+
+impl Default for Scheduling {
+    fn default() -> Scheduling {
+        Scheduling::Preemptive
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Process {
+    /// Composite Process identifier.
+    pub id: u16,
+    /// ELF or TBF binary of the Process, relative to the manifest.
+    pub binary: String,
+    /// Index of the parent Process, `0` for the Main Process.
+    #[serde(default)]
+    pub parent: u16,
+    /// Stack size in bytes.
+    pub stack: u16,
+    /// Length of the NVM segment.
+    #[serde(default)]
+    pub nvm: u32,
+    /// Length of the DATA segment, if larger than the data of the binary.
+    pub data: Option<u32>,
+    /// Mailbox receiving the Signals of the kernel, `0` for none.
+    #[serde(default)]
+    pub kernel_mailbox: u16,
+    /// Mandatory Access Control bitmap for the VRE.
+    #[serde(default)]
+    pub vre: u32,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Mailbox {
+    pub id: u16,
+    pub owner: u16,
+    pub sender: u16,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Ipc {
+    pub id: u16,
+    /// Length of the shared memory in bytes.
+    pub length: u16,
+    pub writer: u16,
+    pub reader: u16,
+}
+
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Library {
+    pub id: u16,
+    /// Major and minor version of the library required by the Firmware.
+    pub version: u16,
+    /// Indices of the Processes importing the library.
+    pub processes: Vec<u16>,
+}
+
+/// Read a manifest, in JSON if the file ends with `.json` and in TOML otherwise.
+pub fn load(path: &Path) -> Result<Manifest, String> {
+    let text =
+        fs::read_to_string(path).map_err(|e| format!("cannot read {}: {}", path.display(), e))?;
+    let manifest = if path.extension().map_or(false, |ext| ext == "json") {
+        serde_json::from_str(&text).map_err(|e| e.to_string())
+    } else {
+        toml::from_str(&text).map_err(|e| e.to_string())
+    };
+    manifest.map_err(|e| format!("invalid manifest {}: {}", path.display(), e))
+}
+
+/// Parse a UUID in its canonical textual form, dashes being optional.
+pub fn parse_uuid(text: &str) -> Result<u128, String> {
+    let digits: String = text.chars().filter(|&c| c != '-').collect();
+    if digits.len() != 32 {
+        return Err(format!("invalid UUID {}", text));
+    }
+    u128::from_str_radix(&digits, 16).map_err(|_| format!("invalid UUID {}", text))
+}